@@ -16,11 +16,61 @@ def count_nonempty_lines(path: str) -> int:
     return sum(1 for line in text.splitlines() if line.strip())
 
 
+def frame_checksum(line: str) -> int:
+    """
+    Контрольная сумма строки состояния (FNV-1a, 64 бита, по значениям ячеек) —
+    как в строке "#end <hex>", которой модель закрывает каждый кадр.
+    """
+    h = 0xCBF29CE484222325
+    for token in line.split(":"):
+        h = ((h ^ int(token)) * 0x100000001B3) & 0xFFFFFFFFFFFFFFFF
+    return h
+
+
+def last_complete_frame(f) -> str:
+    """
+    Последний полностью записанный кадр открытого файла состояний.
+    Обрезанная последняя строка (без перевода строки) и, в файле с строками "#end",
+    кадр без своей строки "#end" (сбой во время записи) пропускаются с предупреждением;
+    несовпадение контрольной суммы — ValueError.
+    """
+    last, last_checksum, cand, is_marked = "", None, "", False
+    for line in f:
+        s = line.strip()
+        if s.startswith("#end"):
+            # Обрезана сама строка "#end" — её кадр не завершён
+            if not line.endswith("\n"):
+                is_marked = True
+                break
+            if cand:
+                last, last_checksum = cand, int(s[4:].strip(), 16)
+            cand, is_marked = "", True
+            continue
+        if not s or s.startswith("#"):
+            continue
+        if not line.endswith("\n"):
+            print(f"⚠️ {f.name}: обрезанный последний кадр пропущен.")
+            break
+        if cand and not is_marked:
+            last = cand
+        cand = s
+
+    if cand and is_marked:
+        print(f"⚠️ {f.name}: последний кадр без строки #end пропущен.")
+    elif cand:
+        last = cand
+    if last_checksum is not None and frame_checksum(last) != last_checksum:
+        raise ValueError(f"{f.name}: контрольная сумма последнего кадра не совпадает")
+    return last
+
+
 def get_line(path: str, line_number: int) -> str:
     """
     Возвращает:
-      - при line_number == -1 — последнюю непустую строку файла,
+      - при line_number == -1 — последний полностью записанный кадр файла
+        (см. last_complete_frame),
       - при line_number > 0  — указанную строку (1-based).
+    Строки-заголовки (#states, # grid=..., #end) в нумерации не учитываются.
     В случае отсутствия строки возвращает пустую строку.
     Бросает IOError, если файл недоступен, и ValueError, если line_number < -1 или == 0.
     """
@@ -30,19 +80,27 @@ def get_line(path: str, line_number: int) -> str:
     # Открываем в текстовом режиме с игнорированием ошибок декодирования
     with open(path, encoding="utf-8", errors="ignore") as f:
         if line_number == -1:
-            last = ""
-            for line in f:
-                s = line.strip()
-                if s:
-                    last = s
-            return last
+            return last_complete_frame(f)
+
+        lines = (line for line in f if not line.lstrip().startswith("#"))
 
         # line_number > 0
         # Пропускаем первые line_number-1 строк и берём следующую
-        line = next(islice(f, line_number - 1, line_number), "")
+        line = next(islice(lines, line_number - 1, line_number), "")
         return line.strip()
 
 
+def get_states_header(path: str) -> str:
+    """
+    Возвращает первую строку-заголовок #states файла состояний (или пустую строку).
+    """
+    with open(path, encoding="utf-8", errors="ignore") as f:
+        for line in f:
+            if line.lstrip().startswith("#states"):
+                return line.strip()
+    return ""
+
+
 def write_state_from_last_dir(
     cfg_states_file_path: Pt,
     load_prev_state: int,
@@ -78,9 +136,11 @@ def write_state_from_last_dir(
 
     # Получаем нужную строку (1-based; -1 — последняя непустая)
     line = get_line(source_state, load_prev_state)
+    header = get_states_header(source_state)
 
-    # Записываем в целевой файл
-    cfg_states_file_path.write_text(line, encoding="utf-8")
+    # Записываем в целевой файл (вместе с заголовком #states, если он есть)
+    text = f"{header}\n{line}" if header else line
+    cfg_states_file_path.write_text(text, encoding="utf-8")
 
 
 def custom_strtobool(val: str) -> bool:
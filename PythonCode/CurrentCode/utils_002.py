@@ -20,6 +20,40 @@ def read_large_file_lines(file_path: Pt) -> Iterator[str]:
             yield line
 
 
+FRONT_RECORD = np.dtype([("x", "<u2"), ("y", "<u2"), ("z", "<u2"), ("sig", "u1")])
+
+
+def read_front_bin(path: Union[Pt, str]) -> Iterator[Tuple[int, np.ndarray]]:
+    """
+    Генератор кадров Front.bin (FrontExport): (шаг, массив FRONT_RECORD) —
+    координаты x, y, z узлов TPB и сигнатура sig (бит i — сосед i из
+    -x, +x, -y, +y, -z, +z — кристалл). Кадр, оборванный при сбое, пропускается
+    с предупреждением.
+
+    Пример — признаки для ML-модели, по строке на узел:
+        for step, sites in read_front_bin(path):
+            bits = np.unpackbits(sites["sig"][:, None], axis=1, bitorder="little")[:, :6]
+            features = np.column_stack([sites["x"], sites["y"], sites["z"], bits])
+    """
+    with open(path, "rb") as f:
+        head = f.read(20)
+        if len(head) < 20 or head[:4] != b"MCFR":
+            raise ValueError(f"{path}: не файл Front.bin")
+        while True:
+            frame_head = f.read(12)
+            if not frame_head:
+                return
+            body = b""
+            if len(frame_head) == 12:
+                step = int(np.frombuffer(frame_head[:8], dtype="<u8")[0])
+                n = int(np.frombuffer(frame_head[8:], dtype="<u4")[0])
+                body = f.read(n * FRONT_RECORD.itemsize)
+            if len(frame_head) < 12 or len(body) < n * FRONT_RECORD.itemsize:
+                print(f"⚠️ {path}: обрезанный последний кадр пропущен.")
+                return
+            yield step, np.frombuffer(body, dtype=FRONT_RECORD)
+
+
 def custom_strtobool(val: str) -> bool:
     val_lower = val.strip().lower()
     truthy = {"y", "yes", "t", "true", "on", "1"}
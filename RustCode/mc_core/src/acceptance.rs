@@ -0,0 +1,51 @@
+//! Acceptance function of the kernel events (the `Acceptance` key): the probability of
+//! carrying out an event that changes the energy by `d_e` at the temperature `k_t`.
+//!
+//! All three satisfy detailed balance, so the equilibrium states agree; the dynamics
+//! (and the dynamic exponents) differ. Every event of the kernels flips one cell between
+//! its two states, and for a two-state move the heat-bath probability of the new state,
+//! `exp(-E_new/kT) / (exp(-E_old/kT) + exp(-E_new/kT))`, is the Glauber one.
+
+/// Acceptance function of the kernel events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AcceptanceRule {
+    /// `min(1, exp(-dE/kT))`.
+    #[default]
+    Metropolis,
+    /// `1 / (1 + exp(dE/kT))`.
+    Glauber,
+    /// The heat-bath probability of the flipped state, equal to `Glauber` here.
+    HeatBath,
+}
+
+impl AcceptanceRule {
+    /// Rule of an `Acceptance` value (`metropolis`, `glauber`, `heatbath`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "metropolis" => Some(Self::Metropolis),
+            "glauber" => Some(Self::Glauber),
+            "heatbath" | "heat-bath" => Some(Self::HeatBath),
+            _ => None,
+        }
+    }
+
+    /// Probability of accepting an event with the energy change `d_e`.
+    #[inline(always)]
+    pub fn probability(self, d_e: f64, k_t: f64) -> f64 {
+        match self {
+            Self::Metropolis => (-d_e / k_t).exp().min(1.0),
+            Self::Glauber | Self::HeatBath => 1.0 / (1.0 + (d_e / k_t).exp()),
+        }
+    }
+
+    /// Whether an event with the energy change `d_e` is accepted against the uniform
+    /// number `draw()`. Metropolis draws only for `d_e >= 0`, as the kernels always did,
+    /// so its random stream is unchanged; the other rules draw for every event.
+    #[inline(always)]
+    pub fn accepts<F: FnOnce() -> f64>(self, d_e: f64, k_t: f64, draw: F) -> bool {
+        match self {
+            Self::Metropolis => d_e < 0.0 || (-d_e / k_t).exp() > draw(),
+            Self::Glauber | Self::HeatBath => self.probability(d_e, k_t) > draw(),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! `ConfigVersion` of `InitSettings.ini` and the migration of renamed keys.
+//!
+//! Files without the key are version 1. A key listed in a model's migration table is
+//! read under its new name, with a warning, in files older than the version that
+//! removed it, and rejected in newer ones; files of a future version are refused.
+
+/// Config layout version written by this build.
+pub const CONFIG_VERSION: u32 = 2;
+pub const CONFIG_VERSION_KEY: &str = "ConfigVersion";
+
+/// Key renamed (or taken over from the other model) in config version `removed_in`.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyMigration {
+    pub old: &'static str,
+    pub new: &'static str,
+    pub removed_in: u32,
+    /// What to check after the rename, e.g. a changed meaning of the value.
+    pub note: &'static str,
+}
+
+/// Version of a config file: its `ConfigVersion` line before `comment_line`, 1 if none.
+pub fn file_version(lines: &[String], comment_line: &str) -> Result<u32, String> {
+    let value = lines
+        .iter()
+        .map(|line| line.trim())
+        .take_while(|&line| line != comment_line)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == CONFIG_VERSION_KEY)
+        .map(|(_, value)| value.trim());
+
+    let Some(value) = value else {
+        return Ok(1);
+    };
+    match value.parse::<u32>() {
+        Ok(0) | Err(_) => Err(format!("bad value '{}' (a version >= 1)", value)),
+        Ok(version) if version > CONFIG_VERSION => Err(format!(
+            "version {} is newer than this build reads ({}); use a newer executable",
+            version, CONFIG_VERSION
+        )),
+        Ok(version) => Ok(version),
+    }
+}
+
+/// Migration of `key` in a file of `version`: `Ok(None)` for a current key, `Ok(Some)`
+/// to read it under the new name, `Err` when the file is too new for the old name.
+pub fn migrate_key(
+    key: &str,
+    version: u32,
+    migrations: &'static [KeyMigration],
+) -> Result<Option<&'static KeyMigration>, String> {
+    let Some(migration) = migrations.iter().find(|m| m.old == key) else {
+        return Ok(None);
+    };
+    match version < migration.removed_in {
+        true => Ok(Some(migration)),
+        false => Err(format!(
+            "not a key of ConfigVersion {}; use '{}' ({})",
+            version, migration.new, migration.note
+        )),
+    }
+}
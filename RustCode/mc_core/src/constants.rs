@@ -0,0 +1,6 @@
+/// Boltzmann constant in Joules per Kelvin.
+pub const K_BOLTZMANN: f64 = 1.380649e-23;
+
+pub const CONFIG_FILE_NAME: &str = "InitSettings.ini";
+pub const INIT_TIME_STATES_FILE_NAME: &str = "InitStates.ini";
+pub const TIME_STATES_FILE_NAME: &str = "TimeStates.txt";
@@ -0,0 +1,115 @@
+//! Surface energy change of the kernel events from the per-axis crystal neighbour counts
+//! of the site (`compute_neighbor_sums`: 0..=2 along each axis).
+//!
+//! Along an axis with no crystal neighbours an attached particle opens two faces
+//! (`+e2`), with two it closes them (`-e2`), with one it moves a face. Both models and
+//! all modes take the energies from here, so the kernels cannot drift apart.
+
+/// Surface energy change of attaching a particle to a gas site with the neighbour counts
+/// `sums`; `e2` - the energies of the two faces normal to each axis.
+#[inline(always)]
+pub fn attach_energy(
+    (smx_yz, smy_xz, smz_xy): (u8, u8, u8),
+    (ex2, ey2, ez2): (f64, f64, f64),
+) -> f64 {
+    let axis_energy = |sm: u8, e2: f64| match sm {
+        0 => e2,
+        2 => -e2,
+        _ => 0.0,
+    };
+
+    axis_energy(smx_yz, ex2) + axis_energy(smy_xz, ey2) + axis_energy(smz_xy, ez2)
+}
+
+/// Surface energy change of detaching the particle of a crystal site with the neighbour
+/// counts `sums`: the reverse of `attach_energy` from the same configuration.
+#[inline(always)]
+pub fn detach_energy(sums: (u8, u8, u8), e2: (f64, f64, f64)) -> f64 {
+    -attach_energy(sums, e2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Face energies with distinct magnitudes, so a wrong axis shows up in the sum.
+    const E2: (f64, f64, f64) = (0.375, 1.25, 2.5);
+
+    /// The inline blocks of the kernels before they were shared: attach (`sign = 1.0`)
+    /// adds `e2` along an axis with no crystal neighbours and removes it with two,
+    /// detach (`sign = -1.0`) the opposite.
+    fn inline_energy(
+        (smx_yz, smy_xz, smz_xy): (u8, u8, u8),
+        (ex2, ey2, ez2): (f64, f64, f64),
+        sign: f64,
+    ) -> f64 {
+        let mut surf_en_change = 0.0;
+        match smx_yz {
+            0 => surf_en_change += sign * ex2,
+            2 => surf_en_change -= sign * ex2,
+            _ => {}
+        }
+        match smy_xz {
+            0 => surf_en_change += sign * ey2,
+            2 => surf_en_change -= sign * ey2,
+            _ => {}
+        }
+        match smz_xy {
+            0 => surf_en_change += sign * ez2,
+            2 => surf_en_change -= sign * ez2,
+            _ => {}
+        }
+        surf_en_change
+    }
+
+    fn all_sums() -> impl Iterator<Item = (u8, u8, u8)> {
+        (0..=2u8).flat_map(|n1| (0..=2u8).flat_map(move |n2| (0..=2u8).map(move |n3| (n1, n2, n3))))
+    }
+
+    #[test]
+    fn covers_all_27_combinations() {
+        assert_eq!(all_sums().count(), 27);
+    }
+
+    #[test]
+    fn attach_matches_inline_formula() {
+        for sums in all_sums() {
+            assert_eq!(
+                attach_energy(sums, E2),
+                inline_energy(sums, E2, 1.0),
+                "sums {:?}",
+                sums
+            );
+        }
+    }
+
+    #[test]
+    fn detach_matches_inline_formula() {
+        for sums in all_sums() {
+            assert_eq!(
+                detach_energy(sums, E2),
+                inline_energy(sums, E2, -1.0),
+                "sums {:?}",
+                sums
+            );
+        }
+    }
+
+    #[test]
+    fn detach_undoes_attach() {
+        for sums in all_sums() {
+            assert_eq!(
+                attach_energy(sums, E2) + detach_energy(sums, E2),
+                0.0,
+                "sums {:?}",
+                sums
+            );
+        }
+    }
+
+    #[test]
+    fn one_neighbour_axes_do_not_change_energy() {
+        assert_eq!(attach_energy((1, 1, 1), E2), 0.0);
+        assert_eq!(attach_energy((0, 1, 2), E2), E2.0 - E2.2);
+    }
+}
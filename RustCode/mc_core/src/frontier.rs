@@ -0,0 +1,345 @@
+use std::{cmp::max, collections::HashMap, fmt::Debug};
+
+/// Type (0 - none, 2 - TPA, 3 - TPB) and local index of every site of a frontier.
+///
+/// The backings trade memory for speed: full-grid arrays (`DenseIndex`), a map holding
+/// only the front sites (`HashedIndex`), for small crystals on big grids, or two bitsets
+/// of the site types without the local indices (`BitsetIndex`), 2 bits per site instead
+/// of 9 bytes - a removal then looks the site up in its list, O(front).
+pub trait SiteIndex: Debug + Clone {
+    /// Name of the backing in benchmarks and messages.
+    const NAME: &'static str;
+
+    fn with_size(total_grid_size: usize) -> Self;
+
+    /// Bytes allocated by `with_size`; 0 for an index that grows with the front.
+    fn bytes(total_grid_size: usize) -> usize;
+
+    fn site_type(&self, idxg: usize) -> u8;
+
+    /// Local index of the front site `idxg` in its list `sites`.
+    fn idxl(&self, idxg: usize, sites: &[usize]) -> usize;
+
+    /// Sets the type and local index of `idxg`; type 0 drops a site.
+    fn set(&mut self, idxg: usize, site_type: u8, idxl: usize);
+
+    fn set_idxl(&mut self, idxg: usize, idxl: usize);
+}
+
+/// Two full-grid arrays: the type and the local index of every site.
+#[derive(Debug, Clone)]
+pub struct DenseIndex {
+    idxg_to_type: Box<[u8]>,
+    idxg_to_idxl: Box<[usize]>,
+}
+
+impl SiteIndex for DenseIndex {
+    const NAME: &'static str = "dense";
+
+    fn with_size(total_grid_size: usize) -> Self {
+        Self {
+            idxg_to_type: vec![0; total_grid_size].into_boxed_slice(),
+            idxg_to_idxl: vec![0; total_grid_size].into_boxed_slice(),
+        }
+    }
+
+    fn bytes(total_grid_size: usize) -> usize {
+        total_grid_size * (1 + size_of::<usize>())
+    }
+
+    #[inline(always)]
+    fn site_type(&self, idxg: usize) -> u8 {
+        self.idxg_to_type[idxg]
+    }
+
+    #[inline(always)]
+    fn idxl(&self, idxg: usize, _sites: &[usize]) -> usize {
+        self.idxg_to_idxl[idxg]
+    }
+
+    #[inline(always)]
+    fn set(&mut self, idxg: usize, site_type: u8, idxl: usize) {
+        self.idxg_to_type[idxg] = site_type;
+        self.idxg_to_idxl[idxg] = idxl;
+    }
+
+    #[inline(always)]
+    fn set_idxl(&mut self, idxg: usize, idxl: usize) {
+        self.idxg_to_idxl[idxg] = idxl;
+    }
+}
+
+/// Type and local index of the front sites only: allocates nothing until the first
+/// site is added and then grows with the front. Slower per event (a hash lookup instead
+/// of an index), for many small crystals on big grids.
+#[derive(Debug, Clone, Default)]
+pub struct HashedIndex(HashMap<usize, (u8, usize)>);
+
+impl SiteIndex for HashedIndex {
+    const NAME: &'static str = "hashed";
+
+    fn with_size(_total_grid_size: usize) -> Self {
+        Self::default()
+    }
+
+    fn bytes(_total_grid_size: usize) -> usize {
+        0
+    }
+
+    #[inline(always)]
+    fn site_type(&self, idxg: usize) -> u8 {
+        self.0.get(&idxg).map_or(0, |&(site_type, _)| site_type)
+    }
+
+    #[inline(always)]
+    fn idxl(&self, idxg: usize, _sites: &[usize]) -> usize {
+        self.0.get(&idxg).map_or(0, |&(_, idxl)| idxl)
+    }
+
+    #[inline(always)]
+    fn set(&mut self, idxg: usize, site_type: u8, idxl: usize) {
+        match site_type {
+            0 => {
+                self.0.remove(&idxg);
+            }
+            _ => {
+                self.0.insert(idxg, (site_type, idxl));
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn set_idxl(&mut self, idxg: usize, idxl: usize) {
+        if let Some(site) = self.0.get_mut(&idxg) {
+            site.1 = idxl;
+        }
+    }
+}
+
+/// One bit per site for each list and no local indices: 2 bits per site, for near-full
+/// lattices where the dense arrays are mostly unused; a removal scans its list.
+#[derive(Debug, Clone)]
+pub struct BitsetIndex {
+    tpa_bits: Box<[u64]>,
+    tpb_bits: Box<[u64]>,
+}
+
+#[inline(always)]
+fn has_bit(bits: &[u64], idxg: usize) -> bool {
+    bits[idxg >> 6] & (1 << (idxg & 63)) != 0
+}
+
+#[inline(always)]
+fn put_bit(bits: &mut [u64], idxg: usize, is_set: bool) {
+    match is_set {
+        true => bits[idxg >> 6] |= 1 << (idxg & 63),
+        false => bits[idxg >> 6] &= !(1 << (idxg & 63)),
+    }
+}
+
+impl SiteIndex for BitsetIndex {
+    const NAME: &'static str = "bitset";
+
+    fn with_size(total_grid_size: usize) -> Self {
+        let words = total_grid_size.div_ceil(64);
+        Self {
+            tpa_bits: vec![0; words].into_boxed_slice(),
+            tpb_bits: vec![0; words].into_boxed_slice(),
+        }
+    }
+
+    fn bytes(total_grid_size: usize) -> usize {
+        2 * total_grid_size.div_ceil(64) * size_of::<u64>()
+    }
+
+    #[inline(always)]
+    fn site_type(&self, idxg: usize) -> u8 {
+        match (has_bit(&self.tpa_bits, idxg), has_bit(&self.tpb_bits, idxg)) {
+            (true, _) => 2,
+            (_, true) => 3,
+            _ => 0,
+        }
+    }
+
+    #[inline(always)]
+    fn idxl(&self, idxg: usize, sites: &[usize]) -> usize {
+        sites.iter().rposition(|&site| site == idxg).unwrap_or(0)
+    }
+
+    #[inline(always)]
+    fn set(&mut self, idxg: usize, site_type: u8, _idxl: usize) {
+        put_bit(&mut self.tpa_bits, idxg, site_type == 2);
+        put_bit(&mut self.tpb_bits, idxg, site_type == 3);
+    }
+
+    #[inline(always)]
+    fn set_idxl(&mut self, _idxg: usize, _idxl: usize) {}
+}
+
+/// Site index of the build: the bitsets with the `front-bitset` feature, else the hashed
+/// index with `front-hashed`, else the dense arrays.
+#[cfg(feature = "front-bitset")]
+pub type BuildIndex = BitsetIndex;
+#[cfg(all(feature = "front-hashed", not(feature = "front-bitset")))]
+pub type BuildIndex = HashedIndex;
+#[cfg(not(any(feature = "front-hashed", feature = "front-bitset")))]
+pub type BuildIndex = DenseIndex;
+
+/// The frontier of the simulation, backed by the site index of the build.
+pub type Frontier = FrontierOf<BuildIndex>;
+
+/// TPA and TPB sites in two lists, with the site index `I` locating every site in its
+/// list for O(1) removal.
+#[derive(Debug, Clone)]
+pub struct FrontierOf<I: SiteIndex> {
+    pub tpas: Vec<usize>,
+    pub tpbs: Vec<usize>,
+    index: I,
+    pub tpas_size: usize,
+    pub tpbs_size: usize,
+}
+
+impl<I: SiteIndex> FrontierOf<I> {
+    /// An empty frontier over a grid of `total_grid_size` sites.
+    pub fn new(total_grid_size: usize) -> Self {
+        let initial_capacity = match I::bytes(total_grid_size) {
+            0 => 0,
+            _ => max(total_grid_size / 10, 128),
+        };
+        Self {
+            tpas: Vec::with_capacity(initial_capacity),
+            tpbs: Vec::with_capacity(initial_capacity),
+            index: I::with_size(total_grid_size),
+            tpas_size: 0,
+            tpbs_size: 0,
+        }
+    }
+
+    /// Bytes allocated by `new` (the site index and the list capacities), 0 for the
+    /// hashed index, which grows with the front.
+    pub fn new_bytes(total_grid_size: usize) -> usize {
+        match I::bytes(total_grid_size) {
+            0 => 0,
+            bytes => bytes + 2 * max(total_grid_size / 10, 128) * size_of::<usize>(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn tpa_add(&mut self, idxg: usize) {
+        if self.index.site_type(idxg) == 2 {
+            return;
+        }
+
+        self.index.set(idxg, 2, self.tpas_size);
+        self.tpas.push(idxg);
+        self.tpas_size += 1;
+    }
+
+    #[inline(always)]
+    pub fn tpa_rem(&mut self, idxg: usize) {
+        if self.index.site_type(idxg) != 2 {
+            return;
+        }
+
+        let idxl = self.index.idxl(idxg, &self.tpas);
+        self.index.set(idxg, 0, 0);
+        self.tpas_size -= 1;
+
+        let last_idxg = self.tpas.pop().unwrap();
+
+        if idxl != self.tpas_size {
+            self.tpas[idxl] = last_idxg;
+            self.index.set_idxl(last_idxg, idxl);
+        }
+    }
+
+    #[inline(always)]
+    pub fn tpb_add(&mut self, idxg: usize) {
+        if self.index.site_type(idxg) == 3 {
+            return;
+        }
+        self.index.set(idxg, 3, self.tpbs_size);
+        self.tpbs.push(idxg);
+        self.tpbs_size += 1;
+    }
+
+    #[inline(always)]
+    pub fn tpb_rem(&mut self, idxg: usize) {
+        if self.index.site_type(idxg) != 3 {
+            return;
+        }
+        let idxl = self.index.idxl(idxg, &self.tpbs);
+        self.index.set(idxg, 0, 0);
+        self.tpbs_size -= 1;
+        let last_idxg = self.tpbs.pop().unwrap();
+
+        if idxl != self.tpbs_size {
+            self.tpbs[idxl] = last_idxg;
+            self.index.set_idxl(last_idxg, idxl);
+        }
+    }
+
+    /// Removes all sites, keeping the allocations.
+    pub fn clear(&mut self) {
+        for &idxg in self.tpas.iter().chain(&self.tpbs) {
+            self.index.set(idxg, 0, 0);
+        }
+        self.tpas.clear();
+        self.tpbs.clear();
+        self.tpas_size = 0;
+        self.tpbs_size = 0;
+    }
+
+    #[inline(always)]
+    pub fn is_tpa(&self, idxg: usize) -> bool {
+        self.index.site_type(idxg) == 2
+    }
+
+    #[inline(always)]
+    pub fn is_tpb(&self, idxg: usize) -> bool {
+        self.index.site_type(idxg) == 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lists of a frontier after a fixed mix of additions and removals (repeats and
+    /// removals of absent sites included; TPA on even sites, TPB on odd), sorted.
+    fn churned<I: SiteIndex>() -> (Vec<usize>, Vec<usize>) {
+        const SIZE: usize = 200;
+        let mut front = FrontierOf::<I>::new(SIZE);
+        for step in 0..2_000 {
+            let idxg = step * 37 % SIZE;
+            match step % 5 {
+                0 | 1 => front.tpa_add(idxg & !1),
+                2 => front.tpb_add(idxg | 1),
+                3 => front.tpa_rem((step * 11 % SIZE) & !1),
+                _ => front.tpb_rem((step * 13 % SIZE) | 1),
+            }
+        }
+
+        for (sites, size, is_type) in [
+            (&front.tpas, front.tpas_size, 2),
+            (&front.tpbs, front.tpbs_size, 3),
+        ] {
+            assert_eq!(sites.len(), size);
+            for &idxg in sites {
+                assert_eq!(front.index.site_type(idxg), is_type);
+            }
+        }
+        let (mut tpas, mut tpbs) = (front.tpas.clone(), front.tpbs.clone());
+        tpas.sort_unstable();
+        tpbs.sort_unstable();
+        (tpas, tpbs)
+    }
+
+    #[test]
+    fn backings_hold_the_same_sites() {
+        let dense = churned::<DenseIndex>();
+        assert!(!dense.0.is_empty() && !dense.1.is_empty());
+        assert_eq!(churned::<HashedIndex>(), dense);
+        assert_eq!(churned::<BitsetIndex>(), dense);
+    }
+}
@@ -0,0 +1,206 @@
+//! The `key: value` lines of `InitSettings.ini` and the state lines, read and written
+//! the same way by both models; the keys themselves (the dispatch tables) are per model.
+
+use crate::{
+    config_version::{self, CONFIG_VERSION_KEY, KeyMigration},
+    settings::SettingsError,
+    unknown_keys::UnknownKey,
+};
+use std::{
+    collections::HashMap,
+    env::current_exe,
+    io::{Error as IoError, Result as IoResult, Write},
+    path::PathBuf,
+};
+
+/// Parser of the value of one key into the settings (or settings builder) `S`.
+pub type KeyParser<S> = Box<dyn Fn(&str, &mut S) -> Result<(), SettingsError>>;
+
+/// What `read_config_lines` found in a config file.
+#[derive(Debug, Default)]
+pub struct ConfigLines {
+    /// Line (from 1) of every key read, under its current name.
+    pub seen_keys: HashMap<String, usize>,
+    pub unknown: Vec<UnknownKey>,
+    /// Bad values, duplicate keys and keys retired in the file's version.
+    pub errors: Vec<String>,
+}
+
+impl ConfigLines {
+    /// Error naming the keys of `required_keys(required, mode)` that were neither read
+    /// nor are `is_set` elsewhere (e.g. by a preset); `None` if all are.
+    pub fn missing_keys(
+        &self,
+        required: &[&'static str],
+        mode: (u8, u8),
+        is_set: impl Fn(&str) -> bool,
+    ) -> Option<String> {
+        let missing_keys: Vec<&str> = required_keys(required, mode)
+            .into_iter()
+            .filter(|k| !self.seen_keys.contains_key(*k) && !is_set(k))
+            .collect();
+
+        (!missing_keys.is_empty()).then(|| {
+            format!(
+                "missing required keys for mode {}.{}: {}",
+                mode.0,
+                mode.1,
+                missing_keys.join(", ")
+            )
+        })
+    }
+}
+
+/// Feeds the lines above `comment_line` to the `dispatch` parsers, reading the keys
+/// renamed since config `version` under their new names. Every line is read; the
+/// problems are collected in `ConfigLines::errors` with their line numbers.
+pub fn read_config_lines<S>(
+    lines: &[String],
+    comment_line: &str,
+    version: u32,
+    migrations: &'static [KeyMigration],
+    dispatch: &HashMap<&'static str, KeyParser<S>>,
+    cfg: &mut S,
+) -> ConfigLines {
+    let mut read = ConfigLines::default();
+
+    for (line_num, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed == comment_line {
+            break;
+        }
+        // Blank lines separate groups of keys
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let mut parts = trimmed.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+
+        if key.is_empty() || value.is_empty() {
+            #[cfg(debug_assertions)]
+            eprintln!("⚠️ Warning: Malformed line {}: '{}'", line_num + 1, line);
+            continue;
+        }
+
+        let key = match config_version::migrate_key(key, version, migrations) {
+            Ok(None) => key,
+            Ok(Some(migration)) => {
+                eprintln!(
+                    "⚠️ Warning: Line {}: '{}' is read as '{}' (ConfigVersion {}); {}",
+                    line_num + 1,
+                    key,
+                    migration.new,
+                    version,
+                    migration.note
+                );
+                migration.new
+            }
+            Err(e) => {
+                let e = SettingsError::simple(key.to_string(), e);
+                read.errors.push(format!("line {}: {}", line_num + 1, e));
+                continue;
+            }
+        };
+
+        if let Some(&first_line) = read.seen_keys.get(key) {
+            read.errors.push(format!(
+                "line {}: duplicate key '{}' (first defined on line {})",
+                line_num + 1,
+                key,
+                first_line
+            ));
+            continue;
+        }
+        read.seen_keys.insert(key.to_string(), line_num + 1);
+
+        if key == CONFIG_VERSION_KEY {
+            continue;
+        }
+
+        match dispatch.get(key) {
+            Some(parser) => {
+                if let Err(e) = parser(value, cfg) {
+                    read.errors.push(format!("line {}: {}", line_num + 1, e));
+                }
+            }
+            None => {
+                let known_keys = dispatch.keys().copied().chain([CONFIG_VERSION_KEY]);
+                read.unknown
+                    .push(UnknownKey::new(key, line_num + 1, known_keys));
+            }
+        }
+    }
+
+    read
+}
+
+/// `required` (the keys of every mode) and the keys the mode reads: `dg` at 1.x, the gas
+/// balance at 2.x and the ballistic law at x.2 and x.3.
+pub fn required_keys(required: &[&'static str], (major, minor): (u8, u8)) -> Vec<&'static str> {
+    let mut keys = required.to_vec();
+    keys.extend_from_slice(match major {
+        1 => &["dg"][..],
+        _ => &["C_eq", "C0", "N_tot"],
+    });
+    keys.extend_from_slice(match minor {
+        2 => &["p_b"][..],
+        3 => &["p_b", "p_pow"],
+        _ => &[],
+    });
+    keys
+}
+
+/// One error of `file_name` listing all `errors`, so that a run reports every problem of
+/// its config at once; `Ok` if there are none.
+pub fn check_config_errors(
+    file_name: &'static str,
+    errors: &[String],
+) -> Result<(), SettingsError> {
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(SettingsError::simple(
+            file_name,
+            format!(
+                "{} problem(s):\n    {}",
+                errors.len(),
+                errors.join("\n    ")
+            ),
+        )),
+    }
+}
+
+pub fn get_exe_dir() -> IoResult<PathBuf> {
+    let exe_path = current_exe()?;
+
+    exe_path
+        .parent()
+        .map(|p| p.to_path_buf())
+        .ok_or_else(|| IoError::other("Failed to get the parent directory of the executable."))
+}
+
+pub fn write_state<W: Write>(writer: &mut W, state: &[u8]) -> IoResult<()> {
+    // Get the length of the state array
+    let len = state.len();
+    // If the array is empty, write only a newline character
+    if len == 0 {
+        return writer.write_all(b"\n").map(|_| ());
+    }
+
+    // Create a buffer with precise capacity: each byte (0 or 1) -> 1 character ('0' or '1') + (len-1) separators ':' + 1 newline character
+    let mut buffer = Vec::with_capacity(len + len.saturating_sub(1) + 1);
+
+    // Fill the buffer with values ('0' or '1') and separators ':'
+    buffer.extend(state.iter().flat_map(|&val| [val + b'0', b':']));
+    // Remove the last superfluous separator ':'
+    buffer.pop();
+    // Add the newline character
+    buffer.push(b'\n');
+
+    // Write the buffer to the file
+    writer.write_all(&buffer)?;
+    // Return a successful result
+    Ok(())
+}
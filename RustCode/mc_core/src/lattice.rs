@@ -0,0 +1,516 @@
+use memmap2::Mmap;
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{ErrorKind, Result as IoResult, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Storage type of the neighbour table; `--features idx-u32` halves its size for grids
+/// below 2^32 cells.
+#[cfg(feature = "idx-u32")]
+pub type Idx = u32;
+#[cfg(not(feature = "idx-u32"))]
+pub type Idx = u64;
+
+/// Missing neighbour (non-periodic boundary), as seen after casting an `Idx` to `usize`.
+pub const NO_NEIB: usize = Idx::MAX as usize;
+
+/// Grid dimensions and periodicity: everything the neighbour table depends on.
+type NeibsKey = (usize, usize, usize, bool, bool, bool);
+
+/// Process-wide cache of neighbour tables, shared by all items and by consecutive runs
+/// (e.g. in `serve` mode) with the same grid. Only the most recent tables are kept.
+static NEIBS_CACHE: OnceLock<Mutex<Vec<(NeibsKey, Neibs)>>> = OnceLock::new();
+const NEIBS_CACHE_CAP: usize = 4;
+
+/// Directory of the neighbour table files shared between runs; no files without it.
+pub const NEIBS_CACHE_ENV: &str = "MC_NEIBS_CACHE";
+const NEIBS_FILE_MAGIC: &[u8; 8] = b"MCNEIBS1";
+/// Magic, `Idx` width, an endianness probe and the key, padded so the table that follows
+/// stays aligned for `[Idx; 6]` in the page-aligned mapping.
+const NEIBS_FILE_HEADER_LEN: usize = 64;
+
+/// Neighbour table of a grid shape, built in memory or mapped from its cache file
+/// (`MC_NEIBS_CACHE`); clones share it.
+#[derive(Clone)]
+pub struct Neibs(Arc<NeibsData>);
+
+enum NeibsData {
+    Owned(Box<[[Idx; 6]]>),
+    Mapped { map: Mmap, len: usize },
+}
+
+impl Neibs {
+    fn owned(neibs: Vec<[Idx; 6]>) -> Self {
+        Self(Arc::new(NeibsData::Owned(neibs.into_boxed_slice())))
+    }
+
+    /// Whether the table is mapped from a cache file.
+    pub fn is_mapped(&self) -> bool {
+        matches!(*self.0, NeibsData::Mapped { .. })
+    }
+
+    fn file_path(dir: &Path, (nx, ny, nz, px, py, pz): NeibsKey) -> PathBuf {
+        dir.join(format!(
+            "neibs_{}x{}x{}_p{}{}{}_u{}.bin",
+            nx,
+            ny,
+            nz,
+            px as u8,
+            py as u8,
+            pz as u8,
+            Idx::BITS
+        ))
+    }
+
+    fn file_header(key: NeibsKey) -> [u8; NEIBS_FILE_HEADER_LEN] {
+        let (nx, ny, nz, px, py, pz) = key;
+        let mut header = [0u8; NEIBS_FILE_HEADER_LEN];
+        header[..8].copy_from_slice(NEIBS_FILE_MAGIC);
+        header[8..12].copy_from_slice(&Idx::BITS.to_ne_bytes());
+        header[12..16].copy_from_slice(&1u32.to_ne_bytes());
+        for (i, n) in [nx, ny, nz].into_iter().enumerate() {
+            header[16 + 8 * i..24 + 8 * i].copy_from_slice(&(n as u64).to_ne_bytes());
+        }
+        header[40..43].copy_from_slice(&[px as u8, py as u8, pz as u8]);
+        header
+    }
+
+    /// Maps the cache file of `key`; `Ok(None)` if it is missing, or was written for
+    /// another shape, `Idx` width or byte order.
+    fn map_file(path: &Path, key: NeibsKey, len: usize) -> IoResult<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // SAFETY: cache files are written to a temporary name and renamed into place, never
+        // modified afterwards, so the mapping does not change under the table.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let data_len = len * size_of::<[Idx; 6]>();
+        let is_valid = map.len() == NEIBS_FILE_HEADER_LEN + data_len
+            && map[..NEIBS_FILE_HEADER_LEN] == Self::file_header(key)
+            && (map.as_ptr() as usize + NEIBS_FILE_HEADER_LEN)
+                .is_multiple_of(align_of::<[Idx; 6]>());
+        Ok(is_valid.then(|| Self(Arc::new(NeibsData::Mapped { map, len }))))
+    }
+
+    /// Writes the table to the cache file of `key`, through a temporary file renamed into
+    /// place so that concurrent runs only ever map complete files.
+    fn write_file(&self, path: &Path, key: NeibsKey) -> IoResult<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let path_tmp = path.with_extension(format!("bin.{}.tmp", std::process::id()));
+        let written = File::create(&path_tmp).and_then(|mut file| {
+            file.write_all(&Self::file_header(key))?;
+            // SAFETY: `[Idx; 6]` is plain integers without padding; the bytes are the table.
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    self.as_ptr() as *const u8,
+                    self.len() * size_of::<[Idx; 6]>(),
+                )
+            };
+            file.write_all(bytes)?;
+            file.sync_all()
+        });
+
+        match written.and_then(|_| fs::rename(&path_tmp, path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&path_tmp);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Deref for Neibs {
+    type Target = [[Idx; 6]];
+
+    #[inline(always)]
+    fn deref(&self) -> &[[Idx; 6]] {
+        match &*self.0 {
+            NeibsData::Owned(neibs) => neibs,
+            // SAFETY: `map_file` checked the length and the alignment of the table, and
+            // every bit pattern is a valid `[Idx; 6]`.
+            NeibsData::Mapped { map, len } => unsafe {
+                std::slice::from_raw_parts(
+                    map.as_ptr().add(NEIBS_FILE_HEADER_LEN) as *const [Idx; 6],
+                    *len,
+                )
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Neibs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Neibs")
+            .field("len", &self.len())
+            .field("is_mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+/// Grid axis (0 - x, 1 - y, 2 - z) of every neighbour slot in `Grid::neibs`.
+pub const SLOT_AXES: [usize; 6] = [0, 0, 1, 1, 2, 2];
+
+/// Grid size that `Grid::new` cannot build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    /// A side of 0 cells.
+    ZeroSide { axis: char },
+    /// `nx * ny * nz` does not fit `usize`, or the neighbour index type `Idx`.
+    TooLarge { nx: usize, ny: usize, nz: usize },
+    /// A periodic side of 1 cell: the cell would be its own neighbour on both sides.
+    PeriodicSingleCell { axis: char },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroSide { axis } => write!(f, "the {} side of the grid has 0 cells", axis),
+            Self::TooLarge { nx, ny, nz } => write!(
+                f,
+                "a {}x{}x{} grid has more cells than the neighbour index holds ({})",
+                nx,
+                ny,
+                nz,
+                NO_NEIB - 1
+            ),
+            Self::PeriodicSingleCell { axis } => write!(
+                f,
+                "the {} side of the grid has 1 cell and is periodic (the cell would be its own neighbour)",
+                axis
+            ),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+#[derive(Debug)]
+pub struct Grid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    pub size: usize,
+    pub size_xy: usize,
+    pub size_zx: usize,
+    pub size_zy: usize,
+    pub px: bool,
+    pub py: bool,
+    pub pz: bool,
+    // pub states: Box<[u8]>,
+    pub nx_ib: Box<[usize]>,
+    pub ny_ib: Box<[usize]>,
+    pub nz_ib: Box<[usize]>,
+    pub neibs: Neibs,
+    /// Cells where attachment is allowed (template growth); empty means no restriction.
+    pub template: Box<[u8]>,
+    /// Grid axis of the crystal axes [100], [010], [001] (see `Settings::axis_perm`).
+    pub axis_map: [usize; 3],
+}
+
+impl Grid {
+    pub fn new(
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        px: bool,
+        py: bool,
+        pz: bool,
+    ) -> Result<Self, GridError> {
+        for (axis, n, periodic) in [('x', nx, px), ('y', ny, py), ('z', nz, pz)] {
+            match n {
+                0 => return Err(GridError::ZeroSide { axis }),
+                1 if periodic => return Err(GridError::PeriodicSingleCell { axis }),
+                _ => {}
+            }
+        }
+        // Cell indices must stay below `NO_NEIB`
+        let size = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .filter(|&size| size < NO_NEIB)
+            .ok_or(GridError::TooLarge { nx, ny, nz })?;
+
+        let mut grid = Grid {
+            nx,
+            ny,
+            nz,
+            size,
+            size_xy: nx * ny,
+            size_zx: nz * nx,
+            size_zy: nz * ny,
+            px,
+            py,
+            pz,
+            // states: vec![0u8; size].into_boxed_slice(),
+            nx_ib: vec![0; nx].into_boxed_slice(),
+            ny_ib: vec![0; ny].into_boxed_slice(),
+            nz_ib: vec![0; nz].into_boxed_slice(),
+            neibs: Neibs::owned(Vec::new()),
+            template: Box::new([]),
+            axis_map: [0, 1, 2],
+        };
+        grid.load_neibs();
+        Ok(grid)
+    }
+
+    /// Dimensions only, without the neighbour table: for the index arithmetic of frames
+    /// (`idx_to_xyz`, `region`, `slice`) outside the kernels.
+    pub fn shape(nx: usize, ny: usize, nz: usize) -> Self {
+        Grid {
+            nx,
+            ny,
+            nz,
+            size: nx * ny * nz,
+            size_xy: nx * ny,
+            size_zx: nz * nx,
+            size_zy: nz * ny,
+            px: false,
+            py: false,
+            pz: false,
+            nx_ib: Box::new([]),
+            ny_ib: Box::new([]),
+            nz_ib: Box::new([]),
+            neibs: Neibs::owned(Vec::new()),
+            template: Box::new([]),
+            axis_map: [0, 1, 2],
+        }
+    }
+
+    /// Takes the neighbour table from the process cache, else maps its cache file
+    /// (`MC_NEIBS_CACHE`), else computes it (and writes the file). The lock is only held
+    /// for the lookups, so grids of other shapes are built in parallel.
+    pub fn load_neibs(&mut self) {
+        let key = (self.nx, self.ny, self.nz, self.px, self.py, self.pz);
+        let cache = NEIBS_CACHE.get_or_init(|| Mutex::new(Vec::new()));
+        let cached = |cache: &[(NeibsKey, Neibs)]| {
+            cache
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, neibs)| neibs.clone())
+        };
+
+        if let Some(neibs) = cached(&cache.lock().unwrap()) {
+            self.neibs = neibs;
+            return;
+        }
+
+        let path_file = std::env::var_os(NEIBS_CACHE_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Neibs::file_path(Path::new(&dir), key));
+        let mapped = path_file.as_deref().and_then(|path| {
+            Neibs::map_file(path, key, self.size)
+                .inspect_err(|e| {
+                    eprintln!(
+                        "⚠️ Warning: Failed to map neighbour table '{}': {}",
+                        path.display(),
+                        e
+                    )
+                })
+                .ok()
+                .flatten()
+        });
+        match mapped {
+            Some(neibs) => self.neibs = neibs,
+            None => {
+                self.precomp_neibs();
+                if let Some(path) = &path_file
+                    && let Err(e) = self.neibs.write_file(path, key)
+                {
+                    eprintln!(
+                        "⚠️ Warning: Failed to write neighbour table '{}': {}",
+                        path.display(),
+                        e
+                    );
+                }
+            }
+        }
+
+        // Another grid of this shape may have been built meanwhile; share the first one.
+        let mut cache = cache.lock().unwrap();
+        match cached(&cache) {
+            Some(neibs) => self.neibs = neibs,
+            None => {
+                if cache.len() == NEIBS_CACHE_CAP {
+                    cache.remove(0);
+                }
+                cache.push((key, self.neibs.clone()));
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn slot_axis(slot: usize) -> usize {
+        SLOT_AXES[slot]
+    }
+
+    /// Reorders a per-crystal-axis triple ([100], [010], [001]) into grid axis order (x, y, z).
+    pub fn to_grid_axes<T: Copy>(&self, (a, b, c): (T, T, T)) -> (T, T, T) {
+        let mut out = [a; 3];
+        for (crystal_axis, val) in [a, b, c].into_iter().enumerate() {
+            out[self.axis_map[crystal_axis]] = val;
+        }
+        (out[0], out[1], out[2])
+    }
+
+    /// Moves per-crystal-axis bits (bit `i` - crystal axis `i`) to the bits of their grid axes.
+    pub fn mask_to_grid_axes(&self, mask: u8) -> u8 {
+        (0..3)
+            .filter(|&i| mask & (1 << i) != 0)
+            .fold(0, |acc, i| acc | (1 << self.axis_map[i]))
+    }
+
+    #[inline(always)]
+    pub fn is_in_template(&self, idx: usize) -> bool {
+        self.template.is_empty() || self.template[idx] != 0
+    }
+
+    #[inline(always)]
+    pub fn xyz_to_idx(&self, x: usize, y: usize, z: usize) -> usize {
+        z + y * self.nz + x * self.size_zy
+    }
+
+    #[inline(always)]
+    pub fn idx_to_xyz(&self, idx: usize) -> (usize, usize, usize) {
+        let z = idx % self.nz;
+        let y = (idx / self.nz) % self.ny;
+        let x = idx / self.size_zy;
+        (x, y, z)
+    }
+
+    /// Cells of the box `lo..=hi` (inclusive, per axis), every `stride`-th one from `lo`,
+    /// in the x-y-z order of the frames. Bounds past the grid are clipped.
+    pub fn region(
+        &self,
+        lo: [usize; 3],
+        hi: [usize; 3],
+        stride: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let sizes = [self.nx, self.ny, self.nz];
+        let axis =
+            move |a: usize| (lo[a]..=hi[a].min(sizes[a].saturating_sub(1))).step_by(stride.max(1));
+
+        axis(0).flat_map(move |x| {
+            axis(1).flat_map(move |y| axis(2).map(move |z| self.xyz_to_idx(x, y, z)))
+        })
+    }
+
+    /// The plane `axis == pos` of `state` (0 - x, 1 - y, 2 - z) as a contiguous row-major
+    /// buffer with its `(rows, cols)`: rows run along the lower of the other two axes.
+    pub fn slice(&self, state: &[u8], axis: usize, pos: usize) -> (Vec<u8>, (usize, usize)) {
+        match axis {
+            0 => {
+                let start = pos * self.size_zy;
+                (
+                    state[start..start + self.size_zy].to_vec(),
+                    (self.ny, self.nz),
+                )
+            }
+            1 => (
+                self.region([0, pos, 0], [self.nx, pos, self.nz], 1)
+                    .map(|idx| state[idx])
+                    .collect(),
+                (self.nx, self.nz),
+            ),
+            _ => (
+                self.region([0, 0, pos], [self.nx, self.ny, pos], 1)
+                    .map(|idx| state[idx])
+                    .collect(),
+                (self.nx, self.ny),
+            ),
+        }
+    }
+
+    #[inline(always)]
+    fn xyz_to_periodic_sub(coord: isize, dim_size: usize, periodic: bool) -> usize {
+        let dim = dim_size as isize;
+        if (0..dim).contains(&coord) {
+            coord as usize
+        } else if periodic {
+            coord.rem_euclid(dim) as usize
+        } else {
+            usize::MAX
+        }
+    }
+
+    #[inline(always)]
+    pub fn xyz_to_periodic(&self, x: isize, y: isize, z: isize) -> (usize, usize, usize) {
+        (
+            Self::xyz_to_periodic_sub(x, self.nx, self.px),
+            Self::xyz_to_periodic_sub(y, self.ny, self.py),
+            Self::xyz_to_periodic_sub(z, self.nz, self.pz),
+        )
+    }
+
+    #[inline(always)]
+    pub fn precomp_neibs(&mut self) {
+        let (nx, ny, nz) = (self.nx, self.ny, self.nz);
+        let (px, py, pz) = (self.px, self.py, self.pz);
+        let mut neibs = vec![[Idx::MAX; 6]; self.size];
+
+        for (idx, neibs_entry) in neibs.iter_mut().enumerate() {
+            let (x, y, z) = self.idx_to_xyz(idx);
+            let x = x as isize;
+            let y = y as isize;
+            let z = z as isize;
+
+            let neighbors = [
+                (x - 1, y, z),
+                (x + 1, y, z),
+                (x, y - 1, z),
+                (x, y + 1, z),
+                (x, y, z - 1),
+                (x, y, z + 1),
+            ];
+
+            for (i, &(xi, yi, zi)) in neighbors.iter().enumerate() {
+                let xpi = Self::xyz_to_periodic_sub(xi, nx, px);
+                let ypi = Self::xyz_to_periodic_sub(yi, ny, py);
+                let zpi = Self::xyz_to_periodic_sub(zi, nz, pz);
+
+                neibs_entry[i] = if xpi != usize::MAX && ypi != usize::MAX && zpi != usize::MAX {
+                    (zpi + ypi * self.nz + xpi * self.size_zy) as Idx
+                } else {
+                    Idx::MAX
+                };
+            }
+        }
+
+        self.neibs = Neibs::owned(neibs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_file_maps_back_the_table() {
+        let dir = std::env::temp_dir().join(format!("mc_core_neibs_{}", std::process::id()));
+        let grid = Grid::new(5, 4, 3, true, false, true).unwrap();
+        let key = (5, 4, 3, true, false, true);
+        let path = Neibs::file_path(&dir, key);
+
+        assert!(Neibs::map_file(&path, key, grid.size).unwrap().is_none());
+        grid.neibs.write_file(&path, key).unwrap();
+        let mapped = Neibs::map_file(&path, key, grid.size).unwrap().unwrap();
+        assert!(mapped.is_mapped());
+        assert_eq!(&*mapped, &*grid.neibs);
+
+        // A file of another shape or periodicity is not taken.
+        let other = (5, 4, 3, true, true, true);
+        assert!(Neibs::map_file(&path, other, grid.size).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
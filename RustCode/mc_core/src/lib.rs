@@ -0,0 +1,20 @@
+//! Code shared by `model_1_001` and `model_1_002`: the lattice and its neighbour table,
+//! the frontier, the neighbour sums and energies of a site, the reading of
+//! `InitSettings.ini` and the settings error, the state lines and the common constants.
+//!
+//! Each model keeps its own keys (the dispatch tables and `Settings`), kernels and outputs.
+
+pub mod acceptance;
+pub mod ballistics;
+pub mod config_version;
+pub mod constants;
+pub mod energy;
+pub mod frontier;
+pub mod io_handler;
+pub mod lattice;
+pub mod mode;
+pub mod settings;
+pub mod state_header;
+pub mod summation;
+pub mod unknown_keys;
+pub mod utils;
@@ -0,0 +1,38 @@
+//! Simulation mode `MAJOR.MINOR` (the `mode` key), kept as integers: the kernels are
+//! picked by exact matches, which a value parsed as a float does not guarantee.
+
+/// Modes of both models: 1.X at a fixed `dg`, 2.X with the gas balance; `X` = 1 without,
+/// 2 with a constant, 3 with an energy-dependent ballistic detachment.
+pub const MODES: [(u8, u8); 6] = [(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)];
+
+/// `(major, minor)` of a `MAJOR.MINOR` value, e.g. `2.1` -> `(2, 1)`.
+pub fn parse_mode(value: &str) -> Result<(u8, u8), String> {
+    let invalid = || format!("bad value '{}' (MAJOR.MINOR, e.g. 2.1)", value.trim());
+    let (major, minor) = value.trim().split_once('.').ok_or_else(invalid)?;
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(major) || !is_digits(minor) {
+        return Err(invalid());
+    }
+
+    Ok((
+        major.parse().map_err(|_| invalid())?,
+        minor.parse().map_err(|_| invalid())?,
+    ))
+}
+
+/// `Err` for a mode outside `MODES`.
+pub fn check_mode((major, minor): (u8, u8)) -> Result<(), String> {
+    match MODES.contains(&(major, minor)) {
+        true => Ok(()),
+        false => Err(format!(
+            "unknown mode {}.{} (available: {})",
+            major,
+            minor,
+            MODES
+                .iter()
+                .map(|(major, minor)| format!("{}.{}", major, minor))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
@@ -0,0 +1,82 @@
+//! Error of a settings key, shared by the config readers and the validation of both
+//! models.
+
+use std::{borrow::Cow, error::Error, fmt};
+
+#[derive(Debug)]
+pub struct SettingsError {
+    pub key: Option<Cow<'static, str>>,
+    pub value: Option<String>,
+    pub source: Box<dyn Error + Send + Sync>,
+}
+
+impl SettingsError {
+    pub fn new<K: Into<Cow<'static, str>>, V: Into<String>, E: Error + Send + Sync + 'static>(
+        key: K,
+        value: V,
+        source: E,
+    ) -> Self {
+        Self {
+            key: Some(key.into()),
+            value: Some(value.into()),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn simple<K: Into<Cow<'static, str>>, M: Into<String>>(key: K, message: M) -> Self {
+        Self {
+            key: Some(key.into()),
+            value: None,
+            source: Box::new(SimpleMsg(message.into())),
+        }
+    }
+
+    pub fn from_io(err: std::io::Error) -> Self {
+        Self {
+            key: None,
+            value: None,
+            source: Box::new(err),
+        }
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.key, &self.value) {
+            (Some(key), Some(value)) => {
+                write!(
+                    f,
+                    "Failed to parse '{}' with value '{}': {}",
+                    key, value, self.source
+                )
+            }
+            (Some(key), None) => {
+                write!(f, "Invalid value for '{}': {}", key, self.source)
+            }
+            _ => write!(f, "Settings error: {}", self.source),
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(err: std::io::Error) -> Self {
+        Self::from_io(err)
+    }
+}
+
+#[derive(Debug)]
+struct SimpleMsg(String);
+
+impl fmt::Display for SimpleMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for SimpleMsg {}
@@ -0,0 +1,192 @@
+//! Header line of the state files (`InitStates.ini`, `TimeStates.txt`, `Checkpoint.txt`).
+//!
+//! `#states v=1 dims=SX:SY:SZ enc=bit` describes the state lines that follow it, up to
+//! the next header: `dims` is the shape of one state (a line holds `SX*SY*SZ` values),
+//! `enc` the value encoding (`bit`: 0/1, `u8`: 0..=255). A file without a header is read
+//! the legacy way, checking only the number of values.
+
+use std::fmt;
+
+pub const STATE_HEADER_TAG: &str = "#states";
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateEncoding {
+    Bit,
+    U8,
+}
+
+impl StateEncoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Bit => "bit",
+            Self::U8 => "u8",
+        }
+    }
+
+    fn max_value(self) -> u8 {
+        match self {
+            Self::Bit => 1,
+            Self::U8 => u8::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateHeader {
+    pub version: u32,
+    pub dims: (usize, usize, usize),
+    pub encoding: StateEncoding,
+}
+
+impl fmt::Display for StateHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (sx, sy, sz) = self.dims;
+        write!(
+            f,
+            "{} v={} dims={}:{}:{} enc={}",
+            STATE_HEADER_TAG,
+            self.version,
+            sx,
+            sy,
+            sz,
+            self.encoding.as_str()
+        )
+    }
+}
+
+impl StateHeader {
+    /// Header of the current version for 0/1 states of shape `dims`.
+    pub fn new(dims: (usize, usize, usize)) -> Self {
+        Self {
+            version: STATE_FORMAT_VERSION,
+            dims,
+            encoding: StateEncoding::Bit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dims.0 * self.dims.1 * self.dims.2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_header(line: &str) -> bool {
+        line.trim_start().starts_with(STATE_HEADER_TAG)
+    }
+
+    /// Parses a header line; all fields are required and unknown ones are rejected.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields = line
+            .trim()
+            .strip_prefix(STATE_HEADER_TAG)
+            .ok_or_else(|| format!("not a state header: '{}'", line.trim()))?;
+
+        let (mut version, mut dims, mut encoding) = (None, None, None);
+        for field in fields.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("malformed header field '{}'", field))?;
+            match key {
+                "v" => {
+                    version = Some(
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| format!("bad version '{}'", value))?,
+                    )
+                }
+                "dims" => {
+                    let sizes: Vec<usize> = value
+                        .split(':')
+                        .map(|s| s.parse::<usize>())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| format!("bad dims '{}'", value))?;
+                    dims = match sizes[..] {
+                        [sx, sy, sz] if sx > 0 && sy > 0 && sz > 0 => Some((sx, sy, sz)),
+                        _ => return Err(format!("dims must be 'SX:SY:SZ' > 0, got '{}'", value)),
+                    };
+                }
+                "enc" => {
+                    encoding = Some(match value {
+                        "bit" => StateEncoding::Bit,
+                        "u8" => StateEncoding::U8,
+                        _ => return Err(format!("unknown encoding '{}' (bit, u8)", value)),
+                    })
+                }
+                _ => return Err(format!("unknown header field '{}'", key)),
+            }
+        }
+
+        let header = Self {
+            version: version.ok_or("missing 'v' field")?,
+            dims: dims.ok_or("missing 'dims' field")?,
+            encoding: encoding.ok_or("missing 'enc' field")?,
+        };
+        if header.version > STATE_FORMAT_VERSION {
+            return Err(format!(
+                "format version {} is newer than supported {}",
+                header.version, STATE_FORMAT_VERSION
+            ));
+        }
+
+        Ok(header)
+    }
+
+    /// Checks that the states are of shape `dims` (the grid they are loaded into).
+    pub fn check_dims(&self, dims: (usize, usize, usize)) -> Result<(), String> {
+        match self.dims == dims {
+            true => Ok(()),
+            false => Err(format!(
+                "header dims {}:{}:{} do not match grid {}:{}:{}",
+                self.dims.0, self.dims.1, self.dims.2, dims.0, dims.1, dims.2
+            )),
+        }
+    }
+
+    /// Parses a state line described by this header.
+    pub fn parse_values(&self, line: &(impl AsRef<[u8]> + ?Sized)) -> Result<Vec<u8>, String> {
+        let values = parse_state_line(line.as_ref(), self.encoding.max_value()).map_err(|s| {
+            format!(
+                "value '{}' is not valid for encoding '{}'",
+                s,
+                self.encoding.as_str()
+            )
+        })?;
+
+        match values.len() == self.len() {
+            true => Ok(values),
+            false => Err(format!(
+                "expected {} values (dims {}:{}:{}), got {}",
+                self.len(),
+                self.dims.0,
+                self.dims.1,
+                self.dims.2,
+                values.len()
+            )),
+        }
+    }
+}
+
+/// Values of a `:`-separated state line, each at most `max_value`; `Err` holds the first
+/// token that is not one. Works on the bytes (no UTF-8 check, single digits without
+/// `parse`): loading lines of millions of values is bound by this loop.
+pub fn parse_state_line(line: &[u8], max_value: u8) -> Result<Vec<u8>, String> {
+    let mut values = Vec::with_capacity(line.len() / 2 + 1);
+    for token in line.trim_ascii().split(|&b| b == b':') {
+        let token = token.trim_ascii();
+        let value = match *token {
+            [d @ b'0'..=b'9'] => Some(d - b'0'),
+            _ => std::str::from_utf8(token)
+                .ok()
+                .and_then(|s| s.parse::<u8>().ok()),
+        };
+        match value {
+            Some(v) if v <= max_value => values.push(v),
+            _ => return Err(String::from_utf8_lossy(token).into_owned()),
+        }
+    }
+
+    Ok(values)
+}
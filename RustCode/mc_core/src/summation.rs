@@ -0,0 +1,88 @@
+//! Compensated summation of the accumulated energy changes of both models.
+
+/// Neumaier (improved Kahan) compensated summation.
+///
+/// Keeps a running compensation term so that long sequences of small `±dE`
+/// contributions do not drift because of f64 round-off.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NeumaierSum {
+    sum: f64,
+    comp: f64,
+    abs_sum: f64,
+    count: u64,
+}
+
+impl NeumaierSum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, x: f64) {
+        let t = self.sum + x;
+        if self.sum.abs() >= x.abs() {
+            self.comp += (self.sum - t) + x;
+        } else {
+            self.comp += (x - t) + self.sum;
+        }
+        self.sum = t;
+        self.abs_sum += x.abs();
+        self.count += 1;
+    }
+
+    #[inline(always)]
+    pub fn value(&self) -> f64 {
+        self.sum + self.comp
+    }
+
+    /// Running sum, compensation, sum of magnitudes and term count, e.g. for a checkpoint.
+    pub fn parts(&self) -> (f64, f64, f64, u64) {
+        (self.sum, self.comp, self.abs_sum, self.count)
+    }
+
+    /// Reverse of `parts`.
+    pub fn from_parts((sum, comp, abs_sum, count): (f64, f64, f64, u64)) -> Self {
+        Self {
+            sum,
+            comp,
+            abs_sum,
+            count,
+        }
+    }
+
+    /// Upper estimate of the absolute rounding error of `value()`:
+    /// `2u|S| + n u^2 Σ|x_i|`, with `u` the unit round-off.
+    pub fn error_bound(&self) -> f64 {
+        let u = f64::EPSILON * 0.5;
+        2.0 * u * self.value().abs() + (self.count as f64) * u * u * self.abs_sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_terms_lost_to_round_off() {
+        let mut sum = NeumaierSum::new();
+        for x in [1e16, 1.0, -1e16] {
+            sum.add(x);
+        }
+        assert_eq!(sum.value(), 1.0);
+        assert_eq!(1e16 + 1.0 - 1e16, 0.0);
+    }
+
+    #[test]
+    fn parts_round_trip() {
+        let mut sum = NeumaierSum::new();
+        for i in 0..100 {
+            sum.add(0.1 * i as f64);
+        }
+        let restored = NeumaierSum::from_parts(sum.parts());
+        assert_eq!(restored.value().to_bits(), sum.value().to_bits());
+        assert_eq!(
+            restored.error_bound().to_bits(),
+            sum.error_bound().to_bits()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Unknown keys of `InitSettings.ini`.
+//!
+//! A mistyped key would otherwise leave its setting at the default without a word, so
+//! every key no setting reads is collected with the closest known key as a suggestion
+//! and reported at startup; the models refuse to run with any of them under `Strict`.
+
+use std::fmt;
+
+/// Key of the config file that no setting reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    pub key: String,
+    /// Line of the file, from 1.
+    pub line: usize,
+    /// Closest known key, when it is close enough to be a likely typo.
+    pub suggestion: Option<&'static str>,
+}
+
+impl UnknownKey {
+    pub fn new(key: &str, line: usize, known: impl IntoIterator<Item = &'static str>) -> Self {
+        Self {
+            key: key.to_string(),
+            line,
+            suggestion: closest_key(key, known),
+        }
+    }
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' (line {}", self.key, self.line)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "; did you mean '{}'?", suggestion)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Closest of `known` to `key` by the edit distance ignoring case (a swap of two adjacent
+/// letters counts as one edit), if it is at most a third of the key length (at least
+/// one edit) and the key is not replaced entirely; ties go to the alphabetically first.
+pub fn closest_key<'a>(key: &str, known: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let key_lower: Vec<char> = key.to_lowercase().chars().collect();
+    let len = key_lower.len();
+    let max_dist = (len / 3).max(1).min(len.saturating_sub(1));
+
+    known
+        .into_iter()
+        .map(|candidate| {
+            let candidate_lower: Vec<char> = candidate.to_lowercase().chars().collect();
+            (edit_distance(&key_lower, &candidate_lower), candidate)
+        })
+        .filter(|&(dist, _)| dist <= max_dist)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Startup report of `unknown` keys of `file`; empty when there are none.
+pub fn report(unknown: &[UnknownKey], file: &str) -> String {
+    if unknown.is_empty() {
+        return String::new();
+    }
+
+    let mut report = format!(
+        "⚠️ Warning: {} unknown key(s) in {} are ignored (their settings keep the defaults):",
+        unknown.len(),
+        file
+    );
+    for key in unknown {
+        report += &format!("\n    {}", key);
+    }
+    report
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions and swaps of
+/// adjacent characters.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    rows[0] = (0..=b.len()).collect();
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            let mut dist = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist = dist.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = dist;
+        }
+    }
+    rows[a.len()][b.len()]
+}
@@ -0,0 +1,50 @@
+//! Site-level helpers of the kernels of both models.
+
+use crate::{
+    frontier::Frontier,
+    lattice::{Idx, NO_NEIB, SLOT_AXES},
+};
+
+/// Adds the fronts of `states` to `front`: gas sites next to the crystal (TPA) and
+/// crystal sites next to the gas (TPB). Returns the crystal size.
+pub fn fill_front(states: &[u8], neibs: &[[Idx; 6]], front: &mut Frontier) -> f64 {
+    let mut cluster_size = 0.0;
+
+    for (i, &state) in states.iter().enumerate() {
+        if state == 1 {
+            cluster_size += 1.0;
+
+            let mut has_vacancy_neighbor = false;
+
+            for neib_idx in neibs[i].map(|n| n as usize) {
+                if neib_idx != NO_NEIB && states[neib_idx] == 0 {
+                    has_vacancy_neighbor = true;
+                    front.tpa_add(neib_idx);
+                }
+            }
+
+            if has_vacancy_neighbor {
+                front.tpb_add(i);
+            }
+        }
+    }
+
+    cluster_size
+}
+
+#[inline(always)]
+pub fn compute_neighbor_sums(states: &[u8], idxg_nis: &[Idx; 6]) -> (u8, u8, u8) {
+    let mut axis_neighbors = [0u8; 3];
+
+    for i in 0..6 {
+        let idx = unsafe { *idxg_nis.get_unchecked(i) } as usize;
+        if idx != NO_NEIB {
+            let state = unsafe { *states.get_unchecked(idx) };
+            if state == 1 {
+                axis_neighbors[SLOT_AXES[i]] += 1;
+            }
+        }
+    }
+
+    (axis_neighbors[0], axis_neighbors[1], axis_neighbors[2])
+}
@@ -0,0 +1,9 @@
+//! The model modules as a library, shared by the `model_1_001` binary and other
+//! programs that embed the model through `Simulation::run`.
+
+pub mod mods;
+
+pub use mods::{
+    run::{SimResult, Simulation},
+    settings::{Settings, SettingsError},
+};
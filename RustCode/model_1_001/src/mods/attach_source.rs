@@ -0,0 +1,94 @@
+use crate::mods::lattice::Grid;
+
+/// Eligibility of a sampled TPA site for attachment. The kernels of modes 1.x draw the
+/// site from the whole front as before and skip the attempt on a site the filter
+/// rejects, so an always-true filter leaves the dynamics (and the random sequence)
+/// unchanged.
+pub trait AttachFilter {
+    fn allows(&self, grid: &Grid, idxg: usize) -> bool;
+}
+
+/// Where the growth units come from (`AttachSource`), a stand-in for transport limits
+/// without a diffusion field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttachSource {
+    /// Every TPA site.
+    #[default]
+    Any,
+    /// Vapour feed from a face of the box: only sites with no crystal cell between them
+    /// and that face along the axis (`axis` 0 - x, 1 - y, 2 - z; `up` - the high face).
+    Sight { axis: usize, up: bool },
+    /// Substrate feed: only sites closer than `dist` cells to the face.
+    Near { axis: usize, up: bool, dist: usize },
+}
+
+impl AttachSource {
+    /// `any`, `sight:<face>` or `near:<face>:D`, the face as `+z`, `-x`, ...
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let mut parts = s.split(':').map(str::trim);
+        let source = match (parts.next()?, parts.next(), parts.next()) {
+            ("any" | "none" | "", None, None) => Self::Any,
+            ("sight", Some(face), None) => {
+                let (axis, up) = parse_face(face)?;
+                Self::Sight { axis, up }
+            }
+            ("near", Some(face), Some(dist)) => {
+                let (axis, up) = parse_face(face)?;
+                let dist = dist.parse().ok().filter(|&d| d > 0)?;
+                Self::Near { axis, up, dist }
+            }
+            _ => return None,
+        };
+        parts.next().is_none().then_some(source)
+    }
+}
+
+impl AttachFilter for AttachSource {
+    #[inline(always)]
+    fn allows(&self, grid: &Grid, idxg: usize) -> bool {
+        match *self {
+            Self::Any => true,
+            Self::Sight { axis, up } => {
+                let (x, y, z) = grid.lattice.idx_to_xyz(idxg);
+                let (pos, size, stride) = match axis {
+                    0 => (x, grid.lattice.nx, grid.lattice.size_zy),
+                    1 => (y, grid.lattice.ny, grid.lattice.nz),
+                    _ => (z, grid.lattice.nz, 1),
+                };
+                match up {
+                    true => (1..size - pos).all(|k| grid.states[idxg + k * stride] == 0),
+                    false => (1..=pos).all(|k| grid.states[idxg - k * stride] == 0),
+                }
+            }
+            Self::Near { axis, up, dist } => {
+                let (x, y, z) = grid.lattice.idx_to_xyz(idxg);
+                let (pos, size) = match axis {
+                    0 => (x, grid.lattice.nx),
+                    1 => (y, grid.lattice.ny),
+                    _ => (z, grid.lattice.nz),
+                };
+                match up {
+                    true => size - 1 - pos < dist,
+                    false => pos < dist,
+                }
+            }
+        }
+    }
+}
+
+fn parse_face(face: &str) -> Option<(usize, bool)> {
+    let (sign, axis) = face.split_at_checked(1)?;
+    let up = match sign {
+        "+" => true,
+        "-" => false,
+        _ => return None,
+    };
+    let axis = match axis {
+        "x" => 0,
+        "y" => 1,
+        "z" => 2,
+        _ => return None,
+    };
+    Some((axis, up))
+}
@@ -1,7 +1,28 @@
-/// Boltzmann constant in Joules per Kelvin.
-pub const K_BOLTZMANN: f64 = 1.380649e-23;
+pub use mc_core::constants::*;
 
-pub const CONFIG_FILE_NAME: &str = "InitSettings.ini";
-pub const INIT_TIME_STATES_FILE_NAME: &str = "InitStates.ini";
-pub const TIME_STATES_FILE_NAME: &str = "TimeStates.txt";
-pub const COMMENT_LINE: &str = "/////////////////////// | Для коментарів | /////////////////////////";
+use crate::mods::config_version::KeyMigration;
+
+/// Keys that must be present in the config file; all other keys fall back to defaults.
+pub const REQUIRED_CONFIG_KEYS: [&str; 10] = [
+    "DirPrefix",
+    "Seed",
+    "Sx",
+    "Sy",
+    "Sz",
+    "T",
+    "mode",
+    "LoadPrev",
+    "StepLim",
+    "WriteI",
+];
+
+/// Keys of older configs (and of `model_1_002` configs) read under the current names.
+pub const KEY_MIGRATIONS: [KeyMigration; 1] = [KeyMigration {
+    old: "LoadOption",
+    new: "LoadPrev",
+    removed_in: 2,
+    note: "LoadPrev -1 loads the last state of InitStates.ini, N the state N; LoadOption of model_1_002 loads several",
+}];
+
+pub const COMMENT_LINE: &str =
+    "/////////////////////// | Для коментарів | /////////////////////////";
@@ -0,0 +1,448 @@
+//! Domain-decomposed kernel of mode 1.1 (`Domains` > 1) for a single crystal on a very
+//! large grid.
+//!
+//! The grid is cut along x into `2 * Domains` slabs, each with its own front (in slab-local
+//! indices) and its own ChaCha stream. A sweep runs the even slabs in parallel, then the
+//! odd ones: a working slab only writes its own cells and only reads the idle slabs next
+//! to it, so the phase is race-free. Front sites of a neighbouring slab touched by an event
+//! are reconciled after each phase.
+//!
+//! The dynamics are APPROXIMATE: the attempts of a sweep are shared between the slabs in
+//! proportion to their front sizes at the start of the sweep, and the slabs of one colour
+//! do not see each other's events until the sweep ends. Runs are reproducible for the
+//! same `Seed`, `Domains` and `DomainSweep`, but do not match the serial kernel.
+
+use crate::mods::{
+    acceptance::AcceptanceRule,
+    energy::{attach_energy, detach_energy},
+    frontier::Frontier,
+    lattice::{Grid, Idx, NO_NEIB},
+    rng_streams::{RngStream, RngStreams},
+};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::thread;
+
+/// Surface energies and driving force of one sweep.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepEnergies {
+    pub ex2: f64,
+    pub ey2: f64,
+    pub ez2: f64,
+    pub k_t: f64,
+    pub delta_gibbs: f64,
+    pub acceptance: AcceptanceRule,
+}
+
+/// Result of one sweep over all slabs.
+#[derive(Debug, Default)]
+pub struct SweepOutcome {
+    /// Surface energy change of every slab and phase, in slab order.
+    pub energy_changes: Vec<f64>,
+    pub n_added: u64,
+    pub n_removed: u64,
+    /// An accepted event next to the sample boundary (non-periodic axis).
+    pub hit_boundary: bool,
+}
+
+#[derive(Debug)]
+struct Slab {
+    /// Global index range `[start, end)`, whole yz planes.
+    start: usize,
+    end: usize,
+    /// Front in slab-local indices (`idxg - start`).
+    front: Frontier,
+    rng: ChaCha8Rng,
+}
+
+#[derive(Debug, Default)]
+struct SlabOutcome {
+    energy_change: f64,
+    n_added: u64,
+    n_removed: u64,
+    /// Front candidates of other slabs, global indices.
+    touched: Vec<usize>,
+    hit_boundary: bool,
+}
+
+/// Cells of a working slab and read-only views of the idle slabs on both sides.
+struct SlabView<'a> {
+    own: &'a mut [u8],
+    start: usize,
+    left: (&'a [u8], usize),
+    right: (&'a [u8], usize),
+}
+
+impl SlabView<'_> {
+    #[inline(always)]
+    fn is_own(&self, idxg: usize) -> bool {
+        idxg >= self.start && idxg < self.start + self.own.len()
+    }
+
+    #[inline(always)]
+    fn get(&self, idxg: usize) -> u8 {
+        if self.is_own(idxg) {
+            return self.own[idxg - self.start];
+        }
+        let (cells, start) = match idxg >= self.left.1 && idxg < self.left.1 + self.left.0.len() {
+            true => self.left,
+            false => self.right,
+        };
+        cells[idxg - start]
+    }
+
+    #[inline(always)]
+    fn has_neib_in(&self, idxg_nis: &[Idx; 6], state: u8) -> bool {
+        idxg_nis
+            .iter()
+            .any(|&n| n as usize != NO_NEIB && self.get(n as usize) == state)
+    }
+}
+
+#[derive(Debug)]
+pub struct Domains {
+    slabs: Vec<Slab>,
+    /// First x plane of every slab.
+    x_starts: Vec<usize>,
+    size_zy: usize,
+}
+
+impl Domains {
+    /// Splits the grid into `2 * n_threads` slabs and hands every site of `front` to the
+    /// front of its slab. The slab streams are seeded from the site stream of `rng`.
+    pub fn new(grid: &Grid, front: &Frontier, n_threads: usize, rng: &mut RngStreams) -> Self {
+        let n_slabs = 2 * n_threads;
+        let x_starts: Vec<usize> = (0..n_slabs)
+            .map(|k| k * grid.lattice.nx / n_slabs)
+            .collect();
+
+        let mut slabs: Vec<Slab> = (0..n_slabs)
+            .map(|k| {
+                let x_end = x_starts.get(k + 1).copied().unwrap_or(grid.lattice.nx);
+                let (start, end) = (
+                    x_starts[k] * grid.lattice.size_zy,
+                    x_end * grid.lattice.size_zy,
+                );
+                Slab {
+                    start,
+                    end,
+                    front: Frontier::new(end - start),
+                    rng: ChaCha8Rng::seed_from_u64(rng.get(RngStream::Site).random()),
+                }
+            })
+            .collect();
+
+        let mut domains = Self {
+            slabs: Vec::new(),
+            x_starts,
+            size_zy: grid.lattice.size_zy,
+        };
+        for &idxg in &front.tpas[..front.tpas_size] {
+            let slab = &mut slabs[domains.slab_of(idxg)];
+            slab.front.tpa_add(idxg - slab.start);
+        }
+        for &idxg in &front.tpbs[..front.tpbs_size] {
+            let slab = &mut slabs[domains.slab_of(idxg)];
+            slab.front.tpb_add(idxg - slab.start);
+        }
+        domains.slabs = slabs;
+
+        domains
+    }
+
+    pub fn len(&self) -> usize {
+        self.slabs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slabs.is_empty()
+    }
+
+    #[inline(always)]
+    fn slab_of(&self, idxg: usize) -> usize {
+        let x = idxg / self.size_zy;
+        self.x_starts.partition_point(|&x_start| x_start <= x) - 1
+    }
+
+    pub fn front_sizes(&self) -> (usize, usize) {
+        self.slabs.iter().fold((0, 0), |(tpas, tpbs), slab| {
+            (tpas + slab.front.tpas_size, tpbs + slab.front.tpbs_size)
+        })
+    }
+
+    /// Global indices of all crystal front sites.
+    pub fn tpbs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slabs.iter().flat_map(|slab| {
+            slab.front.tpbs[..slab.front.tpbs_size]
+                .iter()
+                .map(move |&idxl| idxl + slab.start)
+        })
+    }
+
+    /// One sweep of `n_add` add and `n_rem` remove attempts over the whole grid.
+    pub fn sweep(
+        &mut self,
+        grid: &mut Grid,
+        rng: &mut RngStreams,
+        n_add: u64,
+        n_rem: u64,
+        energies: SweepEnergies,
+    ) -> SweepOutcome {
+        let adds = self.share(n_add, |front| front.tpas_size, rng);
+        let rems = self.share(n_rem, |front| front.tpbs_size, rng);
+
+        let mut outcome = SweepOutcome::default();
+        for colour in 0..2 {
+            let slab_outcomes = self.run_phase(grid, colour, &adds, &rems, energies);
+
+            for slab_outcome in slab_outcomes {
+                outcome.energy_changes.push(slab_outcome.energy_change);
+                outcome.n_added += slab_outcome.n_added;
+                outcome.n_removed += slab_outcome.n_removed;
+                outcome.hit_boundary |= slab_outcome.hit_boundary;
+                for idxg in slab_outcome.touched {
+                    self.reconcile(grid, idxg);
+                }
+            }
+        }
+
+        outcome
+    }
+
+    /// Splits `n_attempts` between the slabs in proportion to `size(front)`, rounding
+    /// every share up or down at random so the expected total stays `n_attempts`.
+    fn share(
+        &self,
+        n_attempts: u64,
+        size: impl Fn(&Frontier) -> usize,
+        rng: &mut RngStreams,
+    ) -> Vec<u64> {
+        let total: usize = self.slabs.iter().map(|slab| size(&slab.front)).sum();
+        self.slabs
+            .iter()
+            .map(|slab| match total {
+                0 => 0,
+                _ => {
+                    let expected = n_attempts as f64 * size(&slab.front) as f64 / total as f64;
+                    let floor = expected.floor();
+                    match expected - floor > rng.get(RngStream::Site).random::<f64>() {
+                        true => floor as u64 + 1,
+                        false => floor as u64,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn run_phase(
+        &mut self,
+        grid: &mut Grid,
+        colour: usize,
+        adds: &[u64],
+        rems: &[u64],
+        energies: SweepEnergies,
+    ) -> Vec<SlabOutcome> {
+        let n_slabs = self.slabs.len();
+        let neibs = &grid.lattice.neibs;
+
+        let mut cells: Vec<&mut [u8]> = Vec::with_capacity(n_slabs);
+        let mut rest: &mut [u8] = &mut grid.states;
+        for slab in &self.slabs {
+            let (head, tail) = rest.split_at_mut(slab.end - slab.start);
+            cells.push(head);
+            rest = tail;
+        }
+
+        let mut working = Vec::new();
+        let mut idle: Vec<Option<&[u8]>> = vec![None; n_slabs];
+        for (k, slab_cells) in cells.into_iter().enumerate() {
+            match k % 2 == colour {
+                true => working.push(slab_cells),
+                false => idle[k] = Some(slab_cells),
+            }
+        }
+
+        let starts: Vec<usize> = self.slabs.iter().map(|slab| slab.start).collect();
+        let idle_view = |k: usize| (idle[k].unwrap_or(&[]), starts[k]);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .slabs
+                .iter_mut()
+                .enumerate()
+                .filter(|(k, _)| k % 2 == colour)
+                .zip(working)
+                .map(|((k, slab), own)| {
+                    let mut view = SlabView {
+                        own,
+                        start: slab.start,
+                        left: idle_view((k + n_slabs - 1) % n_slabs),
+                        right: idle_view((k + 1) % n_slabs),
+                    };
+                    let (n_add, n_rem) = (adds[k], rems[k]);
+                    scope.spawn(move || run_slab(slab, &mut view, neibs, n_add, n_rem, energies))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("domain thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Puts `idxg` into the front its state and neighbours call for.
+    fn reconcile(&mut self, grid: &Grid, idxg: usize) {
+        let slab_id = self.slab_of(idxg);
+        let slab = &mut self.slabs[slab_id];
+        let idxl = idxg - slab.start;
+        let has_neib_in = |state: u8| {
+            grid.lattice.neibs[idxg]
+                .iter()
+                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == state)
+        };
+
+        match grid.states[idxg] {
+            0 => {
+                slab.front.tpb_rem(idxl);
+                match has_neib_in(1) {
+                    true => slab.front.tpa_add(idxl),
+                    false => slab.front.tpa_rem(idxl),
+                }
+            }
+            _ => {
+                slab.front.tpa_rem(idxl);
+                match has_neib_in(0) {
+                    true => slab.front.tpb_add(idxl),
+                    false => slab.front.tpb_rem(idxl),
+                }
+            }
+        }
+    }
+}
+
+/// Add and remove attempts of one slab, interleaved as in the serial kernel.
+fn run_slab(
+    slab: &mut Slab,
+    view: &mut SlabView,
+    neibs: &[[Idx; 6]],
+    n_add: u64,
+    n_rem: u64,
+    energies: SweepEnergies,
+) -> SlabOutcome {
+    let SweepEnergies {
+        ex2,
+        ey2,
+        ez2,
+        k_t,
+        delta_gibbs,
+        acceptance,
+    } = energies;
+    let mut outcome = SlabOutcome::default();
+
+    for attempt_id in 0..n_add.max(n_rem) {
+        if attempt_id < n_add && slab.front.tpas_size > 0 {
+            let idxl = slab.front.tpas[slab.rng.random_range(0..slab.front.tpas_size)];
+            let idxg = idxl + slab.start;
+            let idxg_nis = &neibs[idxg];
+
+            let sums = slab_neighbor_sums(view, idxg_nis);
+            let surf_en_change = attach_energy(sums, (ex2, ey2, ez2));
+            let d_e = surf_en_change - delta_gibbs;
+
+            if acceptance.accepts(d_e, k_t, || slab.rng.random()) {
+                outcome.energy_change += surf_en_change;
+                outcome.n_added += 1;
+
+                view.own[idxl] = 1;
+                slab.front.tpa_rem(idxl);
+                slab.front.tpb_add(idxl);
+
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
+                        outcome.hit_boundary = true;
+                        continue;
+                    }
+                    if !view.is_own(neib_idx) {
+                        outcome.touched.push(neib_idx);
+                        continue;
+                    }
+
+                    let neib_idxl = neib_idx - slab.start;
+                    match view.own[neib_idxl] {
+                        0 => slab.front.tpa_add(neib_idxl),
+                        _ => {
+                            if !view.has_neib_in(&neibs[neib_idx], 0) {
+                                slab.front.tpb_rem(neib_idxl);
+                            }
+                        }
+                    }
+                }
+
+                if outcome.hit_boundary {
+                    break;
+                }
+            }
+        }
+
+        if attempt_id < n_rem && slab.front.tpbs_size > 0 {
+            let idxl = slab.front.tpbs[slab.rng.random_range(0..slab.front.tpbs_size)];
+            let idxg = idxl + slab.start;
+            let idxg_nis = &neibs[idxg];
+
+            let sums = slab_neighbor_sums(view, idxg_nis);
+            let surf_en_change = detach_energy(sums, (ex2, ey2, ez2));
+            let d_e = surf_en_change + delta_gibbs;
+
+            if acceptance.accepts(d_e, k_t, || slab.rng.random()) {
+                outcome.energy_change += surf_en_change;
+                outcome.n_removed += 1;
+
+                view.own[idxl] = 0;
+                slab.front.tpb_rem(idxl);
+                slab.front.tpa_add(idxl);
+
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
+                        outcome.hit_boundary = true;
+                        continue;
+                    }
+                    if !view.is_own(neib_idx) {
+                        outcome.touched.push(neib_idx);
+                        continue;
+                    }
+
+                    let neib_idxl = neib_idx - slab.start;
+                    match view.own[neib_idxl] {
+                        0 => {
+                            if !view.has_neib_in(&neibs[neib_idx], 1) {
+                                slab.front.tpa_rem(neib_idxl);
+                            }
+                        }
+                        _ => slab.front.tpb_add(neib_idxl),
+                    }
+                }
+
+                if outcome.hit_boundary {
+                    break;
+                }
+            }
+        }
+    }
+
+    outcome
+}
+
+/// Per-axis crystal neighbour counts of the site with neighbours `idxg_nis`, read through
+/// the slab view.
+#[inline(always)]
+fn slab_neighbor_sums(view: &SlabView, idxg_nis: &[Idx; 6]) -> (u8, u8, u8) {
+    let mut axis_neighbors = [0u8; 3];
+    for (i, idx) in idxg_nis.iter().map(|&n| n as usize).enumerate() {
+        if idx != NO_NEIB && view.get(idx) == 1 {
+            axis_neighbors[i / 2] += 1;
+        }
+    }
+
+    (axis_neighbors[0], axis_neighbors[1], axis_neighbors[2])
+}
@@ -1,13 +1,20 @@
 use crate::mods::{
+    acceptance::AcceptanceRule,
+    attach_source::AttachSource,
+    config_version::{self, CONFIG_VERSION_KEY},
     constants::{
-        COMMENT_LINE, CONFIG_FILE_NAME, INIT_TIME_STATES_FILE_NAME, TIME_STATES_FILE_NAME,
+        COMMENT_LINE, CONFIG_FILE_NAME, INIT_TIME_STATES_FILE_NAME, KEY_MIGRATIONS,
+        REQUIRED_CONFIG_KEYS, TIME_STATES_FILE_NAME,
     },
+    mode,
     settings::{Settings, SettingsError},
+    state_header::StateHeader,
+    unknown_keys,
 };
 use chrono::Utc;
+use mc_core::io_handler::{KeyParser, check_config_errors, read_config_lines};
 use std::{
     collections::HashMap,
-    env::current_exe,
     fs::{self, File},
     io::{BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write},
     path::PathBuf,
@@ -15,6 +22,8 @@ use std::{
 
 use evalexpr::{eval_boolean, eval_number};
 
+pub use mc_core::io_handler::{get_exe_dir, write_state};
+
 macro_rules! parse_and_assign_eval {
     ($map:expr, $field:ident, $type:ty, $key:expr, boolean) => {
         $map.insert(
@@ -39,12 +48,13 @@ macro_rules! parse_and_assign_eval {
     };
 }
 
+/// Reads `InitSettings.ini` into `cfg`. The whole file is read before failing: the bad
+/// values, the duplicate keys and the keys missing for the selected mode come in one error.
 pub fn load_config(cfg: &mut Settings) -> Result<(), Box<dyn std::error::Error>> {
     let file = File::open(cfg.src_path.join(CONFIG_FILE_NAME))?;
     let reader = BufReader::new(file);
 
-    let mut dispatch: HashMap<&str, Box<dyn Fn(&str, &mut Settings) -> Result<(), SettingsError>>> =
-        HashMap::new();
+    let mut dispatch: HashMap<&str, KeyParser<Settings>> = HashMap::new();
 
     dispatch.insert(
         "DirPrefix",
@@ -54,6 +64,8 @@ pub fn load_config(cfg: &mut Settings) -> Result<(), Box<dyn std::error::Error>>
         }),
     );
     parse_and_assign_eval!(dispatch, seed, u64, "Seed", number);
+    parse_and_assign_eval!(dispatch, rng_streams, bool, "RngStreams", boolean);
+    parse_and_assign_eval!(dispatch, strict, bool, "Strict", boolean);
 
     parse_and_assign_eval!(dispatch, sx, usize, "Sx", number);
     parse_and_assign_eval!(dispatch, sy, usize, "Sy", number);
@@ -71,14 +83,65 @@ pub fn load_config(cfg: &mut Settings) -> Result<(), Box<dyn std::error::Error>>
     parse_and_assign_eval!(dispatch, g010, f64, "g010", number);
     parse_and_assign_eval!(dispatch, g001, f64, "g001", number);
 
-    parse_and_assign_eval!(dispatch, mode, f64, "mode", number);
+    dispatch.insert(
+        "mode",
+        Box::new(|v, s| {
+            let (major, minor) =
+                mode::parse_mode(v).map_err(|e| SettingsError::simple("mode", e))?;
+            (s.mode_major, s.mode_minor) = (major, minor);
+            Ok(())
+        }),
+    );
     parse_and_assign_eval!(dispatch, dg, f64, "dg", number);
+    dispatch.insert(
+        "dgExpr",
+        Box::new(|v, s| {
+            s.dg_expr = v.to_string();
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, dg_every, u64, "dgEvery", number);
     parse_and_assign_eval!(dispatch, c_eq, f64, "C_eq", number);
     parse_and_assign_eval!(dispatch, c0, f64, "C0", number);
     parse_and_assign_eval!(dispatch, n_tot, f64, "N_tot", number);
     parse_and_assign_eval!(dispatch, n0_cr, f64, "N0_cr", number);
     parse_and_assign_eval!(dispatch, p_b, f64, "p_b", number);
+    parse_and_assign_eval!(dispatch, p_b_x, f64, "p_b_x", number);
+    parse_and_assign_eval!(dispatch, p_b_y, f64, "p_b_y", number);
+    parse_and_assign_eval!(dispatch, p_b_z, f64, "p_b_z", number);
     parse_and_assign_eval!(dispatch, p_pow, f64, "p_pow", number);
+    parse_and_assign_eval!(dispatch, e_des, f64, "EDes", number);
+    dispatch.insert(
+        "PbExpr",
+        Box::new(|v, s| {
+            s.pb_expr = v.to_string();
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "Acceptance",
+        Box::new(|v, s| {
+            s.acceptance = AcceptanceRule::parse(v).ok_or_else(|| {
+                SettingsError::simple(
+                    "Acceptance",
+                    "must be 'metropolis', 'glauber' or 'heatbath'",
+                )
+            })?;
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "AttachSource",
+        Box::new(|v, s| {
+            s.attach_source = AttachSource::parse(v).ok_or_else(|| {
+                SettingsError::simple(
+                    "AttachSource",
+                    "must be 'any', 'sight:<face>' or 'near:<face>:D' (face: +x, -x, +y, -y, +z, -z; D > 0)",
+                )
+            })?;
+            Ok(())
+        }),
+    );
 
     parse_and_assign_eval!(dispatch, add_i, u64, "AddI", number);
     parse_and_assign_eval!(dispatch, add_from, u64, "AddFrom", number);
@@ -91,64 +154,47 @@ pub fn load_config(cfg: &mut Settings) -> Result<(), Box<dyn std::error::Error>>
     parse_and_assign_eval!(dispatch, print_i, u64, "PrintI", number);
     parse_and_assign_eval!(dispatch, write_i, u64, "WriteI", number);
 
-    for (line_num, line_result) in reader.lines().enumerate() {
-        let line = line_result?;
-        let trimmed = line.trim();
-
-        if trimmed == COMMENT_LINE {
-            break;
-        }
+    parse_and_assign_eval!(dispatch, domains, usize, "Domains", number);
+    parse_and_assign_eval!(dispatch, domain_sweep, u64, "DomainSweep", number);
 
-        let mut parts = trimmed.splitn(2, ':');
-        let key = parts.next().unwrap_or("").trim();
-        let value = parts.next().unwrap_or("").trim();
+    let lines = reader.lines().collect::<IoResult<Vec<String>>>()?;
+    let version = config_version::file_version(&lines, COMMENT_LINE)
+        .map_err(|e| SettingsError::simple(CONFIG_VERSION_KEY, e))?;
 
-        if key.is_empty() || value.is_empty() {
-            #[cfg(debug_assertions)]
-            eprintln!("⚠️ Warning: Malformed line {}: '{}'", line_num + 1, line);
-            continue;
-        }
+    let read = read_config_lines(
+        &lines,
+        COMMENT_LINE,
+        version,
+        &KEY_MIGRATIONS,
+        &dispatch,
+        cfg,
+    );
 
-        if let Some(parser) = dispatch.get(key) {
-            parser(value, cfg)?;
-        } else {
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "⚠️ Warning: Unknown cfg key '{}' found on line {}: '{}'",
-                key,
-                line_num + 1,
-                line
-            );
-        }
+    if !read.unknown.is_empty() {
+        eprintln!("{}", unknown_keys::report(&read.unknown, CONFIG_FILE_NAME));
     }
 
-    Ok(())
-}
+    let missing_keys = read.missing_keys(&REQUIRED_CONFIG_KEYS, cfg.mode(), |_| false);
+    cfg.unknown_keys = read.unknown;
 
-pub fn get_exe_dir() -> IoResult<PathBuf> {
-    current_exe()
-        .map_err(|e| {
-            IoError::new(
-                ErrorKind::Other,
-                format!("Failed to get executable path: {}", e),
-            )
-        })?
-        .parent()
-        .map(PathBuf::from)
-        .ok_or_else(|| IoError::new(ErrorKind::Other, "Failed to get executable directory"))
+    let mut errors = read.errors;
+    errors.extend(missing_keys);
+    check_config_errors(CONFIG_FILE_NAME, &errors)?;
+
+    Ok(())
 }
 
 fn create_dir_name(cfg: &Settings, timestamp: i64) -> String {
-    match cfg.mode {
-        1.1 => format!(
+    match cfg.mode() {
+        (1, 1) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_dg{:e}",
             timestamp, cfg.dir_prefix, cfg.sx, cfg.sy, cfg.sz, cfg.temperature, cfg.dg
         ),
-        1.2 => format!(
+        (1, 2) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_dg{:e}_Pb{:?}",
             timestamp, cfg.dir_prefix, cfg.sx, cfg.sy, cfg.sz, cfg.temperature, cfg.dg, cfg.p_b
         ),
-        1.3 => format!(
+        (1, 3) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_dg{:e}_Pb{:?}_Pp{:?}",
             timestamp,
             cfg.dir_prefix,
@@ -160,11 +206,11 @@ fn create_dir_name(cfg: &Settings, timestamp: i64) -> String {
             cfg.p_b,
             cfg.p_pow
         ),
-        2.1 => format!(
+        (2, 1) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_C{:e}_Nt{:e}",
             timestamp, cfg.dir_prefix, cfg.sx, cfg.sy, cfg.sz, cfg.temperature, cfg.c0, cfg.n_tot
         ),
-        2.2 => format!(
+        (2, 2) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_C{:e}_Nt{:e}_Pb{:?}",
             timestamp,
             cfg.dir_prefix,
@@ -176,7 +222,7 @@ fn create_dir_name(cfg: &Settings, timestamp: i64) -> String {
             cfg.n_tot,
             cfg.p_b
         ),
-        2.3 => format!(
+        (2, 3) => format!(
             "{}_{}_X{}Y{}Z{}_T{:e}_C{:e}_Nt{:e}_Pb{:?}_Pp{:?}",
             timestamp,
             cfg.dir_prefix,
@@ -252,6 +298,9 @@ pub fn prepare_files(cfg: &mut Settings) -> IoResult<PathBuf> {
     Ok(path_dst_states)
 }
 
+/// Loads state line `LoadPrev` (1-based, `#states` header lines not counted; -1 - the
+/// last one) of `InitStates.ini`. A line after a header is checked against its dims and
+/// encoding; without a header only the number of values is checked.
 pub fn load_state(states: &mut Box<[u8]>, cfg: &Settings) -> IoResult<()> {
     let load_line = cfg.load_prev;
     if load_line == 0 {
@@ -261,28 +310,56 @@ pub fn load_state(states: &mut Box<[u8]>, cfg: &Settings) -> IoResult<()> {
     let file = File::open(cfg.src_path.join(INIT_TIME_STATES_FILE_NAME))?;
     let reader = BufReader::new(file);
 
-    let mut target_line: Option<String> = None;
+    let mut header: Option<StateHeader> = None;
+    let mut target_line: Option<(String, Option<StateHeader>)> = None;
+    let mut state_line_num = 0;
 
-    if load_line == -1 {
-        let mut last_valid_line = None;
-        for line_result in reader.lines() {
-            let line = line_result?;
-            if line.contains(':') {
-                last_valid_line = Some(line);
-            }
+    for line_result in reader.lines() {
+        let line = line_result?;
+        if StateHeader::is_header(&line) {
+            header = Some(StateHeader::parse(&line).map_err(|e| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Bad header in {}: {}", INIT_TIME_STATES_FILE_NAME, e),
+                )
+            })?);
+            continue;
         }
-        target_line = last_valid_line;
-    } else if load_line > 0 {
-        for (i, line_result) in reader.lines().enumerate() {
-            if (i + 1) as i64 == load_line {
-                target_line = Some(line_result?);
-                break;
+
+        state_line_num += 1;
+        if load_line == -1 {
+            if line.contains(':') {
+                target_line = Some((line, header));
             }
+        } else if state_line_num == load_line {
+            target_line = Some((line, header));
+            break;
         }
     }
 
     match target_line {
-        Some(line) => {
+        Some((line, Some(header))) => {
+            let values = header
+                .check_dims((cfg.sx, cfg.sy, cfg.sz))
+                .and_then(|_| header.parse_values(&line))
+                .map_err(|e| {
+                    IoError::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "State line {} of {}: {}",
+                            load_line, INIT_TIME_STATES_FILE_NAME, e
+                        ),
+                    )
+                })?;
+            states.copy_from_slice(&values);
+            Ok(())
+        }
+        Some((line, None)) => {
+            println!(
+                "⚠️ '{}' has no '#states' header, checking the value count only.",
+                INIT_TIME_STATES_FILE_NAME
+            );
+
             let values: Vec<&str> = line.split(':').collect();
             if values.len() != states.len() {
                 return Err(IoError::new(
@@ -315,28 +392,9 @@ pub fn load_state(states: &mut Box<[u8]>, cfg: &Settings) -> IoResult<()> {
     }
 }
 
-pub fn write_state(writer: &mut BufWriter<File>, state: &Box<[u8]>) -> IoResult<()> {
-    // Get the length of the state array
-    let len = state.len();
-    // If the array is empty, write only a newline character
-    if len == 0 {
-        return writer.write_all(b"\n").map(|_| ());
-    }
-
-    // Create a buffer with precise capacity: each byte (0 or 1) -> 1 character ('0' or '1') + (len-1) separators ':' + 1 newline character
-    let mut buffer = Vec::with_capacity(len + len.saturating_sub(1) + 1);
-
-    // Fill the buffer with values ('0' or '1') and separators ':'
-    buffer.extend(state.iter().flat_map(|&val| [val + b'0', b':']));
-    // Remove the last superfluous separator ':'
-    buffer.pop();
-    // Add the newline character
-    buffer.push(b'\n');
-
-    // Write the buffer to the file
-    writer.write_all(&buffer)?;
-    // Return a successful result
-    Ok(())
+/// Writes the `#states` header of `TimeStates.txt` (the shape of the saved states).
+pub fn write_states_header(writer: &mut BufWriter<File>, cfg: &Settings) -> IoResult<()> {
+    writeln!(writer, "{}", StateHeader::new((cfg.sx, cfg.sy, cfg.sz)))
 }
 
 pub fn write_f64_state(writer: &mut BufWriter<File>, state: &Vec<f64>) -> IoResult<()> {
@@ -1,6 +1,21 @@
+pub mod attach_source;
 pub mod constants;
-pub mod frontier;
+pub mod domains;
 pub mod io_handler;
 pub mod lattice;
+pub mod rng_streams;
+pub mod run;
+pub mod schedule;
 pub mod settings;
 pub mod simulation;
+
+pub use mc_core::acceptance;
+pub use mc_core::ballistics;
+pub use mc_core::config_version;
+pub use mc_core::energy;
+pub use mc_core::frontier;
+pub use mc_core::mode;
+pub use mc_core::state_header;
+pub use mc_core::summation;
+pub use mc_core::unknown_keys;
+pub use mc_core::utils;
@@ -0,0 +1,138 @@
+//! Entry point for embedding the model in another Rust program: `Settings` filled in
+//! code in, the final state of the run out. The run directory is created in
+//! `cfg.src_path` and written as by the binary.
+//!
+//! ```no_run
+//! use model_1_001::{Settings, Simulation};
+//!
+//! let mut cfg = Settings::new();
+//! (cfg.sx, cfg.sy, cfg.sz) = (40, 40, 40);
+//! cfg.step_lim = 10_000;
+//! let res = Simulation::run(cfg)?;
+//! println!("n_cryst {:.5e} after {} steps", res.n_cryst, res.mk_step);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::mods::{
+    attach_source::AttachSource, constants::INIT_TIME_STATES_FILE_NAME, frontier::Frontier,
+    io_handler, lattice::Grid, rng_streams::RngStreams, settings::Settings,
+    simulation::run_calculations,
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Outcome of `Simulation::run`: the last point of `sim_history.txt` and the final
+/// lattice.
+#[derive(Debug, Clone)]
+pub struct SimResult {
+    /// Directory of the run, with `TimeStates.txt` and `sim_history.txt`.
+    pub dst_path: PathBuf,
+    pub mk_step: u64,
+    pub n_cryst: f64,
+    pub n_gas: f64,
+    pub conc: f64,
+    pub dg: f64,
+    pub total_energy_change: f64,
+    /// Cell states of the grid at the end of the run.
+    pub states: Box<[u8]>,
+    pub elapsed: Duration,
+}
+
+/// One run of the model from `Settings`.
+pub struct Simulation;
+
+impl Simulation {
+    /// Validates `cfg`, builds the grid, creates the run directory, loads the initial
+    /// state (`LoadPrev`) and runs the mode.
+    pub fn run(mut cfg: Settings) -> Result<SimResult> {
+        cfg.validate()?;
+        let sta = Instant::now();
+
+        let mut rng = RngStreams::new(cfg.seed, cfg.rng_streams);
+
+        let mut grid = Grid::new(cfg.sx, cfg.sy, cfg.sz, cfg.px, cfg.py, cfg.pz)
+            .map_err(|e| format!("Invalid grid: {}", e))?;
+
+        let mut front = Frontier::new(grid.lattice.size);
+
+        io_handler::prepare_dir(&mut cfg)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+        println!("📁 DST Path: {}", cfg.dst_path.display());
+
+        let path_dst_states = io_handler::prepare_files(&mut cfg)
+            .map_err(|e| format!("Failed to prepare files: {}", e))?;
+
+        let dst_states = File::create(path_dst_states)?;
+        let mut dst_states_buf = BufWriter::new(dst_states);
+        io_handler::write_states_header(&mut dst_states_buf, &cfg)?;
+
+        if cfg.load_prev != 0 {
+            println!(
+                "Attempting to load state from '{}' line {}",
+                INIT_TIME_STATES_FILE_NAME, cfg.load_prev
+            );
+
+            io_handler::load_state(&mut grid.states, &cfg)
+                .map_err(|e| format!("Failed to load state: {}", e))?;
+            println!("State loaded successfully.");
+        }
+
+        println!("✅ Preparation DONE! (Time: {:?})", sta.elapsed());
+        print_settings(&cfg);
+
+        let mut res = run_calculations(&cfg, &mut grid, &mut front, &mut rng, &mut dst_states_buf)?;
+        res.states = grid.states;
+        res.elapsed = sta.elapsed();
+
+        Ok(res)
+    }
+}
+
+fn print_settings(cfg: &Settings) {
+    println!("DirPrefix: {:?}", cfg.dir_prefix);
+    println!("Seed: {:?};", cfg.seed);
+    println!("Sx: {:?}; Sy: {:?}; Sz: {:?};", cfg.sx, cfg.sy, cfg.sz);
+    println!("Px: {:?}; Py: {:?}; Pz: {:?};", cfg.px, cfg.py, cfg.pz);
+    println!("T: {:.5e};", cfg.temperature);
+    println!(
+        "Ax: {:.5e}; Ay: {:.5e}; Az: {:.5e};",
+        cfg.ax, cfg.ay, cfg.az
+    );
+    println!(
+        "g100: {:.5e}; g010: {:.5e}; g001: {:.5e};",
+        cfg.g100, cfg.g010, cfg.g001
+    );
+    println!(
+        "mode: {}.{}; dg: {:.5e};",
+        cfg.mode_major, cfg.mode_minor, cfg.dg
+    );
+    println!(
+        "C_eq: {:.5e}; C0: {:.5e}; N_tot: {:.5e}; N0_cr: {:.5e}; p_b: {:.5e}; p_pow: {:.5e};",
+        cfg.c_eq, cfg.c0, cfg.n_tot, cfg.n0_cr, cfg.p_b, cfg.p_pow
+    );
+    println!(
+        "AddI: {:?}; AddFrom: {:?}; RemI: {:?}; RemFrom: {:?};",
+        cfg.add_i, cfg.add_from, cfg.rem_i, cfg.rem_from,
+    );
+    println!("LoadPrev: {:?};", cfg.load_prev);
+    println!(
+        "StepLim: {:?}; PrintI: {:?};  WriteI: {:?};",
+        cfg.step_lim, cfg.print_i, cfg.write_i,
+    );
+    if cfg.attach_source != AttachSource::Any {
+        println!("AttachSource: {:?};", cfg.attach_source);
+    }
+    if cfg.domains > 1 {
+        println!(
+            "Domains: {:?}; DomainSweep: {:?};",
+            cfg.domains, cfg.domain_sweep
+        );
+    }
+}
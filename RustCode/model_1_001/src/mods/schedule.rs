@@ -0,0 +1,67 @@
+use crate::mods::settings::{Settings, SettingsError};
+use evalexpr::{ContextWithMutableVariables, HashMapContext, Node, Value, build_operator_tree};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+
+/// Time-varying driving force `dg(step)` for the constant-reservoir modes 1.x.
+///
+/// The `dgExpr` expression is compiled once and re-evaluated every `dgEvery` steps.
+/// Available variables: `step` (current step, float), `dg0` (the `dg` setting),
+/// `kT` (Boltzmann constant times temperature, J) and `T` (K).
+#[derive(Debug)]
+pub struct DgSchedule {
+    node: Node,
+    context: HashMapContext,
+    every: u64,
+}
+
+impl DgSchedule {
+    /// `None` when `dgExpr` is empty or `none`, i.e. `dg` stays constant.
+    pub fn from_settings(cfg: &Settings, k_t: f64) -> Result<Option<Self>, SettingsError> {
+        let expr = cfg.dg_expr.trim();
+        if expr.is_empty() || expr.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+
+        let node: Node =
+            build_operator_tree(expr).map_err(|e| SettingsError::new("dgExpr", expr, e))?;
+
+        let mut context = HashMapContext::new();
+        for (name, value) in [("dg0", cfg.dg), ("kT", k_t), ("T", cfg.temperature)] {
+            context
+                .set_value(name.to_string(), Value::Float(value))
+                .map_err(|e| SettingsError::new("dgExpr", expr, e))?;
+        }
+
+        let mut schedule = Self {
+            node,
+            context,
+            every: cfg.dg_every.max(1),
+        };
+        schedule
+            .eval(0)
+            .map_err(|e| SettingsError::simple("dgExpr", e.to_string()))?;
+
+        Ok(Some(schedule))
+    }
+
+    pub fn eval(&mut self, step_id: u64) -> IoResult<f64> {
+        self.context
+            .set_value("step".to_string(), Value::Float(step_id as f64))
+            .and_then(|_| self.node.eval_number_with_context(&self.context))
+            .map_err(|e| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Failed to evaluate 'dgExpr' at step {}: {}", step_id, e),
+                )
+            })
+    }
+
+    /// Re-evaluates `dg` on the first step and then every `every` steps.
+    #[inline(always)]
+    pub fn update(&mut self, step_id: u64, dg: &mut f64) -> IoResult<()> {
+        if (step_id - 1).is_multiple_of(self.every) {
+            *dg = self.eval(step_id)?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
-use crate::mods::io_handler::get_exe_dir;
-use std::{borrow::Cow, error::Error, fmt, path::PathBuf};
+use crate::mods::{
+    acceptance::AcceptanceRule, attach_source::AttachSource, ballistics::BallisticLaw,
+    io_handler::get_exe_dir, mode, unknown_keys::UnknownKey,
+};
+use std::path::PathBuf;
+
+pub use mc_core::settings::SettingsError;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Settings {
     pub dir_prefix: String,
     pub seed: u64,
+    pub rng_streams: bool,
 
     pub sx: usize,
     pub sy: usize,
@@ -22,14 +28,25 @@ pub struct Settings {
     pub g010: f64,
     pub g001: f64,
 
-    pub mode: f64,
+    /// `mode` as `MAJOR.MINOR`, see `mode()`.
+    pub mode_major: u8,
+    pub mode_minor: u8,
     pub dg: f64,
+    pub dg_expr: String,
+    pub dg_every: u64,
     pub c_eq: f64,
     pub c0: f64,
     pub n_tot: f64,
     pub n0_cr: f64,
     pub p_b: f64,
+    pub p_b_x: f64,
+    pub p_b_y: f64,
+    pub p_b_z: f64,
     pub p_pow: f64,
+    pub e_des: f64,
+    pub pb_expr: String,
+    pub acceptance: AcceptanceRule,
+    pub attach_source: AttachSource,
 
     pub add_i: u64,
     pub add_from: u64,
@@ -42,6 +59,13 @@ pub struct Settings {
     pub print_i: u64,
     pub write_i: u64,
 
+    pub domains: usize,
+    pub domain_sweep: u64,
+
+    /// Refuse to run with keys no setting reads (`Strict`); those met by `load_config`.
+    pub strict: bool,
+    pub unknown_keys: Vec<UnknownKey>,
+
     pub src_path: PathBuf,
     pub dst_path: PathBuf,
 }
@@ -53,6 +77,7 @@ impl Default for Settings {
         Self {
             dir_prefix: "Default".to_string(),
             seed: 1012,
+            rng_streams: false,
 
             sx: 11,
             sy: 11,
@@ -70,14 +95,24 @@ impl Default for Settings {
             g010: 0.54,
             g001: 0.22,
 
-            mode: 1.1,
+            mode_major: 1,
+            mode_minor: 1,
             dg: 0.0,
+            dg_expr: String::new(),
+            dg_every: 1,
             c_eq: 9.58767e-08,
             c0: 9.58767e-08,
             n_tot: 5e12,
             n0_cr: -1.0,
             p_b: 0.3,
+            p_b_x: -1.0,
+            p_b_y: -1.0,
+            p_b_z: -1.0,
             p_pow: 1.0,
+            e_des: -1.0,
+            pb_expr: String::new(),
+            acceptance: AcceptanceRule::Metropolis,
+            attach_source: AttachSource::Any,
 
             add_i: 1,
             add_from: 1,
@@ -90,6 +125,12 @@ impl Default for Settings {
             print_i: 10,
             write_i: 1,
 
+            domains: 0,
+            domain_sweep: 100000,
+
+            strict: false,
+            unknown_keys: Vec::new(),
+
             src_path: exe_dir,
             dst_path: PathBuf::new(),
         }
@@ -101,7 +142,20 @@ impl Settings {
         Self::default()
     }
 
+    /// `(major, minor)` of the mode, e.g. `(2, 1)` for 2.1.
+    pub fn mode(&self) -> (u8, u8) {
+        (self.mode_major, self.mode_minor)
+    }
+
     pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.strict && !self.unknown_keys.is_empty() {
+            let keys: Vec<&str> = self.unknown_keys.iter().map(|k| k.key.as_str()).collect();
+            return Err(SettingsError::simple(
+                "Strict",
+                format!("unknown keys in the config: {}", keys.join(", ")),
+            ));
+        }
+        mode::check_mode(self.mode()).map_err(|e| SettingsError::simple("mode", e))?;
         if self.sx == 0 {
             return Err(SettingsError::simple("Sx", "must be > 0"));
         }
@@ -120,6 +174,9 @@ impl Settings {
         if self.rem_from < 1 {
             return Err(SettingsError::simple("RemFrom", "must be > 0"));
         }
+        // Compiles and test-evaluates the expression; the energies are set at run time.
+        BallisticLaw::new(self.e_des, 1.0, &self.pb_expr, 1.0, self.temperature)
+            .map_err(|e| SettingsError::simple("PbExpr", e))?;
         // if self.ax <= 0.0 || self.ay <= 0.0 || self.az <= 0.0 {
         //     return Err(SettingsError::simple("Ax/Ay/Az", "must be > 0"));
         // }
@@ -132,87 +189,51 @@ impl Settings {
         // if self.print_on_step == 0 {
         //     return Err(SettingsError::simple("PrintOnStep", "must be > 0"));
         // }
-        if self.dir_prefix.trim().is_empty() {
-            return Err(SettingsError::simple("DirPrefix", "cannot be empty"));
-        }
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
-pub struct SettingsError {
-    pub key: Option<Cow<'static, str>>,
-    pub value: Option<String>,
-    pub source: Box<dyn Error + Send + Sync>,
-}
-
-impl SettingsError {
-    pub fn new<K: Into<Cow<'static, str>>, V: Into<String>, E: Error + Send + Sync + 'static>(
-        key: K,
-        value: V,
-        source: E,
-    ) -> Self {
-        Self {
-            key: Some(key.into()),
-            value: Some(value.into()),
-            source: Box::new(source),
+        if self.dg_every == 0 {
+            return Err(SettingsError::simple("dgEvery", "must be > 0"));
         }
-    }
-
-    pub fn simple<K: Into<Cow<'static, str>>, M: Into<String>>(key: K, message: M) -> Self {
-        Self {
-            key: Some(key.into()),
-            value: None,
-            source: Box::new(SimpleMsg(message.into())),
-        }
-    }
-
-    pub fn from_io(err: std::io::Error) -> Self {
-        Self {
-            key: None,
-            value: None,
-            source: Box::new(err),
+        if self.domains > 1 {
+            if self.mode() != (1, 1) {
+                return Err(SettingsError::simple(
+                    "Domains",
+                    "the domain-decomposed kernel is only available in mode 1.1",
+                ));
+            }
+            if self.sx < 2 * self.domains {
+                return Err(SettingsError::simple(
+                    "Domains",
+                    format!("Sx must be >= 2 * Domains = {}", 2 * self.domains),
+                ));
+            }
+            if self.domain_sweep == 0 {
+                return Err(SettingsError::simple("DomainSweep", "must be > 0"));
+            }
         }
-    }
-}
-
-impl fmt::Display for SettingsError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match (&self.key, &self.value) {
-            (Some(key), Some(value)) => {
-                write!(
-                    f,
-                    "Failed to parse '{}' with value '{}': {}",
-                    key, value, self.source
-                )
+        if self.attach_source != AttachSource::Any {
+            if self.mode_major != 1 {
+                return Err(SettingsError::simple(
+                    "AttachSource",
+                    "is only available in modes 1.x",
+                ));
+            }
+            if self.domains > 1 {
+                return Err(SettingsError::simple(
+                    "AttachSource",
+                    "cannot be combined with the domain-decomposed kernel (Domains > 1)",
+                ));
             }
-            (Some(key), None) => {
-                write!(f, "Invalid value for '{}': {}", key, self.source)
+            if let AttachSource::Sight { axis, .. } = self.attach_source
+                && [self.px, self.py, self.pz][axis]
+            {
+                return Err(SettingsError::simple(
+                    "AttachSource",
+                    "a line of sight needs a non-periodic axis (the face would not exist)",
+                ));
             }
-            _ => write!(f, "Settings error: {}", self.source),
         }
+        if self.dir_prefix.trim().is_empty() {
+            return Err(SettingsError::simple("DirPrefix", "cannot be empty"));
+        }
+        Ok(())
     }
 }
-
-impl Error for SettingsError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&*self.source)
-    }
-}
-
-impl From<std::io::Error> for SettingsError {
-    fn from(err: std::io::Error) -> Self {
-        Self::from_io(err)
-    }
-}
-
-#[derive(Debug)]
-struct SimpleMsg(String);
-
-impl fmt::Display for SimpleMsg {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl Error for SimpleMsg {}
@@ -1,15 +1,24 @@
 use crate::mods::{
+    attach_source::AttachFilter,
+    ballistics::{BallisticAxes, BallisticLaw},
     constants::K_BOLTZMANN,
+    domains::{Domains, SweepEnergies},
+    energy::{attach_energy, detach_energy},
     frontier::Frontier,
     io_handler,
-    lattice::Grid,
+    lattice::{Grid, NO_NEIB},
+    rng_streams::{RngStream, RngStreams},
+    run::SimResult,
+    schedule::DgSchedule,
     settings::{Settings, SettingsError},
+    summation::NeumaierSum,
+    utils::{compute_neighbor_sums, fill_front},
 };
 use rand::prelude::*;
-use rand_chacha::ChaCha8Rng;
 use std::{
     fs::File,
     io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult, Result, Write},
+    time::Duration,
 };
 
 struct SimulationState {
@@ -26,23 +35,31 @@ struct SimulationState {
     n_gas_history: Vec<f64>,
     delta_gibbs: f64,
     delta_gibbs_history: Vec<f64>,
-    ballistics_probability: f64,
+    ballistic_axes: BallisticAxes,
     total_energy_change: f64,
+    energy_change_sum: NeumaierSum,
     energy_change_history: Vec<f64>,
+    energy_change_error_history: Vec<f64>,
     crystal_sx: f64,
     crystal_sx_history: Vec<f64>,
     crystal_sy: f64,
     crystal_sy_history: Vec<f64>,
     crystal_sz: f64,
     crystal_sz_history: Vec<f64>,
+    tpas_size: f64,
+    tpas_size_history: Vec<f64>,
+    tpbs_size: f64,
+    tpbs_size_history: Vec<f64>,
     mk_step: u64,
     mk_step_history: Vec<f64>,
+    /// Attachment attempts on TPA sites rejected by the `AttachSource` filter.
+    attach_skips: u64,
 }
 
 impl SimulationState {
     fn new(
         _k_t: f64,
-        _ballistics_probability: f64,
+        _ballistic_axes: BallisticAxes,
         _eq_concentration: f64,
         _concentration: f64,
         _n_total: f64,
@@ -67,20 +84,36 @@ impl SimulationState {
             n_gas_history: Vec::new(),
             delta_gibbs: _delta_gibbs * 1.0,
             delta_gibbs_history: Vec::new(),
-            ballistics_probability: _ballistics_probability * 1.0,
+            ballistic_axes: _ballistic_axes,
             total_energy_change: 0.0,
+            energy_change_sum: NeumaierSum::new(),
             energy_change_history: Vec::new(),
+            energy_change_error_history: Vec::new(),
             crystal_sx: -1.0,
             crystal_sx_history: Vec::new(),
             crystal_sy: -1.0,
             crystal_sy_history: Vec::new(),
             crystal_sz: -1.0,
             crystal_sz_history: Vec::new(),
+            tpas_size: 0.0,
+            tpas_size_history: Vec::new(),
+            tpbs_size: 0.0,
+            tpbs_size_history: Vec::new(),
             mk_step: 0,
             mk_step_history: Vec::new(),
+            attach_skips: 0,
         }
     }
 
+    /// Whether the drawn TPA site `idxg` may take the attachment; counts the skipped ones.
+    fn attach_eligible(&mut self, filter: &dyn AttachFilter, grid: &Grid, idxg: usize) -> bool {
+        let is_eligible = filter.allows(grid, idxg);
+        if !is_eligible {
+            self.attach_skips += 1;
+        }
+        is_eligible
+    }
+
     /// Обновляет состояние после события (присоединение/отсоединение).
     /// `particle_change`: 1.0 для присоединения, -1.0 для отсоединения.
     fn update(&mut self, k_t: f64, particle_change: f64) -> bool {
@@ -108,7 +141,8 @@ impl SimulationState {
     }
 
     fn calculate_energy_change(&mut self, energy_change: f64) {
-        self.total_energy_change += energy_change;
+        self.energy_change_sum.add(energy_change);
+        self.total_energy_change = self.energy_change_sum.value();
     }
 
     fn measure_crystal_sizes(&mut self, grid: &Grid, front: &Frontier) {
@@ -127,7 +161,7 @@ impl SimulationState {
             }
             _ => {
                 for &idxg in &front.tpbs[..front.tpbs_size] {
-                    let (x, y, z) = grid.idx_to_xyz(idxg);
+                    let (x, y, z) = grid.lattice.idx_to_xyz(idxg);
 
                     x_start = x_start.min(x);
                     x_end = x_end.max(x);
@@ -146,15 +180,45 @@ impl SimulationState {
         }
     }
 
+    /// `measure_crystal_sizes` and `measure_front_sizes` for the slab fronts of `domains`.
+    fn measure_domains(&mut self, grid: &Grid, domains: &Domains) {
+        let (mut start, mut end) = ([usize::MAX; 3], [usize::MIN; 3]);
+        for idxg in domains.tpbs() {
+            let (x, y, z) = grid.lattice.idx_to_xyz(idxg);
+            for (axis, coord) in [x, y, z].into_iter().enumerate() {
+                start[axis] = start[axis].min(coord);
+                end[axis] = end[axis].max(coord);
+            }
+        }
+
+        let (tpas_size, tpbs_size) = domains.front_sizes();
+        let sizes = match tpbs_size {
+            0 => [0.0; 3],
+            _ => [0, 1, 2].map(|axis| (end[axis] - start[axis]) as f64 + 1.0),
+        };
+        (self.crystal_sx, self.crystal_sy, self.crystal_sz) = (sizes[0], sizes[1], sizes[2]);
+        self.tpas_size = tpas_size as f64;
+        self.tpbs_size = tpbs_size as f64;
+    }
+
+    fn measure_front_sizes(&mut self, front: &Frontier) {
+        self.tpas_size = front.tpas_size as f64;
+        self.tpbs_size = front.tpbs_size as f64;
+    }
+
     fn add_history_point(&mut self) {
         self.n_gas_history.push(self.n_gas);
         self.n_crystal_history.push(self.n_crystal);
         self.concentration_history.push(self.concentration);
         self.delta_gibbs_history.push(self.delta_gibbs);
         self.energy_change_history.push(self.total_energy_change);
+        self.energy_change_error_history
+            .push(self.energy_change_sum.error_bound());
         self.crystal_sx_history.push(self.crystal_sx);
         self.crystal_sy_history.push(self.crystal_sy);
         self.crystal_sz_history.push(self.crystal_sz);
+        self.tpas_size_history.push(self.tpas_size);
+        self.tpbs_size_history.push(self.tpbs_size);
         self.mk_step_history.push(self.mk_step as f64);
     }
 }
@@ -163,9 +227,11 @@ fn sim_mode_1_1(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
+    dg_schedule: &mut Option<DgSchedule>,
+    attach_filter: &dyn AttachFilter,
     print_check_part: bool,
     write_check_part: bool,
     add_check_part: bool,
@@ -184,35 +250,28 @@ fn sim_mode_1_1(
 
     let (mut surf_en_change, mut d_e);
     'simulation_loop: for step_id in 1..=cfg.step_lim {
+        if let Some(schedule) = dg_schedule.as_mut() {
+            schedule.update(step_id, &mut sim_state.delta_gibbs)?;
+        }
+
         let is_add_step = add_check_part && (step_id >= add_from) && (step_id % add_i == 0);
         let is_rem_step = rem_check_part && (step_id >= rem_from) && (step_id % rem_i == 0);
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if sim_state.attach_eligible(attach_filter, grid, idxg)
+                && cfg
+                    .acceptance
+                    .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 1;
@@ -221,8 +280,8 @@ fn sim_mode_1_1(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -230,9 +289,9 @@ fn sim_mode_1_1(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -266,30 +325,18 @@ fn sim_mode_1_1(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 0;
@@ -298,17 +345,17 @@ fn sim_mode_1_1(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -348,6 +395,7 @@ fn sim_mode_1_1(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -362,13 +410,136 @@ fn sim_mode_1_1(
     Ok(())
 }
 
+/// Mode 1.1 on the domain-decomposed kernel (`Domains` > 1), see `mods::domains`.
+///
+/// Runs sweeps of up to `DomainSweep` steps, cut at the next write, print and `dgEvery`
+/// step, so the outputs land on the same steps as in the serial kernel. The dynamics
+/// within a sweep are approximate.
+fn sim_mode_1_1_domains(
+    cfg: &Settings,
+    grid: &mut Grid,
+    front: &mut Frontier,
+    rng: &mut RngStreams,
+    dst_states_buf: &mut BufWriter<File>,
+    sim_state: &mut SimulationState,
+    dg_schedule: &mut Option<DgSchedule>,
+    print_check_part: bool,
+    write_check_part: bool,
+    add_check_part: bool,
+    add_i: u64,
+    add_from: u64,
+    rem_check_part: bool,
+    rem_i: u64,
+    rem_from: u64,
+    k_t: f64,
+    ex2: f64,
+    ey2: f64,
+    ez2: f64,
+) -> Result<()> {
+    sim_state.delta_gibbs = cfg.dg * 1.0;
+
+    let mut domains = Domains::new(grid, front, cfg.domains, rng);
+    *front = Frontier::new(0);
+    eprintln!(
+        "⚠️ Warning: domain-decomposed kernel ({} threads, {} slabs along x, sweeps of {} steps); the dynamics are approximate and do not match the serial mode 1.1.",
+        cfg.domains,
+        domains.len(),
+        cfg.domain_sweep
+    );
+
+    let next_multiple = |step_id: u64, interval: u64, pre_flag: bool| match pre_flag {
+        true => (step_id / interval + 1) * interval,
+        false => u64::MAX,
+    };
+    let count_steps = |(first, last): (u64, u64), interval: u64, from: u64, pre_flag: bool| {
+        let first = first.max(from);
+        match pre_flag && first <= last {
+            true => last / interval - (first - 1) / interval,
+            false => 0,
+        }
+    };
+
+    let mut step_id = 0;
+    while step_id < cfg.step_lim {
+        let last = (step_id + cfg.domain_sweep)
+            .min(cfg.step_lim)
+            .min(next_multiple(step_id, cfg.write_i, write_check_part))
+            .min(next_multiple(step_id, cfg.print_i, print_check_part))
+            .min(next_multiple(step_id, cfg.dg_every, dg_schedule.is_some()));
+
+        if let Some(schedule) = dg_schedule.as_mut() {
+            schedule.update(step_id + 1, &mut sim_state.delta_gibbs)?;
+        }
+
+        let outcome = domains.sweep(
+            grid,
+            rng,
+            count_steps((step_id + 1, last), add_i, add_from, add_check_part),
+            count_steps((step_id + 1, last), rem_i, rem_from, rem_check_part),
+            SweepEnergies {
+                ex2,
+                ey2,
+                ez2,
+                k_t,
+                delta_gibbs: sim_state.delta_gibbs,
+                acceptance: cfg.acceptance,
+            },
+        );
+        for energy_change in outcome.energy_changes {
+            sim_state.calculate_energy_change(energy_change);
+        }
+        step_id = last;
+        sim_state.mk_step = step_id;
+
+        if outcome.hit_boundary {
+            println!(
+                "Step: {} -> Status: Sample boundary cell found in neighbors.\nSimulation stalled or completed.",
+                step_id
+            );
+            break;
+        }
+
+        let (tpa_len, tpb_len) = domains.front_sizes();
+        if tpa_len.min(tpb_len) == 0 {
+            eprintln!(
+                "Step: {} -> Found an empty Front: | TPA: {} - TPB: {} |.\nSimulation stalled or completed.",
+                step_id, tpa_len, tpb_len
+            );
+            break;
+        }
+
+        if should_perform_action(step_id, cfg.write_i, write_check_part) {
+            io_handler::write_state(dst_states_buf, &grid.states)?;
+            dst_states_buf.flush()?;
+
+            sim_state.measure_domains(grid, &domains);
+            sim_state.add_history_point();
+        }
+
+        if should_perform_action(step_id, cfg.print_i, print_check_part) {
+            println!(
+                "Steps: {}/{} | TPA: {} TPB: {} | Sweep: +{} -{}",
+                step_id, cfg.step_lim, tpa_len, tpb_len, outcome.n_added, outcome.n_removed,
+            );
+        }
+    }
+
+    drop(domains);
+    *front = Frontier::new(grid.lattice.size);
+    rebuild_front(grid, front);
+
+    Ok(())
+}
+
 fn sim_mode_1_2(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
+    dg_schedule: &mut Option<DgSchedule>,
+    attach_filter: &dyn AttachFilter,
     print_check_part: bool,
     write_check_part: bool,
     add_check_part: bool,
@@ -387,35 +558,28 @@ fn sim_mode_1_2(
 
     let (mut surf_en_change, mut d_e);
     'simulation_loop: for step_id in 1..=cfg.step_lim {
+        if let Some(schedule) = dg_schedule.as_mut() {
+            schedule.update(step_id, &mut sim_state.delta_gibbs)?;
+        }
+
         let is_add_step = add_check_part && (step_id >= add_from) && (step_id % add_i == 0);
         let is_rem_step = rem_check_part && (step_id >= rem_from) && (step_id % rem_i == 0);
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if sim_state.attach_eligible(attach_filter, grid, idxg)
+                && cfg
+                    .acceptance
+                    .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 1;
@@ -424,8 +588,8 @@ fn sim_mode_1_2(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -433,9 +597,9 @@ fn sim_mode_1_2(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -469,30 +633,18 @@ fn sim_mode_1_2(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 0;
@@ -501,17 +653,17 @@ fn sim_mode_1_2(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -544,78 +696,69 @@ fn sim_mode_1_2(
             }
         }
 
-        if sim_state.ballistics_probability > rng.random::<f64>() {
-            let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
+        if sim_state.ballistic_axes.gate() > rng.get(RngStream::Ballistic).random::<f64>() {
+            'ballistic_rem: {
+                let tpb_len = front.tpbs_size;
+                let idxl = rng.get(RngStream::Ballistic).random_range(0..tpb_len);
+                let idxg = front.tpbs[idxl];
+                let idxg_nis = &grid.lattice.neibs[idxg];
+                let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
+                let keep = sim_state.ballistic_axes.keep((smx_yz, smy_xz, smz_xy));
+                if keep < 1.0 && keep <= rng.get(RngStream::Ballistic).random::<f64>() {
+                    break 'ballistic_rem;
+                }
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
-            // d_e = surf_en_change + sim_state.delta_gibbs;
+                surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                // d_e = surf_en_change + sim_state.delta_gibbs;
 
-            sim_state.calculate_energy_change(surf_en_change);
+                sim_state.calculate_energy_change(surf_en_change);
 
-            grid.states[idxg] = 0;
-            front.tpb_rem(idxg);
-            front.tpa_add(idxg);
+                grid.states[idxg] = 0;
+                front.tpb_rem(idxg);
+                front.tpa_add(idxg);
 
-            let mut has_invalid_neib = false;
+                let mut has_invalid_neib = false;
 
-            for &neib_idx in idxg_nis.iter() {
-                if neib_idx == usize::MAX {
-                    has_invalid_neib = true;
-                    continue;
-                }
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
+                        has_invalid_neib = true;
+                        continue;
+                    }
 
-                match grid.states[neib_idx] {
-                    0 => {
-                        if !grid.neibs[neib_idx]
-                            .iter()
-                            .any(|&n| n != usize::MAX && grid.states[n] == 1)
-                        {
-                            front.tpa_rem(neib_idx);
+                    match grid.states[neib_idx] {
+                        0 => {
+                            if !grid.lattice.neibs[neib_idx]
+                                .iter()
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
+                            {
+                                front.tpa_rem(neib_idx);
+                            }
                         }
+                        1 => front.tpb_add(neib_idx),
+                        _ => {} // Handle unexpected states if necessary
                     }
-                    1 => front.tpb_add(neib_idx),
-                    _ => {} // Handle unexpected states if necessary
                 }
-            }
 
-            if has_invalid_neib {
-                sim_state.mk_step = step_id;
-                println!(
-                    "Step: {} -> Status: Sample boundary cell found in neighbors.\nSimulation stalled or completed.",
-                    step_id
-                );
+                if has_invalid_neib {
+                    sim_state.mk_step = step_id;
+                    println!(
+                        "Step: {} -> Status: Sample boundary cell found in neighbors.\nSimulation stalled or completed.",
+                        step_id
+                    );
 
-                break 'simulation_loop;
-            }
+                    break 'simulation_loop;
+                }
 
-            let (tpa_len, tpb_len) = (front.tpas_size, front.tpbs_size);
-            if tpa_len.min(tpb_len) == 0 {
-                sim_state.mk_step = step_id;
-                eprintln!(
-                    "Step: {} -> Ballistic Rem action. Found an empty Front: | TPA: {} - TPB: {} |.\nSimulation stalled or completed.",
-                    step_id, tpa_len, tpb_len
-                );
+                let (tpa_len, tpb_len) = (front.tpas_size, front.tpbs_size);
+                if tpa_len.min(tpb_len) == 0 {
+                    sim_state.mk_step = step_id;
+                    eprintln!(
+                        "Step: {} -> Ballistic Rem action. Found an empty Front: | TPA: {} - TPB: {} |.\nSimulation stalled or completed.",
+                        step_id, tpa_len, tpb_len
+                    );
 
-                break 'simulation_loop;
+                    break 'simulation_loop;
+                }
             }
         }
 
@@ -626,6 +769,7 @@ fn sim_mode_1_2(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -644,9 +788,11 @@ fn sim_mode_1_3(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
+    dg_schedule: &mut Option<DgSchedule>,
+    attach_filter: &dyn AttachFilter,
     print_check_part: bool,
     write_check_part: bool,
     add_check_part: bool,
@@ -659,42 +805,35 @@ fn sim_mode_1_3(
     ex2: f64,
     ey2: f64,
     ez2: f64,
-    eisol: f64,
+    ballistic: &mut BallisticLaw,
 ) -> Result<()> {
     sim_state.delta_gibbs = cfg.dg * 1.0;
     let p_pow = cfg.p_pow;
 
     let (mut surf_en_change, mut d_e);
     'simulation_loop: for step_id in 1..=cfg.step_lim {
+        if let Some(schedule) = dg_schedule.as_mut() {
+            schedule.update(step_id, &mut sim_state.delta_gibbs)?;
+        }
+
         let is_add_step = add_check_part && (step_id >= add_from) && (step_id % add_i == 0);
         let is_rem_step = rem_check_part && (step_id >= rem_from) && (step_id % rem_i == 0);
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if sim_state.attach_eligible(attach_filter, grid, idxg)
+                && cfg
+                    .acceptance
+                    .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 1;
@@ -703,8 +842,8 @@ fn sim_mode_1_3(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -712,9 +851,9 @@ fn sim_mode_1_3(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -748,30 +887,18 @@ fn sim_mode_1_3(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 sim_state.calculate_energy_change(surf_en_change);
 
                 grid.states[idxg] = 0;
@@ -780,17 +907,17 @@ fn sim_mode_1_3(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -832,33 +959,18 @@ fn sim_mode_1_3(
                 break 'simulation_loop;
             }
             _ => {
-                let idxl = rng.random_range(0..tpb_len);
+                let idxl = rng.get(RngStream::Ballistic).random_range(0..tpb_len);
                 let idxg = front.tpbs[idxl];
-                let idxg_nis = &grid.neibs[idxg];
+                let idxg_nis = &grid.lattice.neibs[idxg];
                 let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-                surf_en_change = 0.0;
-                match smx_yz {
-                    0 => surf_en_change -= ex2,
-                    2 => surf_en_change += ex2,
-                    _ => {}
-                }
-                match smy_xz {
-                    0 => surf_en_change -= ey2,
-                    2 => surf_en_change += ey2,
-                    _ => {}
-                }
-                match smz_xy {
-                    0 => surf_en_change -= ez2,
-                    2 => surf_en_change += ez2,
-                    _ => {}
-                }
+                surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
                 // d_e = surf_en_change + sim_state.delta_gibbs;
 
-                let prob = sim_state.ballistics_probability
-                    * (1.0f64 - (surf_en_change / eisol)).powf(p_pow);
+                let p_b = sim_state.ballistic_axes.site((smx_yz, smy_xz, smz_xy));
+                let prob = ballistic.probability(surf_en_change, p_b, p_pow);
                 // // println!("E: {:.5e}; P: {:.5e}", energy, prob);
-                if prob > rng.random::<f64>() {
+                if prob > rng.get(RngStream::Ballistic).random::<f64>() {
                     // sim_state.calculate_energy_change(d_e + eisol);
                     sim_state.calculate_energy_change(surf_en_change);
 
@@ -868,17 +980,17 @@ fn sim_mode_1_3(
 
                     let mut has_invalid_neib = false;
 
-                    for &neib_idx in idxg_nis.iter() {
-                        if neib_idx == usize::MAX {
+                    for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                        if neib_idx == NO_NEIB {
                             has_invalid_neib = true;
                             continue;
                         }
 
                         match grid.states[neib_idx] {
                             0 => {
-                                if !grid.neibs[neib_idx]
+                                if !grid.lattice.neibs[neib_idx]
                                     .iter()
-                                    .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                    .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                                 {
                                     front.tpa_rem(neib_idx);
                                 }
@@ -919,6 +1031,7 @@ fn sim_mode_1_3(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -937,7 +1050,7 @@ fn sim_mode_2_1(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
     print_check_part: bool,
@@ -970,30 +1083,18 @@ fn sim_mode_2_1(
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 let not_accepted = sim_state.update(k_t, 1.0);
 
                 if not_accepted {
@@ -1009,8 +1110,8 @@ fn sim_mode_2_1(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -1018,9 +1119,9 @@ fn sim_mode_2_1(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -1054,30 +1155,18 @@ fn sim_mode_2_1(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 let not_accepted = sim_state.update(k_t, -1.0);
 
                 if not_accepted {
@@ -1093,17 +1182,17 @@ fn sim_mode_2_1(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -1143,6 +1232,7 @@ fn sim_mode_2_1(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -1169,7 +1259,7 @@ fn sim_mode_2_2(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
     print_check_part: bool,
@@ -1202,30 +1292,18 @@ fn sim_mode_2_2(
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 let not_accepted = sim_state.update(k_t, 1.0);
 
                 if not_accepted {
@@ -1241,8 +1319,8 @@ fn sim_mode_2_2(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -1250,9 +1328,9 @@ fn sim_mode_2_2(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -1286,30 +1364,18 @@ fn sim_mode_2_2(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 let not_accepted = sim_state.update(k_t, -1.0);
 
                 if not_accepted {
@@ -1325,17 +1391,17 @@ fn sim_mode_2_2(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -1368,91 +1434,82 @@ fn sim_mode_2_2(
             }
         }
 
-        if sim_state.ballistics_probability > rng.random::<f64>() {
-            let tpb_len = front.tpbs_size;
-            if tpb_len == 0 {
-                sim_state.mk_step = step_id;
-                eprintln!("TPB Frontier is empty. Simulation stalled or completed.");
+        if sim_state.ballistic_axes.gate() > rng.get(RngStream::Ballistic).random::<f64>() {
+            'ballistic_rem: {
+                let tpb_len = front.tpbs_size;
+                if tpb_len == 0 {
+                    sim_state.mk_step = step_id;
+                    eprintln!("TPB Frontier is empty. Simulation stalled or completed.");
 
-                break 'simulation_loop;
-            }
+                    break 'simulation_loop;
+                }
 
-            let not_accepted = sim_state.update(k_t, -1.0);
+                let not_accepted = sim_state.update(k_t, -1.0);
 
-            if not_accepted {
-                continue 'simulation_loop;
-            }
+                if not_accepted {
+                    continue 'simulation_loop;
+                }
 
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
+                let idxl = rng.get(RngStream::Ballistic).random_range(0..tpb_len);
+                let idxg = front.tpbs[idxl];
+                let idxg_nis = &grid.lattice.neibs[idxg];
+                let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
+                let keep = sim_state.ballistic_axes.keep((smx_yz, smy_xz, smz_xy));
+                if keep < 1.0 && keep <= rng.get(RngStream::Ballistic).random::<f64>() {
+                    break 'ballistic_rem;
+                }
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
-            // d_e = surf_en_change + sim_state.delta_gibbs;
+                surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                // d_e = surf_en_change + sim_state.delta_gibbs;
 
-            sim_state.calculate_energy_change(surf_en_change);
+                sim_state.calculate_energy_change(surf_en_change);
 
-            grid.states[idxg] = 0;
-            front.tpb_rem(idxg);
-            front.tpa_add(idxg);
+                grid.states[idxg] = 0;
+                front.tpb_rem(idxg);
+                front.tpa_add(idxg);
 
-            let mut has_invalid_neib = false;
+                let mut has_invalid_neib = false;
 
-            for &neib_idx in idxg_nis.iter() {
-                if neib_idx == usize::MAX {
-                    has_invalid_neib = true;
-                    continue;
-                }
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
+                        has_invalid_neib = true;
+                        continue;
+                    }
 
-                match grid.states[neib_idx] {
-                    0 => {
-                        if !grid.neibs[neib_idx]
-                            .iter()
-                            .any(|&n| n != usize::MAX && grid.states[n] == 1)
-                        {
-                            front.tpa_rem(neib_idx);
+                    match grid.states[neib_idx] {
+                        0 => {
+                            if !grid.lattice.neibs[neib_idx]
+                                .iter()
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
+                            {
+                                front.tpa_rem(neib_idx);
+                            }
                         }
+                        1 => front.tpb_add(neib_idx),
+                        _ => {} // Handle unexpected states if necessary
                     }
-                    1 => front.tpb_add(neib_idx),
-                    _ => {} // Handle unexpected states if necessary
                 }
-            }
 
-            if has_invalid_neib {
-                sim_state.mk_step = step_id;
-                println!(
-                    "Step: {} -> Status: Sample boundary cell found in neighbors.\nSimulation stalled or completed.",
-                    step_id
-                );
+                if has_invalid_neib {
+                    sim_state.mk_step = step_id;
+                    println!(
+                        "Step: {} -> Status: Sample boundary cell found in neighbors.\nSimulation stalled or completed.",
+                        step_id
+                    );
 
-                break 'simulation_loop;
-            }
+                    break 'simulation_loop;
+                }
 
-            let (tpa_len, tpb_len) = (front.tpas_size, front.tpbs_size);
-            if tpa_len.min(tpb_len) == 0 {
-                sim_state.mk_step = step_id;
-                eprintln!(
-                    "Step: {} -> Ballistic Rem action. Found an empty Front: | TPA: {} - TPB: {} |.\nSimulation stalled or completed.",
-                    step_id, tpa_len, tpb_len
-                );
+                let (tpa_len, tpb_len) = (front.tpas_size, front.tpbs_size);
+                if tpa_len.min(tpb_len) == 0 {
+                    sim_state.mk_step = step_id;
+                    eprintln!(
+                        "Step: {} -> Ballistic Rem action. Found an empty Front: | TPA: {} - TPB: {} |.\nSimulation stalled or completed.",
+                        step_id, tpa_len, tpb_len
+                    );
 
-                break 'simulation_loop;
+                    break 'simulation_loop;
+                }
             }
         }
 
@@ -1463,6 +1520,7 @@ fn sim_mode_2_2(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -1489,7 +1547,7 @@ fn sim_mode_2_3(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
     sim_state: &mut SimulationState,
     print_check_part: bool,
@@ -1504,7 +1562,7 @@ fn sim_mode_2_3(
     ex2: f64,
     ey2: f64,
     ez2: f64,
-    eisol: f64,
+    ballistic: &mut BallisticLaw,
 ) -> Result<()> {
     println!(
         "Ceq: {:.5e}; C: {:.5e}; nv_gas: {:.5e}; nv_cryst: {:.5e}; dg: {:.5e}",
@@ -1523,30 +1581,18 @@ fn sim_mode_2_3(
 
         if is_add_step {
             let tpa_len = front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpa_len);
             let idxg = front.tpas[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
+            surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change - sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Add).random())
+            {
                 let not_accepted = sim_state.update(k_t, 1.0);
 
                 if not_accepted {
@@ -1562,8 +1608,8 @@ fn sim_mode_2_3(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
@@ -1571,9 +1617,9 @@ fn sim_mode_2_3(
                     match grid.states[neib_idx] {
                         0 => front.tpa_add(neib_idx),
                         1 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 0)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 0)
                             {
                                 front.tpb_rem(neib_idx);
                             }
@@ -1607,30 +1653,18 @@ fn sim_mode_2_3(
 
         if is_rem_step {
             let tpb_len = front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
+            let idxl = rng.get(RngStream::Site).random_range(0..tpb_len);
             let idxg = front.tpbs[idxl];
-            let idxg_nis = &grid.neibs[idxg];
+            let idxg_nis = &grid.lattice.neibs[idxg];
             let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
+            surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
             d_e = surf_en_change + sim_state.delta_gibbs;
 
-            if d_e < 0.0 || (-d_e / k_t).exp() > rng.random::<f64>() {
+            if cfg
+                .acceptance
+                .accepts(d_e, k_t, || rng.get(RngStream::Rem).random())
+            {
                 let not_accepted = sim_state.update(k_t, -1.0);
 
                 if not_accepted {
@@ -1646,17 +1680,17 @@ fn sim_mode_2_3(
 
                 let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
+                for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                    if neib_idx == NO_NEIB {
                         has_invalid_neib = true;
                         continue;
                     }
 
                     match grid.states[neib_idx] {
                         0 => {
-                            if !grid.neibs[neib_idx]
+                            if !grid.lattice.neibs[neib_idx]
                                 .iter()
-                                .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                             {
                                 front.tpa_rem(neib_idx);
                             }
@@ -1698,33 +1732,18 @@ fn sim_mode_2_3(
                 break 'simulation_loop;
             }
             _ => {
-                let idxl = rng.random_range(0..tpb_len);
+                let idxl = rng.get(RngStream::Ballistic).random_range(0..tpb_len);
                 let idxg = front.tpbs[idxl];
-                let idxg_nis = &grid.neibs[idxg];
+                let idxg_nis = &grid.lattice.neibs[idxg];
                 let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&grid.states, idxg_nis);
 
-                surf_en_change = 0.0;
-                match smx_yz {
-                    0 => surf_en_change -= ex2,
-                    2 => surf_en_change += ex2,
-                    _ => {}
-                }
-                match smy_xz {
-                    0 => surf_en_change -= ey2,
-                    2 => surf_en_change += ey2,
-                    _ => {}
-                }
-                match smz_xy {
-                    0 => surf_en_change -= ez2,
-                    2 => surf_en_change += ez2,
-                    _ => {}
-                }
+                surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
                 // d_e = surf_en_change + sim_state.delta_gibbs;
 
-                let prob = sim_state.ballistics_probability
-                    * (1.0f64 - (surf_en_change / eisol)).powf(p_pow);
+                let p_b = sim_state.ballistic_axes.site((smx_yz, smy_xz, smz_xy));
+                let prob = ballistic.probability(surf_en_change, p_b, p_pow);
                 // // println!("E: {:.5e}; P: {:.5e}", energy, prob);
-                if prob > rng.random::<f64>() {
+                if prob > rng.get(RngStream::Ballistic).random::<f64>() {
                     let not_accepted = sim_state.update(k_t, -1.0);
 
                     if not_accepted {
@@ -1740,17 +1759,17 @@ fn sim_mode_2_3(
 
                     let mut has_invalid_neib = false;
 
-                    for &neib_idx in idxg_nis.iter() {
-                        if neib_idx == usize::MAX {
+                    for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                        if neib_idx == NO_NEIB {
                             has_invalid_neib = true;
                             continue;
                         }
 
                         match grid.states[neib_idx] {
                             0 => {
-                                if !grid.neibs[neib_idx]
+                                if !grid.lattice.neibs[neib_idx]
                                     .iter()
-                                    .any(|&n| n != usize::MAX && grid.states[n] == 1)
+                                    .any(|&n| n as usize != NO_NEIB && grid.states[n as usize] == 1)
                                 {
                                     front.tpa_rem(neib_idx);
                                 }
@@ -1791,6 +1810,7 @@ fn sim_mode_2_3(
             dst_states_buf.flush()?;
 
             sim_state.measure_crystal_sizes(&grid, &front);
+            sim_state.measure_front_sizes(&front);
             sim_state.add_history_point();
         }
 
@@ -1817,9 +1837,9 @@ pub fn run_calculations(
     cfg: &Settings,
     grid: &mut Grid,
     front: &mut Frontier,
-    rng: &mut ChaCha8Rng,
+    rng: &mut RngStreams,
     dst_states_buf: &mut BufWriter<File>,
-) -> Result<()> {
+) -> Result<SimResult> {
     let path_out_file_1 = cfg.dst_path.join("sim_history.txt");
     let out_file_1 = File::create(path_out_file_1)?;
     let mut out_file_1_buf = BufWriter::new(out_file_1);
@@ -1832,6 +1852,8 @@ pub fn run_calculations(
     );
     let (ex2, ey2, ez2) = (ex * 2.0, ey * 2.0, ez * 2.0);
     let eisol = ex2 + ey2 + ez2;
+    let mut ballistic = BallisticLaw::new(cfg.e_des, eisol, &cfg.pb_expr, k_t, cfg.temperature)
+        .map_err(|e| IoError::new(ErrorKind::InvalidInput, format!("Invalid 'PbExpr': {}", e)))?;
 
     activate_center(cfg, grid)?;
     let n_cr_calculated = rebuild_front(grid, front);
@@ -1854,12 +1876,47 @@ pub fn run_calculations(
         }
     };
 
-    let mut sim_state = SimulationState::new(k_t, cfg.p_b, cfg.c_eq, cfg.c0, cfg.n_tot, n_cr);
+    let ballistic_axes = BallisticAxes::new(cfg.p_b, (cfg.p_b_x, cfg.p_b_y, cfg.p_b_z));
+    let mut sim_state =
+        SimulationState::new(k_t, ballistic_axes, cfg.c_eq, cfg.c0, cfg.n_tot, n_cr);
     sim_state.measure_crystal_sizes(&grid, &front);
+    sim_state.measure_front_sizes(&front);
     sim_state.add_history_point();
 
-    match cfg.mode {
-        1.1 => {
+    let mut dg_schedule = DgSchedule::from_settings(cfg, k_t)
+        .map_err(|e| IoError::new(ErrorKind::InvalidInput, e.to_string()))?;
+    if dg_schedule.is_some() && cfg.mode_major == 2 {
+        eprintln!(
+            "⚠️ Warning: dgExpr is only used in modes 1.x, ignored in mode {}.{}.",
+            cfg.mode_major, cfg.mode_minor
+        );
+    }
+
+    match cfg.mode() {
+        (1, 1) if cfg.domains > 1 => {
+            let _ = sim_mode_1_1_domains(
+                &cfg,
+                grid,
+                front,
+                rng,
+                dst_states_buf,
+                &mut sim_state,
+                &mut dg_schedule,
+                print_check_part,
+                write_check_part,
+                add_check_part,
+                cfg.add_i,
+                cfg.add_from,
+                rem_check_part,
+                cfg.rem_i,
+                cfg.rem_from,
+                k_t,
+                ex2,
+                ey2,
+                ez2,
+            );
+        }
+        (1, 1) => {
             let _ = sim_mode_1_1(
                 &cfg,
                 grid,
@@ -1867,6 +1924,8 @@ pub fn run_calculations(
                 rng,
                 dst_states_buf,
                 &mut sim_state,
+                &mut dg_schedule,
+                &cfg.attach_source,
                 print_check_part,
                 write_check_part,
                 add_check_part,
@@ -1882,7 +1941,7 @@ pub fn run_calculations(
                 eisol,
             );
         }
-        1.2 => {
+        (1, 2) => {
             let _ = sim_mode_1_2(
                 &cfg,
                 grid,
@@ -1890,6 +1949,8 @@ pub fn run_calculations(
                 rng,
                 dst_states_buf,
                 &mut sim_state,
+                &mut dg_schedule,
+                &cfg.attach_source,
                 print_check_part,
                 write_check_part,
                 add_check_part,
@@ -1905,7 +1966,7 @@ pub fn run_calculations(
                 eisol,
             );
         }
-        1.3 => {
+        (1, 3) => {
             let _ = sim_mode_1_3(
                 &cfg,
                 grid,
@@ -1913,6 +1974,8 @@ pub fn run_calculations(
                 rng,
                 dst_states_buf,
                 &mut sim_state,
+                &mut dg_schedule,
+                &cfg.attach_source,
                 print_check_part,
                 write_check_part,
                 add_check_part,
@@ -1925,10 +1988,10 @@ pub fn run_calculations(
                 ex2,
                 ey2,
                 ez2,
-                eisol,
+                &mut ballistic,
             );
         }
-        2.1 => {
+        (2, 1) => {
             let _ = sim_mode_2_1(
                 &cfg,
                 grid,
@@ -1951,7 +2014,7 @@ pub fn run_calculations(
                 eisol,
             );
         }
-        2.2 => {
+        (2, 2) => {
             let _ = sim_mode_2_2(
                 &cfg,
                 grid,
@@ -1974,7 +2037,7 @@ pub fn run_calculations(
                 eisol,
             );
         }
-        2.3 => {
+        (2, 3) => {
             let _ = sim_mode_2_3(
                 &cfg,
                 grid,
@@ -1994,16 +2057,24 @@ pub fn run_calculations(
                 ex2,
                 ey2,
                 ez2,
-                eisol,
+                &mut ballistic,
             );
         }
         _ => {}
     }
 
+    if sim_state.attach_skips > 0 {
+        println!(
+            "📒 {} attachment attempt(s) on TPA sites outside the feed skipped (AttachSource).",
+            sim_state.attach_skips
+        );
+    }
+
     io_handler::write_state(dst_states_buf, &grid.states)?;
     dst_states_buf.flush()?;
 
     sim_state.measure_crystal_sizes(&grid, &front);
+    sim_state.measure_front_sizes(&front);
     sim_state.add_history_point();
 
     io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.n_gas_history)?;
@@ -2015,9 +2086,22 @@ pub fn run_calculations(
     io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.crystal_sy_history)?;
     io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.crystal_sz_history)?;
     io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.mk_step_history)?;
+    io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.tpas_size_history)?;
+    io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.tpbs_size_history)?;
+    io_handler::write_f64_state(&mut out_file_1_buf, &sim_state.energy_change_error_history)?;
     out_file_1_buf.flush()?;
 
-    Ok(())
+    Ok(SimResult {
+        dst_path: cfg.dst_path.clone(),
+        mk_step: sim_state.mk_step,
+        n_cryst: sim_state.n_crystal,
+        n_gas: sim_state.n_gas,
+        conc: sim_state.concentration,
+        dg: sim_state.delta_gibbs,
+        total_energy_change: sim_state.total_energy_change,
+        states: Box::default(),
+        elapsed: Duration::ZERO,
+    })
 }
 
 #[inline(always)]
@@ -2027,9 +2111,9 @@ fn should_perform_action(step_id: u64, interval: u64, pre_flag: bool) -> bool {
 
 #[inline(always)]
 fn activate_center(cfg: &Settings, grid: &mut Grid) -> IoResult<()> {
-    let center_id = grid.xyz_to_idx(cfg.sx / 2, cfg.sy / 2, cfg.sz / 2);
+    let center_id = grid.lattice.xyz_to_idx(cfg.sx / 2, cfg.sy / 2, cfg.sz / 2);
 
-    if center_id >= grid.size {
+    if center_id >= grid.lattice.size {
         return Err(IoError::new(
             ErrorKind::InvalidData,
             "Center index out of bounds",
@@ -2044,59 +2128,11 @@ fn activate_center(cfg: &Settings, grid: &mut Grid) -> IoResult<()> {
 #[inline(always)]
 fn rebuild_front(grid: &Grid, front: &mut Frontier) -> f64 {
     println!("Обновление фронтов газа и кластера...");
-    let states = &grid.states;
-    let neibs = &grid.neibs;
-
-    let mut n_cr_calculated = 0.0;
-
-    for (i, &state) in states.iter().enumerate() {
-        if state == 1 {
-            n_cr_calculated += 1.0;
-
-            let mut has_vacancy_neighbor = false;
-
-            for neib_idx in neibs[i] {
-                if neib_idx != usize::MAX {
-                    if states[neib_idx] == 0 {
-                        has_vacancy_neighbor = true;
-                        front.tpa_add(neib_idx);
-                    }
-                }
-            }
-
-            if has_vacancy_neighbor {
-                front.tpb_add(i);
-            }
-        }
-    }
+    let n_cr_calculated = fill_front(&grid.states, &grid.lattice.neibs, front);
     println!(
         "Обновление завершено! Узлов фронта газа: {}, Узлов фронта кластера: {}",
         front.tpas_size, front.tpbs_size
     );
 
-    return n_cr_calculated;
-}
-
-#[inline(always)]
-fn compute_neighbor_sums(states: &[u8], idxg_nis: &[usize; 6]) -> (u8, u8, u8) {
-    let mut x_axis_neighbors = 0;
-    let mut y_axis_neighbors = 0;
-    let mut z_axis_neighbors = 0;
-
-    for i in 0..6 {
-        let idx = unsafe { *idxg_nis.get_unchecked(i) };
-        if idx != usize::MAX {
-            let state = unsafe { *states.get_unchecked(idx) };
-            if state == 1 {
-                match i {
-                    0 | 1 => x_axis_neighbors += 1,
-                    2 | 3 => y_axis_neighbors += 1,
-                    4 | 5 => z_axis_neighbors += 1,
-                    _ => unreachable!(), // Цикл всегда от 0 до 5
-                }
-            }
-        }
-    }
-
-    (x_axis_neighbors, y_axis_neighbors, z_axis_neighbors)
+    n_cr_calculated
 }
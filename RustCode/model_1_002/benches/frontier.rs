@@ -0,0 +1,156 @@
+//! Frontier backings against each other across front densities: the dense arrays (the
+//! default), the hashed site index (`front-hashed`) and the site bitsets
+//! (`front-bitset`), to find where one overtakes another.
+//!
+//! `cargo bench -p model_1_002 --bench frontier`; the churn bench always builds all
+//! three, the kernel bench runs the backing of the build, so compare it across builds
+//! with `--features front-hashed` or `--features front-bitset`.
+
+use criterion::{
+    BenchmarkGroup, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+    measurement::WallTime,
+};
+use model_1_002::mods::{
+    constants::K_BOLTZMANN,
+    frontier::{BitsetIndex, BuildIndex, DenseIndex, Frontier, FrontierOf, HashedIndex, SiteIndex},
+    item::Item,
+    lattice::Grid,
+    utils::rebuild_front,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// Edge of the periodic cubic grid of the churn bench.
+const CHURN_EDGE: usize = 64;
+/// Share of the grid in the front.
+const DENSITIES: [f64; 5] = [0.001, 0.01, 0.05, 0.2, 0.5];
+/// Removals (each followed by an addition) per measured batch.
+const CHURN_OPS: usize = 4096;
+/// Edge of the kernel bench grid and the share of it taken by the crystal cube.
+const KERNEL_EDGE: usize = 40;
+const FILLS: [f64; 2] = [0.125, 0.9];
+/// Kernel steps per measured batch.
+const KERNEL_STEPS: u64 = 1_000;
+
+/// Removal of a random front site and addition of a random site off the front, at a
+/// constant front size of `density` of the grid.
+fn bench_churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frontier_churn");
+    group.throughput(Throughput::Elements(2 * CHURN_OPS as u64));
+
+    for density in DENSITIES {
+        churn::<DenseIndex>(&mut group, density);
+        churn::<HashedIndex>(&mut group, density);
+        churn::<BitsetIndex>(&mut group, density);
+    }
+    group.finish();
+}
+
+fn churn<I: SiteIndex>(group: &mut BenchmarkGroup<WallTime>, density: f64) {
+    let size = CHURN_EDGE.pow(3);
+    let n_front = ((size as f64 * density) as usize).max(1);
+    let mut rng = ChaCha8Rng::seed_from_u64(1012);
+    let mut front = FrontierOf::<I>::new(size);
+    while front.tpas_size < n_front {
+        front.tpa_add(rng.random_range(0..size));
+    }
+
+    group.bench_function(BenchmarkId::new(I::NAME, density), |b| {
+        b.iter(|| {
+            for _ in 0..CHURN_OPS {
+                let idxg = front.tpas[rng.random_range(0..front.tpas_size)];
+                front.tpa_rem(idxg);
+                loop {
+                    let idxg = rng.random_range(0..size);
+                    if !front.is_tpa(idxg) {
+                        front.tpa_add(idxg);
+                        break;
+                    }
+                }
+            }
+            black_box(front.tpas_size)
+        })
+    });
+}
+
+/// Periodic `n^3` grid with a centred crystal cube taking `fill` of it.
+fn seeded(n: usize, fill: f64) -> (Grid, Box<[u8]>) {
+    let grid = Grid::new(n, n, n, true, true, true).expect("valid bench grid");
+    let edge = ((n as f64 * fill.cbrt()).round() as usize).clamp(1, n - 2);
+    let lo = (n - edge) / 2;
+    let state = (0..grid.size)
+        .map(|idx| {
+            let (x, y, z) = grid.idx_to_xyz(idx);
+            [x, y, z].iter().all(|c| (lo..lo + edge).contains(c)) as u8
+        })
+        .collect();
+    (grid, state)
+}
+
+/// Mode 2.1 steps near equilibrium with the backing of the build, for a small crystal
+/// and for one filling most of the lattice.
+fn bench_kernel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frontier_kernel_2_1");
+    group.throughput(Throughput::Elements(KERNEL_STEPS));
+    let dir = std::env::temp_dir().join("model_1_002_bench_frontier");
+
+    for fill in FILLS {
+        let (mut grid, state0) = seeded(KERNEL_EDGE, fill);
+        let n_cryst0 = state0.iter().filter(|&&cell| cell == 1).count() as f64;
+        let k_t = K_BOLTZMANN * 300.0;
+        let energies = (2.0 * k_t, 2.0 * k_t, 2.0 * k_t);
+
+        let name = BuildIndex::NAME;
+        let mut front0 = Frontier::new(grid.size);
+        rebuild_front(&state0, &grid.neibs, &mut front0);
+        let mut item = Item::new(
+            0,
+            state0.clone(),
+            front0.clone(),
+            dir.join(format!("{name}_{fill}")),
+        )
+        .expect("bench item directory");
+        let mut rng = ChaCha8Rng::seed_from_u64(1012);
+
+        group.bench_function(BenchmarkId::new(name, fill), |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    item.state.copy_from_slice(&state0);
+                    item.front = front0.clone();
+                    item.is_alive = true;
+                    item.simlog.initialize(
+                        k_t,
+                        (1, 1),
+                        0.5 * k_t,
+                        1.0,
+                        1.0,
+                        1e12,
+                        n_cryst0,
+                        0.0,
+                        0.0,
+                    );
+
+                    let start = Instant::now();
+                    for step_id in 1..=KERNEL_STEPS {
+                        let flags = (true, true, false, false);
+                        if !item.mode_2_1_step(&mut rng, &mut grid, energies, 0, step_id, flags) {
+                            break;
+                        }
+                    }
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, bench_churn, bench_kernel);
+criterion_main!(benches);
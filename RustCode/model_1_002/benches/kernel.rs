@@ -0,0 +1,190 @@
+//! Speed of the simulation hot paths across grid sizes: the attach/detach kernel, the
+//! front updates, the neighbour sums and the state serialization.
+//!
+//! `cargo bench -p model_1_002`; criterion keeps the previous results in
+//! `target/criterion` and reports the change against them.
+
+use criterion::{
+    BenchmarkGroup, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+    measurement::WallTime,
+};
+use model_1_002::mods::{
+    constants::K_BOLTZMANN,
+    frontier::{DenseIndex, Frontier, FrontierOf, HashedIndex, SiteIndex},
+    io_handler,
+    item::Item,
+    lattice::Grid,
+    utils::{compute_neighbor_sums, rebuild_front},
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// Edges of the periodic cubic grids.
+const SIZES: [usize; 3] = [20, 40, 80];
+/// Kernel steps per measured batch.
+const KERNEL_STEPS: u64 = 1_000;
+
+/// Periodic `n^3` grid with a centred crystal cube of edge `n / 2`.
+fn seeded(n: usize) -> (Grid, Box<[u8]>) {
+    let grid = Grid::new(n, n, n, true, true, true).expect("valid bench grid");
+    let (lo, hi) = (n / 4, n / 4 + n / 2);
+    let state = (0..grid.size)
+        .map(|idx| {
+            let (x, y, z) = grid.idx_to_xyz(idx);
+            [x, y, z].iter().all(|c| (lo..hi).contains(c)) as u8
+        })
+        .collect();
+    (grid, state)
+}
+
+fn front_of(grid: &Grid, state: &[u8]) -> Frontier {
+    let mut front = Frontier::new(state.len());
+    rebuild_front(state, &grid.neibs, &mut front);
+    front
+}
+
+/// Mode 2.1 steps (attach and detach every step) near equilibrium, from the seeded
+/// state each batch.
+fn bench_kernel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kernel_2_1");
+    group.throughput(Throughput::Elements(KERNEL_STEPS));
+    let dir = std::env::temp_dir().join("model_1_002_bench");
+
+    for n in SIZES {
+        let (mut grid, state0) = seeded(n);
+        let front0 = front_of(&grid, &state0);
+        let mut item = Item::new(0, state0.clone(), front0.clone(), dir.join(n.to_string()))
+            .expect("bench item directory");
+        let k_t = K_BOLTZMANN * 300.0;
+        let energies = (2.0 * k_t, 2.0 * k_t, 2.0 * k_t);
+        let n_cryst0 = state0.iter().filter(|&&cell| cell == 1).count() as f64;
+        let mut rng = ChaCha8Rng::seed_from_u64(1012);
+
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    item.state.copy_from_slice(&state0);
+                    item.front = front0.clone();
+                    item.is_alive = true;
+                    item.simlog.initialize(
+                        k_t,
+                        (1, 1),
+                        0.5 * k_t,
+                        1.0,
+                        1.0,
+                        1e12,
+                        n_cryst0,
+                        0.0,
+                        0.0,
+                    );
+
+                    let start = Instant::now();
+                    for step_id in 1..=KERNEL_STEPS {
+                        let flags = (true, true, false, false);
+                        if !item.mode_2_1_step(&mut rng, &mut grid, energies, 0, step_id, flags) {
+                            break;
+                        }
+                    }
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Add and remove of random sites, dense and hashed site index.
+fn bench_frontier(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frontier_add_rem");
+    for n in SIZES {
+        let size = n * n * n;
+        let mut rng = ChaCha8Rng::seed_from_u64(1012);
+        let sites: Vec<usize> = (0..4096).map(|_| rng.random_range(0..size)).collect();
+        group.throughput(Throughput::Elements(2 * sites.len() as u64));
+
+        add_rem::<DenseIndex>(&mut group, n, &sites);
+        add_rem::<HashedIndex>(&mut group, n, &sites);
+    }
+    group.finish();
+}
+
+fn add_rem<I: SiteIndex>(group: &mut BenchmarkGroup<WallTime>, n: usize, sites: &[usize]) {
+    let mut front = FrontierOf::<I>::new(n * n * n);
+    group.bench_with_input(BenchmarkId::new(I::NAME, n), sites, |b, sites| {
+        b.iter(|| {
+            for &idxg in sites {
+                front.tpa_add(idxg);
+            }
+            for &idxg in sites {
+                front.tpa_rem(idxg);
+            }
+            black_box(front.tpas_size)
+        })
+    });
+}
+
+/// Neighbour sums of every cell of the seeded grid.
+fn bench_neighbor_sums(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbor_sums");
+    for n in SIZES {
+        let (grid, state) = seeded(n);
+        group.throughput(Throughput::Elements(grid.size as u64));
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter(|| {
+                grid.neibs
+                    .iter()
+                    .map(|idxg_nis| {
+                        let (sx, sy, sz) = compute_neighbor_sums(&state, idxg_nis);
+                        (sx + sy + sz) as u64
+                    })
+                    .sum::<u64>()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// One snapshot of the seeded state as a `TimeStates.txt` line and as a packed
+/// `TimeStates.bin` frame.
+fn bench_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("state_serialization");
+    for n in SIZES {
+        let (_, state) = seeded(n);
+        group.throughput(Throughput::Bytes(state.len() as u64));
+
+        let mut line = Vec::with_capacity(2 * state.len());
+        group.bench_function(BenchmarkId::new("text", n), |b| {
+            b.iter(|| {
+                line.clear();
+                io_handler::write_state(&mut line, &state).unwrap();
+                black_box(line.len())
+            })
+        });
+
+        let mut frame = Vec::with_capacity(state.len());
+        group.bench_function(BenchmarkId::new("bin_packed", n), |b| {
+            b.iter(|| {
+                frame.clear();
+                io_handler::write_state_bin(&mut frame, &state, true).unwrap();
+                black_box(frame.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_kernel,
+    bench_frontier,
+    bench_neighbor_sums,
+    bench_serialization
+);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+//! The model modules as a library, shared by the `model_1_002` binary and the
+//! benchmarks in `benches/`, and embeddable in other programs through
+//! `Simulation::run`.
+
+pub mod mods;
+
+pub use mods::{
+    ensemble::{Ensemble, Termination},
+    run::{SimResult, Simulation},
+    settings::{Settings, SettingsBuilder, SettingsError},
+};
@@ -10,11 +10,10 @@
 // use rand::SeedableRng;
 // use rand_chacha::ChaCha8Rng;
 
-mod mods;
-
-use mods::{
-    constants::INIT_TIME_STATES_FILE_NAME, ensemble::Ensemble, frontier::Frontier, io_handler,
-    lattice::Grid, settings::Settings,
+use model_1_002::mods::{
+    compare, constants::INIT_TIME_STATES_FILE_NAME, convert, diff, distributed, ensemble::Ensemble,
+    exact, fixture, frontier::Frontier, io_handler, lattice::Grid, manifest, pack, plots, reweight,
+    server, settings::Settings,
 };
 
 use std::{fs::File, io::BufWriter, time::Instant};
@@ -25,9 +24,46 @@ use rand_chacha::ChaCha8Rng;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let sta1 = Instant::now();
 
-    let mut ensemble = match Ensemble::new() {
+    let args: Vec<String> = std::env::args().collect();
+    type Tool = fn(&[String]) -> Result<(), Box<dyn std::error::Error>>;
+    let tool: Option<(Tool, &str)> = match args.get(1).map(String::as_str) {
+        Some("convert") => Some((convert::run, "Conversion")),
+        Some("compare") => Some((compare::run, "Comparison")),
+        Some("diff") => Some((diff::run, "Diff")),
+        Some("plots") => Some((plots::run, "Plotting")),
+        Some("exact") => Some((exact::run, "Exact comparison")),
+        Some("reweight") => Some((reweight::run, "Reweighting")),
+        Some("fixture") => Some((fixture::run, "Fixture generation")),
+        Some("serve") => Some((server::run, "Server")),
+        Some("manifest") => Some((manifest::run, "Manifest")),
+        Some("pack") => Some((pack::run, "Packing")),
+        Some("rank") => Some((distributed::run, "Distributed rank")),
+        _ => None,
+    };
+    if let Some((run, what)) = tool {
+        if let Err(e) = run(&args[2..]) {
+            eprintln!("❌ {} failed: {}", what, e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let ensemble = match args.get(1).map(String::as_str) {
+        Some("--preset") => match args.get(2) {
+            Some(preset) => Ensemble::with_preset(preset),
+            None => {
+                eprintln!("❌ Usage: --preset <name>");
+                std::process::exit(1);
+            }
+        },
+        _ => Ensemble::new(),
+    };
+    let mut ensemble = match ensemble {
         Ok(e) => {
             println!("✅ Ensemble created successfully!");
+            if e.cfg.preset != "none" {
+                println!("📒 Preset: {}", e.cfg.preset);
+            }
             e
         }
         Err(e) => {
@@ -36,7 +72,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let _ = ensemble.run_simulation();
+    if let Err(e) = ensemble.run_simulation() {
+        eprintln!("❌ Simulation failed: {}", e);
+        ensemble.salvage();
+        std::process::exit(1);
+    }
 
     // println!("src_path: {:?}", ensemble.cfg.src_path);
 
@@ -0,0 +1,52 @@
+use crate::mods::lattice::Grid;
+
+/// Core region of an item whose crystal cells never detach (`Anchor`), so dissolution
+/// fluctuations during a long induction period cannot annihilate the nucleus. Rejected
+/// detachment attempts on it (plain, ballistic and swaps) are counted by the item and
+/// printed at the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Off,
+    /// The crystal cells of the initial state (of the resumed state on `Resume`).
+    Seed,
+    /// Grid box `X0:X1:Y0:Y1:Z0:Z1`, bounds inclusive, clipped to the grid.
+    Box([(usize, usize); 3]),
+}
+
+impl Anchor {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Some(Self::Off),
+            "seed" => Some(Self::Seed),
+            s => {
+                let bounds: Vec<usize> = s
+                    .split(':')
+                    .map(|v| v.trim().parse().ok())
+                    .collect::<Option<_>>()?;
+                match bounds[..] {
+                    [x0, x1, y0, y1, z0, z1] if x0 <= x1 && y0 <= y1 && z0 <= z1 => {
+                        Some(Self::Box([(x0, x1), (y0, y1), (z0, z1)]))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Protected cells of an item with the initial `state` on `grid`; empty when off.
+    pub fn mask(&self, state: &[u8], grid: &Grid) -> Box<[bool]> {
+        match self {
+            Self::Off => Box::new([]),
+            Self::Seed => state.iter().map(|&cell| cell == 1).collect(),
+            Self::Box(bounds) => (0..grid.size)
+                .map(|idx| {
+                    let (x, y, z) = grid.idx_to_xyz(idx);
+                    [x, y, z]
+                        .iter()
+                        .zip(bounds)
+                        .all(|(c, (lo, hi))| (lo..=hi).contains(&c))
+                })
+                .collect(),
+        }
+    }
+}
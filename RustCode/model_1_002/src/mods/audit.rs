@@ -0,0 +1,127 @@
+use crate::mods::{
+    constants::RNG_AUDIT_FILE_NAME,
+    faults::{self, FaultPoint},
+    rng::KernelRng,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::PathBuf,
+};
+
+/// Event types whose RNG draws are counted separately.
+#[derive(Debug, Clone, Copy)]
+pub enum RngEvent {
+    Add,
+    Rem,
+    Swap,
+    Ballistic,
+}
+
+const RNG_EVENT_NAMES: [&str; 4] = ["Add", "Rem", "Swap", "Ballistic"];
+
+/// Debug counter of RNG draws, measured in 32-bit words of the ChaCha stream.
+///
+/// Items mark the start of every event with `enter`; the draws up to the next mark
+/// are booked to that event. The ensemble-level audit writes one
+/// `step:draws:stream_pos` line per step, so the first step where two runs diverge
+/// can be located by comparing (or bisecting) their audit files.
+#[derive(Debug, Default)]
+pub struct RngAudit {
+    pub is_on: bool,
+    pub totals: [u128; 4],
+    current: Option<(RngEvent, u128)>,
+    last_step_pos: u128,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl RngAudit {
+    pub fn new(is_on: bool) -> Self {
+        Self {
+            is_on,
+            ..Self::default()
+        }
+    }
+
+    pub fn create_out_file(&mut self, path_dst: PathBuf) -> IoResult<()> {
+        let path_out_file = path_dst.join(RNG_AUDIT_FILE_NAME);
+
+        let out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        self.out_file_buf = Some(out_file_buf);
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn enter(&mut self, event: RngEvent, rng: &impl KernelRng) {
+        if !self.is_on {
+            return;
+        }
+
+        let pos = rng.word_pos();
+        if let Some((prev_event, prev_pos)) = self.current.replace((event, pos)) {
+            self.totals[prev_event as usize] += pos - prev_pos;
+        }
+    }
+
+    /// Books the draws of the last open event and moves the totals of `item_audit` here.
+    pub fn collect(&mut self, item_audit: &mut RngAudit, rng: &impl KernelRng) {
+        if !self.is_on {
+            return;
+        }
+
+        if let Some((prev_event, prev_pos)) = item_audit.current.take() {
+            item_audit.totals[prev_event as usize] += rng.word_pos() - prev_pos;
+        }
+        for (total, item_total) in self.totals.iter_mut().zip(item_audit.totals.iter_mut()) {
+            *total += std::mem::take(item_total);
+        }
+    }
+
+    pub fn write_step(&mut self, step_id: u64, rng: &impl KernelRng) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+
+        let pos = rng.word_pos();
+        let draws = pos - self.last_step_pos;
+        self.last_step_pos = pos;
+
+        if let Some(fbuf) = self.out_file_buf.as_mut() {
+            faults::inject(FaultPoint::Stats)?;
+            writeln!(fbuf, "{}:{}:{}", step_id, draws, pos)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the per-event totals and appends them to the audit file as `# ` lines.
+    pub fn finish(&mut self) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+
+        let summary = RNG_EVENT_NAMES
+            .iter()
+            .zip(self.totals.iter())
+            .map(|(name, total)| format!("{}: {}", name, total))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        println!("RNG draws (32-bit words) -> {}", summary);
+
+        if let Some(fbuf) = self.out_file_buf.as_mut() {
+            for (name, total) in RNG_EVENT_NAMES.iter().zip(self.totals.iter()) {
+                writeln!(fbuf, "# {}:{}", name, total)?;
+            }
+            fbuf.flush()?;
+        }
+
+        Ok(())
+    }
+}
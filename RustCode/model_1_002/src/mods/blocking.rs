@@ -0,0 +1,163 @@
+use crate::mods::item::Item;
+
+/// Fewest blocks a level needs before its error estimate is trusted.
+const MIN_BLOCKS: u64 = 16;
+
+#[derive(Debug, Clone, Default)]
+struct BlockLevel {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    pending: Option<f64>,
+}
+
+impl BlockLevel {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn error(&self) -> Option<f64> {
+        if self.count < MIN_BLOCKS {
+            return None;
+        }
+        let n = self.count as f64;
+        Some((self.m2 / (n - 1.0) / n).sqrt())
+    }
+}
+
+/// Online blocking analysis (Flyvbjerg-Petersen) of a correlated series.
+///
+/// Level `k` holds the Welford statistics of block averages over `2^k` samples; only
+/// one unpaired value is kept per level, so memory grows as `log2(N)`. The error of
+/// the mean is the largest estimate over the levels with at least `MIN_BLOCKS` blocks,
+/// and `tau = 0.5 * (error / naive_error)^2` estimates the integrated autocorrelation
+/// time in samples.
+#[derive(Debug, Default)]
+pub struct BlockingAnalysis {
+    levels: Vec<BlockLevel>,
+}
+
+impl BlockingAnalysis {
+    pub fn push(&mut self, value: f64) {
+        let mut value = value;
+        let mut level_id = 0;
+
+        loop {
+            if self.levels.len() == level_id {
+                self.levels.push(BlockLevel::default());
+            }
+
+            let level = &mut self.levels[level_id];
+            level.push(value);
+
+            match level.pending.take() {
+                None => {
+                    level.pending = Some(value);
+                    return;
+                }
+                Some(prev) => {
+                    value = 0.5 * (prev + value);
+                    level_id += 1;
+                }
+            }
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.levels.first().map_or(0, |l| l.count)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.levels.first().map_or(0.0, |l| l.mean)
+    }
+
+    /// Standard error of the mean assuming uncorrelated samples.
+    pub fn naive_error(&self) -> Option<f64> {
+        self.levels.first().and_then(BlockLevel::error)
+    }
+
+    pub fn error(&self) -> Option<f64> {
+        self.levels
+            .iter()
+            .filter_map(BlockLevel::error)
+            .reduce(f64::max)
+    }
+
+    pub fn tau(&self) -> Option<f64> {
+        match (self.error(), self.naive_error()) {
+            (Some(err), Some(naive)) if naive > 0.0 => Some(0.5 * (err / naive).powi(2)),
+            _ => None,
+        }
+    }
+
+    /// `true` while fewer than two levels have enough blocks, i.e. the error estimate
+    /// has not had a chance to plateau.
+    pub fn is_too_short(&self) -> bool {
+        self.levels.iter().filter(|l| l.error().is_some()).count() < 2
+    }
+
+    pub fn summary(&self, name: &str) -> String {
+        match (self.error(), self.tau()) {
+            (Some(err), Some(tau)) => format!(
+                "{}: {:.6e} +- {:.3e} (tau_int ~ {:.1} samples, {} samples){}",
+                name,
+                self.mean(),
+                err,
+                tau,
+                self.count(),
+                match self.is_too_short() {
+                    true => " [run too short for a reliable error]",
+                    false => "",
+                }
+            ),
+            _ => format!(
+                "{}: {:.6e} ({} samples, too few for an error estimate)",
+                name,
+                self.mean(),
+                self.count()
+            ),
+        }
+    }
+}
+
+/// Online error bars of the ensemble observables, sampled at write steps.
+///
+/// Roughness is the number of surface (TPB) sites per crystal site over all items.
+#[derive(Debug, Default)]
+pub struct ObservableStats {
+    pub from_step: u64,
+    pub n_cryst: BlockingAnalysis,
+    pub roughness: BlockingAnalysis,
+}
+
+impl ObservableStats {
+    pub fn new(from_step: u64) -> Self {
+        Self {
+            from_step,
+            ..Self::default()
+        }
+    }
+
+    pub fn sample(&mut self, step_id: u64, items: &[Item], n_cryst: f64) {
+        if step_id < self.from_step {
+            return;
+        }
+
+        let n_surf: usize = items.iter().map(|item| item.front.tpbs_size).sum();
+
+        self.n_cryst.push(n_cryst);
+        if n_cryst > 0.0 {
+            self.roughness.push(n_surf as f64 / n_cryst);
+        }
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            self.n_cryst.summary("n_cryst"),
+            self.roughness.summary("roughness"),
+        ]
+    }
+}
@@ -0,0 +1,80 @@
+use crate::mods::{settings::Settings, state::SimLog};
+use chrono::Utc;
+use std::{
+    fs::OpenOptions,
+    io::{Error as IoError, Result as IoResult, Write},
+    path::{Path, PathBuf},
+};
+
+const INDEX_HEADER: &str =
+    "# finished\tsettings_hash\tdir\tmk_step\tn_cryst\tconc\tdg\titems_alive\titems";
+
+/// `Campaign_<name>.txt` in `CampaignDir` (`none` - the program folder `src_path`).
+pub fn index_path(cfg: &Settings, src_path: &Path) -> PathBuf {
+    let dir = match cfg.campaign_dir_path() {
+        None => src_path.to_path_buf(),
+        Some(dir) => PathBuf::from(dir),
+    };
+    dir.join(format!("Campaign_{}.txt", cfg.campaign.trim()))
+}
+
+/// FNV-1a hash of the settings (the rank aside), equal for runs of the same config.
+pub fn settings_hash(cfg: &Settings) -> u64 {
+    let cfg = Settings {
+        rank: 0,
+        ..cfg.clone()
+    };
+    format!("{:?}", cfg)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// Appends the run to the campaign index under an exclusive lock, so runs finishing at
+/// the same time (other processes, other nodes on a shared disk) do not mix their lines.
+/// Tab-separated: the directory may hold ':'.
+pub fn append_run(
+    cfg: &Settings,
+    src_path: &Path,
+    dst_path: &Path,
+    simlog: &SimLog,
+    (items_alive, items): (usize, usize),
+) -> IoResult<PathBuf> {
+    let path_index = index_path(cfg, src_path);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path_index)
+        .map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to open file '{}': {}", path_index.display(), e),
+            )
+        })?;
+    file.lock()?;
+
+    let mut line = String::new();
+    if file.metadata()?.len() == 0 {
+        line.push_str(INDEX_HEADER);
+        line.push('\n');
+    }
+    let dir = dst_path.canonicalize().unwrap_or(dst_path.to_path_buf());
+    line.push_str(&format!(
+        "{}\t{:016x}\t{}\t{}\t{:.5e}\t{:.5e}\t{:.5e}\t{}\t{}\n",
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        settings_hash(cfg),
+        dir.display(),
+        simlog.mk_step.val,
+        simlog.n_cryst.val,
+        simlog.conc.val,
+        simlog.dg.val,
+        items_alive,
+        items
+    ));
+    (&file).write_all(line.as_bytes())?;
+    (&file).flush()?;
+    file.unlock()?;
+
+    Ok(path_index)
+}
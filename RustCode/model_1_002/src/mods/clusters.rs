@@ -0,0 +1,227 @@
+use crate::mods::{
+    constants::{CLUSTERS_FILE_NAME, MERGES_FILE_NAME},
+    lattice::{Idx, NO_NEIB},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Identity of the separate crystals (clusters) on the grid of an item (`ClusterTrack`).
+///
+/// Several seeds in one state grow from the one shared front, so the kernel already
+/// interleaves their events; the tracker keeps which crystal every cell belongs to. The
+/// initial state is labelled by its 6-connected components; an attachment joins the
+/// cluster of its crystal neighbours, and one touching several clusters merges them into
+/// the largest (a coalescence, listed in `Merges.txt`). Cluster ids are union-find sets,
+/// so a merge relabels no cells. A cluster split by detachments keeps its id.
+///
+/// Every write step appends `step:n_1:n_2:...` to `Clusters.txt`: the cells of every
+/// cluster id, 0 once merged into another one or dissolved. `Merges.txt` lists the step,
+/// ids and sizes of every merge, the `clusters` row of `SimLog` the count of clusters. A
+/// resumed run labels the clusters anew.
+#[derive(Debug, Default)]
+pub struct ClusterTracker {
+    pub is_on: bool,
+    /// Cluster id of every cell, 0 - gas; the cluster is the set `root(id)`.
+    owner: Vec<u32>,
+    /// Union-find parent of every id (index 0 unused).
+    parent: Vec<u32>,
+    /// Cells of every root id.
+    sizes: Vec<u64>,
+    /// Coalescences since the last write: step, surviving and absorbed id, their sizes.
+    merges: Vec<(u64, u32, u32, u64, u64)>,
+    pub n_merges: u64,
+    /// Step of the last row of `Clusters.txt`.
+    last_step: Option<u64>,
+    sizes_buf: Option<BufWriter<File>>,
+    merges_buf: Option<BufWriter<File>>,
+}
+
+impl ClusterTracker {
+    /// Labels the crystal cells of `state` by their connected components, in the order of
+    /// the lowest cell index.
+    pub fn new(is_on: bool, state: &[u8], neibs: &[[Idx; 6]]) -> Self {
+        if !is_on {
+            return Self::default();
+        }
+
+        let mut tracker = Self {
+            is_on,
+            owner: vec![0; state.len()],
+            parent: vec![0],
+            sizes: vec![0],
+            ..Self::default()
+        };
+        let mut stack = Vec::new();
+        for idx0 in 0..state.len() {
+            if state[idx0] != 1 || tracker.owner[idx0] != 0 {
+                continue;
+            }
+
+            let id = tracker.new_cluster();
+            tracker.owner[idx0] = id;
+            stack.push(idx0);
+            while let Some(idx) = stack.pop() {
+                tracker.sizes[id as usize] += 1;
+                for neib_idx in neibs[idx].iter().map(|&n| n as usize) {
+                    if neib_idx != NO_NEIB && state[neib_idx] == 1 && tracker.owner[neib_idx] == 0 {
+                        tracker.owner[neib_idx] = id;
+                        stack.push(neib_idx);
+                    }
+                }
+            }
+        }
+
+        tracker
+    }
+
+    fn new_cluster(&mut self) -> u32 {
+        let id = self.parent.len() as u32;
+        self.parent.push(id);
+        self.sizes.push(0);
+        id
+    }
+
+    fn root(&mut self, mut id: u32) -> u32 {
+        while self.parent[id as usize] != id {
+            let grandparent = self.parent[self.parent[id as usize] as usize];
+            self.parent[id as usize] = grandparent;
+            id = grandparent;
+        }
+        id
+    }
+
+    /// Clusters present now.
+    pub fn count(&self) -> usize {
+        (1..self.parent.len())
+            .filter(|&id| self.parent[id] == id as u32 && self.sizes[id] > 0)
+            .count()
+    }
+
+    /// The gas cell `idxg` became crystal; `state` - the item state, either before or
+    /// after the change.
+    #[inline(always)]
+    pub fn attach(&mut self, idxg: usize, state: &[u8], neibs: &[[Idx; 6]], step_id: u64) {
+        if !self.is_on {
+            return;
+        }
+
+        let mut roots = [0u32; 6];
+        let mut n_roots = 0;
+        for neib_idx in neibs[idxg].iter().map(|&n| n as usize) {
+            if neib_idx == NO_NEIB || state[neib_idx] != 1 || self.owner[neib_idx] == 0 {
+                continue;
+            }
+            let root = self.root(self.owner[neib_idx]);
+            if !roots[..n_roots].contains(&root) {
+                roots[n_roots] = root;
+                n_roots += 1;
+            }
+        }
+
+        let survivor = match roots[..n_roots]
+            .iter()
+            .copied()
+            .max_by_key(|&root| (self.sizes[root as usize], std::cmp::Reverse(root)))
+        {
+            Some(survivor) => survivor,
+            None => self.new_cluster(),
+        };
+        for &absorbed in roots[..n_roots].iter().filter(|&&root| root != survivor) {
+            let (n_survivor, n_absorbed) =
+                (self.sizes[survivor as usize], self.sizes[absorbed as usize]);
+            self.merges
+                .push((step_id, survivor, absorbed, n_survivor, n_absorbed));
+            self.parent[absorbed as usize] = survivor;
+            self.sizes[survivor as usize] += n_absorbed;
+            self.sizes[absorbed as usize] = 0;
+            self.n_merges += 1;
+        }
+
+        self.owner[idxg] = survivor;
+        self.sizes[survivor as usize] += 1;
+    }
+
+    /// The crystal cell `idxg` became gas.
+    #[inline(always)]
+    pub fn detach(&mut self, idxg: usize) {
+        if !self.is_on || self.owner[idxg] == 0 {
+            return;
+        }
+
+        let root = self.root(self.owner[idxg]);
+        self.sizes[root as usize] -= 1;
+        self.owner[idxg] = 0;
+    }
+
+    /// Appends the cluster sizes of `step_id` and the coalescences since the last write;
+    /// the files are created on the first call. A step already written is skipped.
+    pub fn write_step(&mut self, path_dst: &Path, step_id: u64) -> IoResult<()> {
+        if self.last_step == Some(step_id) {
+            return Ok(());
+        }
+        self.last_step = Some(step_id);
+
+        let sizes_buf = match &mut self.sizes_buf {
+            Some(buf) => buf,
+            None => {
+                let mut buf = create_file(path_dst, CLUSTERS_FILE_NAME)?;
+                writeln!(
+                    buf,
+                    "# step:cells of cluster 1, 2, ... (0 - merged or dissolved)"
+                )?;
+                self.sizes_buf.insert(buf)
+            }
+        };
+        write!(sizes_buf, "{}", step_id)?;
+        for size in &self.sizes[1..] {
+            write!(sizes_buf, ":{}", size)?;
+        }
+        writeln!(sizes_buf)?;
+
+        if self.merges.is_empty() {
+            return Ok(());
+        }
+        let merges_buf = match &mut self.merges_buf {
+            Some(buf) => buf,
+            None => {
+                let mut buf = create_file(path_dst, MERGES_FILE_NAME)?;
+                writeln!(buf, "# step:into:from:n_into:n_from")?;
+                self.merges_buf.insert(buf)
+            }
+        };
+        for (step, into, from, n_into, n_from) in self.merges.drain(..) {
+            writeln!(
+                merges_buf,
+                "{}:{}:{}:{}:{}",
+                step, into, from, n_into, n_from
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        for buf in [&mut self.sizes_buf, &mut self.merges_buf]
+            .into_iter()
+            .flatten()
+        {
+            buf.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn create_file(path_dst: &Path, name: &str) -> IoResult<BufWriter<File>> {
+    let path_out_file = path_dst.join(name);
+    Ok(BufWriter::new(File::create(&path_out_file).map_err(
+        |e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        },
+    )?))
+}
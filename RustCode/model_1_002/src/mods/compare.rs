@@ -0,0 +1,364 @@
+use crate::mods::{
+    constants::{COMMENT_LINE, CONFIG_FILE_NAME, TIME_STATES_BIN_FILE_NAME, TIME_STATES_FILE_NAME},
+    io_handler::{self, StateDiff},
+    state::{self, SIM_LOG_ROWS},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Entry point of the `compare` subcommand.
+///
+/// Usage: `compare <run_a> <run_b> [--out <file>] [--strict]`. Both arguments are result
+/// directories (or item sub-directories) holding `InitSettings.ini` and `SimLog.txt`
+/// (or, for a crashed run, `SimLogRec.txt`).
+/// The report lists differing settings, the final value of every logged quantity, the
+/// RMS deviation of the histories over the write steps present in both runs and the
+/// first differing frame and cell of every pair of state files (streamed, see
+/// `io_handler::compare_state_files`). With `--strict` differing or unpaired state files
+/// fail the command, for regression runs.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: compare <run_a> <run_b> [--out <file>] [--strict]";
+
+    let mut runs: Vec<PathBuf> = Vec::new();
+    let mut out_path: Option<PathBuf> = None;
+    let mut is_strict = false;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                out_path = Some(PathBuf::from(
+                    args_iter.next().ok_or("Missing value after '--out'")?,
+                ));
+            }
+            "--strict" => is_strict = true,
+            _ => runs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [run_a, run_b] = &runs[..] else {
+        return Err(usage.into());
+    };
+
+    let mut report = vec![
+        format!("A: {}", run_a.display()),
+        format!("B: {}", run_b.display()),
+    ];
+
+    report.push(String::new());
+    report.push("# Settings".to_string());
+    report.extend(compare_settings(run_a, run_b)?);
+
+    let log_a = read_sim_log(run_a)?;
+    let log_b = read_sim_log(run_b)?;
+
+    report.push(String::new());
+    report.push("# Final values (A | B | B - A)".to_string());
+    for (name, (row_a, row_b)) in SIM_LOG_ROWS.iter().zip(log_a.iter().zip(&log_b)) {
+        if let (Some(&a), Some(&b)) = (row_a.last(), row_b.last()) {
+            report.push(format!(
+                "{:>16}: {:.6e} | {:.6e} | {:+.3e}",
+                name,
+                a,
+                b,
+                b - a
+            ));
+        }
+    }
+
+    report.push(String::new());
+    report.push("# RMS deviation over common write steps (abs | rel. to RMS of A)".to_string());
+    report.extend(compare_histories(&log_a, &log_b));
+
+    report.push(String::new());
+    report.push("# States (first difference)".to_string());
+    let (state_lines, n_state_diffs) = compare_states(run_a, run_b)?;
+    report.extend(state_lines);
+
+    for line in &report {
+        println!("{}", line);
+    }
+
+    if let Some(out_path) = out_path {
+        fs::write(&out_path, report.join("\n") + "\n")?;
+        println!("✅ Report written to '{}'", out_path.display());
+    }
+
+    if is_strict && n_state_diffs > 0 {
+        return Err(format!("{} state file(s) differ", n_state_diffs).into());
+    }
+
+    Ok(())
+}
+
+/// Raw `key: value` pairs of a config file, up to the general info section.
+fn read_config_pairs(path: &Path) -> Result<BTreeMap<String, String>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {e}", path.display()))?;
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .take_while(|line| *line != COMMENT_LINE)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+        .collect())
+}
+
+fn compare_settings(run_a: &Path, run_b: &Path) -> Result<Vec<String>> {
+    let cfg_a = read_config_pairs(&run_a.join(CONFIG_FILE_NAME))?;
+    let cfg_b = read_config_pairs(&run_b.join(CONFIG_FILE_NAME))?;
+
+    let mut keys: Vec<&String> = cfg_a.keys().chain(cfg_b.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let missing = "<missing>".to_string();
+    let lines: Vec<String> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (a, b) = (
+                cfg_a.get(key).unwrap_or(&missing),
+                cfg_b.get(key).unwrap_or(&missing),
+            );
+            (a != b).then(|| format!("{:>16}: {} | {}", key, a, b))
+        })
+        .collect();
+
+    Ok(match lines.is_empty() {
+        true => vec!["identical".to_string()],
+        false => lines,
+    })
+}
+
+fn read_sim_log(dir: &Path) -> Result<Vec<Vec<f64>>> {
+    let rows = state::read_sim_log_rows(dir)
+        .map_err(|e| format!("Failed to read the log of '{}': {e}", dir.display()))?;
+
+    rows.iter()
+        .map(|row| -> Result<Vec<f64>> {
+            Ok(row
+                .iter()
+                .map(|s| s.parse::<f64>())
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .map_err(|e| format!("{}: {e}", dir.display()))?)
+        })
+        .collect()
+}
+
+/// Aligns the rows of both logs by `mk_step` and returns one line per quantity.
+fn compare_histories(log_a: &[Vec<f64>], log_b: &[Vec<f64>]) -> Vec<String> {
+    let step_row = SIM_LOG_ROWS.iter().position(|&n| n == "mk_step").unwrap();
+    let (Some(steps_a), Some(steps_b)) = (log_a.get(step_row), log_b.get(step_row)) else {
+        return vec!["no mk_step history".to_string()];
+    };
+
+    let index_b: HashMap<u64, usize> = steps_b
+        .iter()
+        .enumerate()
+        .map(|(i, &step)| (step as u64, i))
+        .collect();
+    let common: Vec<(usize, usize)> = steps_a
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &step)| index_b.get(&(step as u64)).map(|&j| (i, j)))
+        .collect();
+
+    if common.is_empty() {
+        return vec!["no common write steps".to_string()];
+    }
+
+    let mut lines = vec![format!("{:>16}: {}", "common steps", common.len())];
+    for (row_id, name) in SIM_LOG_ROWS.iter().enumerate() {
+        let (Some(row_a), Some(row_b)) = (log_a.get(row_id), log_b.get(row_id)) else {
+            continue;
+        };
+        if row_id == step_row || row_a.len() != steps_a.len() || row_b.len() != steps_b.len() {
+            continue;
+        }
+
+        let n = common.len() as f64;
+        let (sum_sq_diff, sum_sq_a) = common.iter().fold((0.0, 0.0), |(sd, sa), &(i, j)| {
+            let diff = row_b[j] - row_a[i];
+            (sd + diff * diff, sa + row_a[i] * row_a[i])
+        });
+        let rms = (sum_sq_diff / n).sqrt();
+        let rms_a = (sum_sq_a / n).sqrt();
+        let rel = match rms_a > 0.0 {
+            true => format!("{:.3e}", rms / rms_a),
+            false => "-".to_string(),
+        };
+
+        lines.push(format!("{:>16}: {:.6e} | {}", name, rms, rel));
+    }
+
+    lines
+}
+
+/// State files of a run by the directory holding them, relative to the run: its own
+/// `TimeStates.bin` (or `TimeStates.txt`) and those of its item sub-directories.
+fn state_files(dir: &Path) -> BTreeMap<PathBuf, PathBuf> {
+    let mut sub_dirs = vec![PathBuf::new()];
+    if let Ok(entries) = fs::read_dir(dir) {
+        sub_dirs.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| PathBuf::from(entry.file_name())),
+        );
+    }
+
+    sub_dirs
+        .into_iter()
+        .filter_map(|sub_dir| {
+            let file = [TIME_STATES_BIN_FILE_NAME, TIME_STATES_FILE_NAME]
+                .iter()
+                .map(|name| sub_dir.join(name))
+                .find(|path| dir.join(path).is_file())?;
+            Some((sub_dir, file))
+        })
+        .collect()
+}
+
+/// One line per state file of either run (text and binary ones of the same directory
+/// are compared with each other) and the number of files that differ or have no pair.
+fn compare_states(run_a: &Path, run_b: &Path) -> Result<(Vec<String>, usize)> {
+    let files_a = state_files(run_a);
+    let files_b = state_files(run_b);
+
+    let mut sub_dirs: Vec<&PathBuf> = files_a.keys().chain(files_b.keys()).collect();
+    sub_dirs.sort();
+    sub_dirs.dedup();
+    if sub_dirs.is_empty() {
+        return Ok((vec!["no state files".to_string()], 0));
+    }
+
+    let mut lines = Vec::new();
+    let mut n_diffs = 0;
+    for sub_dir in sub_dirs {
+        let (label, line) = match (files_a.get(sub_dir), files_b.get(sub_dir)) {
+            (Some(file_a), Some(file_b)) => {
+                let diff =
+                    io_handler::compare_state_files(&run_a.join(file_a), &run_b.join(file_b))?;
+                if !matches!(diff, StateDiff::Identical { .. }) {
+                    n_diffs += 1;
+                }
+                (file_a, diff.to_string())
+            }
+            (Some(file), None) | (None, Some(file)) => {
+                n_diffs += 1;
+                let side = if files_a.contains_key(sub_dir) {
+                    "A"
+                } else {
+                    "B"
+                };
+                (file, format!("only in {}", side))
+            }
+            (None, None) => continue,
+        };
+        lines.push(format!("{:>16}: {}", label.display(), line));
+    }
+
+    Ok((lines, n_diffs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_of(rows: &[(&str, &[f64])]) -> Vec<Vec<f64>> {
+        let mut log = vec![Vec::new(); SIM_LOG_ROWS.len()];
+        for (name, row) in rows {
+            log[SIM_LOG_ROWS.iter().position(|n| n == name).unwrap()] = row.to_vec();
+        }
+        log
+    }
+
+    #[test]
+    fn histories_are_aligned_by_write_step() {
+        // Common steps 10 and 20: n_cryst 4, 4 in A and 6, 6 in B, so the RMS deviation
+        // is 2 and the RMS of A 4.
+        let log_a = log_of(&[
+            ("mk_step", &[0.0, 10.0, 20.0]),
+            ("n_cryst", &[1.0, 4.0, 4.0]),
+        ]);
+        let log_b = log_of(&[
+            ("mk_step", &[10.0, 20.0, 30.0]),
+            ("n_cryst", &[6.0, 6.0, 0.0]),
+        ]);
+
+        assert_eq!(
+            compare_histories(&log_a, &log_b),
+            [
+                "    common steps: 2",
+                "         n_cryst: 2.000000e0 | 5.000e-1"
+            ]
+        );
+        assert_eq!(
+            compare_histories(&log_a, &log_of(&[("mk_step", &[5.0])])),
+            ["no common write steps"]
+        );
+    }
+
+    #[test]
+    fn runs_of_a_small_grid_report_their_differences() {
+        let root = std::env::temp_dir().join(format!("model_1_002_compare_{}", std::process::id()));
+        let (run_a, run_b) = (root.join("a"), root.join("b"));
+        for dir in [
+            run_a.join("00000"),
+            run_a.join("00001"),
+            run_b.join("00000"),
+        ] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(run_a.join(CONFIG_FILE_NAME), "T:300\nSx:2\n").unwrap();
+        fs::write(run_b.join(CONFIG_FILE_NAME), "T:310\n\nSx:2\n").unwrap();
+
+        // 2x2x2 frames; frame 1 of B has cell 5 in the crystal, A has it empty.
+        let frame_0 = [0u8, 0, 0, 1, 0, 0, 0, 0];
+        let frame_1 = [0u8, 0, 1, 1, 0, 0, 0, 0];
+        let mut frame_1_b = frame_1;
+        frame_1_b[5] = 1;
+        let write_frames = |path: PathBuf, frames: &[&[u8]]| {
+            let mut text = Vec::new();
+            for frame in frames {
+                io_handler::write_frame(&mut text, frame).unwrap();
+            }
+            fs::write(path, text).unwrap();
+        };
+        write_frames(
+            run_a.join("00000").join(TIME_STATES_FILE_NAME),
+            &[&frame_0, &frame_1],
+        );
+        write_frames(run_a.join("00001").join(TIME_STATES_FILE_NAME), &[&frame_0]);
+        write_frames(
+            run_b.join("00000").join(TIME_STATES_FILE_NAME),
+            &[&frame_0, &frame_1_b],
+        );
+
+        assert_eq!(
+            compare_settings(&run_a, &run_b).unwrap(),
+            ["               T: 300 | 310"]
+        );
+        assert_eq!(compare_settings(&run_a, &run_a).unwrap(), ["identical"]);
+
+        let (lines, n_diffs) = compare_states(&run_a, &run_b).unwrap();
+        assert_eq!(
+            lines,
+            [
+                "00000/TimeStates.txt: frame 1 differs first at cell 5 (0 | 1)",
+                "00001/TimeStates.txt: only in A",
+            ]
+        );
+        assert_eq!(n_diffs, 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,8 +1,78 @@
-/// Boltzmann constant in Joules per Kelvin.
-pub const K_BOLTZMANN: f64 = 1.380649e-23;
+pub use mc_core::constants::*;
 
-pub const CONFIG_FILE_NAME: &str = "InitSettings.ini";
-pub const INIT_TIME_STATES_FILE_NAME: &str = "InitStates.ini";
-pub const TIME_STATES_FILE_NAME: &str = "TimeStates.txt";
+use crate::mods::config_version::KeyMigration;
+
+pub const TIME_STATES_STEPS_FILE_NAME: &str = "TimeStatesSteps.txt";
 pub const SIM_LOG_FILE_NAME: &str = "SimLog.txt";
-pub const COMMENT_LINE: &str = "/////////////////////////////// | GENERAL INFO | ///////////////////////////////";
\ No newline at end of file
+pub const SIM_LOG_STATS_FILE_NAME: &str = "SimLogStats.txt";
+pub const SIM_LOG_REC_FILE_NAME: &str = "SimLogRec.txt";
+pub const LEGACY_SIM_LOG_FILE_NAME: &str = "sim_history.txt";
+pub const TIME_STATES_BIN_FILE_NAME: &str = "TimeStates.bin";
+pub const TIME_STATES_ZST_FILE_NAME: &str = "TimeStates.zst";
+pub const SIM_LOG_BIN_FILE_NAME: &str = "SimLog.bin";
+pub const RNG_AUDIT_FILE_NAME: &str = "RngAudit.txt";
+pub const FACET_STATS_FILE_NAME: &str = "FacetStats.txt";
+pub const GROUP_STATS_FILE_NAME: &str = "GroupStats.txt";
+#[cfg(feature = "plots")]
+pub const PLOTS_DIR_NAME: &str = "plots";
+pub const KOSSEL_CHECK_FILE_NAME: &str = "KosselCheck.txt";
+pub const RESIDENCE_HIST_FILE_NAME: &str = "ResidenceHist.txt";
+pub const AGE_MAP_FILE_NAME: &str = "AgeMap.txt";
+/// `DensityProfile_<axis>.txt`.
+pub const DENSITY_PROFILE_FILE_STEM: &str = "DensityProfile";
+pub const EVENTS_FILE_NAME: &str = "Events.xyz";
+pub const FRONT_FILE_NAME: &str = "Front.bin";
+pub const FIRST_PASSAGE_FILE_NAME: &str = "FirstPassage.txt";
+pub const DETACH_SPECTRUM_FILE_NAME: &str = "DetachSpectrum.txt";
+pub const PROBES_FILE_NAME: &str = "Probes.txt";
+pub const CLUSTERS_FILE_NAME: &str = "Clusters.txt";
+pub const MERGES_FILE_NAME: &str = "Merges.txt";
+pub const ENERGY_HIST_FILE_NAME: &str = "EnergyHist.txt";
+pub const NUCLEI_FILE_NAME: &str = "Nuclei.txt";
+pub const SUMMARY_FILE_NAME: &str = "Summary.txt";
+pub const CONTROL_FILE_NAME: &str = "control";
+pub const CHECKPOINT_FILE_NAME: &str = "Checkpoint.txt";
+pub const MANIFEST_STATUS_FILE_NAME: &str = "ManifestStatus.txt";
+pub const RUN_META_FILE_NAME: &str = "RunMeta.txt";
+pub const PACK_MANIFEST_FILE_NAME: &str = "PackManifest.txt";
+/// Extension of a packed run (`pack`, `PackRun`).
+pub const PACK_EXTENSION: &str = "tar.zst";
+
+/// Magic bytes and format version of the binary outputs.
+pub const TIME_STATES_BIN_MAGIC: &[u8; 4] = b"MCTS";
+pub const SIM_LOG_BIN_MAGIC: &[u8; 4] = b"MCSL";
+pub const TIME_STATES_ZST_MAGIC: &[u8; 4] = b"MCTZ";
+pub const FRONT_BIN_MAGIC: &[u8; 4] = b"MCFR";
+pub const BIN_FORMAT_VERSION: u32 = 1;
+
+/// Line closing every frame of a text state file: `#end <checksum>` (`io_handler::write_frame`).
+pub const FRAME_END_TAG: &str = "#end";
+
+/// Largest `PoissonAdd`/`PoissonRem`/`PoissonBal` mean.
+pub const POISSON_MEAN_MAX: f64 = 100.0;
+
+/// Keys that must be present in the config file in every mode (the mode adds its own,
+/// see `io_handler::load_config_with_preset`); all other keys fall back to defaults.
+pub const REQUIRED_CONFIG_KEYS: [&str; 10] = [
+    "DirPrefix",
+    "Seed",
+    "Sx",
+    "Sy",
+    "Sz",
+    "T",
+    "mode",
+    "LoadOption",
+    "StepLim",
+    "WriteI",
+];
+
+/// Keys of older configs (and of `model_1_001` configs) read under the current names.
+pub const KEY_MIGRATIONS: [KeyMigration; 1] = [KeyMigration {
+    old: "LoadPrev",
+    new: "LoadOption",
+    removed_in: 2,
+    note: "LoadOption -1 loads all states of InitStates.ini, N the first N; LoadPrev of model_1_001 picks one",
+}];
+
+pub const COMMENT_LINE: &str =
+    "/////////////////////////////// | GENERAL INFO | ///////////////////////////////";
@@ -0,0 +1,368 @@
+use crate::mods::{
+    constants::{
+        CONFIG_FILE_NAME, FRAME_END_TAG, LEGACY_SIM_LOG_FILE_NAME, SIM_LOG_BIN_FILE_NAME,
+        SIM_LOG_FILE_NAME, SIM_LOG_REC_FILE_NAME, TIME_STATES_BIN_FILE_NAME, TIME_STATES_FILE_NAME,
+        TIME_STATES_ZST_FILE_NAME,
+    },
+    io_handler, state, writer,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write},
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const SUPPORTED_FORMATS: [&str; 3] = ["bin", "rows", "txt"];
+
+/// Entry point of the `convert` subcommand.
+///
+/// Usage: `convert <results dir> [--format bin|rows|txt]`. Walks the directory tree.
+/// `bin` rewrites every `TimeStates.txt` and `SimLog.txt`/`sim_history.txt` next to
+/// the original, embedding the `InitSettings.ini` found in the same or a parent
+/// directory as metadata (after the snapshot region header line, if any); text files are
+/// left untouched. `rows` restores the empty
+/// `SimLog.txt` of a crashed run from its `SimLogRec.txt` records. `txt` appends the
+/// frames of `TimeStates.zst` (`SnapCompress:true`) to the header of `TimeStates.txt`.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut root: Option<PathBuf> = None;
+    let mut format = "bin".to_string();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                format = args_iter
+                    .next()
+                    .ok_or("Missing value after '--format'")?
+                    .to_string();
+            }
+            _ => root = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let root = root.ok_or("Usage: convert <results dir> [--format bin|rows|txt]")?;
+    if !SUPPORTED_FORMATS.contains(&format.as_str()) {
+        return Err(format!(
+            "Unsupported format '{}' (available: {})",
+            format,
+            SUPPORTED_FORMATS.join(", ")
+        )
+        .into());
+    }
+
+    let mut converted = 0;
+    walk_dirs(&root, &mut |dir| {
+        converted += match format.as_str() {
+            "rows" => restore_log(dir)?,
+            "txt" => unpack_states(dir)?,
+            _ => convert_dir(&root, dir)?,
+        };
+        Ok(())
+    })?;
+    println!(
+        "✅ Converted {} file(s) under '{}'",
+        converted,
+        root.display()
+    );
+
+    Ok(())
+}
+
+/// Calls `visit` on `dir` and then on every directory below it, the sub-directories in
+/// name order.
+fn walk_dirs(dir: &Path, visit: &mut impl FnMut(&Path) -> Result<()>) -> Result<()> {
+    visit(dir)?;
+
+    let mut sub_dirs: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_dir())
+        .collect();
+    sub_dirs.sort();
+
+    for sub_dir in sub_dirs {
+        walk_dirs(&sub_dir, visit)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the binary files of `dir`, with the `InitSettings.ini` of `dir` or of the
+/// nearest parent under `root` as metadata; returns the files written.
+fn convert_dir(root: &Path, dir: &Path) -> Result<usize> {
+    let meta = match dir
+        .ancestors()
+        .take_while(|d| d.starts_with(root))
+        .map(|d| d.join(CONFIG_FILE_NAME))
+        .find(|path_config| path_config.exists())
+    {
+        Some(path_config) => fs::read(&path_config)?,
+        None => Vec::new(),
+    };
+    let mut converted = 0;
+
+    let path_states = dir.join(TIME_STATES_FILE_NAME);
+    if path_states.exists() {
+        convert_time_states(&path_states, &dir.join(TIME_STATES_BIN_FILE_NAME), &meta)?;
+        converted += 1;
+    }
+
+    for log_name in [SIM_LOG_FILE_NAME, LEGACY_SIM_LOG_FILE_NAME] {
+        let path_log = dir.join(log_name);
+        if path_log.exists() {
+            convert_log(&path_log, &dir.join(SIM_LOG_BIN_FILE_NAME), &meta)?;
+            converted += 1;
+            break;
+        }
+    }
+
+    Ok(converted)
+}
+
+/// Rewrites `SimLog.txt` of `dir` in the row layout if it is empty but records exist.
+fn restore_log(dir: &Path) -> Result<usize> {
+    let path_log = dir.join(SIM_LOG_FILE_NAME);
+    let is_log_empty = fs::read_to_string(&path_log).map_or(true, |t| t.trim().is_empty());
+    if !is_log_empty || !dir.join(SIM_LOG_REC_FILE_NAME).exists() {
+        return Ok(0);
+    }
+
+    let rows = state::read_sim_log_rows(dir)?;
+    fs::write(&path_log, state::sim_log_text(&rows))?;
+    println!(
+        "{} -> {} ({} points)",
+        dir.join(SIM_LOG_REC_FILE_NAME).display(),
+        path_log.display(),
+        rows.iter().map(Vec::len).max().unwrap_or(0)
+    );
+
+    Ok(1)
+}
+
+/// Writes the frames of the `TimeStates.zst` of `dir` into the `TimeStates.txt` next to
+/// it; a `TimeStates.txt` that already holds frames is left as it is.
+fn unpack_states(dir: &Path) -> Result<usize> {
+    let path_packed = dir.join(TIME_STATES_ZST_FILE_NAME);
+    if !path_packed.exists() {
+        return Ok(0);
+    }
+
+    let path_states = dir.join(TIME_STATES_FILE_NAME);
+    let text = fs::read_to_string(&path_states).unwrap_or_default();
+    if text
+        .lines()
+        .any(|l| !l.trim().is_empty() && !l.starts_with('#'))
+    {
+        eprintln!(
+            "⚠️ Warning: '{}' already holds frames, skipped.",
+            path_states.display()
+        );
+        return Ok(0);
+    }
+
+    let frames = writer::read_packed_frames(&path_packed)
+        .map_err(|e| format!("Failed to read '{}': {e}", path_packed.display()))?;
+    let mut fbuf = BufWriter::new(File::create(&path_states)?);
+    fbuf.write_all(text.as_bytes())?;
+    for (_, values) in &frames {
+        io_handler::write_frame(&mut fbuf, values)?;
+    }
+    fbuf.flush()?;
+
+    println!(
+        "{} -> {} ({} frames)",
+        path_packed.display(),
+        path_states.display(),
+        frames.len()
+    );
+
+    Ok(1)
+}
+
+fn parse_err(path: &Path, line_num: usize, msg: String) -> IoError {
+    IoError::new(
+        ErrorKind::InvalidData,
+        format!("{} (line {}): {}", path.display(), line_num + 1, msg),
+    )
+}
+
+/// The frames are read through `io_handler::StateFrames`, so their `#end` lines are
+/// checked and a final frame cut by a crash is left out. The file is read twice, first
+/// for the frame length and the encoding, then frame by frame into the output, so no
+/// more than one frame is held in memory.
+fn convert_time_states(src: &Path, dst: &Path, meta: &[u8]) -> IoResult<()> {
+    // The `#` lines but the frame ends go to the metadata
+    let mut header = String::new();
+    for line in BufReader::new(File::open(src)?).lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') && !trimmed.starts_with(FRAME_END_TAG) {
+            header.push_str(trimmed);
+            header.push('\n');
+        }
+    }
+
+    let mut states = io_handler::StateFrames::open(src)?;
+    let mut frame = Vec::new();
+    let mut frame_len = None;
+    let mut n_frames = 0;
+    let mut packed = true;
+    while states.next_frame(&mut frame)? {
+        let len = *frame_len.get_or_insert(frame.len());
+        if len != frame.len() {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{}: frame {} has {} values, expected {}",
+                    src.display(),
+                    n_frames,
+                    frame.len(),
+                    len
+                ),
+            ));
+        }
+        packed &= frame.iter().all(|&v| v <= 1);
+        n_frames += 1;
+    }
+
+    let mut writer = BufWriter::new(File::create(dst)?);
+    let meta = [header.as_bytes(), meta].concat();
+    io_handler::write_states_bin_header(&mut writer, packed, frame_len.unwrap_or(0), &meta)?;
+    let mut states = io_handler::StateFrames::open(src)?;
+    for _ in 0..n_frames {
+        if !states.next_frame(&mut frame)? {
+            break;
+        }
+        io_handler::write_state_bin(&mut writer, &frame, packed)?;
+    }
+    writer.flush()?;
+
+    println!(
+        "{} -> {} ({} frames)",
+        src.display(),
+        dst.display(),
+        n_frames
+    );
+
+    Ok(())
+}
+
+/// `SimLog.txt` is read through `state::read_sim_log_rows`, so an interrupted run is
+/// converted from its records.
+fn convert_log(src: &Path, dst: &Path, meta: &[u8]) -> IoResult<()> {
+    let text_rows: Vec<Vec<String>> = match src.file_name().is_some_and(|n| n == SIM_LOG_FILE_NAME)
+    {
+        true => state::read_sim_log_rows(src.parent().unwrap_or(Path::new(".")))?,
+        false => BufReader::new(File::open(src)?)
+            .lines()
+            .map(|line| {
+                line.map(|l| match l.trim() {
+                    "" => Vec::new(),
+                    trimmed => trimmed.split(':').map(|s| s.trim().to_string()).collect(),
+                })
+            })
+            .collect::<IoResult<_>>()?,
+    };
+
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for (line_num, text_row) in text_rows.iter().enumerate() {
+        let row = text_row
+            .iter()
+            .map(|s| s.parse::<f64>())
+            .collect::<std::result::Result<Vec<f64>, _>>()
+            .map_err(|e| parse_err(src, line_num, e.to_string()))?;
+        rows.push(row);
+    }
+
+    let mut writer = BufWriter::new(File::create(dst)?);
+    io_handler::write_log_bin(&mut writer, &rows, meta)?;
+    writer.flush()?;
+
+    println!(
+        "{} -> {} ({} rows)",
+        src.display(),
+        dst.display(),
+        rows.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::writer::{QueueFull, SnapFormat, SnapshotWriter};
+
+    const FRAMES: [[u8; 8]; 3] = [
+        [0, 0, 0, 1, 0, 0, 0, 0],
+        [0, 0, 1, 1, 2, 0, 0, 0],
+        [1, 0, 1, 1, 2, 0, 1, 0],
+    ];
+
+    fn frames_of(path: &Path) -> Vec<Vec<u8>> {
+        let mut states = io_handler::StateFrames::open(path).unwrap();
+        let mut frames = Vec::new();
+        let mut frame = Vec::new();
+        while states.next_frame(&mut frame).unwrap() {
+            frames.push(frame.clone());
+        }
+        frames
+    }
+
+    fn convert(root: &Path, format: &str) {
+        let args = [root.display().to_string(), "--format".into(), format.into()];
+        run(&args).unwrap();
+    }
+
+    #[test]
+    fn formats_round_trip_to_the_same_frames() {
+        let root = std::env::temp_dir().join(format!("model_1_002_convert_{}", std::process::id()));
+        let (dir_text, dir_packed) = (root.join("00000"), root.join("00001"));
+        for dir in [&dir_text, &dir_packed] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(root.join(CONFIG_FILE_NAME), "Sx:2\nSy:2\nSz:2\n").unwrap();
+
+        // Item 0: text frames and the records of a crashed run (empty `SimLog.txt`).
+        let mut text = Vec::new();
+        for frame in &FRAMES {
+            io_handler::write_frame(&mut text, frame).unwrap();
+        }
+        fs::write(dir_text.join(TIME_STATES_FILE_NAME), text).unwrap();
+        fs::write(dir_text.join(SIM_LOG_FILE_NAME), "").unwrap();
+        fs::write(
+            dir_text.join(SIM_LOG_REC_FILE_NAME),
+            "n_cryst=1:mk_step=0\nn_cryst=3:mk_step=10\n",
+        )
+        .unwrap();
+
+        // Item 1: the same frames in `TimeStates.zst` only.
+        let path_packed_states = dir_packed.join(TIME_STATES_FILE_NAME);
+        fs::write(&path_packed_states, "").unwrap();
+        let mut snapshots = SnapshotWriter::start(4, QueueFull::Block, SnapFormat::Packed).unwrap();
+        let handle = snapshots.handle().unwrap();
+        for (step, frame) in FRAMES.iter().enumerate() {
+            handle.send(1, &path_packed_states, step as u64, Box::new(*frame), true);
+        }
+        snapshots.finish();
+
+        convert(&root, "rows");
+        let rows = state::read_sim_log_rows(&dir_text).unwrap();
+        let row = |name| &rows[state::SIM_LOG_ROWS.iter().position(|&n| n == name).unwrap()];
+        assert_eq!(row("n_cryst"), &["1", "3"]);
+        assert_eq!(row("mk_step"), &["0", "10"]);
+
+        convert(&root, "txt");
+        assert_eq!(frames_of(&path_packed_states), FRAMES);
+
+        convert(&root, "bin");
+        for dir in [&dir_text, &dir_packed] {
+            assert_eq!(frames_of(&dir.join(TIME_STATES_BIN_FILE_NAME)), FRAMES);
+        }
+        assert!(dir_text.join(SIM_LOG_BIN_FILE_NAME).exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
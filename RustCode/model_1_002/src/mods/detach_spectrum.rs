@@ -0,0 +1,127 @@
+use crate::mods::{
+    constants::DETACH_SPECTRUM_FILE_NAME,
+    energy::detach_energy,
+    frontier::Frontier,
+    lattice::Idx,
+    utils::{compute_neighbor_sums, kossel_detach_energy},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Neighbour classes `(sx, sy, sz)`, 0..=2 crystal neighbours along each grid axis.
+const N_CLASSES: usize = 27;
+
+/// Detachment energy spectrum of the TPB sites of an item (`DetachSpectrumI`): every
+/// `every` steps the TPB set is swept and its sites counted by neighbour class, one row
+/// per sweep in `DetachSpectrum.txt` (`step:n_tpb:e_mean:c_000:...:c_222`, class
+/// `sx sy sz` - crystal neighbours along the grid axes x, y, z). The energy of every
+/// class - the cost of the detachment under the `DetachModel` of the run - is listed in
+/// the header, so the rows are the spectrum itself. A sweep is O(front size).
+#[derive(Debug, Default)]
+pub struct DetachSpectrum {
+    pub is_on: bool,
+    every: u64,
+    is_bond_counting: bool,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl DetachSpectrum {
+    pub fn new(every: u64, is_bond_counting: bool) -> Self {
+        Self {
+            is_on: every > 0,
+            every,
+            is_bond_counting,
+            out_file_buf: None,
+        }
+    }
+
+    /// Detachment energy of the class `i` (`sx·9 + sy·3 + sz`).
+    fn class_energy(&self, i: usize, e2: (f64, f64, f64)) -> f64 {
+        let sums = ((i / 9) as u8, (i / 3 % 3) as u8, (i % 3) as u8);
+        match self.is_bond_counting {
+            true => kossel_detach_energy(sums, e2),
+            false => detach_energy(sums, e2),
+        }
+    }
+
+    /// Appends the spectrum of `front` after `step_id`, on the sweep steps; the file is
+    /// created on the first sweep. `e2` - face energies along the grid axes.
+    pub fn sweep(
+        &mut self,
+        path_dst: &Path,
+        step_id: u64,
+        state: &[u8],
+        front: &Frontier,
+        neibs: &[[Idx; 6]],
+        e2: (f64, f64, f64),
+    ) -> IoResult<()> {
+        if !self.is_on || !step_id.is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        let mut counts = [0u32; N_CLASSES];
+        for &idxg in &front.tpbs {
+            let (sx, sy, sz) = compute_neighbor_sums(state, &neibs[idxg]);
+            counts[sx as usize * 9 + sy as usize * 3 + sz as usize] += 1;
+        }
+        let energies: [f64; N_CLASSES] = std::array::from_fn(|i| self.class_energy(i, e2));
+
+        if self.out_file_buf.is_none() {
+            let path_out_file = path_dst.join(DETACH_SPECTRUM_FILE_NAME);
+            let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("Failed to create file '{}': {}", path_out_file.display(), e),
+                )
+            })?);
+            write!(
+                out_file_buf,
+                "# DetachModel={} e_detach (J) of the classes 000..222",
+                match self.is_bond_counting {
+                    true => "bonds",
+                    false => "axis",
+                }
+            )?;
+            for energy in energies {
+                write!(out_file_buf, ":{:.5e}", energy)?;
+            }
+            writeln!(out_file_buf)?;
+            writeln!(out_file_buf, "# step:n_tpb:e_mean:c_000:...:c_222")?;
+            self.out_file_buf = Some(out_file_buf);
+        }
+        let Some(out_file_buf) = &mut self.out_file_buf else {
+            return Ok(());
+        };
+
+        let n_tpb = front.tpbs.len();
+        let e_sum: f64 = counts
+            .iter()
+            .zip(energies)
+            .map(|(&count, energy)| count as f64 * energy)
+            .sum();
+        write!(
+            out_file_buf,
+            "{}:{}:{:.5e}",
+            step_id,
+            n_tpb,
+            match n_tpb {
+                0 => 0.0,
+                n => e_sum / n as f64,
+            }
+        )?;
+        for count in counts {
+            write!(out_file_buf, ":{}", count)?;
+        }
+        writeln!(out_file_buf)
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match &mut self.out_file_buf {
+            Some(out_file_buf) => out_file_buf.flush(),
+            None => Ok(()),
+        }
+    }
+}
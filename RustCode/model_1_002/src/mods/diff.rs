@@ -0,0 +1,389 @@
+use crate::mods::{constants::CONFIG_FILE_NAME, io_handler, item::SnapshotRegion, lattice::Grid};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Lowest and highest `(x, y, z)` of a set of cells.
+type BoundingBox = ((usize, usize, usize), (usize, usize, usize));
+
+/// Cell codes of the difference volume written with `--out`.
+const DIFF_UNCHANGED: u8 = 0;
+const DIFF_ATTACHED: u8 = 1;
+const DIFF_DETACHED: u8 = 2;
+const DIFF_OTHER: u8 = 3;
+
+const DEFAULT_LIST_LIMIT: usize = 20;
+
+/// Characters of the `--slice` map: unchanged empty/crystal, attached, detached, other.
+const SLICE_CHARS: [char; 5] = ['.', '#', '+', '-', '?'];
+
+/// Entry point of the `diff` subcommand.
+///
+/// Usage: `diff <file> <frameA> <frameB> [--dims SX,SY,SZ] [--list N] [--out <file>]
+/// [--slice x|y|z:POS]`.
+/// Compares two frames (0-based) of a `TimeStates.txt` or `TimeStates.bin` file and
+/// prints the number of changed cells, their bounding box and the first `N` of them.
+/// Without `--dims` the grid size is taken from the snapshot region header of the file
+/// (coordinates are then reported in the full grid) or from the nearest
+/// `InitSettings.ini` up the directory tree. `--out` writes a difference volume in the text state format
+/// (0 - unchanged, 1 - attached, 2 - detached, 3 - other change). `--slice` prints the
+/// plane `axis == POS` (frame coordinates) of the frame B as a character map: `.` empty,
+/// `#` crystal, `+` attached, `-` detached, `?` other change.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: diff <file> <frameA> <frameB> [--dims SX,SY,SZ] [--list N] [--out <file>] [--slice x|y|z:POS]";
+
+    let mut positional: Vec<&str> = Vec::new();
+    let mut dims: Option<(usize, usize, usize)> = None;
+    let mut list_limit = DEFAULT_LIST_LIMIT;
+    let mut out_path: Option<PathBuf> = None;
+    let mut slice: Option<(usize, usize)> = None;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--dims" | "-d" => {
+                let value = args_iter.next().ok_or("Missing value after '--dims'")?;
+                dims = Some(parse_dims(value)?);
+            }
+            "--list" | "-l" => {
+                list_limit = args_iter
+                    .next()
+                    .ok_or("Missing value after '--list'")?
+                    .parse()?;
+            }
+            "--out" | "-o" => {
+                out_path = Some(PathBuf::from(
+                    args_iter.next().ok_or("Missing value after '--out'")?,
+                ));
+            }
+            "--slice" | "-s" => {
+                let value = args_iter.next().ok_or("Missing value after '--slice'")?;
+                slice = Some(parse_slice(value)?);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let [path, frame_a, frame_b] = positional[..] else {
+        return Err(usage.into());
+    };
+    let path = Path::new(path);
+    let (frame_a, frame_b): (usize, usize) = (frame_a.parse()?, frame_b.parse()?);
+
+    let state_a = load_frame(path, frame_a)?;
+    let state_b = load_frame(path, frame_b)?;
+    if state_a.len() != state_b.len() {
+        return Err(format!("Frame sizes differ: {} vs {}", state_a.len(), state_b.len()).into());
+    }
+
+    let region = read_region(path);
+    let dims = match (dims, region) {
+        (Some(dims), _) => Some(dims),
+        (None, Some(region)) => Some(region.dims()),
+        (None, None) => dims_from_config(path),
+    };
+    let to_xyz = |idx: usize, grid: &Grid| {
+        let xyz = grid.idx_to_xyz(idx);
+        region.map_or(xyz, |region| region.to_grid_xyz(xyz))
+    };
+    let grid = match dims {
+        Some((sx, sy, sz)) if sx * sy * sz == state_a.len() => Some(Grid::shape(sx, sy, sz)),
+        Some((sx, sy, sz)) => {
+            return Err(format!(
+                "Grid {}x{}x{} does not match the frame size {}",
+                sx,
+                sy,
+                sz,
+                state_a.len()
+            )
+            .into());
+        }
+        None => {
+            eprintln!("⚠️ Warning: Grid size unknown, reporting flat cell indices.");
+            None
+        }
+    };
+
+    let diff = diff_states(&state_a, &state_b);
+    let changed: Vec<usize> = (0..diff.len())
+        .filter(|&idx| diff[idx] != DIFF_UNCHANGED)
+        .collect();
+    let count_of = |code: u8| diff.iter().filter(|&&v| v == code).count();
+
+    println!(
+        "{}: frame {} -> frame {} ({} cells)",
+        path.display(),
+        frame_a,
+        frame_b,
+        diff.len()
+    );
+    println!(
+        "Changed: {} | Attached: {} | Detached: {} | Other: {}",
+        changed.len(),
+        count_of(DIFF_ATTACHED),
+        count_of(DIFF_DETACHED),
+        count_of(DIFF_OTHER)
+    );
+
+    if let Some(grid) = &grid
+        && let Some((lo, hi)) = bounding_box(grid, &changed)
+    {
+        let (lo, hi) = match region {
+            Some(region) => (region.to_grid_xyz(lo), region.to_grid_xyz(hi)),
+            None => (lo, hi),
+        };
+        println!("Bounding box: {:?} - {:?}", lo, hi);
+    }
+
+    for &idx in changed.iter().take(list_limit) {
+        let (from, to) = (state_a[idx], state_b[idx]);
+        match &grid {
+            Some(grid) => println!("  {:?}: {} -> {}", to_xyz(idx, grid), from, to),
+            None => println!("  [{}]: {} -> {}", idx, from, to),
+        }
+    }
+    if changed.len() > list_limit {
+        println!("  ... {} more", changed.len() - list_limit);
+    }
+
+    if let Some((axis, pos)) = slice {
+        let grid = grid.as_ref().ok_or("'--slice' needs the grid size")?;
+        print_slice(grid, &state_b, &diff, axis, pos)?;
+    }
+
+    if let Some(out_path) = out_path {
+        let mut writer = BufWriter::new(File::create(&out_path)?);
+        io_handler::write_frame(&mut writer, &diff.into_boxed_slice())?;
+        writer.flush()?;
+        println!("✅ Difference volume written to '{}'", out_path.display());
+    }
+
+    Ok(())
+}
+
+fn parse_dims(value: &str) -> Result<(usize, usize, usize)> {
+    let parts = value
+        .split([',', 'x'])
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<std::result::Result<Vec<usize>, _>>()?;
+
+    match parts[..] {
+        [sx, sy, sz] => Ok((sx, sy, sz)),
+        _ => Err(format!("Invalid '--dims' value '{}', expected SX,SY,SZ", value).into()),
+    }
+}
+
+/// `x|y|z:POS` of `--slice`.
+fn parse_slice(value: &str) -> Result<(usize, usize)> {
+    let invalid = || format!("Invalid '--slice' value '{}', expected x|y|z:POS", value);
+    let (axis, pos) = value.split_once(':').ok_or_else(invalid)?;
+    let axis = ["x", "y", "z"]
+        .iter()
+        .position(|&a| a == axis.trim())
+        .ok_or_else(invalid)?;
+
+    Ok((axis, pos.trim().parse().map_err(|_| invalid())?))
+}
+
+fn print_slice(grid: &Grid, state: &[u8], diff: &[u8], axis: usize, pos: usize) -> Result<()> {
+    let sizes = [grid.nx, grid.ny, grid.nz];
+    if pos >= sizes[axis] {
+        return Err(format!(
+            "Slice position {} is outside the {}x{}x{} frame",
+            pos, grid.nx, grid.ny, grid.nz
+        )
+        .into());
+    }
+
+    let (states, (_, cols)) = grid.slice(state, axis, pos);
+    let (codes, _) = grid.slice(diff, axis, pos);
+    let axes = match axis {
+        0 => "y rows, z columns",
+        1 => "x rows, z columns",
+        _ => "x rows, y columns",
+    };
+    println!("Slice {}={} ({}):", ["x", "y", "z"][axis], pos, axes);
+
+    for (row_states, row_codes) in states.chunks(cols).zip(codes.chunks(cols)) {
+        let row: String = row_states
+            .iter()
+            .zip(row_codes)
+            .map(|(&s, &code)| match code {
+                DIFF_UNCHANGED => SLICE_CHARS[(s == 1) as usize],
+                code => SLICE_CHARS[(code as usize + 1).min(4)],
+            })
+            .collect();
+        println!("  {}", row);
+    }
+
+    Ok(())
+}
+
+/// Grid size from the first `InitSettings.ini` found next to `path` or in its parents.
+fn dims_from_config(path: &Path) -> Option<(usize, usize, usize)> {
+    let dir = path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join(CONFIG_FILE_NAME).exists())?;
+    let cfg = io_handler::load_config(&dir.to_path_buf())
+        .ok()?
+        .build()
+        .ok()?;
+
+    Some((cfg.sx, cfg.sy, cfg.sz))
+}
+
+fn load_frame(path: &Path, frame_id: usize) -> Result<Vec<u8>> {
+    if path.extension().is_some_and(|ext| ext == "bin") {
+        let mut reader = BufReader::new(File::open(path)?);
+        let (packed, frame_len, _) = io_handler::read_states_bin_header(&mut reader)?;
+        let frame_bytes = match packed {
+            true => frame_len.div_ceil(8),
+            false => frame_len,
+        };
+        reader.seek_relative((frame_bytes * frame_id) as i64)?;
+
+        return io_handler::read_state_bin(&mut reader, frame_len, packed).map_err(|e| {
+            format!(
+                "Frame {} not found in '{}': {}",
+                frame_id,
+                path.display(),
+                e
+            )
+            .into()
+        });
+    }
+
+    let mut frames = io_handler::StateFrames::open(path)?;
+    let mut state = Vec::new();
+    for _ in 0..=frame_id {
+        if !frames.next_frame(&mut state)? {
+            return Err(format!("Frame {} not found in '{}'", frame_id, path.display()).into());
+        }
+    }
+
+    Ok(state)
+}
+
+/// Snapshot region from the header line of a text file or the metadata of a binary one.
+fn read_region(path: &Path) -> Option<SnapshotRegion> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+
+    let first_line = match path.extension().is_some_and(|ext| ext == "bin") {
+        true => {
+            let (_, _, metadata) = io_handler::read_states_bin_header(&mut reader).ok()?;
+            String::from_utf8_lossy(&metadata)
+                .lines()
+                .next()?
+                .to_string()
+        }
+        false => reader.lines().next()?.ok()?,
+    };
+
+    SnapshotRegion::from_header(&first_line)
+}
+
+fn diff_states(state_a: &[u8], state_b: &[u8]) -> Vec<u8> {
+    state_a
+        .iter()
+        .zip(state_b)
+        .map(|(&a, &b)| match (a, b) {
+            _ if a == b => DIFF_UNCHANGED,
+            (0, 1) => DIFF_ATTACHED,
+            (1, 0) => DIFF_DETACHED,
+            _ => DIFF_OTHER,
+        })
+        .collect()
+}
+
+fn bounding_box(grid: &Grid, changed: &[usize]) -> Option<BoundingBox> {
+    let (first, rest) = changed.split_first()?;
+    let start = grid.idx_to_xyz(*first);
+
+    Some(rest.iter().fold((start, start), |(lo, hi), &idx| {
+        let (x, y, z) = grid.idx_to_xyz(idx);
+        (
+            (lo.0.min(x), lo.1.min(y), lo.2.min(z)),
+            (hi.0.max(x), hi.1.max(y), hi.2.max(z)),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames of a 3x4x5 grid: `(1,1,1)` leaves the crystal, `(2,3,0)` joins it,
+    /// `(0,2,4)` turns from gas into a marked cell, `(1,1,2)` stays crystal.
+    fn frames(grid: &Grid) -> (Vec<u8>, Vec<u8>) {
+        let mut state_a = vec![0u8; grid.size];
+        state_a[grid.xyz_to_idx(1, 1, 1)] = 1;
+        state_a[grid.xyz_to_idx(1, 1, 2)] = 1;
+
+        let mut state_b = state_a.clone();
+        state_b[grid.xyz_to_idx(1, 1, 1)] = 0;
+        state_b[grid.xyz_to_idx(2, 3, 0)] = 1;
+        state_b[grid.xyz_to_idx(0, 2, 4)] = 2;
+        (state_a, state_b)
+    }
+
+    #[test]
+    fn frames_of_a_small_grid_give_the_changed_cells() {
+        let grid = Grid::shape(3, 4, 5);
+        let (state_a, state_b) = frames(&grid);
+
+        let dir = std::env::temp_dir().join(format!("model_1_002_diff_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path_text = dir.join("TimeStates.txt");
+        let path_bin = dir.join("TimeStates.bin");
+        let mut text = BufWriter::new(File::create(&path_text).unwrap());
+        let mut bin = BufWriter::new(File::create(&path_bin).unwrap());
+        io_handler::write_states_bin_header(&mut bin, false, grid.size, &[]).unwrap();
+        for state in [&state_a, &state_b] {
+            io_handler::write_frame(&mut text, state).unwrap();
+            io_handler::write_state_bin(&mut bin, state, false).unwrap();
+        }
+        drop((text, bin));
+
+        for path in [&path_text, &path_bin] {
+            assert_eq!(load_frame(path, 0).unwrap(), state_a);
+            assert_eq!(load_frame(path, 1).unwrap(), state_b);
+            assert!(load_frame(path, 2).is_err());
+        }
+
+        let diff = diff_states(&state_a, &state_b);
+        let changed: Vec<usize> = (0..diff.len())
+            .filter(|&idx| diff[idx] != DIFF_UNCHANGED)
+            .collect();
+        let codes: Vec<((usize, usize, usize), u8)> = changed
+            .iter()
+            .map(|&idx| (grid.idx_to_xyz(idx), diff[idx]))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                ((0, 2, 4), DIFF_OTHER),
+                ((1, 1, 1), DIFF_DETACHED),
+                ((2, 3, 0), DIFF_ATTACHED),
+            ]
+        );
+        assert_eq!(bounding_box(&grid, &changed), Some(((0, 1, 0), (2, 3, 4))));
+        assert_eq!(bounding_box(&grid, &[]), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dims_and_slice_arguments_are_parsed() {
+        assert_eq!(parse_dims("3,4,5").unwrap(), (3, 4, 5));
+        assert_eq!(parse_dims("3x4x5").unwrap(), (3, 4, 5));
+        assert!(parse_dims("3,4").is_err());
+        assert_eq!(parse_slice("y:2").unwrap(), (1, 2));
+        assert!(parse_slice("w:2").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::mods::{constants::SIM_LOG_FILE_NAME, ensemble::Ensemble, io_handler, state::SimLog};
+use std::{
+    error::Error,
+    fs,
+    io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+const CONNECT_RETRY: Duration = Duration::from_millis(500);
+
+/// Entry point of the `rank` subcommand.
+///
+/// Usage: `rank <r> [<config dir>]`. Runs rank `r` of a distributed ensemble described by
+/// `Ranks`, `CoordAddr` and `SyncI` in the shared `InitSettings.ini`; rank 0 is the
+/// coordinator and listens on `CoordAddr`, the other ranks connect to it.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: rank <r> [<config dir>]";
+
+    let rank: usize = args.first().ok_or(usage)?.parse()?;
+    let src_path = match args.get(1) {
+        Some(dir) => PathBuf::from(dir),
+        None => io_handler::get_exe_dir().map_err(|e| format!("get_exe_dir() failed: {e}"))?,
+    };
+
+    let mut cfg = io_handler::load_config(&src_path)
+        .map_err(|e| format!("Failed to load config from {:?}: {e}", src_path))?
+        .build()?;
+    if cfg.ranks < 2 {
+        return Err("'rank' needs Ranks >= 2 in InitSettings.ini".into());
+    }
+    if rank >= cfg.ranks {
+        return Err(format!("Rank {} out of 0..{}", rank, cfg.ranks).into());
+    }
+    cfg.rank = rank;
+
+    let mut ensemble = Ensemble::with_settings(cfg, src_path)?;
+    println!(
+        "✅ Rank {}/{}: {} item(s)",
+        rank,
+        ensemble.cfg.ranks,
+        ensemble.items.len()
+    );
+    ensemble
+        .run_simulation()
+        .inspect_err(|_| ensemble.salvage())
+}
+
+#[derive(Debug)]
+enum Peers {
+    Coordinator(Vec<BufReader<TcpStream>>),
+    Worker(BufReader<TcpStream>),
+}
+
+/// Connection of one rank of a distributed ensemble.
+///
+/// Items are split by `item_gid % ranks`. Between synchronizations every rank keeps a
+/// replica of the shared reservoir updated with its own items only; every `sync_i`
+/// steps the workers send their crystal totals to rank 0, which sums them, applies the
+/// correction to its reservoir and sends the exact `n_cryst`/`n_gas` back. A stop
+/// requested on any rank (all items finished, steering, steady state) takes effect at
+/// the next synchronization, so all ranks leave the loop on the same step. At the end
+/// the workers send the `SimLog.txt` of their items to rank 0.
+#[derive(Debug)]
+pub struct RankLink {
+    pub rank: usize,
+    pub ranks: usize,
+    sync_i: u64,
+    peers: Peers,
+    global_n_cryst: f64,
+    local_n_cryst_at_sync: f64,
+    is_stop_requested: bool,
+}
+
+impl RankLink {
+    pub fn connect(rank: usize, ranks: usize, addr: &str, sync_i: u64) -> IoResult<Self> {
+        let peers = match rank {
+            0 => {
+                let listener = TcpListener::bind(addr)?;
+                println!("🌐 Rank 0: waiting for {} rank(s) on {}", ranks - 1, addr);
+
+                let mut workers: Vec<Option<BufReader<TcpStream>>> =
+                    (1..ranks).map(|_| None).collect();
+                while workers.iter().any(Option::is_none) {
+                    let (stream, peer_addr) = listener.accept()?;
+                    stream.set_nodelay(true)?;
+                    let mut reader = BufReader::new(stream);
+                    let worker_rank: usize = read_fields(&mut reader, "hello", 1)?[0] as usize;
+                    match workers.get_mut(worker_rank.wrapping_sub(1)) {
+                        Some(slot @ None) => *slot = Some(reader),
+                        _ => {
+                            return Err(IoError::new(
+                                ErrorKind::InvalidData,
+                                format!("Unexpected rank {} from {}", worker_rank, peer_addr),
+                            ));
+                        }
+                    }
+                    println!("🌐 Rank {} connected from {}", worker_rank, peer_addr);
+                }
+                Peers::Coordinator(workers.into_iter().flatten().collect())
+            }
+            _ => {
+                let started = Instant::now();
+                let stream = loop {
+                    match TcpStream::connect(addr) {
+                        Ok(stream) => break stream,
+                        Err(e) if started.elapsed() > CONNECT_TIMEOUT => return Err(e),
+                        Err(_) => thread::sleep(CONNECT_RETRY),
+                    }
+                };
+                stream.set_nodelay(true)?;
+                writeln!(&stream, "hello {}", rank)?;
+                Peers::Worker(BufReader::new(stream))
+            }
+        };
+
+        Ok(Self {
+            rank,
+            ranks,
+            sync_i: sync_i.max(1),
+            peers,
+            global_n_cryst: 0.0,
+            local_n_cryst_at_sync: 0.0,
+            is_stop_requested: false,
+        })
+    }
+
+    /// Sets the starting totals: the ensemble crystal size and the local part of it.
+    pub fn init_totals(&mut self, global_n_cryst: f64, local_n_cryst: f64) {
+        self.global_n_cryst = global_n_cryst;
+        self.local_n_cryst_at_sync = local_n_cryst;
+    }
+
+    /// Ensemble crystal size estimated from the last synchronization and the local change.
+    #[inline(always)]
+    pub fn estimate(&self, local_n_cryst: f64) -> f64 {
+        self.global_n_cryst + local_n_cryst - self.local_n_cryst_at_sync
+    }
+
+    pub fn request_stop(&mut self) {
+        self.is_stop_requested = true;
+    }
+
+    /// Synchronizes the reservoir on every `sync_i`-th and on the last step.
+    /// Returns `true` when all ranks have to stop.
+    pub fn sync(
+        &mut self,
+        step_id: u64,
+        step_lim: u64,
+        local_n_cryst: f64,
+        alive: usize,
+        simlog: &mut SimLog,
+    ) -> IoResult<bool> {
+        if !step_id.is_multiple_of(self.sync_i) && step_id != step_lim {
+            return Ok(false);
+        }
+
+        let is_stop = match &mut self.peers {
+            Peers::Coordinator(workers) => {
+                let (mut n_cryst, mut alive, mut is_stop) =
+                    (local_n_cryst, alive, self.is_stop_requested);
+                for worker in workers.iter_mut() {
+                    let fields = read_fields(worker, "sync", 4)?;
+                    if fields[0] as u64 != step_id {
+                        return Err(IoError::new(
+                            ErrorKind::InvalidData,
+                            format!("Rank out of step: {} vs {}", fields[0], step_id),
+                        ));
+                    }
+                    n_cryst += fields[1];
+                    alive += fields[2] as usize;
+                    is_stop |= fields[3] != 0.0;
+                }
+                is_stop |= alive == 0;
+
+                simlog.update_n_sizes(n_cryst - simlog.n_cryst.val);
+                for worker in workers.iter_mut() {
+                    writeln!(
+                        worker.get_mut(),
+                        "state {} {} {}",
+                        simlog.n_cryst.val,
+                        simlog.n_gas.val,
+                        is_stop as u8
+                    )?;
+                }
+                is_stop
+            }
+            Peers::Worker(coordinator) => {
+                writeln!(
+                    coordinator.get_mut(),
+                    "sync {} {} {} {}",
+                    step_id,
+                    local_n_cryst,
+                    alive,
+                    self.is_stop_requested as u8
+                )?;
+                let fields = read_fields(coordinator, "state", 3)?;
+                simlog.n_cryst.val = fields[0];
+                simlog.n_gas.val = fields[1];
+                fields[2] != 0.0
+            }
+        };
+
+        self.global_n_cryst = simlog.n_cryst.val;
+        self.local_n_cryst_at_sync = local_n_cryst;
+
+        Ok(is_stop)
+    }
+
+    /// Collects the item `SimLog.txt` files of all ranks in the directory of rank 0.
+    pub fn gather_logs(&mut self, dst_path: &Path) -> IoResult<()> {
+        match &mut self.peers {
+            Peers::Coordinator(workers) => {
+                let mut gathered = 0;
+                for worker in workers.iter_mut() {
+                    loop {
+                        let mut line = String::new();
+                        worker.read_line(&mut line)?;
+                        let mut parts = line.split_whitespace();
+                        match (parts.next(), parts.next(), parts.next()) {
+                            (Some("log"), Some(name), Some(len)) => {
+                                let len: usize = len.parse().map_err(|e| {
+                                    IoError::new(ErrorKind::InvalidData, format!("{e}"))
+                                })?;
+                                let mut text = vec![0u8; len];
+                                worker.read_exact(&mut text)?;
+
+                                let item_dir = dst_path.join(name);
+                                fs::create_dir_all(&item_dir)?;
+                                fs::write(item_dir.join(SIM_LOG_FILE_NAME), text)?;
+                                gathered += 1;
+                            }
+                            (Some("done"), _, _) => break,
+                            _ => {
+                                return Err(IoError::new(
+                                    ErrorKind::InvalidData,
+                                    format!("Unexpected message: '{}'", line.trim()),
+                                ));
+                            }
+                        }
+                    }
+                }
+                println!(
+                    "🌐 Rank 0: gathered {} item log(s) from {} rank(s)",
+                    gathered,
+                    self.ranks - 1
+                );
+            }
+            Peers::Worker(coordinator) => {
+                let stream = coordinator.get_mut();
+                let mut item_dirs: Vec<PathBuf> = fs::read_dir(dst_path)?
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.join(SIM_LOG_FILE_NAME).is_file())
+                    .collect();
+                item_dirs.sort();
+
+                for item_dir in item_dirs {
+                    let text = fs::read(item_dir.join(SIM_LOG_FILE_NAME))?;
+                    let name = item_dir.file_name().unwrap_or_default().to_string_lossy();
+                    writeln!(stream, "log {} {}", name, text.len())?;
+                    stream.write_all(&text)?;
+                }
+                writeln!(stream, "done")?;
+                stream.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a `<tag> <values...>` line with `len` numeric values.
+fn read_fields(reader: &mut BufReader<TcpStream>, tag: &str, len: usize) -> IoResult<Vec<f64>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(IoError::new(
+            ErrorKind::UnexpectedEof,
+            format!("Connection closed while waiting for '{}'", tag),
+        ));
+    }
+
+    let mut parts = line.split_whitespace();
+    let fields: Option<Vec<f64>> = match parts.next() {
+        Some(t) if t == tag => parts.map(|v| v.parse().ok()).collect(),
+        _ => None,
+    };
+    match fields {
+        Some(fields) if fields.len() == len => Ok(fields),
+        _ => Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Expected '{}' with {} value(s), got '{}'",
+                tag,
+                len,
+                line.trim()
+            ),
+        )),
+    }
+}
@@ -1,30 +1,129 @@
 use crate::mods::{
-    constants::{K_BOLTZMANN, SIM_LOG_FILE_NAME},
+    anchor::Anchor,
+    audit::RngAudit,
+    ballistics::{BallisticAxes, BallisticLaw},
+    blocking::ObservableStats,
+    campaign,
+    clusters::ClusterTracker,
+    constants::{
+        CONTROL_FILE_NAME, FACET_STATS_FILE_NAME, GROUP_STATS_FILE_NAME, K_BOLTZMANN,
+        KOSSEL_CHECK_FILE_NAME, NUCLEI_FILE_NAME, RNG_AUDIT_FILE_NAME, SIM_LOG_FILE_NAME,
+        SUMMARY_FILE_NAME, TIME_STATES_ZST_FILE_NAME,
+    },
+    detach_spectrum::DetachSpectrum,
+    distributed::RankLink,
+    events::EventExport,
+    facets::FacetStats,
+    faults::{self, FaultPoint},
+    front_export::FrontExport,
     frontier::Frontier,
+    groups::ItemGroups,
+    heat::HeatField,
     io_handler,
-    item::Item,
-    lattice::Grid,
+    item::{Item, Mode23Params, SnapshotPolicy, SnapshotRegion},
+    kossel::KosselCheck,
+    lattice::{Grid, GridError},
+    nuclei::{self, NucleiFilter},
+    pack,
+    passage::{self, FirstPassage, PassageTimes},
+    passivation::Passivation,
+    probes::{self, SiteProbes},
+    profile::DensityProfile,
+    residence::ResidenceStats,
+    resume::{self, ResumePoint},
+    reweight::EnergyHistogram,
+    rng,
     settings::Settings,
-    state::SimLog,
+    state::{ConcNegAction, CrystalFaces, SimLog},
+    state_header::StateHeader,
+    status::{self, StatusServer},
+    steady::SteadyStateDetector,
+    steering::Steering,
+    step_extend::StepLimExtender,
+    sublattice::SubLattice,
+    thermostat::OuThermostat,
     utils,
+    view::{self, StateView, WriteObserver},
+    write_tuner::WriteTuner,
+    writer::{self, QueueFull, SnapFormat, SnapshotWriter},
 };
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
-use std::{error::Error, io, path::PathBuf};
+use std::{
+    error::Error,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
+/// Why the run loop ended, the `termination` line of `Summary.txt`. A rank stopped by
+/// another one reports `Completed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// `StepLim` reached or no items left.
+    Completed,
+    /// `SteadyStop`.
+    SteadyState,
+    /// `stop` in the control file.
+    Stopped,
+    /// `ConcNegAction:abort` or `QueueFull:abort`.
+    Aborted,
+    /// `MaxHours` of wall-clock time passed.
+    TimeLimited,
+}
+
+impl Termination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::SteadyState => "steady-state",
+            Self::Stopped => "stopped",
+            Self::Aborted => "aborted",
+            Self::TimeLimited => "time-limited",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Ensemble {
     pub cfg: Settings,
     pub rng: ChaCha8Rng,
     pub grid: Grid,
+    pub item_grids: Vec<Grid>,
     pub items: Vec<Item>,
     pub simlog: SimLog,
+    pub steady: SteadyStateDetector,
+    pub rng_audit: RngAudit,
+    pub facet_stats: FacetStats,
+    pub kossel: KosselCheck,
+    pub stats: ObservableStats,
+    pub groups: ItemGroups,
+    pub thermostat: OuThermostat,
+    /// First-passage times of the finished items (`FirstPassage`).
+    pub passages: PassageTimes,
+    /// `WriteI` in effect, raised when writing takes too much of the run (`WriteIoPct`).
+    pub write_tuner: WriteTuner,
+    /// `StepLim` in effect, extended while the crystals still grow (`StepLimMax`).
+    pub step_ext: StepLimExtender,
+    /// Background writer of the item snapshots (`WriteQueue > 0`).
+    pub writer: SnapshotWriter,
+    /// Connection to the other ranks of a distributed ensemble (`Ranks > 1`).
+    pub link: Option<RankLink>,
+    /// Volume and crystal cells of every item of the ensemble, local or not.
+    pub item_totals: Vec<(f64, f64)>,
+    /// First step of the run loop: 1, or the step after the checkpoint of `ResumeFrom`.
+    pub first_step: u64,
     pub src_path: PathBuf,
     pub dst_path: PathBuf,
     pub items_len: usize,
     pub items_len0: usize,
+    /// Start of the run, for `MaxHours`, and why it ended.
+    pub started: Instant,
+    pub termination: Termination,
+    /// Callback of a library user on every write step (`set_on_write`).
+    pub on_write: Option<WriteObserver>,
 }
 
 impl Ensemble {
@@ -32,32 +131,122 @@ impl Ensemble {
         let exe_dir =
             io_handler::get_exe_dir().map_err(|e| format!("get_exe_dir() failed: {e}"))?;
 
-        let mut cfg = Settings::new();
+        Self::from_dir(exe_dir)
+    }
 
-        io_handler::load_config(&mut cfg, &exe_dir)
-            .map_err(|e| format!("Failed to load config from {:?}: {e}", exe_dir))?;
-        cfg.validate()?;
+    /// `new` with the preset `preset` in place of the `Preset` key (`--preset`).
+    pub fn with_preset(preset: &str) -> Result<Self> {
+        let exe_dir =
+            io_handler::get_exe_dir().map_err(|e| format!("get_exe_dir() failed: {e}"))?;
+        let cfg = io_handler::load_config_with_preset(&exe_dir, Some(preset))
+            .map_err(|e| format!("Failed to load config from {:?}: {e}", exe_dir))?
+            .build()?;
 
+        Self::with_settings(cfg, exe_dir)
+    }
+
+    /// Loads `InitSettings.ini`/`InitStates.ini` from `src_path`; results go to a new
+    /// directory inside it.
+    pub fn from_dir(src_path: PathBuf) -> Result<Self> {
+        let cfg = io_handler::load_config(&src_path)
+            .map_err(|e| format!("Failed to load config from {:?}: {e}", src_path))?
+            .build()?;
+
+        Self::with_settings(cfg, src_path)
+    }
+
+    pub fn with_settings(cfg: Settings, src_path: PathBuf) -> Result<Self> {
+        utils::check_memory_budget(&cfg, 0)?;
         let rng = ChaCha8Rng::seed_from_u64(cfg.seed);
 
-        let grid = Grid::new(cfg.sx, cfg.sy, cfg.sz, cfg.px, cfg.py, cfg.pz);
+        let mut grid = Grid::new(cfg.sx, cfg.sy, cfg.sz, cfg.px, cfg.py, cfg.pz)
+            .map_err(|e| format!("Invalid grid: {e}"))?;
+        grid.axis_map = cfg
+            .axis_perm()
+            .ok_or("AxisMap must be a permutation of 'xyz'")?;
         let mut simlog = SimLog::new();
+        simlog.log_decim = cfg.log_decim;
 
         simlog.tot_denergy.is_on = false;
+        simlog.tot_denergy_err.is_on = false;
         simlog.cryst_sx.is_on = false;
         simlog.cryst_sy.is_on = false;
         simlog.cryst_sz.is_on = false;
+        simlog.tpas_size.is_on = false;
+        simlog.tpbs_size.is_on = false;
+        simlog.surf_area.is_on = false;
+        simlog.sv_ratio.is_on = false;
+        simlog.sphericity.is_on = false;
+        simlog.rot_angle.is_on = false;
+        simlog.rot_step.is_on = false;
+        simlog.inertia_aniso.is_on = false;
+
+        let steady = SteadyStateDetector::new(cfg.steady_w, cfg.steady_tol, cfg.steady_m);
+        let rng_audit = RngAudit::new(cfg.rng_audit);
+        let facet_stats = FacetStats::new(cfg.facet_stats && cfg.mode_major == 2);
+        let kossel = KosselCheck::new(
+            cfg.kossel_check && cfg.mode_major == 2,
+            cfg.add_i,
+            cfg.rem_i,
+            cfg.acceptance_rule(),
+        );
+        let stats = ObservableStats::new(cfg.stats_from);
+        let thermostat = OuThermostat::new(cfg.temperature, cfg.t_sigma, cfg.t_tau, cfg.t_every);
+        let passages =
+            PassageTimes::new(&passage::parse_thresholds(&cfg.first_passage).unwrap_or_default());
+        let write_tuner = WriteTuner::new(
+            cfg.write_i,
+            cfg.write_io_pct,
+            match cfg.write_i_max {
+                0 => cfg.step_lim,
+                write_i_max => write_i_max,
+            },
+        );
+        let step_ext = StepLimExtender::new(
+            cfg.step_lim,
+            cfg.step_lim_max,
+            cfg.extend_rate,
+            cfg.extend_pct,
+            cfg.extend_window,
+        );
+        simlog.temp.val = cfg.temperature;
+        simlog.temp.is_on = thermostat.is_on;
+        let link = match cfg.ranks > 1 {
+            true => Some(
+                RankLink::connect(cfg.rank, cfg.ranks, &cfg.coord_addr, cfg.sync_i)
+                    .map_err(|e| format!("Rank {}: failed to connect: {e}", cfg.rank))?,
+            ),
+            false => None,
+        };
 
         let mut ensemble = Self {
             cfg,
             rng,
             grid,
+            item_grids: Vec::new(),
             items: Vec::new(),
             simlog: simlog,
-            src_path: exe_dir,
+            steady,
+            rng_audit,
+            facet_stats,
+            kossel,
+            stats,
+            groups: ItemGroups::default(),
+            thermostat,
+            write_tuner,
+            step_ext,
+            passages,
+            writer: SnapshotWriter::off(),
+            link,
+            item_totals: Vec::new(),
+            first_step: 1,
+            src_path,
             dst_path: PathBuf::new(),
             items_len: 0,
             items_len0: 0,
+            started: Instant::now(),
+            termination: Termination::Completed,
+            on_write: None,
         };
 
         ensemble.initialization_stage1()?;
@@ -66,87 +255,486 @@ impl Ensemble {
         Ok(ensemble)
     }
 
-    fn initialization_stage1(&mut self) -> Result<()> {
-        let state_size = self.grid.size;
+    /// Restores the RNG position, the thermostat, the reservoir and the item counters and
+    /// fronts of a checkpoint, over what the initialization derived from its states.
+    fn apply_resume(&mut self, point: ResumePoint) -> Result<()> {
+        if point.items.len() != self.items.len() {
+            return Err(format!(
+                "The checkpoint holds {} item record(s) for {} state(s)",
+                point.items.len(),
+                self.items.len()
+            )
+            .into());
+        }
+
+        self.rng = point.rng;
+        self.thermostat.temperature = point.temperature;
+        self.simlog.temp.val = point.temperature;
+        point.ensemble.apply(&mut self.simlog);
+        self.simlog.mk_step.val = point.step;
+
+        for (item, saved) in self.items.iter_mut().zip(point.items) {
+            saved.scalars.apply(&mut item.simlog);
+            saved.sums.apply(&mut item.simlog);
+            item.simlog.mk_step.val = point.step;
 
-        let loaded_states_data =
-            io_handler::load_states(&self).map_err(|e| format!("Failed to load states: {e}"))?;
+            item.front = Frontier::new(item.state.len());
+            for idxg in saved.tpas {
+                item.front.tpa_add(idxg);
+            }
+            for idxg in saved.tpbs {
+                item.front.tpb_add(idxg);
+            }
+
+            if let Some(rng) = item.rng.as_mut() {
+                let (stream, word_pos) = saved.rng_pos.ok_or(
+                    "The checkpoint has no item random streams (taken without Deterministic)",
+                )?;
+                rng.set_stream(stream);
+                rng.set_word_pos(word_pos);
+            }
+        }
+        self.first_step = point.step + 1;
+
+        Ok(())
+    }
+
+    fn initialization_stage1(&mut self) -> Result<()> {
+        let mut item_dims = io_handler::load_item_dims(&self)
+            .map_err(|e| format!("Failed to load item dims: {e}"))?;
+        let mut loaded_states_data = io_handler::load_states(&self, &item_dims)
+            .map_err(|e| format!("Failed to load states: {e}"))?;
+        let mut item_tags = io_handler::load_item_groups(self)
+            .map_err(|e| format!("Failed to load item groups: {e}"))?;
+
+        // A checkpoint keeps the items of the run it was taken from.
+        let nuclei_filter = match self.cfg.resume_path() {
+            None => NucleiFilter::parse(&self.cfg.nuclei_filter).unwrap_or(NucleiFilter::Off),
+            Some(_) => NucleiFilter::Off,
+        };
+        let nuclei_report = match nuclei_filter {
+            NucleiFilter::Off => None,
+            action => {
+                let dg = nuclei::initial_dg(&self.cfg);
+                let n_crit = nuclei::critical_size(&self.cfg, dg);
+                let nuclei = nuclei::filter(
+                    &mut loaded_states_data,
+                    &mut item_dims,
+                    (self.cfg.sx, self.cfg.sy, self.cfg.sz),
+                    n_crit,
+                    action,
+                );
+                let n_sub = nuclei.iter().filter(|n| !n.is_critical).count();
+                println!(
+                    "📒 Nuclei: {} of {} below the critical size n* = {:.3e} (dg: {:.5e}){}",
+                    n_sub,
+                    nuclei.len(),
+                    n_crit,
+                    dg,
+                    match action {
+                        NucleiFilter::Drop => ", dropped",
+                        _ => "",
+                    }
+                );
+                if loaded_states_data.is_empty() && !nuclei.is_empty() {
+                    return Err(format!(
+                        "No initial state reaches the critical size n* = {:.3e} (NucleiFilter: drop)",
+                        n_crit
+                    )
+                    .into());
+                }
+                Some((dg, n_crit, nuclei))
+            }
+        };
+        self.grid.template = io_handler::load_template(&self)
+            .map_err(|e| format!("Failed to load template: {e}"))?;
+
+        // The dropped nuclei take their tags along.
+        if let Some((_, _, nuclei)) = &nuclei_report
+            && nuclei_filter == NucleiFilter::Drop
+        {
+            item_tags = nuclei
+                .iter()
+                .filter(|nucleus| nucleus.item_gid.is_some())
+                .filter_map(|nucleus| item_tags.get(nucleus.line).cloned())
+                .collect();
+        }
 
         self.items_len0 = loaded_states_data.len();
-        self.items_len = self.items_len0;
+        self.groups = ItemGroups::new(&item_tags, self.items_len0);
+        if self.groups.is_on {
+            println!("📒 Item groups: {}", self.groups.names.join(", "));
+        }
+        self.item_totals = loaded_states_data
+            .iter()
+            .map(|state| {
+                let n_cryst = state.iter().filter(|&&cell| cell == 1).count();
+                (state.len() as f64, n_cryst as f64)
+            })
+            .collect();
+
+        // A rank of a distributed ensemble keeps every `ranks`-th item.
+        let (ranks, rank) = (self.cfg.ranks.max(1), self.cfg.rank);
+        let is_local = |item_gid: usize| item_gid % ranks == rank;
+        self.items_len = (0..self.items_len0).filter(|&gid| is_local(gid)).count();
+        utils::check_memory_budget(&self.cfg, self.items_len)?;
+
+        // `ItemMaxMb` is shared evenly between the items.
+        let snapshot_policy = SnapshotPolicy {
+            interval: self.cfg.item_write_i,
+            keep_first: self.cfg.item_keep_first,
+            keep_last: self.cfg.item_keep_last,
+            max_bytes: (self.cfg.item_max_mb * 1e6 / self.items_len0.max(1) as f64) as u64,
+        };
+        let passage_thresholds =
+            passage::parse_thresholds(&self.cfg.first_passage).unwrap_or_default();
 
         self.dst_path = io_handler::prepare_main_dir(&self)
             .map_err(|e| format!("Failed to prepare main dir: {e}"))?;
 
         let _ = self.simlog.create_out_file(self.dst_path.clone());
+        if self.rng_audit.is_on {
+            self.rng_audit
+                .create_out_file(self.dst_path.clone())
+                .map_err(|e| format!("Failed to create RNG audit file: {e}"))?;
+        }
+        if self.facet_stats.is_on {
+            self.facet_stats
+                .create_out_file(self.dst_path.clone(), self.grid.axis_map)
+                .map_err(|e| format!("Failed to create facet stats file: {e}"))?;
+        }
+        if self.groups.is_on {
+            self.groups
+                .create_out_file(self.dst_path.clone())
+                .map_err(|e| format!("Failed to create group stats file: {e}"))?;
+        }
 
         io_handler::prepare_files(&self).map_err(|e| format!("Failed to prepare files: {e}"))?;
+        if let Some((dg, n_crit, nuclei)) = &nuclei_report {
+            nuclei::write_report(&self.dst_path.join(NUCLEI_FILE_NAME), *dg, *n_crit, nuclei)
+                .map_err(|e| format!("Failed to write the nuclei report: {e}"))?;
+        }
+
+        let grid_ids: Vec<usize> = (0..loaded_states_data.len())
+            .map(|item_gid| {
+                self.grid_id_for(item_dims.get(item_gid).copied())
+                    .map_err(|e| format!("Item {item_gid}: invalid grid: {e}"))
+            })
+            .collect::<std::result::Result<_, _>>()?;
 
         self.items = loaded_states_data
             .into_iter()
             .enumerate()
+            .filter(|&(item_gid, _)| is_local(item_gid))
             .map(|(item_gid, state_data)| {
                 let item_dst_path = self.dst_path.join(format!("{:05}", item_gid));
-                let mut item = Item::new(item_gid, state_size, item_dst_path)
+                let front = Frontier::new(state_data.len());
+                let state = state_data.into_boxed_slice();
+                let mut item = Item::new(item_gid, state, front, item_dst_path)
                     .map_err(|e| format!("Failed to create item {item_gid}: {e}"))?;
-                item.state.copy_from_slice(&state_data);
+                item.grid_id = grid_ids[item_gid];
+                item.snapshot_policy = snapshot_policy;
+                item.rng_audit.is_on = self.cfg.rng_audit;
+                item.facet_stats.is_on = self.facet_stats.is_on;
+                item.residence = ResidenceStats::new(self.cfg.residence_stats, item.state.len());
+                item.profile = DensityProfile::new(&self.cfg.density_profile);
+                item.events = EventExport::new(self.cfg.event_export);
+                item.front_export = FrontExport::new(self.cfg.front_export);
+                item.detach_spectrum =
+                    DetachSpectrum::new(self.cfg.detach_spectrum_i, self.cfg.is_bond_counting());
+                item.first_passage = FirstPassage::new(&passage_thresholds);
+                item.stall_thr = self.cfg.stall_thr;
+                item.stale_retry = self.cfg.stale_retry;
+                item.throttle_thr = self.cfg.throttle_thr;
+                item.throttle_i = self.cfg.throttle_i;
+                item.poisson_means = (
+                    self.cfg.poisson_add,
+                    self.cfg.poisson_rem,
+                    self.cfg.poisson_bal,
+                );
+                item.front_rebuild_i = self.cfg.front_rebuild_i;
+                item.front_rebuild_adopt = self.cfg.front_rebuild_adopt;
+                item.simlog.front_div.is_on = self.cfg.front_rebuild_i > 0;
+                item.passivation = Passivation::new(self.cfg.pass_rate, self.cfg.pass_life);
+                item.simlog.pass_sites.is_on = item.passivation.is_on();
+                item.energy_hist = EnergyHistogram::new(
+                    self.cfg.energy_hist_bin * K_BOLTZMANN * self.cfg.temperature,
+                    self.cfg.stats_from,
+                );
+                item.rng = self
+                    .cfg
+                    .deterministic
+                    .then(|| rng::item_stream(self.cfg.seed, item_gid));
+                item.simlog.log_decim = self.cfg.log_decim;
+                item.simlog.mink_v.is_on = self.cfg.minkowski;
+                item.simlog.mink_s.is_on = self.cfg.minkowski;
+                item.simlog.mink_m.is_on = self.cfg.minkowski;
+                item.simlog.mink_chi.is_on = self.cfg.minkowski;
+                item.is_bond_counting = self.cfg.is_bond_counting();
+                item.acceptance = self.cfg.acceptance_rule();
                 Ok(item)
             })
             .collect::<Result<Vec<_>>>()?;
 
+        if self.cfg.write_queue > 0 {
+            let policy = QueueFull::parse(&self.cfg.queue_full).unwrap_or(QueueFull::Block);
+            let format = SnapFormat::new(self.cfg.snap_compress, self.cfg.snap_dual);
+            self.writer = SnapshotWriter::start(self.cfg.write_queue, policy, format)
+                .map_err(|e| format!("Failed to start the snapshot writer: {e}"))?;
+            for item in self.items.iter_mut() {
+                item.writer = self.writer.handle();
+            }
+        }
+
+        let roi = self.cfg.item_roi_bounds();
+        let anchor = Anchor::parse(&self.cfg.anchor).unwrap_or(Anchor::Off);
+        let probe_coords = probes::parse_probes(&self.cfg.probes).unwrap_or_default();
+        let sub_lattice = SubLattice::parse(&self.cfg.sub_lattice).unwrap_or_default();
+        let broken_axes = sub_lattice.broken_axes(&self.grid);
+        if !broken_axes.is_empty() {
+            eprintln!(
+                "⚠️ Warning: SubLattice: the periodic grid size along {} is not a multiple of the stride; neighbours across that boundary share a sub-lattice.",
+                broken_axes
+                    .iter()
+                    .map(|&axis| ["x", "y", "z"][axis])
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        for item in self.items.iter_mut() {
+            let grid = item_grid(&mut self.grid, &mut self.item_grids, item.grid_id);
+            item.anchor = anchor.mask(&item.state, grid);
+            item.sub_lattice = sub_lattice;
+            let (probes, outside) = SiteProbes::new(&probe_coords, &item.state, grid);
+            item.probes = probes;
+            for [x, y, z] in outside {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: Probe {}:{}:{} is outside the grid; skipped.",
+                    item.item_gid, x, y, z
+                );
+            }
+            item.clusters = ClusterTracker::new(self.cfg.cluster_track, &item.state, &grid.neibs);
+            item.simlog.clusters.is_on = item.clusters.is_on;
+            item.heat = HeatField::new(
+                self.cfg.heat_release,
+                self.cfg.heat_cell,
+                self.cfg.heat_diff,
+                self.cfg.heat_decay,
+                grid,
+            );
+            item.simlog.heat_t_mean.is_on = item.heat.is_on();
+            item.simlog.heat_t_max.is_on = item.heat.is_on();
+            let mut frame_dims = (grid.nx, grid.ny, grid.nz);
+            if self.cfg.is_snapshot_subsampled() {
+                let region = SnapshotRegion::new(frame_dims, roi, self.cfg.item_stride)
+                    .map_err(|e| format!("Item {}: ItemRoi {e}", item.item_gid))?;
+                frame_dims = region.dims();
+                item.set_snapshot_region(region)
+                    .map_err(|e| format!("Failed to write snapshot header: {e}"))?;
+            }
+            item.write_states_header(StateHeader::new(frame_dims))
+                .map_err(|e| format!("Failed to write states header: {e}"))?;
+        }
+
         Ok(())
     }
 
+    /// Grid id for an item of size `dims`: the ensemble grid if it matches (or `None`),
+    /// otherwise an item grid of that size, created on first use.
+    fn grid_id_for(
+        &mut self,
+        dims: Option<(usize, usize, usize)>,
+    ) -> std::result::Result<usize, GridError> {
+        let Some((sx, sy, sz)) = dims else {
+            return Ok(0);
+        };
+        if (sx, sy, sz) == (self.grid.nx, self.grid.ny, self.grid.nz) {
+            return Ok(0);
+        }
+
+        if let Some(pos) = self
+            .item_grids
+            .iter()
+            .position(|g| (g.nx, g.ny, g.nz) == (sx, sy, sz))
+        {
+            return Ok(pos + 1);
+        }
+
+        let mut grid = Grid::new(sx, sy, sz, self.cfg.px, self.cfg.py, self.cfg.pz)?;
+        grid.axis_map = self.grid.axis_map;
+        self.item_grids.push(grid);
+        Ok(self.item_grids.len())
+    }
+
     fn initialization_stage2(&mut self) -> Result<()> {
         let cfg = &self.cfg;
-        let neibs = &*self.grid.neibs;
 
         let k_t = K_BOLTZMANN * self.cfg.temperature;
-        let n_tot = cfg.n_tot / self.items_len0 as f64;
+        // The reservoir is shared out in proportion to the item volumes. The shares are
+        // indexed by `item_gid` and cover all items, also those of other ranks.
+        let volume_tot: f64 = self.item_totals.iter().map(|&(volume, _)| volume).sum();
+        let n_tots: Vec<f64> = self
+            .item_totals
+            .iter()
+            .map(|&(volume, _)| cfg.n_tot * volume / volume_tot)
+            .collect();
         let (mode, dg, c_eq, c0, n0_cr, p_b, p_pow) = (
-            cfg.mode, cfg.dg, cfg.c_eq, cfg.c0, cfg.n0_cr, cfg.p_b, cfg.p_pow,
+            cfg.mode(),
+            cfg.dg,
+            cfg.c_eq,
+            cfg.c0,
+            cfg.n0_cr,
+            cfg.p_b,
+            cfg.p_pow,
         );
 
-        let mut n0_cr_ensemble = 0.0;
-
-        for (item_lid, item) in self.items.iter_mut().enumerate() {
-            let n0_cr_calculated = utils::rebuild_front(&*item.state, neibs, &mut item.front);
+        for item in self.items.iter_mut() {
+            let neibs = match item.grid_id {
+                0 => &*self.grid.neibs,
+                id => &*self.item_grids[id - 1].neibs,
+            };
+            utils::rebuild_front(&*item.state, neibs, &mut item.front);
+            item.faces = CrystalFaces::of_state(&item.state, neibs);
+        }
+        let n_cryst0s: Vec<f64> = self
+            .item_totals
+            .iter()
+            .map(|&(_, n0_cr_calculated)| if n0_cr < 0.0 { n0_cr_calculated } else { n0_cr })
+            .collect();
+        let n0_cr_ensemble: f64 = n_cryst0s.iter().sum();
+
+        let gas_dist = utils::parse_gas_dist(&cfg.gas_dist).unwrap_or_default();
+        if !gas_dist.is_empty() && gas_dist.len() != n_cryst0s.len() {
+            return Err(format!(
+                "GasDist lists {} value(s) for {} item(s)",
+                gas_dist.len(),
+                n_cryst0s.len()
+            )
+            .into());
+        }
+        let is_gas_weighted =
+            !gas_dist.is_empty() || cfg.gas_dist_pow != 0.0 || cfg.gas_dist_noise > 0.0;
+        let gas_weights = match gas_dist.is_empty() {
+            true => utils::gas_weights(
+                &n_cryst0s,
+                cfg.gas_dist_pow,
+                cfg.gas_dist_noise,
+                &mut self.rng,
+            ),
+            false => utils::listed_gas_weights(&gas_dist),
+        };
 
-            let n_cryst0 = { if n0_cr < 0.0 { n0_cr_calculated } else { n0_cr } };
+        let n_gas0_ensemble: f64 = (0..n_tots.len())
+            .map(|gid| c0 * gas_weights[gid] * (n_tots[gid] - n_cryst0s[gid]))
+            .sum();
 
-            n0_cr_ensemble += n_cryst0;
+        let p_b_axes = BallisticAxes::new(
+            p_b,
+            self.grid.to_grid_axes((cfg.p_b_x, cfg.p_b_y, cfg.p_b_z)),
+        );
+        for item in self.items.iter_mut() {
+            let gid = item.item_gid;
+            let (n_cryst0, n_tot) = (n_cryst0s[gid], n_tots[gid]);
+            let c0 = c0 * gas_weights[gid];
 
             item.simlog
                 .initialize(k_t, mode, dg, c_eq, c0, n_tot, n_cryst0, p_b, p_pow);
-            // println!("ID: {item_lid} (n_cryst0: {n_cryst0}) --> {:?}", item.state);
+            item.simlog.cell = self.grid.to_grid_axes((cfg.ax, cfg.ay, cfg.az));
+            item.simlog.p_b_axes = p_b_axes;
+            if cfg.mode_minor >= 2 {
+                item.simlog
+                    .track_desorption(cfg.desorption_energy(), cfg.energy_accounting_policy());
+            }
+            if item.heat.is_on() {
+                item.simlog.heat_t_mean.val = cfg.temperature;
+                item.simlog.heat_t_max.val = cfg.temperature;
+            }
         }
 
+        // The shared reservoir holds exactly the gas handed out to the items.
+        let c0_ensemble = match is_gas_weighted {
+            true => n_gas0_ensemble / (cfg.n_tot - n0_cr_ensemble),
+            false => c0,
+        };
+
+        self.simlog.dg_exact = cfg.dg_exact;
         self.simlog.initialize(
             k_t,
             mode,
             dg,
             c_eq,
-            c0,
+            c0_ensemble,
             cfg.n_tot,
             n0_cr_ensemble,
             p_b,
             p_pow,
         );
 
+        if let Some(link) = self.link.as_mut() {
+            let n_cryst0_local: f64 = self.items.iter().map(|item| n_cryst0s[item.item_gid]).sum();
+            link.init_totals(n0_cr_ensemble, n_cryst0_local);
+        }
+
+        if cfg.mode_major == 2 {
+            self.simlog.n_exch.is_on = cfg.k_exch > 0.0 || cfg.chemostat_c >= 0.0;
+            self.simlog.exch_flux.is_on = self.simlog.n_exch.is_on;
+            if c0_ensemble < c_eq {
+                println!(
+                    "Dissolution regime: C0 = {:.5e} < C_eq = {:.5e}",
+                    c0_ensemble, c_eq
+                );
+            }
+        }
+
+        if let Some(resume_from) = self.cfg.resume_path() {
+            let path_checkpoint = resume::checkpoint_path(resume_from);
+            let point = resume::read(&path_checkpoint)
+                .map_err(|e| format!("Failed to read the resume data: {e}"))?;
+            self.apply_resume(point)?;
+            println!(
+                "✅ Resumed from '{}' after step {}",
+                path_checkpoint.display(),
+                self.first_step - 1
+            );
+        }
+
         for (item_lid, item) in self.items.iter_mut().enumerate() {
-            item.simlog.n_gas.is_on = false;
-            item.simlog.conc.is_on = false;
+            if !is_gas_weighted {
+                item.simlog.n_gas.is_on = false;
+                item.simlog.conc.is_on = false;
+                item.simlog.depletion.is_on = false;
+            }
 
             item.simlog.dg.val = self.simlog.dg.val;
-            item.write_action(&mut self.grid);
+            item.write_action(
+                item_grid(&mut self.grid, &mut self.item_grids, item.grid_id),
+                false,
+            );
         }
 
         self.simlog.add_log_point();
         // println!("{:#?}", &self.simlog);
 
+        if self.kossel.is_on {
+            self.kossel
+                .create_out_file(self.dst_path.clone(), self.simlog.n_cryst.val)
+                .map_err(|e| format!("Failed to create Kossel check file: {e}"))?;
+        }
+
         Ok(())
     }
 
+    /// Calls `f` on every write step of `run_simulation` with the step and the borrowed
+    /// states of the local items, so observables are computed without copying the grids.
+    pub fn set_on_write(&mut self, f: impl FnMut(u64, &[StateView<'_>]) + Send + 'static) {
+        self.on_write = Some(WriteObserver::new(f));
+    }
+
+    /// Borrowed states of the local items, e.g. after `run_simulation`.
+    pub fn views(&self) -> Vec<StateView<'_>> {
+        view::views(&self.grid, &self.item_grids, &self.items)
+    }
+
     pub fn run_simulation(&mut self) -> Result<()> {
         let rng = &mut self.rng;
         let cfg = &self.cfg;
@@ -157,63 +745,186 @@ impl Ensemble {
             cfg.g010 * cfg.ax * cfg.az,
             cfg.g001 * cfg.ax * cfg.ay,
         );
-        let (ex2, ey2, ez2) = (ex * 2.0, ey * 2.0, ez * 2.0);
+        let (ex2, ey2, ez2) = grid.to_grid_axes((ex * 2.0, ey * 2.0, ez * 2.0));
         let eisol = ex2 + ey2 + ez2;
-
-        let (add_check_part, rem_check_part, write_check_part, print_check_part) = (
-            cfg.add_i > 0,
-            cfg.rem_i > 0,
-            cfg.write_i > 0,
-            cfg.print_i > 0,
-        );
+        let mut ballistic = BallisticLaw::new(
+            cfg.e_des,
+            eisol,
+            &cfg.pb_expr,
+            K_BOLTZMANN * cfg.temperature,
+            cfg.temperature,
+        )
+        .map_err(|e| format!("Invalid 'PbExpr': {e}"))?;
+        let face_mask = grid.mask_to_grid_axes(cfg.face_mask());
+
+        let (add_check_part, rem_check_part) = (cfg.add_i > 0, cfg.rem_i > 0);
+        let mut print_i = cfg.print_i;
+        let mut steering = Steering::new(self.dst_path.join(CONTROL_FILE_NAME), cfg.control_i);
+        let swap_check_part = cfg.swap_i > 0;
+        let conc_neg_action =
+            ConcNegAction::parse(&cfg.conc_neg_action).unwrap_or(ConcNegAction::Warn);
+        let exch_check_part = cfg.k_exch > 0.0;
+        let chemostat_check_part = cfg.chemostat_c >= 0.0;
+        let max_time =
+            (cfg.max_hours > 0.0).then(|| Duration::from_secs_f64(cfg.max_hours * 3600.0));
 
         let mut n_cryst_ensemble = 0.0;
         let mut is_item_alive = true;
+        let first_step = self.first_step;
+        let max_step = self.step_ext.max_step();
+        let status_server = match status::parse_addr(&cfg.status_addr) {
+            Some(addr) => {
+                let server = StatusServer::start(
+                    addr,
+                    &self.dst_path,
+                    first_step,
+                    self.step_ext.limit,
+                    self.items.len(),
+                )
+                .map_err(|e| format!("Failed to start the status endpoint on '{addr}': {e}"))?;
+                println!("🌐 Status endpoint: http://{}/status", server.addr);
+                Some(server)
+            }
+            None => None,
+        };
+        self.write_tuner.start();
+        for item in self.items.iter_mut() {
+            item.first_passage
+                .update(item.simlog.n_cryst.val, first_step - 1);
+        }
+
+        faults::disable_on_error(
+            self.rng_audit.write_step(0, rng),
+            RNG_AUDIT_FILE_NAME,
+            &mut self.rng_audit.is_on,
+        );
+
+        match cfg.mode() {
+            (1, _) => {}
+            (2, _) => match cfg.mode() {
+                (2, 1) => {
+                    'simulation_loop: for step_id in first_step..=max_step {
+                        let steer = steering.poll(step_id);
+                        if let Some(new_print_i) = steer.print_i {
+                            print_i = new_print_i;
+                        }
 
-        match cfg.mode {
-            1.1 | 1.2 | 1.3 => {}
-            2.1 | 2.2 | 2.3 => match cfg.mode {
-                2.1 => {
-                    'simulation_loop: for step_id in 1..=cfg.step_lim {
                         let is_add_step = add_check_part
                             && (step_id >= cfg.add_from)
                             && ((step_id % cfg.add_i) == 0);
                         let is_rem_step = rem_check_part
                             && (step_id >= cfg.rem_from)
                             && ((step_id % cfg.rem_i) == 0);
-                        let is_write_step = write_check_part && ((step_id % cfg.write_i) == 0);
-                        let is_print_step = print_check_part && ((step_id % cfg.print_i) == 0);
+                        let is_swap_step = swap_check_part
+                            && (step_id >= cfg.swap_from)
+                            && ((step_id % cfg.swap_i) == 0);
+                        let is_write_step = self.write_tuner.is_write_step(step_id) || steer.write;
+                        let is_print_step = print_i > 0 && ((step_id % print_i) == 0);
+
+                        if self.thermostat.update(step_id, rng) {
+                            let k_t = K_BOLTZMANN * self.thermostat.temperature;
+                            self.simlog.k_t = k_t;
+                            self.simlog.temp.val = self.thermostat.temperature;
+                            for item in self.items.iter_mut() {
+                                item.simlog.k_t = k_t;
+                            }
+                        }
 
                         n_cryst_ensemble = 0.0;
                         for (item_lid, item) in self.items.iter_mut().enumerate() {
+                            if item.is_throttled(step_id, is_write_step) {
+                                n_cryst_ensemble += item.simlog.n_cryst.val;
+                                continue;
+                            }
+                            let mut own_rng = item.rng.take();
+                            let item_rng = match &mut own_rng {
+                                Some(own_rng) => own_rng,
+                                None => &mut *rng,
+                            };
                             is_item_alive = item.mode_2_1_step(
-                                rng,
-                                grid,
+                                item_rng,
+                                item_grid(grid, &mut self.item_grids, item.grid_id),
                                 (ex2, ey2, ez2),
+                                face_mask,
                                 step_id,
-                                (is_add_step, is_rem_step, is_write_step),
+                                (is_add_step, is_rem_step, is_swap_step, is_write_step),
                             );
+                            self.rng_audit.collect(&mut item.rng_audit, item_rng);
+                            item.rng = own_rng;
+                            self.facet_stats.collect(&mut item.facet_stats);
 
                             match is_item_alive {
                                 true => {
                                     n_cryst_ensemble += item.simlog.n_cryst.val;
                                 }
                                 false => {
-                                    item.write_action(grid);
-                                    item.simlog.write_log_to_file()?;
+                                    item.write_action(
+                                        item_grid(grid, &mut self.item_grids, item.grid_id),
+                                        true,
+                                    );
+                                    write_item_log(item);
+                                    self.passages.collect(
+                                        item.item_gid,
+                                        &mut item.first_passage,
+                                        item.simlog.n_cryst.val,
+                                        step_id,
+                                    );
                                     // println!("{:#?}", &self.simlog);
                                 }
                             }
                         }
 
+                        faults::disable_on_error(
+                            self.rng_audit.write_step(step_id, rng),
+                            RNG_AUDIT_FILE_NAME,
+                            &mut self.rng_audit.is_on,
+                        );
+                        if is_write_step {
+                            let write_time = self
+                                .items
+                                .iter_mut()
+                                .map(|item| std::mem::take(&mut item.write_time))
+                                .sum();
+                            self.write_tuner.update(step_id, write_time);
+                            if let Some(on_write) = self.on_write.as_mut() {
+                                on_write.call(
+                                    step_id,
+                                    &view::views(grid, &self.item_grids, &self.items),
+                                );
+                            }
+                        }
                         self.items.retain(|item| item.is_alive);
 
+                        let n_cryst_local = n_cryst_ensemble;
+                        if let Some(link) = self.link.as_ref() {
+                            n_cryst_ensemble = link.estimate(n_cryst_local);
+                        }
                         self.simlog
                             .update_n_sizes(n_cryst_ensemble - self.simlog.n_cryst.val);
-
-                        self.simlog.update_conc_and_dg();
-
-                        if self.items.len() == 0 {
+                        if exch_check_part {
+                            self.simlog.exchange(cfg.c_ext, cfg.k_exch);
+                        }
+                        if chemostat_check_part {
+                            self.simlog.chemostat(cfg.chemostat_c, cfg.chemostat_rate);
+                        }
+                        let is_rank_stop = match self.link.as_mut() {
+                            Some(link) => link.sync(
+                                step_id,
+                                cfg.step_lim,
+                                n_cryst_local,
+                                self.items.len(),
+                                &mut self.simlog,
+                            )?,
+                            None => false,
+                        };
+
+                        let is_conc_abort = self.simlog.update_conc_and_dg(
+                            step_id,
+                            cfg.conc_neg_max,
+                            conc_neg_action,
+                        );
+
+                        if (self.items.len() == 0 && self.link.is_none()) || is_rank_stop {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
 
@@ -227,10 +938,66 @@ impl Ensemble {
                         if is_write_step {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
+                            faults::disable_on_error(
+                                self.facet_stats.write_step(step_id),
+                                FACET_STATS_FILE_NAME,
+                                &mut self.facet_stats.is_on,
+                            );
+                            faults::disable_on_error(
+                                self.kossel.write_step(
+                                    step_id,
+                                    self.simlog.n_cryst.val,
+                                    self.items.len(),
+                                    self.simlog.dg.val,
+                                    self.simlog.k_t,
+                                ),
+                                KOSSEL_CHECK_FILE_NAME,
+                                &mut self.kossel.is_on,
+                            );
+                            self.stats
+                                .sample(step_id, &self.items, self.simlog.n_cryst.val);
+                            faults::disable_on_error(
+                                self.groups.write_step(step_id, &self.items),
+                                GROUP_STATS_FILE_NAME,
+                                &mut self.groups.is_on,
+                            );
+
+                            if self.steady.update(step_id, self.simlog.n_cryst.val) {
+                                self.simlog.mark_steady_state(step_id);
+                                println!("Step: {} -> Steady state detected.", step_id);
+
+                                if cfg.steady_stop {
+                                    self.termination = Termination::SteadyState;
+                                    match self.link.as_mut() {
+                                        Some(link) => link.request_stop(),
+                                        None => break 'simulation_loop,
+                                    }
+                                }
+                            }
+                        }
+
+                        if let Some(status_server) = &status_server {
+                            let items_alive =
+                                self.items.iter().filter(|item| item.is_alive).count();
+                            status_server.update(
+                                step_id,
+                                self.step_ext.limit,
+                                items_alive,
+                                &self.simlog,
+                            );
                         }
 
                         if is_print_step {
-                            println!("Steps: {}/{}", step_id, cfg.step_lim,);
+                            println!("Steps: {}/{}", step_id, self.step_ext.limit);
+                            if self.kossel.is_on {
+                                println!(
+                                    "Kossel check: v_sim / v_mf = {:.5e}",
+                                    self.kossel.last_ratio
+                                );
+                            }
+                            if let Some(status) = self.writer.queue_status() {
+                                println!("Write queue: {}", status);
+                            }
                             // println!(
                             //     "Ceq: {:.5e}; C: {:.5e}; nv_gas: {:.5e}; nv_cryst: {:.5e}; dg: {:.5e}",
                             //     sim_state.eq_concentration,
@@ -240,49 +1007,201 @@ impl Ensemble {
                             //     sim_state.delta_gibbs
                             // );
                         }
+
+                        // Past `MaxHours` the step is finished like the last one: logged,
+                        // checkpointed, then the final snapshots are written below.
+                        let is_time_up = self.termination != Termination::TimeLimited
+                            && max_time.is_some_and(|max| self.started.elapsed() >= max);
+                        if is_time_up && !is_write_step {
+                            self.simlog.mk_step.val = step_id;
+                            self.simlog.add_log_point();
+                        }
+
+                        let is_checkpoint_step =
+                            cfg.checkpoint_i > 0 && step_id % cfg.checkpoint_i == 0;
+                        if steer.checkpoint || is_checkpoint_step || is_time_up {
+                            let resume = resume::resume_line(
+                                step_id,
+                                rng,
+                                self.thermostat.temperature,
+                                &self.simlog,
+                            );
+                            if let Err(e) = io_handler::write_checkpoint(
+                                &self.dst_path,
+                                &self.items,
+                                grid,
+                                &self.item_grids,
+                                step_id,
+                                Some(&resume),
+                            ) {
+                                eprintln!("⚠️ Warning: {e}; the run goes on.");
+                            }
+                        }
+                        if is_conc_abort {
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.writer.is_aborted() {
+                            eprintln!(
+                                "❌ Step: {} -> The snapshot queue is full (QueueFull:abort); stopping the run.",
+                                step_id
+                            );
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if steer.stop {
+                            println!("Step: {} -> Stop requested via control file.", step_id);
+                            self.termination = Termination::Stopped;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if is_time_up {
+                            println!(
+                                "⏱️ Step: {} -> MaxHours ({}) reached; finalizing the run.",
+                                step_id, cfg.max_hours
+                            );
+                            self.termination = Termination::TimeLimited;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.step_ext.update(step_id, || item_sizes(&self.items)) {
+                            break 'simulation_loop;
+                        }
                     }
                 }
-                2.2 => {
-                    'simulation_loop: for step_id in 1..=cfg.step_lim {
+                (2, 2) => {
+                    'simulation_loop: for step_id in first_step..=max_step {
+                        let steer = steering.poll(step_id);
+                        if let Some(new_print_i) = steer.print_i {
+                            print_i = new_print_i;
+                        }
+
                         let is_add_step = add_check_part
                             && (step_id >= cfg.add_from)
                             && ((step_id % cfg.add_i) == 0);
                         let is_rem_step = rem_check_part
                             && (step_id >= cfg.rem_from)
                             && ((step_id % cfg.rem_i) == 0);
-                        let is_write_step = write_check_part && ((step_id % cfg.write_i) == 0);
-                        let is_print_step = print_check_part && ((step_id % cfg.print_i) == 0);
+                        let is_swap_step = swap_check_part
+                            && (step_id >= cfg.swap_from)
+                            && ((step_id % cfg.swap_i) == 0);
+                        let is_write_step = self.write_tuner.is_write_step(step_id) || steer.write;
+                        let is_print_step = print_i > 0 && ((step_id % print_i) == 0);
+
+                        if self.thermostat.update(step_id, rng) {
+                            let k_t = K_BOLTZMANN * self.thermostat.temperature;
+                            self.simlog.k_t = k_t;
+                            self.simlog.temp.val = self.thermostat.temperature;
+                            for item in self.items.iter_mut() {
+                                item.simlog.k_t = k_t;
+                            }
+                        }
 
                         n_cryst_ensemble = 0.0;
                         for (item_lid, item) in self.items.iter_mut().enumerate() {
+                            if item.is_throttled(step_id, is_write_step) {
+                                n_cryst_ensemble += item.simlog.n_cryst.val;
+                                continue;
+                            }
+                            let mut own_rng = item.rng.take();
+                            let item_rng = match &mut own_rng {
+                                Some(own_rng) => own_rng,
+                                None => &mut *rng,
+                            };
                             is_item_alive = item.mode_2_2_step(
-                                rng,
-                                grid,
+                                item_rng,
+                                item_grid(grid, &mut self.item_grids, item.grid_id),
                                 (ex2, ey2, ez2),
+                                face_mask,
                                 step_id,
-                                (is_add_step, is_rem_step, is_write_step),
+                                (is_add_step, is_rem_step, is_swap_step, is_write_step),
                             );
+                            self.rng_audit.collect(&mut item.rng_audit, item_rng);
+                            item.rng = own_rng;
+                            self.facet_stats.collect(&mut item.facet_stats);
 
                             match is_item_alive {
                                 true => {
                                     n_cryst_ensemble += item.simlog.n_cryst.val;
                                 }
                                 false => {
-                                    item.write_action(grid);
-                                    item.simlog.write_log_to_file()?;
+                                    item.write_action(
+                                        item_grid(grid, &mut self.item_grids, item.grid_id),
+                                        true,
+                                    );
+                                    write_item_log(item);
+                                    self.passages.collect(
+                                        item.item_gid,
+                                        &mut item.first_passage,
+                                        item.simlog.n_cryst.val,
+                                        step_id,
+                                    );
                                     // println!("{:#?}", &self.simlog);
                                 }
                             }
                         }
 
+                        faults::disable_on_error(
+                            self.rng_audit.write_step(step_id, rng),
+                            RNG_AUDIT_FILE_NAME,
+                            &mut self.rng_audit.is_on,
+                        );
+                        if is_write_step {
+                            let write_time = self
+                                .items
+                                .iter_mut()
+                                .map(|item| std::mem::take(&mut item.write_time))
+                                .sum();
+                            self.write_tuner.update(step_id, write_time);
+                            if let Some(on_write) = self.on_write.as_mut() {
+                                on_write.call(
+                                    step_id,
+                                    &view::views(grid, &self.item_grids, &self.items),
+                                );
+                            }
+                        }
                         self.items.retain(|item| item.is_alive);
 
+                        let n_cryst_local = n_cryst_ensemble;
+                        if let Some(link) = self.link.as_ref() {
+                            n_cryst_ensemble = link.estimate(n_cryst_local);
+                        }
                         self.simlog
                             .update_n_sizes(n_cryst_ensemble - self.simlog.n_cryst.val);
-
-                        self.simlog.update_conc_and_dg();
-
-                        if self.items.len() == 0 {
+                        if exch_check_part {
+                            self.simlog.exchange(cfg.c_ext, cfg.k_exch);
+                        }
+                        if chemostat_check_part {
+                            self.simlog.chemostat(cfg.chemostat_c, cfg.chemostat_rate);
+                        }
+                        let is_rank_stop = match self.link.as_mut() {
+                            Some(link) => link.sync(
+                                step_id,
+                                cfg.step_lim,
+                                n_cryst_local,
+                                self.items.len(),
+                                &mut self.simlog,
+                            )?,
+                            None => false,
+                        };
+
+                        let is_conc_abort = self.simlog.update_conc_and_dg(
+                            step_id,
+                            cfg.conc_neg_max,
+                            conc_neg_action,
+                        );
+
+                        if (self.items.len() == 0 && self.link.is_none()) || is_rank_stop {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
 
@@ -296,10 +1215,66 @@ impl Ensemble {
                         if is_write_step {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
+                            faults::disable_on_error(
+                                self.facet_stats.write_step(step_id),
+                                FACET_STATS_FILE_NAME,
+                                &mut self.facet_stats.is_on,
+                            );
+                            faults::disable_on_error(
+                                self.kossel.write_step(
+                                    step_id,
+                                    self.simlog.n_cryst.val,
+                                    self.items.len(),
+                                    self.simlog.dg.val,
+                                    self.simlog.k_t,
+                                ),
+                                KOSSEL_CHECK_FILE_NAME,
+                                &mut self.kossel.is_on,
+                            );
+                            self.stats
+                                .sample(step_id, &self.items, self.simlog.n_cryst.val);
+                            faults::disable_on_error(
+                                self.groups.write_step(step_id, &self.items),
+                                GROUP_STATS_FILE_NAME,
+                                &mut self.groups.is_on,
+                            );
+
+                            if self.steady.update(step_id, self.simlog.n_cryst.val) {
+                                self.simlog.mark_steady_state(step_id);
+                                println!("Step: {} -> Steady state detected.", step_id);
+
+                                if cfg.steady_stop {
+                                    self.termination = Termination::SteadyState;
+                                    match self.link.as_mut() {
+                                        Some(link) => link.request_stop(),
+                                        None => break 'simulation_loop,
+                                    }
+                                }
+                            }
+                        }
+
+                        if let Some(status_server) = &status_server {
+                            let items_alive =
+                                self.items.iter().filter(|item| item.is_alive).count();
+                            status_server.update(
+                                step_id,
+                                self.step_ext.limit,
+                                items_alive,
+                                &self.simlog,
+                            );
                         }
 
                         if is_print_step {
-                            println!("Steps: {}/{}", step_id, cfg.step_lim,);
+                            println!("Steps: {}/{}", step_id, self.step_ext.limit);
+                            if self.kossel.is_on {
+                                println!(
+                                    "Kossel check: v_sim / v_mf = {:.5e}",
+                                    self.kossel.last_ratio
+                                );
+                            }
+                            if let Some(status) = self.writer.queue_status() {
+                                println!("Write queue: {}", status);
+                            }
                             // println!(
                             //     "Ceq: {:.5e}; C: {:.5e}; nv_gas: {:.5e}; nv_cryst: {:.5e}; dg: {:.5e}",
                             //     sim_state.eq_concentration,
@@ -309,49 +1284,204 @@ impl Ensemble {
                             //     sim_state.delta_gibbs
                             // );
                         }
+
+                        // Past `MaxHours` the step is finished like the last one: logged,
+                        // checkpointed, then the final snapshots are written below.
+                        let is_time_up = self.termination != Termination::TimeLimited
+                            && max_time.is_some_and(|max| self.started.elapsed() >= max);
+                        if is_time_up && !is_write_step {
+                            self.simlog.mk_step.val = step_id;
+                            self.simlog.add_log_point();
+                        }
+
+                        let is_checkpoint_step =
+                            cfg.checkpoint_i > 0 && step_id % cfg.checkpoint_i == 0;
+                        if steer.checkpoint || is_checkpoint_step || is_time_up {
+                            let resume = resume::resume_line(
+                                step_id,
+                                rng,
+                                self.thermostat.temperature,
+                                &self.simlog,
+                            );
+                            if let Err(e) = io_handler::write_checkpoint(
+                                &self.dst_path,
+                                &self.items,
+                                grid,
+                                &self.item_grids,
+                                step_id,
+                                Some(&resume),
+                            ) {
+                                eprintln!("⚠️ Warning: {e}; the run goes on.");
+                            }
+                        }
+                        if is_conc_abort {
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.writer.is_aborted() {
+                            eprintln!(
+                                "❌ Step: {} -> The snapshot queue is full (QueueFull:abort); stopping the run.",
+                                step_id
+                            );
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if steer.stop {
+                            println!("Step: {} -> Stop requested via control file.", step_id);
+                            self.termination = Termination::Stopped;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if is_time_up {
+                            println!(
+                                "⏱️ Step: {} -> MaxHours ({}) reached; finalizing the run.",
+                                step_id, cfg.max_hours
+                            );
+                            self.termination = Termination::TimeLimited;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.step_ext.update(step_id, || item_sizes(&self.items)) {
+                            break 'simulation_loop;
+                        }
                     }
                 }
-                2.3 => {
-                    'simulation_loop: for step_id in 1..=cfg.step_lim {
+                (2, 3) => {
+                    'simulation_loop: for step_id in first_step..=max_step {
+                        let steer = steering.poll(step_id);
+                        if let Some(new_print_i) = steer.print_i {
+                            print_i = new_print_i;
+                        }
+
                         let is_add_step = add_check_part
                             && (step_id >= cfg.add_from)
                             && ((step_id % cfg.add_i) == 0);
                         let is_rem_step = rem_check_part
                             && (step_id >= cfg.rem_from)
                             && ((step_id % cfg.rem_i) == 0);
-                        let is_write_step = write_check_part && ((step_id % cfg.write_i) == 0);
-                        let is_print_step = print_check_part && ((step_id % cfg.print_i) == 0);
+                        let is_swap_step = swap_check_part
+                            && (step_id >= cfg.swap_from)
+                            && ((step_id % cfg.swap_i) == 0);
+                        let is_write_step = self.write_tuner.is_write_step(step_id) || steer.write;
+                        let is_print_step = print_i > 0 && ((step_id % print_i) == 0);
+
+                        if self.thermostat.update(step_id, rng) {
+                            let k_t = K_BOLTZMANN * self.thermostat.temperature;
+                            self.simlog.k_t = k_t;
+                            self.simlog.temp.val = self.thermostat.temperature;
+                            for item in self.items.iter_mut() {
+                                item.simlog.k_t = k_t;
+                            }
+                        }
 
                         n_cryst_ensemble = 0.0;
                         for (item_lid, item) in self.items.iter_mut().enumerate() {
+                            if item.is_throttled(step_id, is_write_step) {
+                                n_cryst_ensemble += item.simlog.n_cryst.val;
+                                continue;
+                            }
+                            let mut own_rng = item.rng.take();
+                            let item_rng = match &mut own_rng {
+                                Some(own_rng) => own_rng,
+                                None => &mut *rng,
+                            };
                             is_item_alive = item.mode_2_3_step(
-                                rng,
-                                grid,
-                                (ex2, ey2, ez2, eisol),
+                                item_rng,
+                                item_grid(grid, &mut self.item_grids, item.grid_id),
+                                Mode23Params {
+                                    e2: (ex2, ey2, ez2),
+                                    face_mask,
+                                    ballistic: &mut ballistic,
+                                },
                                 step_id,
-                                (is_add_step, is_rem_step, is_write_step),
+                                (is_add_step, is_rem_step, is_swap_step, is_write_step),
                             );
+                            self.rng_audit.collect(&mut item.rng_audit, item_rng);
+                            item.rng = own_rng;
+                            self.facet_stats.collect(&mut item.facet_stats);
 
                             match is_item_alive {
                                 true => {
                                     n_cryst_ensemble += item.simlog.n_cryst.val;
                                 }
                                 false => {
-                                    item.write_action(grid);
-                                    item.simlog.write_log_to_file()?;
+                                    item.write_action(
+                                        item_grid(grid, &mut self.item_grids, item.grid_id),
+                                        true,
+                                    );
+                                    write_item_log(item);
+                                    self.passages.collect(
+                                        item.item_gid,
+                                        &mut item.first_passage,
+                                        item.simlog.n_cryst.val,
+                                        step_id,
+                                    );
                                     // println!("{:#?}", &self.simlog);
                                 }
                             }
                         }
 
+                        faults::disable_on_error(
+                            self.rng_audit.write_step(step_id, rng),
+                            RNG_AUDIT_FILE_NAME,
+                            &mut self.rng_audit.is_on,
+                        );
+                        if is_write_step {
+                            let write_time = self
+                                .items
+                                .iter_mut()
+                                .map(|item| std::mem::take(&mut item.write_time))
+                                .sum();
+                            self.write_tuner.update(step_id, write_time);
+                            if let Some(on_write) = self.on_write.as_mut() {
+                                on_write.call(
+                                    step_id,
+                                    &view::views(grid, &self.item_grids, &self.items),
+                                );
+                            }
+                        }
                         self.items.retain(|item| item.is_alive);
 
+                        let n_cryst_local = n_cryst_ensemble;
+                        if let Some(link) = self.link.as_ref() {
+                            n_cryst_ensemble = link.estimate(n_cryst_local);
+                        }
                         self.simlog
                             .update_n_sizes(n_cryst_ensemble - self.simlog.n_cryst.val);
-
-                        self.simlog.update_conc_and_dg();
-
-                        if self.items.len() == 0 {
+                        if exch_check_part {
+                            self.simlog.exchange(cfg.c_ext, cfg.k_exch);
+                        }
+                        if chemostat_check_part {
+                            self.simlog.chemostat(cfg.chemostat_c, cfg.chemostat_rate);
+                        }
+                        let is_rank_stop = match self.link.as_mut() {
+                            Some(link) => link.sync(
+                                step_id,
+                                cfg.step_lim,
+                                n_cryst_local,
+                                self.items.len(),
+                                &mut self.simlog,
+                            )?,
+                            None => false,
+                        };
+
+                        let is_conc_abort = self.simlog.update_conc_and_dg(
+                            step_id,
+                            cfg.conc_neg_max,
+                            conc_neg_action,
+                        );
+
+                        if (self.items.len() == 0 && self.link.is_none()) || is_rank_stop {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
 
@@ -365,10 +1495,66 @@ impl Ensemble {
                         if is_write_step {
                             self.simlog.mk_step.val = step_id;
                             self.simlog.add_log_point();
+                            faults::disable_on_error(
+                                self.facet_stats.write_step(step_id),
+                                FACET_STATS_FILE_NAME,
+                                &mut self.facet_stats.is_on,
+                            );
+                            faults::disable_on_error(
+                                self.kossel.write_step(
+                                    step_id,
+                                    self.simlog.n_cryst.val,
+                                    self.items.len(),
+                                    self.simlog.dg.val,
+                                    self.simlog.k_t,
+                                ),
+                                KOSSEL_CHECK_FILE_NAME,
+                                &mut self.kossel.is_on,
+                            );
+                            self.stats
+                                .sample(step_id, &self.items, self.simlog.n_cryst.val);
+                            faults::disable_on_error(
+                                self.groups.write_step(step_id, &self.items),
+                                GROUP_STATS_FILE_NAME,
+                                &mut self.groups.is_on,
+                            );
+
+                            if self.steady.update(step_id, self.simlog.n_cryst.val) {
+                                self.simlog.mark_steady_state(step_id);
+                                println!("Step: {} -> Steady state detected.", step_id);
+
+                                if cfg.steady_stop {
+                                    self.termination = Termination::SteadyState;
+                                    match self.link.as_mut() {
+                                        Some(link) => link.request_stop(),
+                                        None => break 'simulation_loop,
+                                    }
+                                }
+                            }
+                        }
+
+                        if let Some(status_server) = &status_server {
+                            let items_alive =
+                                self.items.iter().filter(|item| item.is_alive).count();
+                            status_server.update(
+                                step_id,
+                                self.step_ext.limit,
+                                items_alive,
+                                &self.simlog,
+                            );
                         }
 
                         if is_print_step {
-                            println!("Steps: {}/{}", step_id, cfg.step_lim,);
+                            println!("Steps: {}/{}", step_id, self.step_ext.limit);
+                            if self.kossel.is_on {
+                                println!(
+                                    "Kossel check: v_sim / v_mf = {:.5e}",
+                                    self.kossel.last_ratio
+                                );
+                            }
+                            if let Some(status) = self.writer.queue_status() {
+                                println!("Write queue: {}", status);
+                            }
                             // println!(
                             //     "Ceq: {:.5e}; C: {:.5e}; nv_gas: {:.5e}; nv_cryst: {:.5e}; dg: {:.5e}",
                             //     sim_state.eq_concentration,
@@ -378,23 +1564,311 @@ impl Ensemble {
                             //     sim_state.delta_gibbs
                             // );
                         }
+
+                        // Past `MaxHours` the step is finished like the last one: logged,
+                        // checkpointed, then the final snapshots are written below.
+                        let is_time_up = self.termination != Termination::TimeLimited
+                            && max_time.is_some_and(|max| self.started.elapsed() >= max);
+                        if is_time_up && !is_write_step {
+                            self.simlog.mk_step.val = step_id;
+                            self.simlog.add_log_point();
+                        }
+
+                        let is_checkpoint_step =
+                            cfg.checkpoint_i > 0 && step_id % cfg.checkpoint_i == 0;
+                        if steer.checkpoint || is_checkpoint_step || is_time_up {
+                            let resume = resume::resume_line(
+                                step_id,
+                                rng,
+                                self.thermostat.temperature,
+                                &self.simlog,
+                            );
+                            if let Err(e) = io_handler::write_checkpoint(
+                                &self.dst_path,
+                                &self.items,
+                                grid,
+                                &self.item_grids,
+                                step_id,
+                                Some(&resume),
+                            ) {
+                                eprintln!("⚠️ Warning: {e}; the run goes on.");
+                            }
+                        }
+                        if is_conc_abort {
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.writer.is_aborted() {
+                            eprintln!(
+                                "❌ Step: {} -> The snapshot queue is full (QueueFull:abort); stopping the run.",
+                                step_id
+                            );
+                            self.termination = Termination::Aborted;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if steer.stop {
+                            println!("Step: {} -> Stop requested via control file.", step_id);
+                            self.termination = Termination::Stopped;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if is_time_up {
+                            println!(
+                                "⏱️ Step: {} -> MaxHours ({}) reached; finalizing the run.",
+                                step_id, cfg.max_hours
+                            );
+                            self.termination = Termination::TimeLimited;
+                            match self.link.as_mut() {
+                                Some(link) => link.request_stop(),
+                                None => break 'simulation_loop,
+                            }
+                        }
+                        if self.step_ext.update(step_id, || item_sizes(&self.items)) {
+                            break 'simulation_loop;
+                        }
                     }
                 }
                 _ => {}
             },
             _ => {}
         }
+        if let Some(status_server) = &status_server {
+            status_server.finish(self.termination.as_str());
+        }
 
+        let mut residence = ResidenceStats::new(cfg.residence_stats, 0);
         if self.items.len() > 0 {
             for (item_lid, item) in self.items.iter_mut().enumerate() {
                 item.simlog.dg.val = self.simlog.dg.val;
-                item.write_action(grid);
-                item.simlog.write_log_to_file()?;
+                item.write_action(item_grid(grid, &mut self.item_grids, item.grid_id), true);
+                write_item_log(item);
+                residence.merge_hist(&item.residence);
+                self.passages.collect(
+                    item.item_gid,
+                    &mut item.first_passage,
+                    item.simlog.n_cryst.val,
+                    item.simlog.mk_step.val,
+                );
+            }
+        }
+        self.writer.finish();
+        if residence.is_on {
+            residence
+                .write_hist(&self.dst_path)
+                .map_err(|e| format!("Failed to write residence histogram: {e}"))?;
+        }
+        if self.passages.is_on {
+            self.passages
+                .write(&self.dst_path)
+                .map_err(|e| format!("Failed to write first-passage times: {e}"))?;
+        }
+
+        if let Some(link) = self.link.as_mut() {
+            link.gather_logs(&self.dst_path)
+                .map_err(|e| format!("Rank {}: failed to gather logs: {e}", link.rank))?;
+        }
+
+        faults::retry_io(SIM_LOG_FILE_NAME, || self.simlog.write_log_to_file())?;
+        faults::disable_on_error(
+            self.rng_audit.finish(),
+            RNG_AUDIT_FILE_NAME,
+            &mut self.rng_audit.is_on,
+        );
+        faults::disable_on_error(
+            self.facet_stats.finish(),
+            FACET_STATS_FILE_NAME,
+            &mut self.facet_stats.is_on,
+        );
+        faults::disable_on_error(
+            self.groups.finish(),
+            GROUP_STATS_FILE_NAME,
+            &mut self.groups.is_on,
+        );
+        faults::disable_on_error(
+            self.kossel.finish(),
+            KOSSEL_CHECK_FILE_NAME,
+            &mut self.kossel.is_on,
+        );
+        self.write_summary()?;
+        if self.cfg.campaign_name().is_some() {
+            let appended = campaign::append_run(
+                &self.cfg,
+                &self.src_path,
+                &self.dst_path,
+                &self.simlog,
+                (self.items.len(), self.items_len),
+            );
+            match appended {
+                Ok(path) => println!("📒 Run added to campaign index '{}'", path.display()),
+                Err(e) => eprintln!("⚠️ Warning: Failed to update the campaign index: {e}"),
+            }
+        }
+
+        #[cfg(feature = "plots")]
+        match crate::mods::plots::write_plots(&self.dst_path) {
+            Ok(count) => println!(
+                "📈 {} plot(s) written to '{}'",
+                count,
+                self.dst_path.display()
+            ),
+            Err(e) => eprintln!("⚠️ Warning: Failed to write plots: {e}"),
+        }
+
+        if self.cfg.pack_run && self.cfg.rank == 0 {
+            match pack::pack_dir(&self.dst_path, None, pack::PACK_ZSTD_LEVEL) {
+                Ok((archive, entries)) => println!(
+                    "📦 Run packed into '{}' ({} file(s))",
+                    archive.display(),
+                    entries.len()
+                ),
+                Err(e) => eprintln!("⚠️ Warning: Failed to pack the run: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves what a failed run can still save: a checkpoint of the alive items, their
+    /// `SimLog.txt` and the queued snapshots; the ensemble rows stay in `SimLogRec.txt`.
+    pub fn salvage(&mut self) {
+        let step_id = self.simlog.mk_step.val;
+        let written = io_handler::write_checkpoint(
+            &self.dst_path,
+            &self.items,
+            &self.grid,
+            &self.item_grids,
+            step_id,
+            None,
+        );
+        if let Err(e) = written {
+            eprintln!("⚠️ Warning: {e}");
+        }
+        for item in self.items.iter_mut().filter(|item| item.is_alive) {
+            write_item_log(item);
+        }
+        self.writer.finish();
+    }
+
+    /// Compares `TimeStates.txt` with `TimeStates.zst` in every item directory of the run
+    /// (`SnapDual`); the `snap_dual` line of the summary.
+    fn check_dual_snapshots(&self) -> String {
+        let mut item_dirs: Vec<PathBuf> = fs::read_dir(&self.dst_path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.join(TIME_STATES_ZST_FILE_NAME).exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+        item_dirs.sort();
+
+        let (mut n_frames, mut n_matched) = (0, 0);
+        for item_dir in &item_dirs {
+            match writer::compare_dual(item_dir) {
+                Ok((frames, None)) => {
+                    n_frames += frames;
+                    n_matched += 1;
+                }
+                Ok((_, Some(difference))) => {
+                    eprintln!("❌ SnapDual: '{}': {}.", item_dir.display(), difference)
+                }
+                Err(e) => eprintln!("❌ SnapDual: '{}': {}.", item_dir.display(), e),
+            }
+        }
+
+        format!(
+            "snap_dual: TimeStates.txt and TimeStates.zst match in {}/{} item(s) ({} frames)",
+            n_matched,
+            item_dirs.len(),
+            n_frames
+        )
+    }
+
+    fn write_summary(&self) -> Result<()> {
+        let mut lines = self.stats.summary();
+        lines.extend(self.groups.summary(&self.items));
+        lines.extend(self.passages.summary());
+        if self.cfg.preset != "none" {
+            lines.push(format!("preset: {}", self.cfg.preset));
+        }
+        lines.push(format!(
+            "termination: {} (wall-clock {:.1} s)",
+            self.termination.as_str(),
+            self.started.elapsed().as_secs_f64()
+        ));
+        lines.push(format!(
+            "conc_neg_count: {}{}",
+            self.simlog.conc_neg_count,
+            match self.simlog.conc_neg_exceeded {
+                true => format!(" [exceeded ConcNegMax: {}]", self.cfg.conc_neg_max),
+                false => String::new(),
             }
+        ));
+        if self.cfg.snap_dual {
+            lines.push(self.check_dual_snapshots());
+        }
+        if self.write_tuner.is_on {
+            lines.push(format!(
+                "write_i: {} (WriteI: {}, raised {} time(s); last write time share {:.1}%)",
+                self.write_tuner.write_i,
+                self.write_tuner.write_i0,
+                self.write_tuner.n_adjustments,
+                self.write_tuner.last_share * 100.0
+            ));
+        }
+        if self.step_ext.is_on {
+            lines.push(format!(
+                "step_lim: {} (StepLim: {}, extended {} time(s); last growth rate {:.3e} cells/step)",
+                self.step_ext.limit,
+                self.step_ext.limit0,
+                self.step_ext.n_extensions,
+                self.step_ext.last_rate
+            ));
+        }
+        for line in &lines {
+            println!("{}", line);
         }
 
-        self.simlog.write_log_to_file()?;
+        let path_summary = self.dst_path.join(SUMMARY_FILE_NAME);
+        let text = lines.join("\n") + "\n";
+        faults::retry_io(SUMMARY_FILE_NAME, || {
+            faults::inject(FaultPoint::Summary)?;
+            fs::write(&path_summary, &text)
+        })
+        .map_err(|e| format!("Failed to write '{}': {e}", path_summary.display()))?;
 
         Ok(())
     }
 }
+
+/// Writes the final `SimLog.txt` of an item, retrying a failed write; if it still fails
+/// the run goes on, the rows stay in the item `SimLogRec.txt`.
+fn write_item_log(item: &mut Item) {
+    if let Err(e) = faults::retry_io(SIM_LOG_FILE_NAME, || item.simlog.write_log_to_file()) {
+        eprintln!(
+            "[Item ID: {:05}] ⚠️ Warning: Failed to write {}: {}; rebuild it from SimLogRec.txt with `convert --format rows`.",
+            item.item_gid, SIM_LOG_FILE_NAME, e
+        );
+    }
+}
+
+/// Crystal sizes of the alive items (`StepLimMax`).
+fn item_sizes(items: &[Item]) -> Vec<f64> {
+    items.iter().map(|item| item.simlog.n_cryst.val).collect()
+}
+
+/// Grid of an item: the ensemble grid for `grid_id == 0`, otherwise its own item grid.
+fn item_grid<'a>(grid: &'a mut Grid, item_grids: &'a mut [Grid], grid_id: usize) -> &'a mut Grid {
+    match grid_id {
+        0 => grid,
+        id => &mut item_grids[id - 1],
+    }
+}
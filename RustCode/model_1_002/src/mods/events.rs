@@ -0,0 +1,110 @@
+use crate::mods::{constants::EVENTS_FILE_NAME, lattice::Grid};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Accepted kernel event, the `type` column of `Events.xyz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventKind {
+    Attach = 1,
+    Detach = 2,
+    /// Detachment by the ballistic channel (modes 2.2, 2.3).
+    Ballistic = 3,
+    /// The two cells of a surface swap: the one left and the one taken.
+    SwapOut = 4,
+    SwapIn = 5,
+}
+
+/// Accepted events of an item as an extended XYZ trajectory (`Events.xyz`), one frame
+/// per write step with the events since the previous one: OVITO (and ASE) read it as
+/// particles with `Position` (Å, cell centres), `type` (`EventKind`) and `step`, so growth
+/// movies are rendered from the events alone instead of full occupancy volumes.
+///
+/// Events are buffered between write steps, ~16 bytes each.
+#[derive(Debug, Default)]
+pub struct EventExport {
+    pub is_on: bool,
+    events: Vec<(u64, usize, EventKind)>,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl EventExport {
+    pub fn new(is_on: bool) -> Self {
+        Self {
+            is_on,
+            ..Self::default()
+        }
+    }
+
+    #[inline(always)]
+    pub fn record(&mut self, kind: EventKind, idxg: usize, step_id: u64) {
+        if self.is_on {
+            self.events.push((step_id, idxg, kind));
+        }
+    }
+
+    /// Appends the buffered events as the frame of `step_id`; the file is created on the
+    /// first call. `cell` - cell sizes (m) along the grid axes.
+    pub fn write_frame(
+        &mut self,
+        path_dst: &Path,
+        step_id: u64,
+        grid: &Grid,
+        cell: (f64, f64, f64),
+    ) -> IoResult<()> {
+        if self.out_file_buf.is_none() {
+            let path_out_file = path_dst.join(EVENTS_FILE_NAME);
+            self.out_file_buf = Some(BufWriter::new(File::create(&path_out_file).map_err(
+                |e| {
+                    IoError::new(
+                        e.kind(),
+                        format!("Failed to create file '{}': {}", path_out_file.display(), e),
+                    )
+                },
+            )?));
+        }
+        let Some(out_file_buf) = &mut self.out_file_buf else {
+            return Ok(());
+        };
+
+        // Å per cell
+        let (ax, ay, az) = (cell.0 * 1e10, cell.1 * 1e10, cell.2 * 1e10);
+        writeln!(out_file_buf, "{}", self.events.len())?;
+        writeln!(
+            out_file_buf,
+            "Lattice=\"{:.6} 0 0 0 {:.6} 0 0 0 {:.6}\" Properties=pos:R:3:type:I:1:step:I:1 Time={} pbc=\"{} {} {}\"",
+            grid.nx as f64 * ax,
+            grid.ny as f64 * ay,
+            grid.nz as f64 * az,
+            step_id,
+            if grid.px { 'T' } else { 'F' },
+            if grid.py { 'T' } else { 'F' },
+            if grid.pz { 'T' } else { 'F' },
+        )?;
+        for &(event_step, idxg, kind) in &self.events {
+            let (x, y, z) = grid.idx_to_xyz(idxg);
+            writeln!(
+                out_file_buf,
+                "{:.4} {:.4} {:.4} {} {}",
+                (x as f64 + 0.5) * ax,
+                (y as f64 + 0.5) * ay,
+                (z as f64 + 0.5) * az,
+                kind as u8,
+                event_step
+            )?;
+        }
+        self.events.clear();
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match &mut self.out_file_buf {
+            Some(out_file_buf) => out_file_buf.flush(),
+            None => Ok(()),
+        }
+    }
+}
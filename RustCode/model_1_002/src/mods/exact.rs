@@ -0,0 +1,459 @@
+use crate::mods::{
+    constants::K_BOLTZMANN,
+    frontier::Frontier,
+    io_handler,
+    item::Item,
+    lattice::{Grid, Idx, SLOT_AXES},
+    settings::Settings,
+};
+use rand::SeedableRng;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::{error::Error, fs, path::PathBuf};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Cluster-size histogram, site occupations and the (Hastings, one-way) rejections of
+/// `sample_kernel`.
+type KernelSample = (Vec<f64>, Vec<f64>, (u64, u64));
+
+/// Largest grid whose configurations are enumerated (2^N Boltzmann terms).
+const MAX_SITES: usize = 30;
+
+const DEFAULT_DIMS: (usize, usize, usize) = (3, 3, 3);
+const DEFAULT_STEPS: u64 = 10_000_000;
+const DEFAULT_TOL: f64 = 0.01;
+
+/// Entry point of the `exact` subcommand.
+///
+/// Usage: `exact [<config_dir>] [--dims SX,SY,SZ] [--steps N] [--burn N] [--seed S]
+/// [--tol X] [--out <file>]`. Acceptance test of the energetics on a tiny periodic grid
+/// (at most `MAX_SITES` cells): the cluster size distribution `P(n)` and the site
+/// occupations sampled with the mode 2.1 kernel (`Item::mode_2_1_step`, constant `dg`,
+/// see `sample_kernel`) are compared with exact Boltzmann sums
+/// `exp(-(E_surf - n * dg) / kT)` over every non-empty configuration without vacancies.
+/// Energies, `T`, `dg`, `AxisMap`, `DetachModel` and `Acceptance` come from the
+/// `InitSettings.ini` in `config_dir` (default: next to the executable), the grid size
+/// from `--dims`; the grid is always periodic (an open boundary stops the kernel). Fails
+/// when `max |dP(n)|` exceeds `--tol`.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut config_dir: Option<PathBuf> = None;
+    let mut dims = DEFAULT_DIMS;
+    let mut steps = DEFAULT_STEPS;
+    let mut burn: Option<u64> = None;
+    let mut seed = 1;
+    let mut tol = DEFAULT_TOL;
+    let mut out_path: Option<PathBuf> = None;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or(format!("Missing value after '{name}'"))
+        };
+        match arg.as_str() {
+            "--dims" | "-d" => dims = parse_dims(value("--dims")?)?,
+            "--steps" | "-n" => steps = value("--steps")?.parse()?,
+            "--burn" => burn = Some(value("--burn")?.parse()?),
+            "--seed" => seed = value("--seed")?.parse()?,
+            "--tol" => tol = value("--tol")?.parse()?,
+            "--out" | "-o" => out_path = Some(PathBuf::from(value("--out")?)),
+            _ if config_dir.is_none() => config_dir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument '{}'", arg).into()),
+        }
+    }
+
+    let config_dir = match config_dir {
+        Some(dir) => dir,
+        None => io_handler::get_exe_dir()?,
+    };
+    let cfg = io_handler::load_config(&config_dir)
+        .map_err(|e| format!("Failed to load config from {:?}: {e}", config_dir))?
+        .build()?;
+
+    let (sx, sy, sz) = dims;
+    if sx < 2 || sy < 2 || sz < 2 || sx * sy * sz > MAX_SITES {
+        return Err(format!(
+            "Grid {}x{}x{} not supported: every size must be >= 2, at most {} cells",
+            sx, sy, sz, MAX_SITES
+        )
+        .into());
+    }
+
+    let mut grid = Grid::new(sx, sy, sz, true, true, true)?;
+    grid.axis_map = cfg
+        .axis_perm()
+        .ok_or("AxisMap must be a permutation of 'xyz'")?;
+    let n_sites = grid.size;
+
+    let k_t = K_BOLTZMANN * cfg.temperature;
+    let (ex, ey, ez) = (
+        cfg.g100 * cfg.ay * cfg.az,
+        cfg.g010 * cfg.ax * cfg.az,
+        cfg.g001 * cfg.ax * cfg.ay,
+    );
+    let e2 = grid.to_grid_axes((ex * 2.0, ey * 2.0, ez * 2.0));
+
+    println!(
+        "Exact enumeration: {}x{}x{} grid, 2^{} configurations, kT: {:.5e}, dg: {:.5e}, DetachModel: {}",
+        sx, sy, sz, n_sites, k_t, cfg.dg, cfg.detach_model
+    );
+    let (p_exact, occ_exact) = boltzmann_sums(&grid.neibs, e2, cfg.dg, k_t);
+
+    let burn = burn.unwrap_or(steps / 10);
+    println!("MC kernel: {} steps after {} burn-in steps...", steps, burn);
+    let (p_mc, occ_mc, (n_reject, n_one_way)) =
+        sample_kernel(&mut grid, e2, &cfg, k_t, steps, burn, seed)?;
+
+    let mut report = vec![
+        format!("Grid: {}x{}x{} (periodic)", sx, sy, sz),
+        format!(
+            "kT: {:.6e}, dg: {:.6e}, dg/kT: {:.4}",
+            k_t,
+            cfg.dg,
+            cfg.dg / k_t
+        ),
+        format!("DetachModel: {}", cfg.detach_model),
+        format!("Acceptance: {}", cfg.acceptance),
+        format!("MC steps: {} (burn-in {}), seed {}", steps, burn, seed),
+        format!("Detachments of the last cell rejected: {}", n_reject),
+        format!("One-way moves undone: {}", n_one_way),
+        String::new(),
+        format!(
+            "{:>4} | {:>12} | {:>12} | {:>10}",
+            "n", "P_exact", "P_mc", "P_mc - P"
+        ),
+    ];
+
+    let mut max_dp: f64 = 0.0;
+    for n in 1..n_sites {
+        let dp = p_mc[n] - p_exact[n];
+        max_dp = max_dp.max(dp.abs());
+        report.push(format!(
+            "{:>4} | {:>12.6e} | {:>12.6e} | {:>+10.3e}",
+            n, p_exact[n], p_mc[n], dp
+        ));
+    }
+
+    let max_docc = occ_exact
+        .iter()
+        .zip(&occ_mc)
+        .map(|(a, b)| (b - a).abs())
+        .fold(0.0, f64::max);
+    let mean_n = |p: &[f64]| {
+        p.iter()
+            .enumerate()
+            .map(|(n, &w)| n as f64 * w)
+            .sum::<f64>()
+    };
+
+    report.push(String::new());
+    report.push(format!(
+        "<n>: exact {:.6} | mc {:.6}",
+        mean_n(&p_exact),
+        mean_n(&p_mc)
+    ));
+    report.push(format!("max |dP(n)|: {:.3e} (tol {:.3e})", max_dp, tol));
+    report.push(format!("max |d<s_i>|: {:.3e}", max_docc));
+
+    let is_pass = max_dp <= tol;
+    report.push(format!("Result: {}", if is_pass { "PASS" } else { "FAIL" }));
+
+    for line in &report {
+        println!("{}", line);
+    }
+
+    if let Some(out_path) = out_path {
+        fs::write(&out_path, report.join("\n") + "\n")?;
+        println!("✅ Report written to '{}'", out_path.display());
+    }
+
+    match is_pass {
+        true => Ok(()),
+        false => Err(format!("max |dP(n)| = {:.3e} exceeds tol {:.3e}", max_dp, tol).into()),
+    }
+}
+
+fn parse_dims(value: &str) -> Result<(usize, usize, usize)> {
+    let parts = value
+        .split([',', 'x'])
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<std::result::Result<Vec<usize>, _>>()?;
+
+    match parts[..] {
+        [sx, sy, sz] => Ok((sx, sy, sz)),
+        _ => Err(format!("Invalid '--dims' value '{}', expected SX,SY,SZ", value).into()),
+    }
+}
+
+/// Exact `P(n)` and site occupations over all configurations with at least one crystal
+/// cell and no vacancy (a gas cell with six crystal neighbours; this also excludes the
+/// full grid). Surface energy: `e2_a / 2` per crystal neighbour slot along axis `a` that is not
+/// crystal, which is what `attach_energy` changes by one atom at a time. The weights
+/// are rescaled whenever a lower free energy turns up, so nothing overflows.
+fn boltzmann_sums(
+    neibs: &[[Idx; 6]],
+    (ex2, ey2, ez2): (f64, f64, f64),
+    dg: f64,
+    k_t: f64,
+) -> (Vec<f64>, Vec<f64>) {
+    let n_sites = neibs.len();
+    let half_e2 = [ex2 / 2.0, ey2 / 2.0, ez2 / 2.0];
+    let slot_e: Vec<[(u64, f64); 6]> = neibs
+        .iter()
+        .map(|nis| std::array::from_fn(|s| (1u64 << nis[s], half_e2[SLOT_AXES[s]])))
+        .collect();
+
+    let neib_masks: Vec<u64> = neibs
+        .iter()
+        .map(|nis| nis.iter().fold(0, |mask, &n| mask | 1u64 << n))
+        .collect();
+
+    let mut p_n = vec![0.0; n_sites + 1];
+    let mut occ = vec![0.0; n_sites];
+    let mut log_ref = f64::NEG_INFINITY;
+
+    for config in 1..(1u64 << n_sites) - 1 {
+        let has_vacancy = neib_masks
+            .iter()
+            .enumerate()
+            .any(|(i, &mask)| config >> i & 1 == 0 && config & mask == mask);
+        if has_vacancy {
+            continue;
+        }
+
+        let mut energy = 0.0;
+        let mut n = 0;
+        for (i, slots) in slot_e.iter().enumerate() {
+            if config >> i & 1 == 1 {
+                n += 1;
+                for &(bit, half_e) in slots {
+                    if config & bit == 0 {
+                        energy += half_e;
+                    }
+                }
+            }
+        }
+
+        let log_w = -(energy - n as f64 * dg) / k_t;
+        if log_w > log_ref {
+            let scale = (log_ref - log_w).exp();
+            p_n.iter_mut()
+                .chain(occ.iter_mut())
+                .for_each(|v| *v *= scale);
+            log_ref = log_w;
+        }
+
+        let w = (log_w - log_ref).exp();
+        p_n[n] += w;
+        for (i, o) in occ.iter_mut().enumerate() {
+            if config >> i & 1 == 1 {
+                *o += w;
+            }
+        }
+    }
+
+    let z: f64 = p_n.iter().sum();
+    p_n.iter_mut().chain(occ.iter_mut()).for_each(|v| *v /= z);
+
+    (p_n, occ)
+}
+
+/// Drives the real mode 2.1 kernel one attempt per step (attachment or detachment with
+/// probability 1/2) from a single crystal cell and histograms the cluster size and the
+/// site occupations.
+///
+/// The kernel proposes uniformly from the TPA/TPB fronts, whose sizes differ between a
+/// configuration and its neighbour, so a move it accepts is kept only with the
+/// Hastings factor `min(1, q_rev / q_fwd)` (`q = 1 / front size`) and undone otherwise.
+/// With that correction a kernel acceptance obeying `exp(-dF / kT)` samples the
+/// Boltzmann distribution exactly, i.e. the comparison tests the energetics and not
+/// the order of the add/remove events. Moves to the empty grid and the one-way moves of
+/// `is_one_way` are rejected.
+fn sample_kernel(
+    grid: &mut Grid,
+    e2: (f64, f64, f64),
+    cfg: &Settings,
+    k_t: f64,
+    steps: u64,
+    burn: u64,
+    seed: u64,
+) -> Result<KernelSample> {
+    let n_sites = grid.size;
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let dst_dir = std::env::temp_dir().join(format!("mc_exact_{}", std::process::id()));
+    let state = vec![0; n_sites].into_boxed_slice();
+    let mut item = Item::new(0, state, Frontier::new(n_sites), dst_dir.clone())?;
+    item.is_bond_counting = cfg.is_bond_counting();
+    item.acceptance = cfg.acceptance_rule();
+
+    let seed_idx = grid.xyz_to_idx(grid.nx / 2, grid.ny / 2, grid.nz / 2);
+    item.state[seed_idx] = 1;
+    item.faces.attach((0, 0, 0));
+    item.front.tpb_add(seed_idx);
+    for &neib_idx in &grid.neibs[seed_idx] {
+        item.front.tpa_add(neib_idx as usize);
+    }
+    item.simlog.initialize(
+        k_t,
+        (2, 1),
+        cfg.dg,
+        cfg.c_eq,
+        cfg.c0,
+        cfg.n_tot,
+        1.0,
+        cfg.p_b,
+        cfg.p_pow,
+    );
+    // Mode 2.x derives `dg` from the concentration; here it stays at the `dg` setting.
+    item.simlog.dg.val = cfg.dg;
+
+    let mut hist = vec![0u64; n_sites + 1];
+    let mut occ = vec![0u64; n_sites];
+    let mut n_reject = 0;
+    let mut n_one_way = 0;
+
+    for step_id in 1..=burn + steps {
+        let is_add = rng.random::<bool>();
+        let n_cryst = item.simlog.n_cryst.val;
+
+        if !is_add && n_cryst as usize == 1 {
+            n_reject += 1;
+        } else {
+            let (saved_state, saved_front) = (item.state.clone(), item.front.clone());
+            let saved_n_gas = item.simlog.n_gas.val;
+            let q_fwd = match is_add {
+                true => item.front.tpas_size,
+                false => item.front.tpbs_size,
+            } as f64;
+
+            let flags = (is_add, !is_add, false, false);
+            if !item.mode_2_1_step(&mut rng, grid, e2, 0, step_id, flags) {
+                let _ = fs::remove_dir_all(&dst_dir);
+                return Err(format!("Kernel stopped at step {}", step_id).into());
+            }
+
+            if item.simlog.n_cryst.val != n_cryst {
+                let q_rev = match is_add {
+                    true => item.front.tpbs_size,
+                    false => item.front.tpas_size,
+                } as f64;
+                let idxg = (0..n_sites).find(|&i| item.state[i] != saved_state[i]);
+                let is_one_way = idxg.is_some_and(|i| is_one_way(&grid.neibs, &item.state, i));
+                if is_one_way {
+                    n_one_way += 1;
+                }
+                if is_one_way || q_fwd / q_rev < rng.random::<f64>() {
+                    item.state = saved_state;
+                    item.front = saved_front;
+                    item.simlog.n_cryst.val = n_cryst;
+                    item.simlog.n_gas.val = saved_n_gas;
+                }
+            }
+        }
+
+        if step_id > burn {
+            hist[item.simlog.n_cryst.val as usize] += 1;
+            for (o, &s) in occ.iter_mut().zip(item.state.iter()) {
+                *o += s as u64;
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dst_dir);
+
+    let n_cryst_check = item.state.iter().filter(|&&s| s == 1).count();
+    if n_cryst_check != item.simlog.n_cryst.val as usize {
+        return Err(format!(
+            "n_cryst bookkeeping ({}) differs from the state ({})",
+            item.simlog.n_cryst.val, n_cryst_check
+        )
+        .into());
+    }
+
+    let total = steps.max(1) as f64;
+    Ok((
+        hist.iter().map(|&h| h as f64 / total).collect(),
+        occ.iter().map(|&o| o as f64 / total).collect(),
+        (n_reject, n_one_way),
+    ))
+}
+
+/// Moves of the kernel without a reverse move, checked after the change of `idxg`:
+/// detaching a particle without crystal neighbours (attachment needs one) and an
+/// attachment that encloses a vacancy (a fully bonded particle never detaches, so the
+/// vacancy could only be filled). Configurations with vacancies are therefore left out
+/// on both sides of the comparison.
+fn is_one_way(neibs: &[[Idx; 6]], state: &[u8], idxg: usize) -> bool {
+    let mut neib_idxs = neibs[idxg].iter().map(|&n| n as usize);
+    match state[idxg] {
+        0 => neib_idxs.all(|n| state[n] == 0),
+        _ => neib_idxs.any(|n| state[n] == 0 && neibs[n].iter().all(|&m| state[m as usize] == 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn boltzmann_sums_of_a_cube_match_the_counted_pairs() {
+        // The periodic 2x2x2 grid is a cube: a cell sees its one neighbour per axis in
+        // both slots. kT = 1, dg = 0, half slot energies a, b, c.
+        let grid = Grid::new(2, 2, 2, true, true, true).unwrap();
+        let (a, b, c) = (0.1, 0.2, 0.3);
+        let (p_n, occ) = boltzmann_sums(&grid.neibs, (2.0 * a, 2.0 * b, 2.0 * c), 0.0, 1.0);
+
+        // 8 single cells with all 6 slots open; 4 pairs bonded along each axis (2 slots
+        // closed per cell) and 16 unbonded pairs.
+        let w1 = 8.0 * (-2.0 * (a + b + c)).exp();
+        let w2 = 4.0 * (-4.0 * (b + c)).exp()
+            + 4.0 * (-4.0 * (a + c)).exp()
+            + 4.0 * (-4.0 * (a + b)).exp()
+            + 16.0 * (-4.0 * (a + b + c)).exp();
+        assert_close(p_n[2] / p_n[1], w2 / w1);
+        assert_eq!((p_n[0], p_n[8]), (0.0, 0.0));
+        assert_close(p_n.iter().sum(), 1.0);
+
+        // Every cell is equivalent; the occupations add up to <n>.
+        let mean_n: f64 = p_n.iter().enumerate().map(|(n, &p)| n as f64 * p).sum();
+        occ.iter().for_each(|&o| assert_close(o, occ[0]));
+        assert_close(occ.iter().sum(), mean_n);
+    }
+
+    #[test]
+    fn without_energies_the_configurations_are_counted() {
+        // With no surface energy and dg = 0 every allowed configuration weighs 1: 8 of
+        // one cell, 28 of two, 56 of three less the 8 that enclose a vacancy.
+        let grid = Grid::new(2, 2, 2, true, true, true).unwrap();
+        let (p_n, _) = boltzmann_sums(&grid.neibs, (0.0, 0.0, 0.0), 0.0, 1.0);
+        assert_close(p_n[2] / p_n[1], 28.0 / 8.0);
+        assert_close(p_n[3] / p_n[1], 48.0 / 8.0);
+        // Seven cells always enclose the eighth.
+        assert_eq!(p_n[7], 0.0);
+    }
+
+    #[test]
+    fn one_way_moves_are_told_apart() {
+        let grid = Grid::new(2, 2, 2, true, true, true).unwrap();
+        let mut state = vec![0u8; grid.size];
+        let cell = grid.xyz_to_idx(0, 0, 0);
+        state[cell] = 1;
+        // (1,1,1) was left by a particle without crystal neighbours: one way. (0,0,0)
+        // was attached next to no enclosed gas cell: it can detach again.
+        assert!(is_one_way(&grid.neibs, &state, grid.xyz_to_idx(1, 1, 1)));
+        assert!(!is_one_way(&grid.neibs, &state, cell));
+
+        // (1,1,0) is attached as the last of the three neighbours of (1,1,1).
+        for (x, y, z) in [(0, 1, 1), (1, 0, 1), (1, 1, 0)] {
+            state[grid.xyz_to_idx(x, y, z)] = 1;
+        }
+        assert!(is_one_way(&grid.neibs, &state, grid.xyz_to_idx(1, 1, 0)));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::mods::{
+    constants::FACET_STATS_FILE_NAME,
+    faults::{self, FaultPoint},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::PathBuf,
+};
+
+/// Site classes: a single interface normal along grid axis x, y or z (a flat facet site),
+/// or several normals (edges, kinks, adatoms).
+const FACET_CLASSES: usize = 4;
+const FACET_FAMILY_NAMES: [&str; 3] = ["100", "010", "001"];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FacetCounts {
+    add_att: u64,
+    add_acc: u64,
+    rem_att: u64,
+    rem_acc: u64,
+}
+
+/// Attachment/detachment statistics per facet orientation of the attempted site.
+///
+/// The orientation comes from the per-axis crystal neighbour counts, as in
+/// `is_face_masked`: normals of a gas site point to its crystal neighbours, normals of
+/// a crystal site to its missing ones. Items count their attempts, the ensemble-level
+/// instance collects them every step and writes one line per write step with the
+/// acceptance ratios and the net growth rate (particles per step) of every class.
+#[derive(Debug, Default)]
+pub struct FacetStats {
+    pub is_on: bool,
+    counts: [FacetCounts; FACET_CLASSES],
+    last_step: u64,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl FacetStats {
+    pub fn new(is_on: bool) -> Self {
+        Self {
+            is_on,
+            ..Self::default()
+        }
+    }
+
+    /// Creates `FacetStats.txt` with a header naming the classes by crystal face family
+    /// (`axis_map` - grid axis of [100], [010], [001]).
+    pub fn create_out_file(&mut self, path_dst: PathBuf, axis_map: [usize; 3]) -> IoResult<()> {
+        let path_out_file = path_dst.join(FACET_STATS_FILE_NAME);
+
+        let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        let mut labels: Vec<String> = (0..3)
+            .map(|axis| {
+                let family = axis_map.iter().position(|&a| a == axis).unwrap_or(axis);
+                format!("{{{}}}", FACET_FAMILY_NAMES[family])
+            })
+            .collect();
+        labels.push("other".to_string());
+
+        let header = labels
+            .iter()
+            .map(|l| format!("{l}_add_att:{l}_add_acc:{l}_rem_acc:{l}_rate"))
+            .collect::<Vec<_>>()
+            .join(":");
+        writeln!(out_file_buf, "# step:{}", header)?;
+
+        self.out_file_buf = Some(out_file_buf);
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn class((smx_yz, smy_xz, smz_xy): (u8, u8, u8), attach: bool) -> usize {
+        let is_open = |sm: u8| if attach { sm > 0 } else { sm < 2 };
+        match (is_open(smx_yz), is_open(smy_xz), is_open(smz_xy)) {
+            (true, false, false) => 0,
+            (false, true, false) => 1,
+            (false, false, true) => 2,
+            _ => 3,
+        }
+    }
+
+    #[inline(always)]
+    pub fn record(&mut self, sums: (u8, u8, u8), attach: bool, is_accepted: bool) {
+        if !self.is_on {
+            return;
+        }
+
+        let counts = &mut self.counts[Self::class(sums, attach)];
+        match attach {
+            true => {
+                counts.add_att += 1;
+                counts.add_acc += is_accepted as u64;
+            }
+            false => {
+                counts.rem_att += 1;
+                counts.rem_acc += is_accepted as u64;
+            }
+        }
+    }
+
+    /// Moves the counts of `item_stats` here.
+    pub fn collect(&mut self, item_stats: &mut FacetStats) {
+        if !self.is_on {
+            return;
+        }
+
+        for (total, item_counts) in self.counts.iter_mut().zip(item_stats.counts.iter_mut()) {
+            let c = std::mem::take(item_counts);
+            total.add_att += c.add_att;
+            total.add_acc += c.add_acc;
+            total.rem_att += c.rem_att;
+            total.rem_acc += c.rem_acc;
+        }
+    }
+
+    /// Writes `step` and, per class, `add_att:add_acc:rem_acc:rate` since the previous
+    /// write: attachment attempts, acceptance ratios of attachment and detachment and
+    /// `(accepted add - accepted rem) / steps`; then resets the counts.
+    pub fn write_step(&mut self, step_id: u64) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+
+        let steps = step_id.saturating_sub(self.last_step).max(1) as f64;
+        self.last_step = step_id;
+
+        let ratio = |acc: u64, att: u64| match att {
+            0 => 0.0,
+            _ => acc as f64 / att as f64,
+        };
+        let line = self
+            .counts
+            .iter()
+            .map(|c| {
+                format!(
+                    "{}:{:.5e}:{:.5e}:{:.5e}",
+                    c.add_att,
+                    ratio(c.add_acc, c.add_att),
+                    ratio(c.rem_acc, c.rem_att),
+                    (c.add_acc as f64 - c.rem_acc as f64) / steps
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(":");
+        self.counts = Default::default();
+
+        if let Some(fbuf) = self.out_file_buf.as_mut() {
+            faults::inject(FaultPoint::Stats)?;
+            writeln!(fbuf, "{}:{}", step_id, line)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match self.out_file_buf.as_mut() {
+            Some(fbuf) => fbuf.flush(),
+            None => Ok(()),
+        }
+    }
+}
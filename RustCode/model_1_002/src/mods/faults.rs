@@ -0,0 +1,165 @@
+use std::{io::Result as IoResult, thread, time::Duration};
+
+/// IO operations that can be made to fail with the `fault-inject` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    /// A frame of an item `TimeStates.txt`.
+    Snapshot,
+    /// A line of `SimLogRec.txt`.
+    Record,
+    /// The final `SimLog.txt` of an item or of the ensemble.
+    Log,
+    /// `Checkpoint.txt`.
+    Checkpoint,
+    /// A line of `FacetStats.txt`, `KosselCheck.txt` or `RngAudit.txt`.
+    Stats,
+    /// `Summary.txt`.
+    Summary,
+}
+
+const RETRY_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Runs `op` up to `RETRY_ATTEMPTS` times, waiting `RETRY_DELAY` between the attempts,
+/// for the writes that must not be lost (the final logs): a full scratch disk often
+/// frees up while another job finishes.
+pub fn retry_io<T>(what: &str, mut op: impl FnMut() -> IoResult<T>) -> IoResult<T> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < RETRY_ATTEMPTS => {
+                eprintln!(
+                    "⚠️ Warning: Failed to write {} (attempt {}/{}): {}; retrying in {:?}.",
+                    what, attempt, RETRY_ATTEMPTS, e, RETRY_DELAY
+                );
+                thread::sleep(RETRY_DELAY);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Switches an optional stats file off after a failed write, so the run goes on
+/// without it.
+pub fn disable_on_error(written: IoResult<()>, what: &str, is_on: &mut bool) {
+    if let Err(e) = written {
+        eprintln!(
+            "⚠️ Warning: Failed to write {}: {}; it is switched off for the rest of the run.",
+            what, e
+        );
+        *is_on = false;
+    }
+}
+
+#[cfg(not(feature = "fault-inject"))]
+#[inline(always)]
+pub fn inject(_point: FaultPoint) -> IoResult<()> {
+    Ok(())
+}
+
+#[cfg(feature = "fault-inject")]
+pub use injector::inject;
+
+#[cfg(feature = "fault-inject")]
+mod injector {
+    use super::FaultPoint;
+    use std::{
+        io::{Error as IoError, ErrorKind, Result as IoResult},
+        sync::{Mutex, OnceLock},
+    };
+
+    /// Variable with the faults to inject.
+    const FAULTS_ENV: &str = "MC_FAULTS";
+
+    #[derive(Debug)]
+    struct Fault {
+        point: FaultPoint,
+        from_hit: u64,
+        kind: ErrorKind,
+        is_once: bool,
+        hits: u64,
+    }
+
+    fn parse_point(name: &str) -> Option<FaultPoint> {
+        Some(match name {
+            "snapshot" => FaultPoint::Snapshot,
+            "record" => FaultPoint::Record,
+            "log" => FaultPoint::Log,
+            "checkpoint" => FaultPoint::Checkpoint,
+            "stats" => FaultPoint::Stats,
+            "summary" => FaultPoint::Summary,
+            _ => return None,
+        })
+    }
+
+    /// `MC_FAULTS=<point>@<n>[:full|denied][:once],...`: the `n`-th (1-based) operation
+    /// at `<point>` and all later ones fail (only the `n`-th with `once`) with "no space
+    /// left on device" (`full`, default) or "permission denied" (`denied`).
+    fn parse_faults(spec: &str) -> Vec<Fault> {
+        let mut faults = Vec::new();
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = item.split(':');
+            let parsed = parts.next().and_then(|head| {
+                let (point, n) = head.split_once('@')?;
+                Some((parse_point(point)?, n.parse::<u64>().ok()?.max(1)))
+            });
+            let Some((point, from_hit)) = parsed else {
+                eprintln!(
+                    "⚠️ Warning: Ignoring malformed {} entry '{}'",
+                    FAULTS_ENV, item
+                );
+                continue;
+            };
+
+            let mut fault = Fault {
+                point,
+                from_hit,
+                kind: ErrorKind::StorageFull,
+                is_once: false,
+                hits: 0,
+            };
+            for flag in parts {
+                match flag {
+                    "full" => fault.kind = ErrorKind::StorageFull,
+                    "denied" => fault.kind = ErrorKind::PermissionDenied,
+                    "once" => fault.is_once = true,
+                    _ => eprintln!("⚠️ Warning: Ignoring {} flag '{}'", FAULTS_ENV, flag),
+                }
+            }
+            faults.push(fault);
+        }
+
+        if !faults.is_empty() {
+            println!("🧪 Fault injection: {:?}", faults);
+        }
+        faults
+    }
+
+    fn faults() -> &'static Mutex<Vec<Fault>> {
+        static FAULTS: OnceLock<Mutex<Vec<Fault>>> = OnceLock::new();
+        FAULTS.get_or_init(|| {
+            Mutex::new(parse_faults(&std::env::var(FAULTS_ENV).unwrap_or_default()))
+        })
+    }
+
+    /// Fails the operation at `point` when a configured fault is due.
+    pub fn inject(point: FaultPoint) -> IoResult<()> {
+        let mut faults = faults().lock().unwrap_or_else(|e| e.into_inner());
+        for fault in faults.iter_mut().filter(|f| f.point == point) {
+            fault.hits += 1;
+            let is_due = match fault.is_once {
+                true => fault.hits == fault.from_hit,
+                false => fault.hits >= fault.from_hit,
+            };
+            if is_due {
+                return Err(IoError::new(
+                    fault.kind,
+                    format!("injected fault at {:?} (operation {})", point, fault.hits),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::mods::{io_handler, state_header::StateHeader};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const DEFAULT_SEED: u64 = 1012;
+
+/// Crystal region of a fixture; a state is the union of all the given shapes.
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    /// Ball of radius `r` (cells) around `center`, the grid centre by default.
+    Sphere { r: f64, center: Option<[f64; 3]> },
+    /// Layers `lo..=hi` across `axis`.
+    Slab { axis: usize, lo: usize, hi: usize },
+    /// Box `X0:X1:Y0:Y1:Z0:Z1`, bounds inclusive.
+    Box([(usize, usize); 3]),
+    /// Every cell crystal with probability `p`.
+    Random { p: f64 },
+}
+
+impl Shape {
+    fn contains(&self, xyz: [usize; 3], dims: [usize; 3], rng: &mut ChaCha8Rng) -> bool {
+        match *self {
+            Self::Sphere { r, center } => {
+                let center = center.unwrap_or(dims.map(|n| (n as f64 - 1.0) / 2.0));
+                let d2: f64 = (0..3).map(|a| (xyz[a] as f64 - center[a]).powi(2)).sum();
+                d2 <= r * r
+            }
+            Self::Slab { axis, lo, hi } => (lo..=hi).contains(&xyz[axis]),
+            Self::Box(bounds) => (0..3).all(|a| (bounds[a].0..=bounds[a].1).contains(&xyz[a])),
+            Self::Random { p } => rng.random::<f64>() < p,
+        }
+    }
+}
+
+/// Entry point of the `fixture` subcommand.
+///
+/// Usage: `fixture <out file> --dims SX,SY,SZ [--sphere R [--center X,Y,Z]]
+/// [--slab x|y|z:LO:HI] [--box X0:X1:Y0:Y1:Z0:Z1] [--random P] [--count N] [--seed S]`.
+/// Writes `N` (1 by default) synthetic states in the `InitStates.ini` format, with the
+/// `#states` header, for integration tests and tutorials: a cell is crystal if it lies
+/// in any of the shapes (each option may repeat). `--center` applies to the last
+/// `--sphere`; `--random` states differ per state and are reproducible by `--seed`.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: fixture <out file> --dims SX,SY,SZ [--sphere R [--center X,Y,Z]] [--slab x|y|z:LO:HI] [--box X0:X1:Y0:Y1:Z0:Z1] [--random P] [--count N] [--seed S]";
+
+    let mut out_path: Option<PathBuf> = None;
+    let mut dims: Option<(usize, usize, usize)> = None;
+    let mut shapes: Vec<Shape> = Vec::new();
+    let mut count = 1;
+    let mut seed = DEFAULT_SEED;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| format!("Missing value after '{}'", name))
+        };
+        match arg.as_str() {
+            "--dims" | "-d" => dims = Some(parse_dims(value("--dims")?)?),
+            "--sphere" => {
+                let r: f64 = value("--sphere")?.parse()?;
+                if r.is_nan() || r < 0.0 {
+                    return Err(format!("Invalid '--sphere' radius {}", r).into());
+                }
+                shapes.push(Shape::Sphere { r, center: None });
+            }
+            "--center" => {
+                let center = parse_floats(value("--center")?, "--center")?;
+                match shapes.last_mut() {
+                    Some(Shape::Sphere { center: c, .. }) => *c = Some(center),
+                    _ => return Err("'--center' must follow '--sphere'".into()),
+                }
+            }
+            "--slab" => shapes.push(parse_slab(value("--slab")?)?),
+            "--box" => shapes.push(parse_box(value("--box")?)?),
+            "--random" => {
+                let p: f64 = value("--random")?.parse()?;
+                if !(0.0..=1.0).contains(&p) {
+                    return Err(format!("Invalid '--random' density {}, expected [0, 1]", p).into());
+                }
+                shapes.push(Shape::Random { p });
+            }
+            "--count" | "-n" => count = value("--count")?.parse()?,
+            "--seed" => seed = value("--seed")?.parse()?,
+            _ if out_path.is_none() => out_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, usage).into()),
+        }
+    }
+
+    let (Some(out_path), Some((sx, sy, sz))) = (out_path, dims) else {
+        return Err(usage.into());
+    };
+    if sx == 0 || sy == 0 || sz == 0 {
+        return Err("'--dims' sizes must be > 0".into());
+    }
+    if shapes.is_empty() {
+        println!("⚠️ No shapes given, the states are empty.");
+    }
+
+    let dims = [sx, sy, sz];
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut out_file_buf = BufWriter::new(
+        File::create(&out_path)
+            .map_err(|e| format!("Failed to create file '{}': {}", out_path.display(), e))?,
+    );
+    writeln!(out_file_buf, "{}", StateHeader::new((sx, sy, sz)))?;
+
+    let mut state = vec![0u8; sx * sy * sz];
+    let mut n_cryst_total = 0;
+    for _ in 0..count {
+        // Cell order of `Grid::xyz_to_idx`: z fastest, then y, then x.
+        let mut idx = 0;
+        for x in 0..sx {
+            for y in 0..sy {
+                for z in 0..sz {
+                    let xyz = [x, y, z];
+                    // Every shape is evaluated, so the draws of `--random` do not depend
+                    // on the other shapes.
+                    let is_cryst = shapes.iter().fold(false, |acc, shape| {
+                        shape.contains(xyz, dims, &mut rng) | acc
+                    });
+                    state[idx] = is_cryst as u8;
+                    idx += 1;
+                }
+            }
+        }
+        n_cryst_total += state.iter().filter(|&&cell| cell == 1).count();
+        io_handler::write_frame(&mut out_file_buf, &state)?;
+    }
+    out_file_buf.flush()?;
+
+    println!(
+        "✅ Wrote {} state(s) of {}x{}x{} to '{}' (mean n_cryst: {:.1})",
+        count,
+        sx,
+        sy,
+        sz,
+        out_path.display(),
+        n_cryst_total as f64 / count.max(1) as f64
+    );
+
+    Ok(())
+}
+
+fn parse_dims(value: &str) -> Result<(usize, usize, usize)> {
+    let parts = value
+        .split([',', 'x', ':'])
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<std::result::Result<Vec<usize>, _>>()?;
+
+    match parts[..] {
+        [sx, sy, sz] => Ok((sx, sy, sz)),
+        _ => Err(format!("Invalid '--dims' value '{}', expected SX,SY,SZ", value).into()),
+    }
+}
+
+fn parse_floats(value: &str, name: &str) -> Result<[f64; 3]> {
+    let parts = value
+        .split([',', ':'])
+        .map(|s| s.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()?;
+
+    match parts[..] {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(format!("Invalid '{}' value '{}', expected X,Y,Z", name, value).into()),
+    }
+}
+
+/// `x|y|z:LO:HI` of `--slab`.
+fn parse_slab(value: &str) -> Result<Shape> {
+    let invalid = || format!("Invalid '--slab' value '{}', expected x|y|z:LO:HI", value);
+    let parts: Vec<&str> = value.split(':').map(str::trim).collect();
+    let [axis, lo, hi] = parts[..] else {
+        return Err(invalid().into());
+    };
+    let axis = ["x", "y", "z"]
+        .iter()
+        .position(|&a| a == axis)
+        .ok_or_else(invalid)?;
+    let (lo, hi): (usize, usize) = (
+        lo.parse().map_err(|_| invalid())?,
+        hi.parse().map_err(|_| invalid())?,
+    );
+    if lo > hi {
+        return Err(invalid().into());
+    }
+
+    Ok(Shape::Slab { axis, lo, hi })
+}
+
+/// `X0:X1:Y0:Y1:Z0:Z1` of `--box`.
+fn parse_box(value: &str) -> Result<Shape> {
+    let invalid = || {
+        format!(
+            "Invalid '--box' value '{}', expected X0:X1:Y0:Y1:Z0:Z1",
+            value
+        )
+    };
+    let bounds = value
+        .split(':')
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<std::result::Result<Vec<usize>, _>>()
+        .map_err(|_| invalid())?;
+
+    match bounds[..] {
+        [x0, x1, y0, y1, z0, z1] if x0 <= x1 && y0 <= y1 && z0 <= z1 => {
+            Ok(Shape::Box([(x0, x1), (y0, y1), (z0, z1)]))
+        }
+        _ => Err(invalid().into()),
+    }
+}
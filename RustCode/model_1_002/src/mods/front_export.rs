@@ -0,0 +1,99 @@
+use crate::mods::{
+    constants::{BIN_FORMAT_VERSION, FRONT_BIN_MAGIC, FRONT_FILE_NAME},
+    frontier::Frontier,
+    lattice::{Grid, NO_NEIB},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write},
+    path::Path,
+};
+
+/// TPB sites of an item as a binary table (`Front.bin`), one frame per write step, as
+/// features for growth surrogate models (`FrontExport`).
+///
+/// Layout, little-endian: `MCFR`, format version (u32) and the grid size `nx, ny, nz`
+/// (u32 each); then per frame the step (u64), the site count `n` (u32) and `n` records
+/// of 7 bytes: `x, y, z` (u16, grid axes) and the neighbour signature (u8), bit `i` set
+/// when neighbour `i` of `-x, +x, -y, +y, -z, +z` is crystal (a neighbour past a
+/// non-periodic border is gas). Sites are sorted by grid index. A frame cut by a crash
+/// is the shorter tail of the file; readers drop it.
+#[derive(Debug, Default)]
+pub struct FrontExport {
+    pub is_on: bool,
+    sites: Vec<usize>,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl FrontExport {
+    pub fn new(is_on: bool) -> Self {
+        Self {
+            is_on,
+            ..Self::default()
+        }
+    }
+
+    /// Appends the TPB sites of `front` as the frame of `step_id`; the file is created
+    /// on the first call.
+    pub fn write_frame(
+        &mut self,
+        path_dst: &Path,
+        step_id: u64,
+        state: &[u8],
+        front: &Frontier,
+        grid: &Grid,
+    ) -> IoResult<()> {
+        if self.out_file_buf.is_none() {
+            if [grid.nx, grid.ny, grid.nz].iter().any(|&n| n > 1 << 16) {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "grid sides above 65536 cells do not fit the u16 coordinates",
+                ));
+            }
+            let path_out_file = path_dst.join(FRONT_FILE_NAME);
+            let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("Failed to create file '{}': {}", path_out_file.display(), e),
+                )
+            })?);
+            out_file_buf.write_all(FRONT_BIN_MAGIC)?;
+            out_file_buf.write_all(&BIN_FORMAT_VERSION.to_le_bytes())?;
+            for n in [grid.nx, grid.ny, grid.nz] {
+                out_file_buf.write_all(&(n as u32).to_le_bytes())?;
+            }
+            self.out_file_buf = Some(out_file_buf);
+        }
+        let Some(out_file_buf) = &mut self.out_file_buf else {
+            return Ok(());
+        };
+
+        self.sites.clear();
+        self.sites.extend_from_slice(&front.tpbs);
+        self.sites.sort_unstable();
+
+        out_file_buf.write_all(&step_id.to_le_bytes())?;
+        out_file_buf.write_all(&(self.sites.len() as u32).to_le_bytes())?;
+        for &idxg in &self.sites {
+            let (x, y, z) = grid.idx_to_xyz(idxg);
+            let signature = grid.neibs[idxg]
+                .iter()
+                .enumerate()
+                .filter(|&(_, &neib)| neib as usize != NO_NEIB && state[neib as usize] == 1)
+                .fold(0u8, |signature, (i, _)| signature | 1 << i);
+            for coord in [x, y, z] {
+                out_file_buf.write_all(&(coord as u16).to_le_bytes())?;
+            }
+            out_file_buf.write_all(&[signature])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match &mut self.out_file_buf {
+            Some(out_file_buf) => out_file_buf.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::mods::{
+    constants::GROUP_STATS_FILE_NAME,
+    faults::{self, FaultPoint},
+    item::Item,
+    utils::RunningStats,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::PathBuf,
+};
+
+/// Group of the items beyond the `ItemGroupsFile` table.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// Alive items, crystal size and roughness (TPB sites per crystal site) of one group.
+#[derive(Debug, Default)]
+struct GroupSample {
+    alive: usize,
+    n_cryst: RunningStats,
+    roughness: RunningStats,
+}
+
+/// Item groups of a stratified ensemble (`ItemGroupsFile`), e.g. the items with
+/// impurities against those without within one run.
+///
+/// Every item takes the tag (no ':' or spaces) of its line of the table, the items beyond
+/// it the group `default`. At each write step one line of `GroupStats.txt` gives, for
+/// all the alive items and then for each group in the order of first appearance, the
+/// alive items and the mean and variance over them of the crystal size and the
+/// roughness; `Summary.txt` gets the final values per group. Not available with
+/// `Ranks > 1` or `ResumeFrom`.
+#[derive(Debug, Default)]
+pub struct ItemGroups {
+    pub is_on: bool,
+    pub names: Vec<String>,
+    /// Group of every item by `item_gid`.
+    of_item: Vec<usize>,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl ItemGroups {
+    /// Groups of `n_items` items from their `tags` (by `item_gid`; the remaining items
+    /// go to `DEFAULT_GROUP`). Off without tags.
+    pub fn new(tags: &[String], n_items: usize) -> Self {
+        if tags.is_empty() {
+            return Self::default();
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        let of_item = (0..n_items)
+            .map(|item_gid| {
+                let tag = tags.get(item_gid).map_or(DEFAULT_GROUP, String::as_str);
+                match names.iter().position(|name| name == tag) {
+                    Some(group) => group,
+                    None => {
+                        names.push(tag.to_string());
+                        names.len() - 1
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            is_on: true,
+            names,
+            of_item,
+            out_file_buf: None,
+        }
+    }
+
+    pub fn create_out_file(&mut self, path_dst: PathBuf) -> IoResult<()> {
+        let path_out_file = path_dst.join(GROUP_STATS_FILE_NAME);
+
+        let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        let header = std::iter::once("all")
+            .chain(self.names.iter().map(String::as_str))
+            .map(|g| {
+                format!("{g}_alive:{g}_n_cryst_mean:{g}_n_cryst_var:{g}_rough_mean:{g}_rough_var")
+            })
+            .collect::<Vec<_>>()
+            .join(":");
+        writeln!(out_file_buf, "# step:{}", header)?;
+
+        self.out_file_buf = Some(out_file_buf);
+
+        Ok(())
+    }
+
+    /// All the alive `items` first, then every group.
+    fn samples(&self, items: &[Item]) -> Vec<GroupSample> {
+        let mut samples: Vec<GroupSample> = (0..=self.names.len())
+            .map(|_| GroupSample::default())
+            .collect();
+        for item in items.iter().filter(|item| item.is_alive) {
+            let n_cryst = item.simlog.n_cryst.val;
+            let group = 1 + self.of_item.get(item.item_gid).copied().unwrap_or(0);
+            for id in [0, group] {
+                let sample = &mut samples[id];
+                sample.alive += 1;
+                sample.n_cryst.push(n_cryst);
+                if n_cryst > 0.0 {
+                    sample.roughness.push(item.front.tpbs_size as f64 / n_cryst);
+                }
+            }
+        }
+        samples
+    }
+
+    pub fn write_step(&mut self, step_id: u64, items: &[Item]) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+
+        let var = |stats: &RunningStats| stats.std().powi(2);
+        let line = self
+            .samples(items)
+            .iter()
+            .map(|s| {
+                format!(
+                    "{}:{:.5e}:{:.5e}:{:.5e}:{:.5e}",
+                    s.alive,
+                    s.n_cryst.mean,
+                    var(&s.n_cryst),
+                    s.roughness.mean,
+                    var(&s.roughness)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(":");
+
+        if let Some(fbuf) = self.out_file_buf.as_mut() {
+            faults::inject(FaultPoint::Stats)?;
+            writeln!(fbuf, "{}:{}", step_id, line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Final `Summary.txt` lines, one per group.
+    pub fn summary(&self, items: &[Item]) -> Vec<String> {
+        if !self.is_on {
+            return Vec::new();
+        }
+
+        let samples = self.samples(items);
+        self.names
+            .iter()
+            .zip(&samples[1..])
+            .enumerate()
+            .map(|(group, (name, s))| {
+                let total = self.of_item.iter().filter(|&&g| g == group).count();
+                format!(
+                    "group {}: {}/{} item(s) alive, n_cryst {:.6e} +- {:.3e} (std), roughness {:.6e} +- {:.3e} (std)",
+                    name,
+                    s.alive,
+                    total,
+                    s.n_cryst.mean,
+                    s.n_cryst.std(),
+                    s.roughness.mean,
+                    s.roughness.std()
+                )
+            })
+            .collect()
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match self.out_file_buf.as_mut() {
+            Some(fbuf) => fbuf.flush(),
+            None => Ok(()),
+        }
+    }
+}
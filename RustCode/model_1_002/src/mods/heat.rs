@@ -0,0 +1,158 @@
+use crate::mods::{constants::K_BOLTZMANN, lattice::Grid};
+
+/// Lowest local temperature, as a share of the bath temperature: a cell cooled below it
+/// by detachments is taken at this temperature.
+const T_FLOOR: f64 = 1e-3;
+
+/// Latent heat of the growth front (`HeatRelease`, `HeatCell`, `HeatDiff`, `HeatDecay`).
+///
+/// The grid is split into coarse cells of `cell^3` lattice cells, each holding its
+/// temperature excess over the bath. Every attachment warms its coarse cell by `release`
+/// (K) and every detachment cools it by as much; ballistic removals take no heat. Between
+/// steps the excess diffuses to the 6 neighbouring coarse cells with the diffusion number
+/// `diff` (coarse cells^2 per step; periodic along the periodic grid axes, no flux
+/// through the others) and relaxes to the bath at the rate `decay` per step - the explicit
+/// scheme, stable for `6 * diff + decay <= 1`. The attachment and detachment tests of a
+/// site take the local `kT`; `dg` stays that of the bath.
+///
+/// The mean and the largest temperature of the field are the `heat_t_mean` and
+/// `heat_t_max` rows of `SimLog`, the largest overheat is printed at the end. The field
+/// is not checkpointed, so `HeatRelease` excludes `ResumeFrom`.
+#[derive(Debug, Default)]
+pub struct HeatField {
+    pub release: f64,
+    pub cell: usize,
+    pub diff: f64,
+    pub decay: f64,
+    /// `(ny, nz)` of the lattice and the coarse dimensions with their periodicity.
+    grid_yz: (usize, usize),
+    dims: [usize; 3],
+    periodic: [bool; 3],
+    /// Temperature excess (K) per coarse cell, `cz + (cy + cx * ncy) * ncz`.
+    excess: Vec<f64>,
+    scratch: Vec<f64>,
+    /// Largest local temperature excess reached so far.
+    pub peak: f64,
+    /// Tests taken at the temperature floor.
+    pub floored: u64,
+}
+
+impl HeatField {
+    pub fn new(release: f64, cell: usize, diff: f64, decay: f64, grid: &Grid) -> Self {
+        if release <= 0.0 {
+            return Self::default();
+        }
+
+        let cell = cell.max(1);
+        let dims = [grid.nx, grid.ny, grid.nz].map(|n| n.div_ceil(cell));
+        let size = dims.iter().product();
+        Self {
+            release,
+            cell,
+            diff,
+            decay,
+            grid_yz: (grid.ny, grid.nz),
+            dims,
+            periodic: [grid.px, grid.py, grid.pz],
+            excess: vec![0.0; size],
+            scratch: vec![0.0; size],
+            peak: 0.0,
+            floored: 0,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.release > 0.0
+    }
+
+    #[inline(always)]
+    fn coarse_idx(&self, idxg: usize) -> usize {
+        let (ny, nz) = self.grid_yz;
+        let (x, y, z) = (idxg / (ny * nz), (idxg / nz) % ny, idxg % nz);
+        let [_, ncy, ncz] = self.dims;
+        z / self.cell + (y / self.cell + x / self.cell * ncy) * ncz
+    }
+
+    /// `kT` at the site `idxg` for the bath `k_t`; `k_t` itself when off.
+    #[inline(always)]
+    pub fn k_t(&mut self, idxg: usize, k_t: f64) -> f64 {
+        if !self.is_on() {
+            return k_t;
+        }
+
+        let local = k_t + K_BOLTZMANN * self.excess[self.coarse_idx(idxg)];
+        if local < T_FLOOR * k_t {
+            self.floored += 1;
+            return T_FLOOR * k_t;
+        }
+        local
+    }
+
+    /// Latent heat of an attachment (`sign` 1.0) or a detachment (-1.0) at `idxg`.
+    #[inline(always)]
+    pub fn deposit(&mut self, idxg: usize, sign: f64) {
+        if self.is_on() {
+            let c = self.coarse_idx(idxg);
+            self.excess[c] += sign * self.release;
+        }
+    }
+
+    /// One diffusion and relaxation step of the field.
+    pub fn step(&mut self) {
+        if !self.is_on() {
+            return;
+        }
+
+        let [ncx, ncy, ncz] = self.dims;
+        let idx = |x: usize, y: usize, z: usize| z + (y + x * ncy) * ncz;
+        // Neighbour of `i` along an axis of `n` coarse cells; `None` past a closed edge.
+        let neib = |i: usize, n: usize, periodic: bool, up: bool| match (up, periodic) {
+            _ if n == 1 => None,
+            (true, _) if i + 1 < n => Some(i + 1),
+            (false, _) if i > 0 => Some(i - 1),
+            (true, true) => Some(0),
+            (false, true) => Some(n - 1),
+            _ => None,
+        };
+
+        let (excess, next) = (&self.excess, &mut self.scratch);
+        for x in 0..ncx {
+            for y in 0..ncy {
+                for z in 0..ncz {
+                    let t = excess[idx(x, y, z)];
+                    let mut flow = 0.0;
+                    for up in [false, true] {
+                        if let Some(xn) = neib(x, ncx, self.periodic[0], up) {
+                            flow += excess[idx(xn, y, z)] - t;
+                        }
+                        if let Some(yn) = neib(y, ncy, self.periodic[1], up) {
+                            flow += excess[idx(x, yn, z)] - t;
+                        }
+                        if let Some(zn) = neib(z, ncz, self.periodic[2], up) {
+                            flow += excess[idx(x, y, zn)] - t;
+                        }
+                    }
+                    next[idx(x, y, z)] = t + self.diff * flow - self.decay * t;
+                }
+            }
+        }
+        std::mem::swap(&mut self.excess, &mut self.scratch);
+
+        let max = self.max_excess();
+        self.peak = self.peak.max(max);
+    }
+
+    pub fn mean_excess(&self) -> f64 {
+        match self.excess.len() {
+            0 => 0.0,
+            n => self.excess.iter().sum::<f64>() / n as f64,
+        }
+    }
+
+    pub fn max_excess(&self) -> f64 {
+        self.excess
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+}
@@ -1,28 +1,42 @@
 use crate::mods::{
+    config_version::{self, CONFIG_VERSION_KEY},
     constants::{
-        COMMENT_LINE, CONFIG_FILE_NAME, INIT_TIME_STATES_FILE_NAME, TIME_STATES_FILE_NAME,
+        BIN_FORMAT_VERSION, CHECKPOINT_FILE_NAME, COMMENT_LINE, CONFIG_FILE_NAME, FRAME_END_TAG,
+        INIT_TIME_STATES_FILE_NAME, KEY_MIGRATIONS, REQUIRED_CONFIG_KEYS, SIM_LOG_BIN_MAGIC,
+        TIME_STATES_BIN_MAGIC, TIME_STATES_FILE_NAME,
     },
     ensemble::Ensemble,
-    settings::{Settings, SettingsError},
+    faults::{self, FaultPoint},
+    item::Item,
+    lattice::Grid,
+    mode, resume,
+    settings::{self, PRESET_KEY, PRESETS, Settings, SettingsBuilder, SettingsError},
+    state_header::{StateHeader, parse_state_line},
+    unknown_keys,
 };
 use chrono::Utc;
+use mc_core::io_handler::{KeyParser, check_config_errors, read_config_lines};
+use rayon::prelude::*;
 use std::{
     collections::HashMap,
-    env::current_exe,
     fs::{self, File},
-    io::{BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write},
-    path::PathBuf,
+    io::{
+        BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Read, Result as IoResult, Write,
+    },
+    path::{Path, PathBuf},
 };
 
 use evalexpr::{eval_boolean, eval_number};
 
+pub use mc_core::io_handler::{get_exe_dir, write_state};
+
 macro_rules! parse_and_assign_eval {
     ($map:expr, $field:ident, $type:ty, $key:expr, boolean) => {
         $map.insert(
             $key,
-            Box::new(|v: &str, s: &mut Settings| {
+            Box::new(|v: &str, s: &mut SettingsBuilder| {
                 let val = eval_boolean(v).map_err(|e| SettingsError::new($key, v, e))?;
-                s.$field = val as $type;
+                s.$field(val as $type);
                 Ok(())
             }),
         );
@@ -31,33 +45,130 @@ macro_rules! parse_and_assign_eval {
     ($map:expr, $field:ident, $type:ty, $key:expr, number) => {
         $map.insert(
             $key,
-            Box::new(|v: &str, s: &mut Settings| {
+            Box::new(|v: &str, s: &mut SettingsBuilder| {
                 let val = eval_number(v).map_err(|e| SettingsError::new($key, v, e))?;
-                s.$field = val as $type;
+                s.$field(val as $type);
                 Ok(())
             }),
         );
     };
 }
 
-pub fn load_config(
-    cfg: &mut Settings,
-    exe_dir: &PathBuf,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Whether `key` is a setting of `InitSettings.ini`.
+pub fn is_config_key(key: &str) -> bool {
+    key == CONFIG_VERSION_KEY || config_dispatch().contains_key(key)
+}
+
+pub fn load_config(exe_dir: &PathBuf) -> Result<SettingsBuilder, Box<dyn std::error::Error>> {
+    load_config_with_preset(exe_dir, None)
+}
+
+/// `load_config` with the keys of the preset `preset` (else of the `Preset` key of the
+/// file) set first; the keys of the file override them and the preset counts for the
+/// required ones. The whole file is read before failing: the bad values, the duplicate
+/// keys and the keys missing for the selected mode come in one error.
+pub fn load_config_with_preset(
+    exe_dir: &Path,
+    preset: Option<&str>,
+) -> Result<SettingsBuilder, Box<dyn std::error::Error>> {
     let file = File::open(exe_dir.join(CONFIG_FILE_NAME))?;
     let reader = BufReader::new(file);
 
-    let mut dispatch: HashMap<&str, Box<dyn Fn(&str, &mut Settings) -> Result<(), SettingsError>>> =
-        HashMap::new();
+    let mut cfg = SettingsBuilder::new();
+    let dispatch = config_dispatch();
+
+    let lines = reader.lines().collect::<IoResult<Vec<String>>>()?;
+    let version = config_version::file_version(&lines, COMMENT_LINE)
+        .map_err(|e| SettingsError::simple(CONFIG_VERSION_KEY, e))?;
+
+    let preset = preset.map(str::to_string).or_else(|| {
+        lines
+            .iter()
+            .map(|line| line.trim())
+            .take_while(|line| *line != COMMENT_LINE)
+            .find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == PRESET_KEY).then(|| value.trim().to_string())
+            })
+    });
+    let mut errors: Vec<String> = Vec::new();
+    let mut preset_keys = Vec::new();
+    if let Some(name) = preset.as_deref() {
+        let name = name.trim();
+        if !name.is_empty() && !name.eq_ignore_ascii_case("none") {
+            preset_keys = settings::preset_keys(name).ok_or_else(|| {
+                let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+                SettingsError::simple(
+                    PRESET_KEY,
+                    format!(
+                        "unknown preset '{}' (available: {})",
+                        name,
+                        names.join(", ")
+                    ),
+                )
+            })?;
+        }
+        for (key, value) in &preset_keys {
+            match dispatch.get(key) {
+                Some(parser) => {
+                    if let Err(e) = parser(value, &mut cfg) {
+                        errors.push(format!("preset '{}': {}", name, e));
+                    }
+                }
+                None => errors.push(format!("preset '{}': key '{}' is not a setting", name, key)),
+            }
+        }
+    }
+
+    let read = read_config_lines(
+        &lines,
+        COMMENT_LINE,
+        version,
+        &KEY_MIGRATIONS,
+        &dispatch,
+        &mut cfg,
+    );
+
+    if !read.unknown.is_empty() {
+        eprintln!("{}", unknown_keys::report(&read.unknown, CONFIG_FILE_NAME));
+    }
+
+    // `--preset` wins over the `Preset` key read above.
+    if let Some(name) = preset {
+        cfg.preset(name.trim().to_string());
+    }
+
+    let missing_keys = read.missing_keys(&REQUIRED_CONFIG_KEYS, cfg.mode(), |k| {
+        preset_keys.iter().any(|(key, _)| *key == k)
+    });
+    cfg.unknown_keys(read.unknown);
+
+    errors.extend(read.errors);
+    errors.extend(missing_keys);
+    check_config_errors(CONFIG_FILE_NAME, &errors)?;
+
+    Ok(cfg)
+}
+
+fn config_dispatch() -> HashMap<&'static str, KeyParser<SettingsBuilder>> {
+    let mut dispatch: HashMap<&'static str, KeyParser<SettingsBuilder>> = HashMap::new();
 
     dispatch.insert(
         "DirPrefix",
         Box::new(|v, s| {
-            s.dir_prefix = v.to_string();
+            s.dir_prefix(v.to_string());
             Ok(())
         }),
     );
     parse_and_assign_eval!(dispatch, seed, u64, "Seed", number);
+    dispatch.insert(
+        PRESET_KEY,
+        Box::new(|v, s| {
+            s.preset(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, strict, bool, "Strict", boolean);
 
     parse_and_assign_eval!(dispatch, sx, usize, "Sx", number);
     parse_and_assign_eval!(dispatch, sy, usize, "Sy", number);
@@ -67,6 +178,9 @@ pub fn load_config(
     parse_and_assign_eval!(dispatch, pz, bool, "Pz", boolean);
 
     parse_and_assign_eval!(dispatch, temperature, f64, "T", number);
+    parse_and_assign_eval!(dispatch, t_sigma, f64, "T_sigma", number);
+    parse_and_assign_eval!(dispatch, t_tau, f64, "T_tau", number);
+    parse_and_assign_eval!(dispatch, t_every, u64, "T_every", number);
     parse_and_assign_eval!(dispatch, ax, f64, "Ax", number);
     parse_and_assign_eval!(dispatch, ay, f64, "Ay", number);
     parse_and_assign_eval!(dispatch, az, f64, "Az", number);
@@ -74,70 +188,282 @@ pub fn load_config(
     parse_and_assign_eval!(dispatch, g100, f64, "g100", number);
     parse_and_assign_eval!(dispatch, g010, f64, "g010", number);
     parse_and_assign_eval!(dispatch, g001, f64, "g001", number);
+    dispatch.insert(
+        "AxisMap",
+        Box::new(|v, s| {
+            s.axis_map(v.to_string());
+            Ok(())
+        }),
+    );
 
-    parse_and_assign_eval!(dispatch, mode, f64, "mode", number);
+    dispatch.insert(
+        "mode",
+        Box::new(|v, s| {
+            let (major, minor) =
+                mode::parse_mode(v).map_err(|e| SettingsError::simple("mode", e))?;
+            s.mode_major(major);
+            s.mode_minor(minor);
+            Ok(())
+        }),
+    );
     parse_and_assign_eval!(dispatch, dg, f64, "dg", number);
     parse_and_assign_eval!(dispatch, c_eq, f64, "C_eq", number);
     parse_and_assign_eval!(dispatch, c0, f64, "C0", number);
     parse_and_assign_eval!(dispatch, n_tot, f64, "N_tot", number);
     parse_and_assign_eval!(dispatch, n0_cr, f64, "N0_cr", number);
+    parse_and_assign_eval!(dispatch, gas_dist_pow, f64, "GasDistPow", number);
+    parse_and_assign_eval!(dispatch, gas_dist_noise, f64, "GasDistNoise", number);
+    dispatch.insert(
+        "GasDist",
+        Box::new(|v, s| {
+            s.gas_dist(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, c_ext, f64, "C_ext", number);
+    parse_and_assign_eval!(dispatch, k_exch, f64, "k_exch", number);
+    parse_and_assign_eval!(dispatch, chemostat_c, f64, "ChemostatC", number);
+    parse_and_assign_eval!(dispatch, chemostat_rate, f64, "ChemostatRate", number);
+    parse_and_assign_eval!(dispatch, conc_neg_max, u64, "ConcNegMax", number);
+    dispatch.insert(
+        "ConcNegAction",
+        Box::new(|v, s| {
+            s.conc_neg_action(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, dg_exact, bool, "DgExact", boolean);
     parse_and_assign_eval!(dispatch, p_b, f64, "p_b", number);
+    parse_and_assign_eval!(dispatch, p_b_x, f64, "p_b_x", number);
+    parse_and_assign_eval!(dispatch, p_b_y, f64, "p_b_y", number);
+    parse_and_assign_eval!(dispatch, p_b_z, f64, "p_b_z", number);
     parse_and_assign_eval!(dispatch, p_pow, f64, "p_pow", number);
+    parse_and_assign_eval!(dispatch, e_des, f64, "EDes", number);
+    dispatch.insert(
+        "PbExpr",
+        Box::new(|v, s| {
+            s.pb_expr(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "DetachModel",
+        Box::new(|v, s| {
+            s.detach_model(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "Acceptance",
+        Box::new(|v, s| {
+            s.acceptance(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "EnergyAccounting",
+        Box::new(|v, s| {
+            s.energy_accounting(v.to_string());
+            Ok(())
+        }),
+    );
+
+    parse_and_assign_eval!(dispatch, freeze_100, bool, "Freeze100", boolean);
+    parse_and_assign_eval!(dispatch, freeze_010, bool, "Freeze010", boolean);
+    parse_and_assign_eval!(dispatch, freeze_001, bool, "Freeze001", boolean);
+
+    dispatch.insert(
+        "TemplateFile",
+        Box::new(|v, s| {
+            s.template_file(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "ItemDimsFile",
+        Box::new(|v, s| {
+            s.item_dims_file(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "ItemGroupsFile",
+        Box::new(|v, s| {
+            s.item_groups_file(v.to_string());
+            Ok(())
+        }),
+    );
 
     parse_and_assign_eval!(dispatch, add_i, u64, "AddI", number);
     parse_and_assign_eval!(dispatch, add_from, u64, "AddFrom", number);
     parse_and_assign_eval!(dispatch, rem_i, u64, "RemI", number);
     parse_and_assign_eval!(dispatch, rem_from, u64, "RemFrom", number);
+    parse_and_assign_eval!(dispatch, swap_i, u64, "SwapI", number);
+    parse_and_assign_eval!(dispatch, swap_from, u64, "SwapFrom", number);
 
     parse_and_assign_eval!(dispatch, load_option, i64, "LoadOption", number);
+    dispatch.insert(
+        "NucleiFilter",
+        Box::new(|v, s| {
+            s.nuclei_filter(v.to_string());
+            Ok(())
+        }),
+    );
 
     parse_and_assign_eval!(dispatch, step_lim, u64, "StepLim", number);
     parse_and_assign_eval!(dispatch, print_i, u64, "PrintI", number);
     parse_and_assign_eval!(dispatch, write_i, u64, "WriteI", number);
+    parse_and_assign_eval!(dispatch, write_io_pct, f64, "WriteIoPct", number);
+    parse_and_assign_eval!(dispatch, write_i_max, u64, "WriteIMax", number);
+    parse_and_assign_eval!(dispatch, step_lim_max, u64, "StepLimMax", number);
+    parse_and_assign_eval!(dispatch, extend_rate, f64, "ExtendRate", number);
+    parse_and_assign_eval!(dispatch, extend_pct, f64, "ExtendPct", number);
+    parse_and_assign_eval!(dispatch, extend_window, u64, "ExtendWindow", number);
+    parse_and_assign_eval!(dispatch, control_i, u64, "ControlI", number);
+    parse_and_assign_eval!(dispatch, checkpoint_i, u64, "CheckpointI", number);
+    parse_and_assign_eval!(dispatch, max_hours, f64, "MaxHours", number);
+    dispatch.insert(
+        "ResumeFrom",
+        Box::new(|v, s| {
+            s.resume_from(v.to_string());
+            Ok(())
+        }),
+    );
 
-    for (line_num, line_result) in reader.lines().enumerate() {
-        let line = line_result?;
-        let trimmed = line.trim();
-
-        if trimmed == COMMENT_LINE {
-            break;
-        }
-
-        let mut parts = trimmed.splitn(2, ':');
-        let key = parts.next().unwrap_or("").trim();
-        let value = parts.next().unwrap_or("").trim();
+    parse_and_assign_eval!(dispatch, item_write_i, u64, "ItemWriteI", number);
+    parse_and_assign_eval!(dispatch, item_keep_first, usize, "ItemKeepFirst", number);
+    parse_and_assign_eval!(dispatch, item_keep_last, usize, "ItemKeepLast", number);
+    dispatch.insert(
+        "ItemRoi",
+        Box::new(|v, s| {
+            s.item_roi(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, item_stride, usize, "ItemStride", number);
+    parse_and_assign_eval!(dispatch, item_max_mb, f64, "ItemMaxMb", number);
+    parse_and_assign_eval!(dispatch, stall_thr, usize, "StallThr", number);
+    parse_and_assign_eval!(dispatch, stale_retry, u32, "StaleRetry", number);
+    parse_and_assign_eval!(dispatch, throttle_thr, usize, "ThrottleThr", number);
+    parse_and_assign_eval!(dispatch, throttle_i, u64, "ThrottleI", number);
+    parse_and_assign_eval!(dispatch, poisson_add, f64, "PoissonAdd", number);
+    parse_and_assign_eval!(dispatch, poisson_rem, f64, "PoissonRem", number);
+    parse_and_assign_eval!(dispatch, poisson_bal, f64, "PoissonBal", number);
+    dispatch.insert(
+        "Anchor",
+        Box::new(|v, s| {
+            s.anchor(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "SubLattice",
+        Box::new(|v, s| {
+            s.sub_lattice(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, front_rebuild_i, u64, "FrontRebuildI", number);
+    parse_and_assign_eval!(
+        dispatch,
+        front_rebuild_adopt,
+        bool,
+        "FrontRebuildAdopt",
+        boolean
+    );
+    parse_and_assign_eval!(dispatch, pass_rate, f64, "PassRate", number);
+    parse_and_assign_eval!(dispatch, pass_life, f64, "PassLife", number);
+    parse_and_assign_eval!(dispatch, cluster_track, bool, "ClusterTrack", boolean);
+    parse_and_assign_eval!(dispatch, heat_release, f64, "HeatRelease", number);
+    parse_and_assign_eval!(dispatch, heat_cell, usize, "HeatCell", number);
+    parse_and_assign_eval!(dispatch, heat_diff, f64, "HeatDiff", number);
+    parse_and_assign_eval!(dispatch, heat_decay, f64, "HeatDecay", number);
 
-        if key.is_empty() || value.is_empty() {
-            #[cfg(debug_assertions)]
-            eprintln!("⚠️ Warning: Malformed line {}: '{}'", line_num + 1, line);
-            continue;
-        }
+    parse_and_assign_eval!(dispatch, steady_w, usize, "SteadyW", number);
+    parse_and_assign_eval!(dispatch, steady_tol, f64, "SteadyTol", number);
+    parse_and_assign_eval!(dispatch, steady_m, usize, "SteadyM", number);
+    parse_and_assign_eval!(dispatch, steady_stop, bool, "SteadyStop", boolean);
 
-        if let Some(parser) = dispatch.get(key) {
-            parser(value, cfg)?;
-        } else {
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "⚠️ Warning: Unknown cfg key '{}' found on line {}: '{}'",
-                key,
-                line_num + 1,
-                line
-            );
-        }
-    }
+    parse_and_assign_eval!(dispatch, stats_from, u64, "StatsFrom", number);
+    parse_and_assign_eval!(dispatch, energy_hist_bin, f64, "EnergyHistBin", number);
+    parse_and_assign_eval!(dispatch, log_decim, u64, "LogDecim", number);
 
-    Ok(())
-}
+    parse_and_assign_eval!(dispatch, rng_audit, bool, "RngAudit", boolean);
+    parse_and_assign_eval!(dispatch, facet_stats, bool, "FacetStats", boolean);
+    parse_and_assign_eval!(dispatch, kossel_check, bool, "KosselCheck", boolean);
+    parse_and_assign_eval!(dispatch, residence_stats, bool, "ResidenceStats", boolean);
+    dispatch.insert(
+        "DensityProfile",
+        Box::new(|v, s| {
+            s.density_profile(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, minkowski, bool, "Minkowski", boolean);
+    parse_and_assign_eval!(dispatch, event_export, bool, "EventExport", boolean);
+    parse_and_assign_eval!(dispatch, front_export, bool, "FrontExport", boolean);
+    parse_and_assign_eval!(dispatch, detach_spectrum_i, u64, "DetachSpectrumI", number);
+    dispatch.insert(
+        "FirstPassage",
+        Box::new(|v, s| {
+            s.first_passage(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "Probes",
+        Box::new(|v, s| {
+            s.probes(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, mem_budget_gb, f64, "MemBudgetGb", number);
+    parse_and_assign_eval!(dispatch, sparse_front, bool, "SparseFront", boolean);
+    parse_and_assign_eval!(dispatch, write_queue, usize, "WriteQueue", number);
+    dispatch.insert(
+        "QueueFull",
+        Box::new(|v, s| {
+            s.queue_full(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, snap_compress, bool, "SnapCompress", boolean);
+    parse_and_assign_eval!(dispatch, snap_dual, bool, "SnapDual", boolean);
+    parse_and_assign_eval!(dispatch, pack_run, bool, "PackRun", boolean);
+    dispatch.insert(
+        "Campaign",
+        Box::new(|v, s| {
+            s.campaign(v.to_string());
+            Ok(())
+        }),
+    );
+    dispatch.insert(
+        "CampaignDir",
+        Box::new(|v, s| {
+            s.campaign_dir(v.to_string());
+            Ok(())
+        }),
+    );
 
-pub fn get_exe_dir() -> IoResult<PathBuf> {
-    let exe_path = current_exe()?;
+    parse_and_assign_eval!(dispatch, ranks, usize, "Ranks", number);
+    dispatch.insert(
+        "CoordAddr",
+        Box::new(|v, s| {
+            s.coord_addr(v.to_string());
+            Ok(())
+        }),
+    );
+    parse_and_assign_eval!(dispatch, sync_i, u64, "SyncI", number);
+    parse_and_assign_eval!(dispatch, deterministic, bool, "Deterministic", boolean);
+    dispatch.insert(
+        "StatusAddr",
+        Box::new(|v, s| {
+            s.status_addr(v.to_string());
+            Ok(())
+        }),
+    );
 
-    exe_path.parent().map(|p| p.to_path_buf()).ok_or_else(|| {
-        IoError::new(
-            ErrorKind::Other,
-            "Failed to get the parent directory of the executable.",
-        )
-    })
+    dispatch
 }
 
 fn create_dir_name(ensemble: &Ensemble) -> String {
@@ -149,16 +475,21 @@ fn create_dir_name(ensemble: &Ensemble) -> String {
         timestamp, cfg.dir_prefix, ensemble.items_len0, cfg.sx, cfg.sy, cfg.sz, cfg.temperature,
     );
 
-    let base1 = match cfg.mode >= 2.1 {
+    let base1 = match cfg.mode_major == 2 {
         false => format!("{}_dg{:e}", base0, cfg.dg),
         true => format!("{}_C{:e}_Nt{:e}", base0, cfg.c0, cfg.n_tot),
     };
 
-    match cfg.mode {
-        1.1 | 2.1 => base1,
-        1.2 | 2.2 => format!("{}_Pb{:?}", base1, cfg.p_b),
-        1.3 | 3.3 => format!("{}_Pb{:?}_Pp{:?}", base1, cfg.p_b, cfg.p_pow),
+    let base2 = match cfg.mode().1 {
+        1 => base1,
+        2 => format!("{}_Pb{:?}", base1, cfg.p_b),
+        3 => format!("{}_Pb{:?}_Pp{:?}", base1, cfg.p_b, cfg.p_pow),
         _ => base0,
+    };
+
+    match cfg.ranks > 1 {
+        true => format!("{}_R{}", base2, cfg.rank),
+        false => base2,
     }
 }
 
@@ -215,34 +546,315 @@ pub fn prepare_files(ensemble: &Ensemble) -> IoResult<()> {
     Ok(())
 }
 
-pub fn load_states(ensemble: &Ensemble) -> IoResult<Vec<Vec<u8>>> {
+/// Per-item grid sizes from `ItemDimsFile` (relative to the source directory), one
+/// `Sx:Sy:Sz` line per item in the order of `InitStates.ini`. Empty when the option is off.
+pub fn load_item_dims(ensemble: &Ensemble) -> IoResult<Vec<(usize, usize, usize)>> {
+    let Some(dims_path) = ensemble.cfg.item_dims_path() else {
+        return Ok(Vec::new());
+    };
+
+    let file_path = ensemble.src_path.join(dims_path);
+    let reader = BufReader::new(File::open(&file_path).map_err(|e| {
+        IoError::new(
+            e.kind(),
+            format!("Failed to open item dims '{}': {}", file_path.display(), e),
+        )
+    })?);
+
+    let mut item_dims = Vec::new();
+    for (i, line_result) in reader.lines().enumerate() {
+        let line = line_result?;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let values: Vec<usize> = trimmed
+            .split(':')
+            .filter_map(|s| s.trim().parse::<usize>().ok())
+            .collect();
+
+        match values[..] {
+            [sx, sy, sz] if sx > 0 && sy > 0 && sz > 0 => item_dims.push((sx, sy, sz)),
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Line {} of '{}' must be 'Sx:Sy:Sz' with positive sizes: '{}'",
+                        i + 1,
+                        file_path.display(),
+                        trimmed
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(item_dims)
+}
+
+/// Per-item group tags from `ItemGroupsFile` (relative to the source directory), one tag
+/// per line in the order of `InitStates.ini`. Empty when the option is off.
+pub fn load_item_groups(ensemble: &Ensemble) -> IoResult<Vec<String>> {
+    let Some(groups_path) = ensemble.cfg.item_groups_path() else {
+        return Ok(Vec::new());
+    };
+
+    let file_path = ensemble.src_path.join(groups_path);
+    let reader = BufReader::new(File::open(&file_path).map_err(|e| {
+        IoError::new(
+            e.kind(),
+            format!(
+                "Failed to open item groups '{}': {}",
+                file_path.display(),
+                e
+            ),
+        )
+    })?);
+
+    let mut tags = Vec::new();
+    for (i, line_result) in reader.lines().enumerate() {
+        let line = line_result?;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if trimmed.contains(|c: char| c == ':' || c.is_whitespace()) {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Line {} of '{}' must be a single tag without ':' or spaces: '{}'",
+                    i + 1,
+                    file_path.display(),
+                    trimmed
+                ),
+            ));
+        }
+        tags.push(trimmed.to_string());
+    }
+
+    Ok(tags)
+}
+
+/// Loads the initial item states; line `i` must match the grid size of item `i`
+/// (`item_dims[i]`, or `Sx`/`Sy`/`Sz` beyond the table). Lines after a `#states` header
+/// are checked against its dims and encoding; a file without one only by value count.
+/// The file is read in one pass, then the lines are parsed in parallel.
+pub fn load_states(
+    ensemble: &Ensemble,
+    item_dims: &[(usize, usize, usize)],
+) -> IoResult<Vec<Vec<u8>>> {
     let cfg = &ensemble.cfg;
-    let load_line_count = cfg.load_option;
+    // A resumed run takes all the states of the checkpoint.
+    let (file_path, load_line_count) = match cfg.resume_path() {
+        None => (
+            ensemble.src_path.join(INIT_TIME_STATES_FILE_NAME),
+            cfg.load_option,
+        ),
+        Some(resume_from) => (resume::checkpoint_path(resume_from), -1),
+    };
     let load_line_count_usize = load_line_count as usize;
 
     if load_line_count == 0 {
         return Ok(vec![]);
     }
 
-    let file_path = ensemble.src_path.join(INIT_TIME_STATES_FILE_NAME);
-    let reader = BufReader::new(File::open(&file_path)?);
+    let mut reader = BufReader::new(File::open(&file_path)?);
 
-    let expected_len = cfg.sx * cfg.sy * cfg.sz;
-    let mut all_lines_data = Vec::new();
+    let default_dims = (cfg.sx, cfg.sy, cfg.sz);
+    let mut state_lines = Vec::new();
     let check1 = load_line_count > 0;
+    let mut header: Option<StateHeader> = None;
+    let line_err = |i: usize, msg: String| {
+        IoError::new(
+            ErrorKind::InvalidData,
+            format!("{} (line {}): {}", file_path.display(), i + 1, msg),
+        )
+    };
 
-    for (i, line_result) in reader.lines().enumerate() {
+    // Frames closed by `#end` lines so far; from the first one on, every frame must be.
+    let mut is_marked = false;
+    let mut line = Vec::new();
+    for i in 0.. {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let trimmed = line.trim_ascii();
+
+        if let Some(checksum) = frame_end_checksum(trimmed) {
+            // The end line itself cut: its frame is dropped below
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            let checksum = checksum.map_err(|e| line_err(i, e))?;
+            match state_lines.last_mut() {
+                Some((_, _, _, _, expected @ None)) => *expected = Some(checksum),
+                _ => return Err(line_err(i, "frame end without a frame".to_string())),
+            }
+            is_marked = true;
+            continue;
+        }
+        if let Some((j, _, _, _, None)) = state_lines.last()
+            && is_marked
+            && !trimmed.is_empty()
+            && !trimmed.starts_with(b"#")
+        {
+            return Err(line_err(*j, "frame without its end line".to_string()));
+        }
+
+        if trimmed.starts_with(b"#") {
+            let text = String::from_utf8_lossy(trimmed);
+            if StateHeader::is_header(&text) {
+                header = Some(StateHeader::parse(&text).map_err(|e| line_err(i, e))?);
+            }
+        }
+
+        // Skip empty lines, comments (the `#resume`/`#item` lines of a checkpoint) or
+        // malformed ones
+        if trimmed.is_empty() || trimmed.starts_with(b"#") || !trimmed.contains(&b':') {
+            continue;
+        }
+
+        // If we only load a fixed number of lines, stop when reached
+        if check1 && state_lines.len() >= load_line_count_usize {
+            break;
+        }
+
+        if header.is_none() && state_lines.is_empty() {
+            println!(
+                "⚠️ '{}' has no '#states' header, checking the value count only.",
+                file_path.display()
+            );
+        }
+
+        // A final line without a newline was cut while being written
+        if !line.ends_with(b"\n") {
+            warn_cut_frame(&file_path, state_lines.len());
+            break;
+        }
+
+        let expected_dims = item_dims
+            .get(state_lines.len())
+            .copied()
+            .unwrap_or(default_dims);
+        state_lines.push((i, header, expected_dims, std::mem::take(&mut line), None));
+    }
+    // The final frame of a marked file lost its end line in a crash
+    if let Some((.., None)) = state_lines.last()
+        && is_marked
+    {
+        state_lines.pop();
+        warn_cut_frame(&file_path, state_lines.len());
+    }
+
+    // Ensure we loaded enough lines if required
+    if check1 && state_lines.len() < load_line_count_usize {
+        return Err(IoError::new(
+            ErrorKind::NotFound,
+            format!(
+                "Expected {} state lines, but found only {} in file {}",
+                load_line_count,
+                state_lines.len(),
+                file_path.display()
+            ),
+        ));
+    }
+
+    state_lines
+        .into_par_iter()
+        .map(|(i, header, expected_dims, bytes, checksum)| match header {
+            Some(header) => header
+                .check_dims(expected_dims)
+                .and_then(|_| header.parse_values(&bytes))
+                .and_then(|values| check_frame(values, checksum))
+                .map_err(|e| line_err(i, e)),
+            None => {
+                // Legacy lines skip the tokens that are not values
+                let values = parse_state_line(&bytes, u8::MAX).unwrap_or_else(|_| {
+                    String::from_utf8_lossy(&bytes)
+                        .split(':')
+                        .filter_map(|s| s.trim().parse::<u8>().ok())
+                        .collect()
+                });
+
+                let expected_len = expected_dims.0 * expected_dims.1 * expected_dims.2;
+                if values.len() != expected_len {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "Line {} has incorrect number of values: expected {}, got {}",
+                            i + 1,
+                            expected_len,
+                            values.len()
+                        ),
+                    ));
+                }
+                check_frame(values, checksum).map_err(|e| line_err(i, e))
+            }
+        })
+        .collect()
+}
+
+/// `values` if they match the `checksum` of their `#end` line (none - unmarked).
+fn check_frame(values: Vec<u8>, checksum: Option<u64>) -> Result<Vec<u8>, String> {
+    match checksum {
+        Some(checksum) if frame_checksum(&values) != checksum => Err(format!(
+            "checksum mismatch ({:016x}, end line {:016x}): corrupt frame",
+            frame_checksum(&values),
+            checksum
+        )),
+        _ => Ok(values),
+    }
+}
+
+/// Loads the template mask (first state line of `TemplateFile`, relative to the
+/// source directory). Returns an empty mask when template growth is off.
+pub fn load_template(ensemble: &Ensemble) -> IoResult<Box<[u8]>> {
+    let cfg = &ensemble.cfg;
+    let Some(template_path) = cfg.template_path() else {
+        return Ok(Box::new([]));
+    };
+
+    let file_path = ensemble.src_path.join(template_path);
+    let reader = BufReader::new(File::open(&file_path).map_err(|e| {
+        IoError::new(
+            e.kind(),
+            format!("Failed to open template '{}': {}", file_path.display(), e),
+        )
+    })?);
+
+    let expected_len = cfg.sx * cfg.sy * cfg.sz;
+    let mut header: Option<StateHeader> = None;
+
+    for line_result in reader.lines() {
         let line = line_result?;
         let trimmed = line.trim();
+        let template_err = |msg: String| {
+            IoError::new(
+                ErrorKind::InvalidData,
+                format!("Template '{}': {}", file_path.display(), msg),
+            )
+        };
+
+        if StateHeader::is_header(trimmed) {
+            header = Some(StateHeader::parse(trimmed).map_err(template_err)?);
+            continue;
+        }
 
-        // Skip empty lines or malformed ones
         if trimmed.is_empty() || !trimmed.contains(':') {
             continue;
         }
 
-        // If we only load a fixed number of lines, stop when reached
-        if check1 && all_lines_data.len() >= load_line_count_usize {
-            break;
+        if let Some(header) = &header {
+            let values = header
+                .check_dims((cfg.sx, cfg.sy, cfg.sz))
+                .and_then(|_| header.parse_values(trimmed))
+                .map_err(template_err)?;
+            return Ok(values.into_boxed_slice());
         }
 
         let values: Vec<u8> = trimmed
@@ -254,55 +866,132 @@ pub fn load_states(ensemble: &Ensemble) -> IoResult<Vec<Vec<u8>>> {
             return Err(IoError::new(
                 ErrorKind::InvalidData,
                 format!(
-                    "Line {} has incorrect number of values: expected {}, got {}",
-                    i + 1,
+                    "Template '{}' has incorrect number of values: expected {}, got {}",
+                    file_path.display(),
                     expected_len,
                     values.len()
                 ),
             ));
         }
 
-        all_lines_data.push(values);
+        return Ok(values.into_boxed_slice());
     }
 
-    // Ensure we loaded enough lines if required
-    if check1 && all_lines_data.len() < load_line_count_usize {
+    Err(IoError::new(
+        ErrorKind::InvalidData,
+        format!("Template '{}' contains no state line", file_path.display()),
+    ))
+}
+
+/// Writes the states of the alive items to `Checkpoint.txt` in `path_dst`, one line per
+/// item in the `InitStates.ini` format, so the run can be restarted from them. A
+/// `#states` header precedes the first item and every change of the grid size.
+/// The file is written next to the old one and renamed over it, so a failed write
+/// (e.g. a full disk) leaves the previous checkpoint intact.
+///
+/// With `resume` (the `resume::resume_line` of a completed step) the file also carries
+/// the `#resume`/`#item` lines for an exact restart (`ResumeFrom`).
+pub fn write_checkpoint(
+    path_dst: &Path,
+    items: &[Item],
+    grid: &Grid,
+    item_grids: &[Grid],
+    step_id: u64,
+    resume: Option<&str>,
+) -> IoResult<()> {
+    let path_checkpoint = path_dst.join(CHECKPOINT_FILE_NAME);
+    let path_tmp = path_checkpoint.with_extension("txt.tmp");
+    let written = write_checkpoint_to(&path_tmp, items, grid, item_grids, resume)
+        .and_then(|_| fs::rename(&path_tmp, &path_checkpoint));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&path_tmp);
         return Err(IoError::new(
-            ErrorKind::NotFound,
+            e.kind(),
             format!(
-                "Expected {} state lines, but found only {} in file {}",
-                load_line_count,
-                all_lines_data.len(),
-                file_path.display()
+                "Failed to write checkpoint '{}': {}",
+                path_checkpoint.display(),
+                e
             ),
         ));
     }
 
-    Ok(all_lines_data)
+    println!(
+        "Step: {} -> Checkpoint of {} item(s) written to '{}'",
+        step_id,
+        items.len(),
+        path_checkpoint.display()
+    );
+
+    Ok(())
 }
 
-pub fn write_state(writer: &mut BufWriter<File>, state: &Box<[u8]>) -> IoResult<()> {
-    // Get the length of the state array
-    let len = state.len();
-    // If the array is empty, write only a newline character
-    if len == 0 {
-        return writer.write_all(b"\n").map(|_| ());
+fn write_checkpoint_to(
+    path: &Path,
+    items: &[Item],
+    grid: &Grid,
+    item_grids: &[Grid],
+    resume: Option<&str>,
+) -> IoResult<()> {
+    faults::inject(FaultPoint::Checkpoint)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    if let Some(resume) = resume {
+        writeln!(writer, "{}", resume)?;
+    }
+
+    let mut header: Option<StateHeader> = None;
+    for item in items.iter().filter(|item| item.is_alive) {
+        let item_grid = match item.grid_id {
+            0 => grid,
+            id => &item_grids[id - 1],
+        };
+        let item_header = StateHeader::new((item_grid.nx, item_grid.ny, item_grid.nz));
+        if header != Some(item_header) {
+            writeln!(writer, "{}", item_header)?;
+            header = Some(item_header);
+        }
+        if resume.is_some() {
+            writeln!(writer, "{}", resume::item_line(item))?;
+        }
+        write_frame(&mut writer, &item.state)?;
     }
+    writer.flush()
+}
+
+/// FNV-1a hash of the cell values of a frame, the checksum of its `#end` line.
+pub fn frame_checksum(state: &[u8]) -> u64 {
+    state.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &val| {
+        (hash ^ val as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
 
-    // Create a buffer with precise capacity: each byte (0 or 1) -> 1 character ('0' or '1') + (len-1) separators ':' + 1 newline character
-    let mut buffer = Vec::with_capacity(len + len.saturating_sub(1) + 1);
+/// Writes `state` as a state line closed by its `#end` line, so that a reader tells a
+/// complete frame from one cut by a crash. Readers that skip `#` lines are unaffected.
+pub fn write_frame<W: Write>(writer: &mut W, state: &[u8]) -> IoResult<()> {
+    write_state(writer, state)?;
+    writeln!(writer, "{} {:016x}", FRAME_END_TAG, frame_checksum(state))
+}
 
-    // Fill the buffer with values ('0' or '1') and separators ':'
-    buffer.extend(state.iter().flat_map(|&val| [val + b'0', b':']));
-    // Remove the last superfluous separator ':'
-    buffer.pop();
-    // Add the newline character
-    buffer.push(b'\n');
+/// Checksum of an `#end` line (trimmed); `None` for any other line.
+pub fn frame_end_checksum(line: &[u8]) -> Option<Result<u64, String>> {
+    let value = line.strip_prefix(FRAME_END_TAG.as_bytes())?;
+    let value = String::from_utf8_lossy(value);
+    Some(
+        u64::from_str_radix(value.trim(), 16)
+            .map_err(|_| format!("malformed frame end line '{}'", value.trim())),
+    )
+}
 
-    // Write the buffer to the file
-    writer.write_all(&buffer)?;
-    // Return a successful result
-    Ok(())
+/// Warns that the final frame of `path` was cut while being written (a crash) and is
+/// skipped; `frames` - the complete frames before it.
+fn warn_cut_frame(path: &Path, frames: usize) {
+    eprintln!(
+        "⚠️ Warning: '{}': the final frame is incomplete, skipped; last complete frame: {}.",
+        path.display(),
+        match frames {
+            0 => "none".to_string(),
+            n => format!("{} (0-based)", n - 1),
+        }
+    );
 }
 
 pub fn write_state_uni<T, F>(
@@ -356,3 +1045,376 @@ where
     writer.write_all(b"\n")?;
     Ok(())
 }
+
+/// Writes the header of a binary time-states file.
+///
+/// Layout (little-endian): magic `MCTS`, `u32` version, `u8` encoding
+/// (0 - one byte per cell, 1 - bit-packed 0/1 cells), `u64` cells per frame,
+/// `u64` metadata length followed by the metadata bytes (usually the run config).
+pub fn write_states_bin_header<W: Write>(
+    writer: &mut W,
+    packed: bool,
+    frame_len: usize,
+    metadata: &[u8],
+) -> IoResult<()> {
+    writer.write_all(TIME_STATES_BIN_MAGIC)?;
+    writer.write_all(&BIN_FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[packed as u8])?;
+    writer.write_all(&(frame_len as u64).to_le_bytes())?;
+    writer.write_all(&(metadata.len() as u64).to_le_bytes())?;
+    writer.write_all(metadata)?;
+    Ok(())
+}
+
+/// Appends one frame to a binary time-states file.
+pub fn write_state_bin<W: Write>(writer: &mut W, state: &[u8], packed: bool) -> IoResult<()> {
+    if !packed {
+        return writer.write_all(state);
+    }
+
+    let mut buffer = vec![0u8; state.len().div_ceil(8)];
+    for (i, &val) in state.iter().enumerate() {
+        if val != 0 {
+            buffer[i / 8] |= 1 << (i % 8);
+        }
+    }
+    writer.write_all(&buffer)
+}
+
+/// Reads the header written by `write_states_bin_header`.
+///
+/// Returns the encoding flag, the cells per frame and the metadata bytes.
+pub fn read_states_bin_header<R: Read>(reader: &mut R) -> IoResult<(bool, usize, Vec<u8>)> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != TIME_STATES_BIN_MAGIC {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            "Not a binary time-states file (bad magic)",
+        ));
+    }
+
+    let mut buf4 = [0u8; 4];
+    reader.read_exact(&mut buf4)?;
+    let version = u32::from_le_bytes(buf4);
+    if version != BIN_FORMAT_VERSION {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!("Unsupported binary format version {}", version),
+        ));
+    }
+
+    let mut buf1 = [0u8; 1];
+    reader.read_exact(&mut buf1)?;
+    let packed = buf1[0] != 0;
+
+    let mut buf8 = [0u8; 8];
+    reader.read_exact(&mut buf8)?;
+    let frame_len = u64::from_le_bytes(buf8) as usize;
+    reader.read_exact(&mut buf8)?;
+    let mut metadata = vec![0u8; u64::from_le_bytes(buf8) as usize];
+    reader.read_exact(&mut metadata)?;
+
+    Ok((packed, frame_len, metadata))
+}
+
+/// Reads one frame written by `write_state_bin`.
+pub fn read_state_bin<R: Read>(
+    reader: &mut R,
+    frame_len: usize,
+    packed: bool,
+) -> IoResult<Vec<u8>> {
+    if !packed {
+        let mut state = vec![0u8; frame_len];
+        reader.read_exact(&mut state)?;
+        return Ok(state);
+    }
+
+    let mut buffer = vec![0u8; frame_len.div_ceil(8)];
+    reader.read_exact(&mut buffer)?;
+    Ok((0..frame_len)
+        .map(|i| (buffer[i / 8] >> (i % 8)) & 1)
+        .collect())
+}
+
+/// Writes a binary log file: magic `MCSL`, `u32` version, `u64` metadata
+/// length + metadata, `u32` row count, then every row as `u64` length and
+/// `f64` values.
+pub fn write_log_bin<W: Write>(writer: &mut W, rows: &[Vec<f64>], metadata: &[u8]) -> IoResult<()> {
+    writer.write_all(SIM_LOG_BIN_MAGIC)?;
+    writer.write_all(&BIN_FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&(metadata.len() as u64).to_le_bytes())?;
+    writer.write_all(metadata)?;
+    writer.write_all(&(rows.len() as u32).to_le_bytes())?;
+
+    for row in rows {
+        writer.write_all(&(row.len() as u64).to_le_bytes())?;
+        for &v in row {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Frame-by-frame reader of a state file: a text one (`TimeStates.txt`, `InitStates.ini`,
+/// `Checkpoint.txt`; one state per line, `#` lines skipped) or a `TimeStates.bin`, told
+/// apart by the magic. Holds one frame at a time, so files of any size can be streamed;
+/// `TimeStates.zst` is read by `convert` first.
+///
+/// The `#end` line of a frame is checked against it, a mismatch is an error. A final
+/// frame cut while being written (no newline, no `#end` line in a file that has them, a
+/// short binary frame) ends the file with a warning.
+pub struct StateFrames {
+    reader: BufReader<File>,
+    /// Encoding flag and cells per frame of a binary file, `None` - text.
+    bin: Option<(bool, usize)>,
+    header: Option<StateHeader>,
+    line: Vec<u8>,
+    line_id: usize,
+    /// Line read after a frame in place of its `#end` line, taken by the next call.
+    next_line: Vec<u8>,
+    /// Whether the frames have `#end` lines (seen one).
+    is_marked: bool,
+    /// Complete frames read.
+    frames: usize,
+    path: PathBuf,
+}
+
+impl StateFrames {
+    pub fn open(path: &Path) -> IoResult<Self> {
+        let file = File::open(path).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to open file '{}': {}", path.display(), e),
+            )
+        })?;
+        let mut reader = BufReader::new(file);
+        let bin = match reader.fill_buf()?.starts_with(TIME_STATES_BIN_MAGIC) {
+            true => {
+                let (packed, frame_len, _) = read_states_bin_header(&mut reader)?;
+                Some((packed, frame_len))
+            }
+            false => None,
+        };
+
+        Ok(Self {
+            reader,
+            bin,
+            header: None,
+            line: Vec::new(),
+            line_id: 0,
+            next_line: Vec::new(),
+            is_marked: false,
+            frames: 0,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// `#states` header of the frames read so far (text files).
+    pub fn header(&self) -> Option<StateHeader> {
+        self.header
+    }
+
+    /// Reads the next frame into `frame`; `false` at the end of the file.
+    pub fn next_frame(&mut self, frame: &mut Vec<u8>) -> IoResult<bool> {
+        if let Some((packed, frame_len)) = self.bin {
+            if self.reader.fill_buf()?.is_empty() {
+                return Ok(false);
+            }
+            match read_state_bin(&mut self.reader, frame_len, packed) {
+                Ok(values) => *frame = values,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    warn_cut_frame(&self.path, self.frames);
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            }
+            self.frames += 1;
+            return Ok(true);
+        }
+
+        loop {
+            self.line.clear();
+            if self.next_line.is_empty() {
+                if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                    return Ok(false);
+                }
+                self.line_id += 1;
+            } else {
+                std::mem::swap(&mut self.line, &mut self.next_line);
+            }
+            let trimmed = self.line.trim_ascii();
+
+            if frame_end_checksum(trimmed).is_some() {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    self.line_err("frame end without a frame".to_string()),
+                ));
+            }
+            if trimmed.starts_with(b"#") {
+                let text = String::from_utf8_lossy(trimmed);
+                if StateHeader::is_header(&text) {
+                    self.header = Some(
+                        StateHeader::parse(&text)
+                            .map_err(|e| IoError::new(ErrorKind::InvalidData, self.line_err(e)))?,
+                    );
+                }
+                continue;
+            }
+            if trimmed.is_empty() {
+                continue;
+            }
+            if !self.line.ends_with(b"\n") {
+                warn_cut_frame(&self.path, self.frames);
+                return Ok(false);
+            }
+
+            *frame = match &self.header {
+                Some(header) => header.parse_values(trimmed),
+                None => parse_state_line(trimmed, u8::MAX)
+                    .map_err(|s| format!("value '{}' is not a cell state", s)),
+            }
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, self.line_err(e)))?;
+
+            // The `#end` line right after the frame, if the file has them
+            if self.reader.read_until(b'\n', &mut self.next_line)? > 0 {
+                self.line_id += 1;
+            }
+            match frame_end_checksum(self.next_line.trim_ascii()) {
+                // The `#end` line itself cut
+                Some(_) if !self.next_line.ends_with(b"\n") => {
+                    warn_cut_frame(&self.path, self.frames);
+                    return Ok(false);
+                }
+                Some(checksum) => {
+                    let checksum = checksum
+                        .map_err(|e| IoError::new(ErrorKind::InvalidData, self.line_err(e)))?;
+                    if frame_checksum(frame) != checksum {
+                        return Err(IoError::new(
+                            ErrorKind::InvalidData,
+                            self.line_err(format!(
+                                "checksum mismatch of frame {}: corrupt frame",
+                                self.frames
+                            )),
+                        ));
+                    }
+                    self.next_line.clear();
+                    self.is_marked = true;
+                }
+                None if self.is_marked && self.next_line.is_empty() => {
+                    warn_cut_frame(&self.path, self.frames);
+                    return Ok(false);
+                }
+                None if self.is_marked => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        self.line_err(format!("frame {} has no end line", self.frames)),
+                    ));
+                }
+                None => {}
+            }
+            self.frames += 1;
+            return Ok(true);
+        }
+    }
+
+    fn line_err(&self, msg: String) -> String {
+        format!("{} (line {}): {}", self.path.display(), self.line_id, msg)
+    }
+}
+
+/// First difference of two state files (`compare_state_files`); frames and cells are
+/// 0-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateDiff {
+    /// All `frames` frames are equal cell by cell.
+    Identical { frames: usize },
+    /// First differing cell of the first differing frame, with its value in A and B.
+    Cell {
+        frame: usize,
+        cell: usize,
+        a: u8,
+        b: u8,
+    },
+    /// Frame `frame` has `len_a` cells in A and `len_b` in B.
+    Length {
+        frame: usize,
+        len_a: usize,
+        len_b: usize,
+    },
+    /// The first `frames` frames are equal, then only A (`is_a_longer`) or B goes on.
+    Frames { frames: usize, is_a_longer: bool },
+}
+
+impl std::fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Identical { frames } => write!(f, "identical ({} frames)", frames),
+            Self::Cell { frame, cell, a, b } => {
+                write!(
+                    f,
+                    "frame {} differs first at cell {} ({} | {})",
+                    frame, cell, a, b
+                )
+            }
+            Self::Length {
+                frame,
+                len_a,
+                len_b,
+            } => {
+                write!(f, "frame {} has {} | {} cells", frame, len_a, len_b)
+            }
+            Self::Frames {
+                frames,
+                is_a_longer,
+            } => write!(
+                f,
+                "equal for {} frames, then only {} goes on",
+                frames,
+                if is_a_longer { "A" } else { "B" }
+            ),
+        }
+    }
+}
+
+/// Streams two state files (text or binary, in any combination) frame by frame and
+/// stops at the first difference; memory holds one frame of each.
+pub fn compare_state_files(path_a: &Path, path_b: &Path) -> IoResult<StateDiff> {
+    let mut frames_a = StateFrames::open(path_a)?;
+    let mut frames_b = StateFrames::open(path_b)?;
+    let (mut frame_a, mut frame_b) = (Vec::new(), Vec::new());
+
+    let mut frame = 0;
+    loop {
+        match (
+            frames_a.next_frame(&mut frame_a)?,
+            frames_b.next_frame(&mut frame_b)?,
+        ) {
+            (false, false) => return Ok(StateDiff::Identical { frames: frame }),
+            (true, true) => {}
+            (is_a_longer, _) => {
+                return Ok(StateDiff::Frames {
+                    frames: frame,
+                    is_a_longer,
+                });
+            }
+        }
+
+        if frame_a.len() != frame_b.len() {
+            return Ok(StateDiff::Length {
+                frame,
+                len_a: frame_a.len(),
+                len_b: frame_b.len(),
+            });
+        }
+        if let Some(cell) = frame_a.iter().zip(&frame_b).position(|(a, b)| a != b) {
+            return Ok(StateDiff::Cell {
+                frame,
+                cell,
+                a: frame_a[cell],
+                b: frame_b[cell],
+            });
+        }
+        frame += 1;
+    }
+}
@@ -1,21 +1,169 @@
 use crate::mods::{
-    constants::{SIM_LOG_FILE_NAME, TIME_STATES_FILE_NAME},
+    acceptance::AcceptanceRule,
+    audit::{RngAudit, RngEvent},
+    ballistics::BallisticLaw,
+    clusters::ClusterTracker,
+    constants::{
+        CLUSTERS_FILE_NAME, DENSITY_PROFILE_FILE_STEM, DETACH_SPECTRUM_FILE_NAME, EVENTS_FILE_NAME,
+        FRAME_END_TAG, FRONT_FILE_NAME, K_BOLTZMANN, PROBES_FILE_NAME, SIM_LOG_FILE_NAME,
+        TIME_STATES_FILE_NAME, TIME_STATES_STEPS_FILE_NAME,
+    },
+    detach_spectrum::DetachSpectrum,
+    energy::{attach_energy, detach_energy},
+    events::{EventExport, EventKind},
+    facets::FacetStats,
+    faults::{self, FaultPoint},
+    front_export::FrontExport,
     frontier::Frontier,
+    heat::HeatField,
     io_handler,
-    lattice::Grid,
+    lattice::{Grid, Idx, NO_NEIB},
+    passage::FirstPassage,
+    passivation::Passivation,
+    probes::SiteProbes,
+    profile::DensityProfile,
+    residence::ResidenceStats,
+    reweight::EnergyHistogram,
+    rng::KernelRng,
     settings::Settings,
-    state::SimLog,
-    utils::compute_neighbor_sums,
+    state::{CrystalFaces, SimLog},
+    state_header::StateHeader,
+    sublattice::SubLattice,
+    utils::{
+        compute_neighbor_sums, event_attempts, fill_front, front_divergence, is_face_masked,
+        kossel_attach_energy, kossel_detach_energy,
+    },
+    writer::WriterHandle,
 };
 use rand::SeedableRng;
-use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 use std::{
-    fs::{self, File},
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
     io::{BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write},
-    path::PathBuf,
+    mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
+/// Which per-item snapshots end up in `TimeStates.txt`.
+/// `interval == 0` keeps every write step, `keep_first`/`keep_last == 0` disable the limits.
+/// `max_bytes > 0` caps the file size during the run: when it is exceeded, every second
+/// stored snapshot is dropped and only every second further one is written.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnapshotPolicy {
+    pub interval: u64,
+    pub keep_first: usize,
+    pub keep_last: usize,
+    pub max_bytes: u64,
+}
+
+/// Part of the item grid written to `TimeStates.txt`: the box `lo..=hi` (cells per axis)
+/// taking every `stride`-th cell from `lo`. Recorded as the first line of the file,
+/// `# grid=SX:SY:SZ roi=X0:X1:Y0:Y1:Z0:Z1 stride=K dims=NX:NY:NZ`, frames then hold
+/// `NX*NY*NZ` values in the usual x-y-z order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotRegion {
+    pub grid: (usize, usize, usize),
+    pub lo: [usize; 3],
+    pub hi: [usize; 3],
+    pub stride: usize,
+}
+
+impl SnapshotRegion {
+    /// Clips `roi` (inclusive bounds, `None` - whole grid) to the grid.
+    pub fn new(
+        grid: (usize, usize, usize),
+        roi: Option<[(usize, usize); 3]>,
+        stride: usize,
+    ) -> Result<Self, String> {
+        let sizes = [grid.0, grid.1, grid.2];
+        let (mut lo, mut hi) = ([0; 3], [0; 3]);
+        for axis in 0..3 {
+            let (a, b) = roi.map_or((0, sizes[axis] - 1), |roi| roi[axis]);
+            if a >= sizes[axis] {
+                return Err(format!(
+                    "box {:?} lies outside the {}x{}x{} grid",
+                    roi, grid.0, grid.1, grid.2
+                ));
+            }
+            (lo[axis], hi[axis]) = (a, b.min(sizes[axis] - 1));
+        }
+
+        Ok(Self {
+            grid,
+            lo,
+            hi,
+            stride: stride.max(1),
+        })
+    }
+
+    pub fn dims(&self) -> (usize, usize, usize) {
+        let n = |axis: usize| (self.hi[axis] - self.lo[axis]) / self.stride + 1;
+        (n(0), n(1), n(2))
+    }
+
+    /// Grid indices of the written cells, in frame order.
+    pub fn cells(&self) -> Vec<usize> {
+        let (sx, sy, sz) = self.grid;
+        Grid::shape(sx, sy, sz)
+            .region(self.lo, self.hi, self.stride)
+            .collect()
+    }
+
+    /// Grid coordinates of the frame cell `(i, j, k)`.
+    pub fn to_grid_xyz(&self, (i, j, k): (usize, usize, usize)) -> (usize, usize, usize) {
+        (
+            self.lo[0] + i * self.stride,
+            self.lo[1] + j * self.stride,
+            self.lo[2] + k * self.stride,
+        )
+    }
+
+    pub fn to_header(&self) -> String {
+        let (nx, ny, nz) = self.dims();
+        format!(
+            "# grid={}:{}:{} roi={}:{}:{}:{}:{}:{} stride={} dims={}:{}:{}",
+            self.grid.0,
+            self.grid.1,
+            self.grid.2,
+            self.lo[0],
+            self.hi[0],
+            self.lo[1],
+            self.hi[1],
+            self.lo[2],
+            self.hi[2],
+            self.stride,
+            nx,
+            ny,
+            nz
+        )
+    }
+
+    /// Parses a line written by `to_header`.
+    pub fn from_header(line: &str) -> Option<Self> {
+        let fields = line.trim().strip_prefix('#')?;
+        let value = |key: &str| -> Option<Vec<usize>> {
+            let (_, value) = fields
+                .split_whitespace()
+                .filter_map(|field| field.split_once('='))
+                .find(|(k, _)| *k == key)?;
+            value.split(':').map(|v| v.parse().ok()).collect()
+        };
+
+        let (grid, roi, stride) = (value("grid")?, value("roi")?, value("stride")?);
+        match (&grid[..], &roi[..], &stride[..]) {
+            (&[sx, sy, sz], &[x0, x1, y0, y1, z0, z1], &[stride]) => Some(Self {
+                grid: (sx, sy, sz),
+                lo: [x0, y0, z0],
+                hi: [x1, y1, z1],
+                stride,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Item {
     pub item_gid: usize,
@@ -26,10 +174,93 @@ pub struct Item {
     pub path_dst: PathBuf,
     pub path_time_states: PathBuf,
     pub time_states_fbuf: BufWriter<File>,
+    pub snapshot_policy: SnapshotPolicy,
+    pub snapshot_steps: Vec<u64>,
+    pub snapshot_tail: VecDeque<(u64, Box<[u8]>)>,
+    /// Grid indices written per snapshot (`SnapshotRegion::cells`); empty - the whole state.
+    pub snapshot_cells: Vec<usize>,
+    /// Snapshot candidates seen so far and the thinning factor (every `snapshot_thin`-th
+    /// one is written), see `SnapshotPolicy::max_bytes`.
+    pub snapshot_seq: u64,
+    pub snapshot_thin: u64,
+    /// Snapshots that failed to write (full disk, lost permissions); the run goes on.
+    pub snapshots_lost: u64,
+    /// Time spent in `write_action` since the ensemble last took it (`WriteIoPct`).
+    pub write_time: Duration,
+    /// Queue of the background writer (`WriteQueue > 0`); `None` - written in place.
+    pub writer: Option<WriterHandle>,
+    /// Own random stream (`Deterministic`); `None` - the item draws from the ensemble's.
+    pub rng: Option<ChaCha8Rng>,
+    pub rng_audit: RngAudit,
+    pub facet_stats: FacetStats,
+    pub residence: ResidenceStats,
+    pub profile: DensityProfile,
+    pub events: EventExport,
+    pub front_export: FrontExport,
+    pub detach_spectrum: DetachSpectrum,
+    pub first_passage: FirstPassage,
+    pub probes: SiteProbes,
+    pub stall_thr: usize,
+    pub is_stall_watch: bool,
+    /// Redraws of a front site that no longer holds the state of its front (`StaleRetry`).
+    pub stale_retry: u32,
+    /// Stale front sites drawn so far; each one is dropped from its front.
+    pub stale_sites: u64,
+    /// Below `throttle_thr` TPA or TPB sites (0 - off) the item runs only every
+    /// `throttle_i`-th step (`ThrottleThr`, `ThrottleI`); skipped steps so far.
+    pub throttle_thr: usize,
+    pub throttle_i: u64,
+    pub throttled_steps: u64,
+    /// Mean attempts per step of add, rem and ballistic events (`PoissonAdd`,
+    /// `PoissonRem`, `PoissonBal`); 0 - exactly one.
+    pub poisson_means: (f64, f64, f64),
+    /// Sites that never detach (`Anchor`), empty - off; detachment attempts rejected on
+    /// them so far.
+    pub anchor: Box<[bool]>,
+    pub anchored_attempts: u64,
+    pub sub_lattice: SubLattice,
+    pub sub_lattice_skips: u64,
+    /// Sites blocked by adsorbed species (`PassRate`, `PassLife`); off by default.
+    pub passivation: Passivation,
+    /// Coarse temperature field fed by the latent heat (`HeatRelease`); off by default.
+    pub heat: HeatField,
+    /// Crystal identity of every cell and coalescences (`ClusterTrack`); off by default.
+    pub clusters: ClusterTracker,
+    /// Crystal volume and exposed faces for the morphology rows, kept by the kernels.
+    pub faces: CrystalFaces,
+    /// Visited `(E, n_cryst)` for reweighting (`EnergyHistBin`); off by default.
+    pub energy_hist: EnergyHistogram,
+    /// Every `front_rebuild_i`-th step (0 - off) the front is rebuilt from the state in
+    /// `front_scratch` and compared (`FrontRebuildI`); with `front_rebuild_adopt` a
+    /// diverged front is replaced by the rebuilt one. Checks with a divergence so far and
+    /// the largest one.
+    pub front_rebuild_i: u64,
+    pub front_rebuild_adopt: bool,
+    front_scratch: Option<Frontier>,
+    pub front_div_checks: u64,
+    pub front_div_max: usize,
+    pub is_bond_counting: bool,
+    pub acceptance: AcceptanceRule,
+    /// Grid of the item: 0 - the ensemble grid, `k` - `Ensemble::item_grids[k - 1]`.
+    pub grid_id: usize,
+}
+
+/// Face energies, face mask and ballistic law of a mode 2.3 step.
+pub struct Mode23Params<'a> {
+    pub e2: (f64, f64, f64),
+    pub face_mask: u8,
+    pub ballistic: &'a mut BallisticLaw,
 }
 
 impl Item {
-    pub fn new(item_gid: usize, size: usize, dst_dir: PathBuf) -> IoResult<Self> {
+    /// Takes over the loaded `state` (no second full-size buffer per item) and a `front`
+    /// from `Frontier::new`.
+    pub fn new(
+        item_gid: usize,
+        state: Box<[u8]>,
+        front: Frontier,
+        dst_dir: PathBuf,
+    ) -> IoResult<Self> {
         fs::create_dir_all(&dst_dir).map_err(|e| {
             IoError::new(
                 e.kind(),
@@ -52,9 +283,6 @@ impl Item {
         let mut simlog = SimLog::new();
         let _ = simlog.create_out_file(dst_dir.clone());
 
-        let state = vec![0; size].into_boxed_slice();
-        let front = Frontier::new(size);
-
         Ok(Self {
             item_gid,
             is_alive: true,
@@ -64,6 +292,50 @@ impl Item {
             path_dst: dst_dir,
             path_time_states,
             time_states_fbuf,
+            snapshot_policy: SnapshotPolicy::default(),
+            snapshot_steps: Vec::new(),
+            snapshot_tail: VecDeque::new(),
+            snapshot_cells: Vec::new(),
+            snapshot_seq: 0,
+            snapshot_thin: 1,
+            snapshots_lost: 0,
+            write_time: Duration::ZERO,
+            writer: None,
+            rng: None,
+            rng_audit: RngAudit::default(),
+            facet_stats: FacetStats::default(),
+            residence: ResidenceStats::default(),
+            profile: DensityProfile::default(),
+            events: EventExport::default(),
+            front_export: FrontExport::default(),
+            detach_spectrum: DetachSpectrum::default(),
+            first_passage: FirstPassage::default(),
+            probes: SiteProbes::default(),
+            stall_thr: 0,
+            is_stall_watch: false,
+            stale_retry: 0,
+            stale_sites: 0,
+            throttle_thr: 0,
+            throttle_i: 1,
+            throttled_steps: 0,
+            poisson_means: (0.0, 0.0, 0.0),
+            anchor: Box::new([]),
+            anchored_attempts: 0,
+            sub_lattice: SubLattice::Off,
+            sub_lattice_skips: 0,
+            passivation: Passivation::default(),
+            heat: HeatField::default(),
+            clusters: ClusterTracker::default(),
+            faces: CrystalFaces::default(),
+            energy_hist: EnergyHistogram::default(),
+            front_rebuild_i: 0,
+            front_rebuild_adopt: false,
+            front_scratch: None,
+            front_div_checks: 0,
+            front_div_max: 0,
+            is_bond_counting: false,
+            acceptance: AcceptanceRule::default(),
+            grid_id: 0,
         })
     }
 
@@ -80,6 +352,110 @@ impl Item {
         self.is_alive = false;
     }
 
+    /// Dense writing near a stall: while the TPA or TPB front is smaller than
+    /// `stall_thr` (0 - off) every step is written, regardless of WriteI/ItemWriteI.
+    fn stall_watch(&mut self, step_id: u64) -> bool {
+        if self.stall_thr == 0 {
+            return false;
+        }
+
+        let is_low = self.front.tpas_size < self.stall_thr || self.front.tpbs_size < self.stall_thr;
+        if is_low != self.is_stall_watch {
+            println!(
+                "[Item ID: {:05}] Step: {} -> Front | TPA: {} - TPB: {} | {} threshold {}: {}.",
+                self.item_gid,
+                step_id,
+                self.front.tpas_size,
+                self.front.tpbs_size,
+                if is_low { "below" } else { "back above" },
+                self.stall_thr,
+                if is_low {
+                    "writing every step"
+                } else {
+                    "regular writes"
+                },
+            );
+            self.is_stall_watch = is_low;
+        }
+
+        self.is_stall_watch
+    }
+
+    /// Whether the ensemble skips `step_id` for this item: near a stall (TPA or TPB front
+    /// smaller than `throttle_thr`) only every `throttle_i`-th step and the write steps
+    /// run. The item's clock slows down accordingly; its crystal keeps counting in the
+    /// ensemble as it is, so a skipped step neither takes nor gives back gas.
+    pub fn is_throttled(&mut self, step_id: u64, is_write_step: bool) -> bool {
+        if self.throttle_thr == 0 || is_write_step || step_id.is_multiple_of(self.throttle_i) {
+            return false;
+        }
+
+        let is_low =
+            self.front.tpas_size < self.throttle_thr || self.front.tpbs_size < self.throttle_thr;
+        if is_low {
+            self.throttled_steps += 1;
+        }
+        is_low
+    }
+
+    /// Rebuilds the front from the state on the `front_rebuild_i`-th steps and records in
+    /// how many sites the incremental one differs (`front_div`); adopts the rebuilt front
+    /// on a divergence if `front_rebuild_adopt`.
+    fn check_front(&mut self, neibs: &[[Idx; 6]], step_id: u64) {
+        if self.front_rebuild_i == 0 || !step_id.is_multiple_of(self.front_rebuild_i) {
+            return;
+        }
+
+        let scratch = self
+            .front_scratch
+            .get_or_insert_with(|| Frontier::new(self.state.len()));
+        scratch.clear();
+        fill_front(&self.state, neibs, scratch);
+
+        let divergence = front_divergence(&self.front, scratch);
+        self.simlog.front_div.val = divergence;
+        if divergence > 0 {
+            self.front_div_checks += 1;
+            self.front_div_max = self.front_div_max.max(divergence);
+            if self.front_rebuild_adopt {
+                std::mem::swap(&mut self.front, scratch);
+            }
+        }
+    }
+
+    /// Whether the crystal site `idxg` is anchored; counts the rejected attempt.
+    #[inline(always)]
+    fn is_anchored(&mut self, idxg: usize) -> bool {
+        let is_anchored = !self.anchor.is_empty() && self.anchor[idxg];
+        if is_anchored {
+            self.anchored_attempts += 1;
+        }
+        is_anchored
+    }
+
+    /// Whether the site `idxg` is off the sub-lattice of `step_id` (`SubLattice`); counts
+    /// the skipped attempt.
+    #[inline(always)]
+    fn is_off_sub_lattice(&mut self, grid: &Grid, idxg: usize, step_id: u64) -> bool {
+        let is_off = !self.sub_lattice.allows(grid, idxg, step_id);
+        if is_off {
+            self.sub_lattice_skips += 1;
+        }
+        is_off
+    }
+
+    /// Diffusion step of the heat field and its `SimLog` channels.
+    fn heat_step(&mut self) {
+        if !self.heat.is_on() {
+            return;
+        }
+
+        self.heat.step();
+        let temp = self.simlog.k_t / K_BOLTZMANN;
+        self.simlog.heat_t_mean.val = temp + self.heat.mean_excess();
+        self.simlog.heat_t_max.val = temp + self.heat.max_excess();
+    }
+
     fn handle_stalled_boundary(&mut self, step_id: u64) {
         self.simlog.mk_step.val = step_id;
         println!(
@@ -89,12 +465,440 @@ impl Item {
         self.is_alive = false;
     }
 
-    pub fn write_action(&mut self, grid: &mut Grid) {
-        let _ = io_handler::write_state(&mut self.time_states_fbuf, &self.state);
-        let _ = self.time_states_fbuf.flush();
+    /// Records the shape of the snapshot frames as the `TimeStates.txt` `#states` header.
+    pub fn write_states_header(&mut self, header: StateHeader) -> IoResult<()> {
+        writeln!(self.time_states_fbuf, "{}", header)
+    }
+
+    /// Restricts the snapshots to `region` and records it as the `TimeStates.txt` header.
+    pub fn set_snapshot_region(&mut self, region: SnapshotRegion) -> IoResult<()> {
+        writeln!(self.time_states_fbuf, "{}", region.to_header())?;
+        self.snapshot_cells = region.cells();
+        Ok(())
+    }
+
+    fn write_snapshot(
+        fbuf: &mut BufWriter<File>,
+        snapshot_steps: &mut Vec<u64>,
+        snapshot_cells: &[usize],
+        step_id: u64,
+        state: &[u8],
+    ) -> IoResult<()> {
+        faults::inject(FaultPoint::Snapshot)?;
+        match snapshot_cells.is_empty() {
+            true => io_handler::write_frame(fbuf, state)?,
+            false => {
+                let frame: Vec<u8> = snapshot_cells.iter().map(|&idx| state[idx]).collect();
+                io_handler::write_frame(fbuf, &frame)?
+            }
+        };
+        fbuf.flush()?;
+        snapshot_steps.push(step_id);
+        Ok(())
+    }
+
+    /// Writes a snapshot of `state` (`None` - the current state). A failed write is cut
+    /// from `TimeStates.txt` and skipped, so the file stays readable and the run goes on;
+    /// the first failure of the item is reported, the count at the end.
+    fn write_snapshot_or_skip(&mut self, step_id: u64, state: Option<&[u8]>, is_final: bool) {
+        if self.writer.is_some() {
+            self.queue_snapshot(step_id, state, is_final);
+            return;
+        }
+
+        let len_before = self
+            .time_states_fbuf
+            .flush()
+            .and_then(|_| self.time_states_fbuf.get_ref().metadata())
+            .map(|m| m.len());
+        let written = Self::write_snapshot(
+            &mut self.time_states_fbuf,
+            &mut self.snapshot_steps,
+            &self.snapshot_cells,
+            step_id,
+            state.unwrap_or(&self.state[..]),
+        );
+        let Err(e) = written else {
+            return;
+        };
+
+        self.snapshots_lost += 1;
+        if self.snapshots_lost == 1 {
+            eprintln!(
+                "[Item ID: {:05}] ⚠️ Warning: Failed to write the snapshot of step {} to '{}': {}; skipping failed snapshots.",
+                self.item_gid,
+                step_id,
+                self.path_time_states.display(),
+                e
+            );
+        }
+        if let Err(e) = len_before.and_then(|len| self.cut_time_states(len)) {
+            eprintln!(
+                "[Item ID: {:05}] ⚠️ Warning: Failed to drop the partial snapshot from '{}': {}",
+                self.item_gid,
+                self.path_time_states.display(),
+                e
+            );
+        }
+    }
+
+    /// Hands a snapshot to the background writer; the header lines written so far go to
+    /// the file first. A snapshot dropped on a full queue is not listed in
+    /// `TimeStatesSteps.txt`.
+    fn queue_snapshot(&mut self, step_id: u64, state: Option<&[u8]>, is_final: bool) {
+        let Some(writer) = self.writer.as_ref() else {
+            return;
+        };
+        if let Err(e) = self.time_states_fbuf.flush() {
+            eprintln!(
+                "[Item ID: {:05}] ⚠️ Warning: Failed to write the header of '{}': {}",
+                self.item_gid,
+                self.path_time_states.display(),
+                e
+            );
+        }
+
+        let state = state.unwrap_or(&self.state[..]);
+        let frame = match self.snapshot_cells.is_empty() {
+            true => state.into(),
+            false => self.snapshot_cells.iter().map(|&idx| state[idx]).collect(),
+        };
+        if writer.send(
+            self.item_gid,
+            &self.path_time_states,
+            step_id,
+            frame,
+            is_final,
+        ) {
+            self.snapshot_steps.push(step_id);
+        }
+    }
+
+    /// Truncates `TimeStates.txt` to `len` bytes, discarding the unwritten buffer.
+    fn cut_time_states(&mut self, len: u64) -> IoResult<()> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&self.path_time_states)?;
+        let _ = mem::replace(&mut self.time_states_fbuf, BufWriter::new(file)).into_parts();
+        self.time_states_fbuf.get_ref().set_len(len)
+    }
+
+    fn snapshot_action(&mut self, is_final: bool) {
+        let step_id = self.simlog.mk_step.val;
+        let policy = self.snapshot_policy;
+
+        let on_interval =
+            policy.interval == 0 || step_id.is_multiple_of(policy.interval) || self.is_stall_watch;
+        if !on_interval && !is_final {
+            return;
+        }
+
+        if !is_final {
+            self.snapshot_seq += 1;
+            if !(self.snapshot_seq - 1).is_multiple_of(self.snapshot_thin) {
+                return;
+            }
+        }
+
+        if self.snapshot_steps.len() < policy.keep_first
+            || (policy.keep_first == 0 && policy.keep_last == 0)
+        {
+            self.write_snapshot_or_skip(step_id, None, is_final);
+        } else if policy.keep_last > 0 {
+            if self.snapshot_tail.len() == policy.keep_last {
+                self.snapshot_tail.pop_front();
+            }
+            self.snapshot_tail.push_back((step_id, self.state.clone()));
+        } else if is_final {
+            self.write_snapshot_or_skip(step_id, None, true);
+        }
+
+        if is_final {
+            while let Some((tail_step, tail_state)) = self.snapshot_tail.pop_front() {
+                self.write_snapshot_or_skip(tail_step, Some(&tail_state[..]), true);
+            }
+            if let Some(writer) = self.writer.as_ref() {
+                writer.close(self.item_gid);
+            }
+
+            let path_steps = self.path_dst.join(TIME_STATES_STEPS_FILE_NAME);
+            if let Ok(file) = File::create(&path_steps) {
+                let mut buf = BufWriter::new(file);
+                let _ = io_handler::write_state_uni(&mut buf, &self.snapshot_steps, &|v: u64| {
+                    v.to_string()
+                });
+                let _ = buf.flush();
+            }
+
+            if self.snapshots_lost > 0 {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: {} snapshot(s) were not written to '{}'.",
+                    self.item_gid,
+                    self.snapshots_lost,
+                    self.path_time_states.display()
+                );
+            }
+            if self.stale_sites > 0 {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: {} stale front site(s) were drawn and dropped.",
+                    self.item_gid, self.stale_sites
+                );
+            }
+            if self.throttled_steps > 0 {
+                println!(
+                    "[Item ID: {:05}] 📒 {} step(s) skipped near the stall (ThrottleThr).",
+                    self.item_gid, self.throttled_steps
+                );
+            }
+            if self.front_div_checks > 0 {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: the front diverged from its rebuild at {} check(s), by up to {} site(s){}.",
+                    self.item_gid,
+                    self.front_div_checks,
+                    self.front_div_max,
+                    match self.front_rebuild_adopt {
+                        true => "; the rebuilt front was adopted",
+                        false => "",
+                    }
+                );
+            }
+            if self.anchored_attempts > 0 {
+                println!(
+                    "[Item ID: {:05}] 📒 {} detachment attempt(s) on anchored sites rejected (Anchor).",
+                    self.item_gid, self.anchored_attempts
+                );
+            }
+            if self.sub_lattice_skips > 0 {
+                println!(
+                    "[Item ID: {:05}] 📒 {} event attempt(s) off the active sub-lattice skipped (SubLattice).",
+                    self.item_gid, self.sub_lattice_skips
+                );
+            }
+            if self.passivation.is_on() {
+                println!(
+                    "[Item ID: {:05}] 📒 Passivation: {} adsorbed, {} desorbed, {} event(s) blocked.",
+                    self.item_gid,
+                    self.passivation.adsorbed,
+                    self.passivation.desorbed,
+                    self.passivation.blocked_attempts
+                );
+            }
+            if self.heat.is_on() {
+                println!(
+                    "[Item ID: {:05}] 📒 Heat field: peak excess {:.3} K, {} test(s) at the temperature floor.",
+                    self.item_gid, self.heat.peak, self.heat.floored
+                );
+            }
+            if self.clusters.is_on {
+                println!(
+                    "[Item ID: {:05}] 📒 Clusters: {} present, {} coalescence(s).",
+                    self.item_gid,
+                    self.clusters.count(),
+                    self.clusters.n_merges
+                );
+            }
+        } else if policy.max_bytes > 0 {
+            let file_len = self
+                .time_states_fbuf
+                .get_ref()
+                .metadata()
+                .map_or(0, |m| m.len());
+            if file_len > policy.max_bytes
+                && self.snapshot_steps.len() > 1
+                && let Err(e) = self.thin_snapshots(step_id, file_len)
+            {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: Failed to thin '{}': {}",
+                    self.item_gid,
+                    self.path_time_states.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Rewrites `TimeStates.txt` without every second snapshot (header lines are kept)
+    /// and halves the rate of further ones.
+    fn thin_snapshots(&mut self, step_id: u64, file_len: u64) -> IoResult<()> {
+        self.time_states_fbuf.flush()?;
+
+        // The kept frames are streamed into a temporary file that replaces the original
+        // only once complete, so a crash or a full disk leaves the history intact.
+        let path_tmp = self.path_time_states.with_extension("txt.thin.tmp");
+        let written = self.write_thinned(&path_tmp);
+        let kept_len = match written
+            .and_then(|len| fs::rename(&path_tmp, &self.path_time_states).map(|_| len))
+        {
+            Ok(len) => len,
+            Err(e) => {
+                let _ = fs::remove_file(&path_tmp);
+                return Err(e);
+            }
+        };
+
+        self.time_states_fbuf = BufWriter::new(
+            OpenOptions::new()
+                .append(true)
+                .open(&self.path_time_states)?,
+        );
+        self.snapshot_steps = self.snapshot_steps.iter().copied().step_by(2).collect();
+        self.snapshot_thin *= 2;
+
+        println!(
+            "[Item ID: {:05}] Step: {} -> TimeStates.txt reached {} bytes, thinned to {} bytes ({} snapshots); writing 1 in {} from now on.",
+            self.item_gid,
+            step_id,
+            file_len,
+            kept_len,
+            self.snapshot_steps.len(),
+            self.snapshot_thin
+        );
+
+        Ok(())
+    }
+
+    /// Every other frame of `TimeStates.txt` (with its `#end` line) and the other
+    /// comment lines, written to `path` and synced; returns the bytes written.
+    fn write_thinned(&self, path: &Path) -> IoResult<u64> {
+        let mut reader = BufReader::new(File::open(&self.path_time_states)?);
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut line = Vec::new();
+        let (mut frame_id, mut is_frame_kept, mut kept_len) = (0u64, true, 0u64);
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let text = line.trim_ascii_end();
+            if text.is_empty() {
+                continue;
+            }
+            let is_kept = match text.starts_with(b"#") {
+                // The `#end` line goes with its frame
+                true => !text.starts_with(FRAME_END_TAG.as_bytes()) || is_frame_kept,
+                false => {
+                    is_frame_kept = frame_id.is_multiple_of(2);
+                    frame_id += 1;
+                    is_frame_kept
+                }
+            };
+            if is_kept {
+                writer.write_all(text)?;
+                writer.write_all(b"\n")?;
+                kept_len += text.len() as u64 + 1;
+            }
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        Ok(kept_len)
+    }
+
+    pub fn write_action(&mut self, grid: &mut Grid, is_final: bool) {
+        let started = Instant::now();
+        self.snapshot_action(is_final);
+
+        if is_final && self.residence.is_on {
+            let written = self
+                .residence
+                .write_hist(&self.path_dst)
+                .and_then(|_| self.residence.write_age_map(&self.path_dst, &self.state));
+            if let Err(e) = written {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: Failed to write residence statistics: {}",
+                    self.item_gid, e
+                );
+            }
+        }
+
+        if is_final
+            && self.energy_hist.is_on()
+            && let Err(e) = self.energy_hist.write(&self.path_dst)
+        {
+            eprintln!(
+                "[Item ID: {:05}] ⚠️ Warning: Failed to write the energy histogram: {}",
+                self.item_gid, e
+            );
+        }
+
+        if self.profile.is_on {
+            let step_id = self.simlog.mk_step.val;
+            let written = self
+                .profile
+                .write_step(&self.path_dst, step_id, &self.state, grid)
+                .and_then(|_| match is_final {
+                    true => self.profile.finish(),
+                    false => Ok(()),
+                });
+            faults::disable_on_error(written, DENSITY_PROFILE_FILE_STEM, &mut self.profile.is_on);
+        }
+
+        if self.events.is_on {
+            let step_id = self.simlog.mk_step.val;
+            let written = self
+                .events
+                .write_frame(&self.path_dst, step_id, grid, self.simlog.cell)
+                .and_then(|_| match is_final {
+                    true => self.events.finish(),
+                    false => Ok(()),
+                });
+            faults::disable_on_error(written, EVENTS_FILE_NAME, &mut self.events.is_on);
+        }
+
+        if self.front_export.is_on {
+            let step_id = self.simlog.mk_step.val;
+            let written = self
+                .front_export
+                .write_frame(&self.path_dst, step_id, &self.state, &self.front, grid)
+                .and_then(|_| match is_final {
+                    true => self.front_export.finish(),
+                    false => Ok(()),
+                });
+            faults::disable_on_error(written, FRONT_FILE_NAME, &mut self.front_export.is_on);
+        }
+
+        if self.probes.is_on {
+            let written = self
+                .probes
+                .write_events(&self.path_dst)
+                .and_then(|_| match is_final {
+                    true => self.probes.finish(),
+                    false => Ok(()),
+                });
+            faults::disable_on_error(written, PROBES_FILE_NAME, &mut self.probes.is_on);
+        }
+
+        if is_final && self.detach_spectrum.is_on {
+            let written = self.detach_spectrum.finish();
+            faults::disable_on_error(
+                written,
+                DETACH_SPECTRUM_FILE_NAME,
+                &mut self.detach_spectrum.is_on,
+            );
+        }
+
+        if self.clusters.is_on {
+            let step_id = self.simlog.mk_step.val;
+            let written = self
+                .clusters
+                .write_step(&self.path_dst, step_id)
+                .and_then(|_| match is_final {
+                    true => self.clusters.finish(),
+                    false => Ok(()),
+                });
+            faults::disable_on_error(written, CLUSTERS_FILE_NAME, &mut self.clusters.is_on);
+            self.simlog.clusters.val = self.clusters.count();
+        }
 
         self.simlog.measure_cryst_sizes(grid, &self.front);
+        self.simlog.measure_front_sizes(&self.front);
+        self.simlog.measure_morphology(&self.faces);
+        self.simlog.measure_orientation(&self.state, grid);
+        self.simlog.measure_minkowski(&self.state, grid);
         self.simlog.add_log_point();
+        self.write_time += started.elapsed();
     }
 
     // pub fn mode_1_1_step(
@@ -701,626 +1505,1084 @@ impl Item {
     //     self.is_alive
     // }
 
-    pub fn mode_2_1_step(
+    /// Random gas-front (TPA) site; see `sample_site`. `None` on a passivated site.
+    fn sample_tpa<R: KernelRng>(&mut self, rng: &mut R) -> Option<usize> {
+        self.sample_site(rng, true)
+            .filter(|&idxg| !self.passivation.is_blocked(idxg))
+    }
+
+    /// Random surface (TPB) site; see `sample_site`. `None` on a passivated site.
+    fn sample_tpb<R: KernelRng>(&mut self, rng: &mut R) -> Option<usize> {
+        self.sample_site(rng, false)
+            .filter(|&idxg| !self.passivation.is_blocked(idxg))
+    }
+
+    /// Draws a site of the TPA (`is_tpa`) or TPB front and checks that it still holds the
+    /// state of that front (0 - gas, 1 - crystal). A stale site, which only a front bug
+    /// can leave behind, is counted, dropped from the front and drawn again, up to
+    /// `stale_retry` times; `None` skips the event. A consistent front costs one draw, as
+    /// before, so the trajectory does not change.
+    fn sample_site<R: KernelRng>(&mut self, rng: &mut R, is_tpa: bool) -> Option<usize> {
+        let expected = match is_tpa {
+            true => 0,
+            false => 1,
+        };
+        for _ in 0..=self.stale_retry {
+            let (sites, size) = match is_tpa {
+                true => (&self.front.tpas, self.front.tpas_size),
+                false => (&self.front.tpbs, self.front.tpbs_size),
+            };
+            if size == 0 {
+                return None;
+            }
+            let idxg = sites[rng.random_range(0..size)];
+            if self.state[idxg] == expected {
+                return Some(idxg);
+            }
+
+            self.stale_sites += 1;
+            if self.stale_sites == 1 {
+                eprintln!(
+                    "[Item ID: {:05}] ⚠️ Warning: Step: {} -> {} site {} holds state {}; dropping it from the front and drawing again.",
+                    self.item_gid,
+                    self.simlog.mk_step.val + 1,
+                    match is_tpa {
+                        true => "TPA",
+                        false => "TPB",
+                    },
+                    idxg,
+                    self.state[idxg]
+                );
+            }
+            match is_tpa {
+                true => self.front.tpa_rem(idxg),
+                false => self.front.tpb_rem(idxg),
+            }
+        }
+
+        None
+    }
+
+    /// Exchange move: a random surface atom (TPB) jumps to a random gas-front site (TPA).
+    /// The number of crystal atoms is conserved, so `dg` cancels and only the surface
+    /// energy change enters the acceptance test (`Acceptance`).
+    fn swap_event<R: KernelRng>(
         &mut self,
-        rng: &mut ChaCha8Rng,
-        grid: &mut Grid,
-        (ex2, ey2, ez2): (f64, f64, f64),
+        rng: &mut R,
+        grid: &Grid,
+        energies: (f64, f64, f64),
+        face_mask: u8,
         step_id: u64,
-        (is_add_step, is_rem_step, is_write_step): (bool, bool, bool),
-    ) -> bool {
+    ) {
         let neibs = &*grid.neibs;
+        let Some(idxg_b) = self.sample_tpb(rng) else {
+            return;
+        };
+        if self.is_anchored(idxg_b) {
+            return;
+        }
+        let Some(idxg_a) = self.sample_tpa(rng) else {
+            return;
+        };
+        if !grid.is_in_template(idxg_a) {
+            return;
+        }
+        if self.is_off_sub_lattice(grid, idxg_b, step_id)
+            || self.is_off_sub_lattice(grid, idxg_a, step_id)
+        {
+            return;
+        }
 
-        let (mut surf_en_change, mut d_e) = (0.0, 0.0);
+        let sums_b = compute_neighbor_sums(&self.state, &neibs[idxg_b]);
+        if is_face_masked(face_mask, sums_b, false) {
+            return;
+        }
 
-        if is_add_step {
-            let tpa_len = self.front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
-            let idxg = self.front.tpas[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
+        self.state[idxg_b] = 0;
+        let sums_a = compute_neighbor_sums(&self.state, &neibs[idxg_a]);
+        self.state[idxg_b] = 1;
+
+        if sums_a == (0, 0, 0) || is_face_masked(face_mask, sums_a, true) {
+            return;
+        }
+
+        let surf_en_change = attach_energy(sums_a, energies) + detach_energy(sums_b, energies);
+        let k_t =
+            0.5 * (self.heat.k_t(idxg_a, self.simlog.k_t) + self.heat.k_t(idxg_b, self.simlog.k_t));
+
+        if self
+            .acceptance
+            .accepts(surf_en_change, k_t, || rng.random())
+        {
+            self.simlog.add_denergy(surf_en_change);
+
+            self.state[idxg_b] = 0;
+            self.residence.detach(idxg_b, step_id);
+            self.probes.record(idxg_b, 0, step_id);
+            self.clusters.detach(idxg_b);
+            self.faces.detach(sums_b);
+            self.events.record(EventKind::SwapOut, idxg_b, step_id);
+            self.heat.deposit(idxg_b, -1.0);
+            self.front.tpb_rem(idxg_b);
+            if (sums_b.0 + sums_b.1 + sums_b.2) > 0 {
+                self.front.tpa_add(idxg_b);
             }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
+            let mut has_invalid_neib = self.refresh_neibs_after_rem(neibs, idxg_b);
+
+            self.state[idxg_a] = 1;
+            self.residence.attach(idxg_a, step_id);
+            self.probes.record(idxg_a, 1, step_id);
+            self.clusters.attach(idxg_a, &self.state, neibs, step_id);
+            self.faces.attach(sums_a);
+            self.events.record(EventKind::SwapIn, idxg_a, step_id);
+            self.heat.deposit(idxg_a, 1.0);
+            self.front.tpa_rem(idxg_a);
+            if (sums_a.0 + sums_a.1 + sums_a.2) < 6 {
+                self.front.tpb_add(idxg_a);
+            }
+            has_invalid_neib |= self.refresh_neibs_after_add(neibs, idxg_a);
+
+            if has_invalid_neib {
+                self.handle_stalled_boundary(step_id);
+            } else if self.is_front_empty() {
+                self.handle_stalled_front(step_id, "Swap");
             }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
+        }
+    }
+
+    fn refresh_neibs_after_add(&mut self, neibs: &[[Idx; 6]], idxg: usize) -> bool {
+        let mut has_invalid_neib = false;
+
+        for neib_idx in neibs[idxg].iter().map(|&n| n as usize) {
+            if neib_idx == NO_NEIB {
+                has_invalid_neib = true;
+                continue;
+            }
+
+            match self.state[neib_idx] {
+                0 => self.front.tpa_add(neib_idx),
+                1 => {
+                    if !neibs[neib_idx]
+                        .iter()
+                        .any(|&n| n as usize != NO_NEIB && self.state[n as usize] == 0)
+                    {
+                        self.front.tpb_rem(neib_idx);
+                    }
+                }
                 _ => {}
             }
-            d_e = surf_en_change - self.simlog.dg.val;
+        }
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+        has_invalid_neib
+    }
 
-                self.state[idxg] = 1;
-                self.front.tpa_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) < 6 {
-                    self.front.tpb_add(idxg);
+    fn refresh_neibs_after_rem(&mut self, neibs: &[[Idx; 6]], idxg: usize) -> bool {
+        let mut has_invalid_neib = false;
+
+        for neib_idx in neibs[idxg].iter().map(|&n| n as usize) {
+            if neib_idx == NO_NEIB {
+                has_invalid_neib = true;
+                continue;
+            }
+
+            match self.state[neib_idx] {
+                0 => {
+                    if !neibs[neib_idx]
+                        .iter()
+                        .any(|&n| n as usize != NO_NEIB && self.state[n as usize] == 1)
+                    {
+                        self.front.tpa_rem(neib_idx);
+                    }
                 }
+                1 => self.front.tpb_add(neib_idx),
+                _ => {}
+            }
+        }
+
+        has_invalid_neib
+    }
+
+    pub fn mode_2_1_step<R: KernelRng>(
+        &mut self,
+        rng: &mut R,
+        grid: &mut Grid,
+        (ex2, ey2, ez2): (f64, f64, f64),
+        face_mask: u8,
+        step_id: u64,
+        (is_add_step, is_rem_step, is_swap_step, is_write_step): (bool, bool, bool, bool),
+    ) -> bool {
+        let neibs = &*grid.neibs;
+        self.check_front(neibs, step_id);
+        self.passivation
+            .step(rng, &self.front, self.state.len(), step_id);
+        self.simlog.pass_sites.val = self.passivation.count();
+        self.heat_step();
+        self.energy_hist.sample(step_id, &self.simlog);
+
+        let (mut surf_en_change, mut d_e) = (0.0, 0.0);
 
-                let mut has_invalid_neib = false;
+        if is_add_step {
+            self.rng_audit.enter(RngEvent::Add, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.0) {
+                'add_event: {
+                    let Some(idxg) = self.sample_tpa(rng) else {
+                        break 'add_event;
+                    };
+                    if !grid.is_in_template(idxg) {
+                        break 'add_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'add_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
+                    surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change - self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_attach_energy((ex2, ey2, ez2), self.simlog.dg.val);
                     }
 
-                    match self.state[neib_idx] {
-                        0 => self.front.tpa_add(neib_idx),
-                        1 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 0)
-                            {
-                                self.front.tpb_rem(neib_idx);
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), true);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), true, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 1;
+                        self.residence.attach(idxg, step_id);
+                        self.probes.record(idxg, 1, step_id);
+                        self.clusters.attach(idxg, &self.state, neibs, step_id);
+                        self.faces.attach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Attach, idxg, step_id);
+                        self.heat.deposit(idxg, 1.0);
+                        self.front.tpa_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) < 6 {
+                            self.front.tpb_add(idxg);
+                        }
+
+                        let mut has_invalid_neib = false;
+
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
+
+                            match self.state[neib_idx] {
+                                0 => self.front.tpa_add(neib_idx),
+                                1 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 0
+                                    }) {
+                                        self.front.tpb_rem(neib_idx);
+                                    }
+                                }
+                                _ => {}
                             }
                         }
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Add");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Add");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
         if is_rem_step {
-            let tpb_len = self.front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = self.front.tpbs[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
-            d_e = surf_en_change + self.simlog.dg.val;
+            self.rng_audit.enter(RngEvent::Rem, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.1) {
+                'rem_event: {
+                    let Some(idxg) = self.sample_tpb(rng) else {
+                        break 'rem_event;
+                    };
+                    if self.is_anchored(idxg) {
+                        break 'rem_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'rem_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(-1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+                    surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change + self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    }
 
-                self.state[idxg] = 0;
-                self.front.tpb_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) > 0 {
-                    self.front.tpa_add(idxg);
-                }
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), false);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), false, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(-1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 0;
+                        self.residence.detach(idxg, step_id);
+                        self.probes.record(idxg, 0, step_id);
+                        self.clusters.detach(idxg);
+                        self.faces.detach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Detach, idxg, step_id);
+                        self.heat.deposit(idxg, -1.0);
+                        self.front.tpb_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) > 0 {
+                            self.front.tpa_add(idxg);
+                        }
 
-                let mut has_invalid_neib = false;
+                        let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
-                    }
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
 
-                    match self.state[neib_idx] {
-                        0 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 1)
-                            {
-                                self.front.tpa_rem(neib_idx);
+                            match self.state[neib_idx] {
+                                0 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 1
+                                    }) {
+                                        self.front.tpa_rem(neib_idx);
+                                    }
+                                }
+                                1 => self.front.tpb_add(neib_idx),
+                                _ => {}
                             }
                         }
-                        1 => self.front.tpb_add(neib_idx),
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Rem");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Rem");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
+        if is_swap_step {
+            self.rng_audit.enter(RngEvent::Swap, rng);
+            self.swap_event(rng, grid, (ex2, ey2, ez2), face_mask, step_id);
+            if !self.is_alive {
+                return self.is_alive;
+            }
+        }
+
         self.simlog.mk_step.val = step_id;
+        self.first_passage.update(self.simlog.n_cryst.val, step_id);
+        if self.detach_spectrum.is_on {
+            let written = self.detach_spectrum.sweep(
+                &self.path_dst,
+                step_id,
+                &self.state,
+                &self.front,
+                &grid.neibs,
+                (ex2, ey2, ez2),
+            );
+            faults::disable_on_error(
+                written,
+                DETACH_SPECTRUM_FILE_NAME,
+                &mut self.detach_spectrum.is_on,
+            );
+        }
 
-        if is_write_step {
-            self.write_action(grid);
+        if self.stall_watch(step_id) || is_write_step {
+            self.write_action(grid, false);
         }
 
         self.is_alive
     }
 
-    pub fn mode_2_2_step(
+    pub fn mode_2_2_step<R: KernelRng>(
         &mut self,
-        rng: &mut ChaCha8Rng,
+        rng: &mut R,
         grid: &mut Grid,
         (ex2, ey2, ez2): (f64, f64, f64),
+        face_mask: u8,
         step_id: u64,
-        (is_add_step, is_rem_step, is_write_step): (bool, bool, bool),
+        (is_add_step, is_rem_step, is_swap_step, is_write_step): (bool, bool, bool, bool),
     ) -> bool {
         let neibs = &*grid.neibs;
+        self.check_front(neibs, step_id);
+        self.passivation
+            .step(rng, &self.front, self.state.len(), step_id);
+        self.simlog.pass_sites.val = self.passivation.count();
+        self.heat_step();
+        self.energy_hist.sample(step_id, &self.simlog);
 
         let (mut surf_en_change, mut d_e) = (0.0, 0.0);
 
         if is_add_step {
-            let tpa_len = self.front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
-            let idxg = self.front.tpas[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
-            d_e = surf_en_change - self.simlog.dg.val;
+            self.rng_audit.enter(RngEvent::Add, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.0) {
+                'add_event: {
+                    let Some(idxg) = self.sample_tpa(rng) else {
+                        break 'add_event;
+                    };
+                    if !grid.is_in_template(idxg) {
+                        break 'add_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'add_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+                    surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change - self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_attach_energy((ex2, ey2, ez2), self.simlog.dg.val);
+                    }
 
-                self.state[idxg] = 1;
-                self.front.tpa_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) < 6 {
-                    self.front.tpb_add(idxg);
-                }
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), true);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), true, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 1;
+                        self.residence.attach(idxg, step_id);
+                        self.probes.record(idxg, 1, step_id);
+                        self.clusters.attach(idxg, &self.state, neibs, step_id);
+                        self.faces.attach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Attach, idxg, step_id);
+                        self.heat.deposit(idxg, 1.0);
+                        self.front.tpa_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) < 6 {
+                            self.front.tpb_add(idxg);
+                        }
 
-                let mut has_invalid_neib = false;
+                        let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
-                    }
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
 
-                    match self.state[neib_idx] {
-                        0 => self.front.tpa_add(neib_idx),
-                        1 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 0)
-                            {
-                                self.front.tpb_rem(neib_idx);
+                            match self.state[neib_idx] {
+                                0 => self.front.tpa_add(neib_idx),
+                                1 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 0
+                                    }) {
+                                        self.front.tpb_rem(neib_idx);
+                                    }
+                                }
+                                _ => {}
                             }
                         }
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Add");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Add");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
         if is_rem_step {
-            let tpb_len = self.front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = self.front.tpbs[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
-            d_e = surf_en_change + self.simlog.dg.val;
+            self.rng_audit.enter(RngEvent::Rem, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.1) {
+                'rem_event: {
+                    let Some(idxg) = self.sample_tpb(rng) else {
+                        break 'rem_event;
+                    };
+                    if self.is_anchored(idxg) {
+                        break 'rem_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'rem_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(-1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+                    surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change + self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    }
 
-                self.state[idxg] = 0;
-                self.front.tpb_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) > 0 {
-                    self.front.tpa_add(idxg);
-                }
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), false);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), false, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(-1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 0;
+                        self.residence.detach(idxg, step_id);
+                        self.probes.record(idxg, 0, step_id);
+                        self.clusters.detach(idxg);
+                        self.faces.detach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Detach, idxg, step_id);
+                        self.heat.deposit(idxg, -1.0);
+                        self.front.tpb_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) > 0 {
+                            self.front.tpa_add(idxg);
+                        }
 
-                let mut has_invalid_neib = false;
+                        let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
-                    }
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
 
-                    match self.state[neib_idx] {
-                        0 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 1)
-                            {
-                                self.front.tpa_rem(neib_idx);
+                            match self.state[neib_idx] {
+                                0 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 1
+                                    }) {
+                                        self.front.tpa_rem(neib_idx);
+                                    }
+                                }
+                                1 => self.front.tpb_add(neib_idx),
+                                _ => {}
                             }
                         }
-                        1 => self.front.tpb_add(neib_idx),
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Rem");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Rem");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
-        if self.simlog.p_b > rng.random::<f64>() {
-            let tpb_len = self.front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = self.front.tpbs[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
+        if is_swap_step {
+            self.rng_audit.enter(RngEvent::Swap, rng);
+            self.swap_event(rng, grid, (ex2, ey2, ez2), face_mask, step_id);
+            if !self.is_alive {
+                return self.is_alive;
             }
-            // d_e = surf_en_change + self.simlog.dg.val;
+        }
 
-            self.simlog.update_n_sizes(-1.0);
-            self.simlog.update_conc();
-            self.simlog.add_denergy(surf_en_change);
+        self.rng_audit.enter(RngEvent::Ballistic, rng);
+        for _ in 0..event_attempts(rng, self.poisson_means.2) {
+            if self.simlog.p_b_axes.gate() > rng.random::<f64>() {
+                'ballistic_rem: {
+                    let Some(idxg) = self.sample_tpb(rng) else {
+                        break 'ballistic_rem;
+                    };
+                    if self.is_anchored(idxg) {
+                        break 'ballistic_rem;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'ballistic_rem;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            self.state[idxg] = 0;
-            self.front.tpb_rem(idxg);
-            if (smx_yz + smy_xz + smz_xy) > 0 {
-                self.front.tpa_add(idxg);
-            }
+                    surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    // d_e = surf_en_change + self.simlog.dg.val;
+
+                    if is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), false) {
+                        break 'ballistic_rem;
+                    }
+                    let keep = self.simlog.p_b_axes.keep((smx_yz, smy_xz, smz_xy));
+                    if keep < 1.0 && keep <= rng.random::<f64>() {
+                        break 'ballistic_rem;
+                    }
 
-            let mut has_invalid_neib = false;
+                    self.simlog.update_n_sizes(-1.0);
+                    self.simlog.update_conc();
+                    self.simlog.add_ballistic_denergy(surf_en_change);
+
+                    self.state[idxg] = 0;
+                    self.residence.detach(idxg, step_id);
+                    self.probes.record(idxg, 0, step_id);
+                    self.clusters.detach(idxg);
+                    self.faces.detach((smx_yz, smy_xz, smz_xy));
+                    self.events.record(EventKind::Ballistic, idxg, step_id);
+                    self.front.tpb_rem(idxg);
+                    if (smx_yz + smy_xz + smz_xy) > 0 {
+                        self.front.tpa_add(idxg);
+                    }
 
-            for &neib_idx in idxg_nis.iter() {
-                if neib_idx == usize::MAX {
-                    has_invalid_neib = true;
-                    continue;
-                }
+                    let mut has_invalid_neib = false;
 
-                match self.state[neib_idx] {
-                    0 => {
-                        if !neibs[neib_idx]
-                            .iter()
-                            .any(|&n| n != usize::MAX && self.state[n] == 1)
-                        {
-                            self.front.tpa_rem(neib_idx);
+                    for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                        if neib_idx == NO_NEIB {
+                            has_invalid_neib = true;
+                            continue;
+                        }
+
+                        match self.state[neib_idx] {
+                            0 => {
+                                if !neibs[neib_idx]
+                                    .iter()
+                                    .any(|&n| n as usize != NO_NEIB && self.state[n as usize] == 1)
+                                {
+                                    self.front.tpa_rem(neib_idx);
+                                }
+                            }
+                            1 => self.front.tpb_add(neib_idx),
+                            _ => {}
                         }
                     }
-                    1 => self.front.tpb_add(neib_idx),
-                    _ => {}
-                }
-            }
 
-            if has_invalid_neib {
-                self.handle_stalled_boundary(step_id);
-                return self.is_alive;
-            }
+                    if has_invalid_neib {
+                        self.handle_stalled_boundary(step_id);
+                        return self.is_alive;
+                    }
 
-            if self.is_front_empty() {
-                self.handle_stalled_front(step_id, "Ballistic Rem");
-                return self.is_alive;
+                    if self.is_front_empty() {
+                        self.handle_stalled_front(step_id, "Ballistic Rem");
+                        return self.is_alive;
+                    }
+                }
             }
         }
 
         self.simlog.mk_step.val = step_id;
+        self.first_passage.update(self.simlog.n_cryst.val, step_id);
+        if self.detach_spectrum.is_on {
+            let written = self.detach_spectrum.sweep(
+                &self.path_dst,
+                step_id,
+                &self.state,
+                &self.front,
+                &grid.neibs,
+                (ex2, ey2, ez2),
+            );
+            faults::disable_on_error(
+                written,
+                DETACH_SPECTRUM_FILE_NAME,
+                &mut self.detach_spectrum.is_on,
+            );
+        }
 
-        if is_write_step {
-            self.write_action(grid);
+        if self.stall_watch(step_id) || is_write_step {
+            self.write_action(grid, false);
         }
 
         self.is_alive
     }
 
-    pub fn mode_2_3_step(
+    pub fn mode_2_3_step<R: KernelRng>(
         &mut self,
-        rng: &mut ChaCha8Rng,
+        rng: &mut R,
         grid: &mut Grid,
-        (ex2, ey2, ez2, eisol): (f64, f64, f64, f64),
+        params: Mode23Params,
         step_id: u64,
-        (is_add_step, is_rem_step, is_write_step): (bool, bool, bool),
+        (is_add_step, is_rem_step, is_swap_step, is_write_step): (bool, bool, bool, bool),
     ) -> bool {
+        let Mode23Params {
+            e2: (ex2, ey2, ez2),
+            face_mask,
+            ballistic,
+        } = params;
         let neibs = &*grid.neibs;
+        self.check_front(neibs, step_id);
+        self.passivation
+            .step(rng, &self.front, self.state.len(), step_id);
+        self.simlog.pass_sites.val = self.passivation.count();
+        self.heat_step();
+        self.energy_hist.sample(step_id, &self.simlog);
 
         let (mut surf_en_change, mut d_e) = (0.0, 0.0);
 
         if is_add_step {
-            let tpa_len = self.front.tpas_size;
-            let idxl = rng.random_range(0..tpa_len);
-            let idxg = self.front.tpas[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change += ex2,
-                2 => surf_en_change -= ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change += ey2,
-                2 => surf_en_change -= ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change += ez2,
-                2 => surf_en_change -= ez2,
-                _ => {}
-            }
-            d_e = surf_en_change - self.simlog.dg.val;
+            self.rng_audit.enter(RngEvent::Add, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.0) {
+                'add_event: {
+                    let Some(idxg) = self.sample_tpa(rng) else {
+                        break 'add_event;
+                    };
+                    if !grid.is_in_template(idxg) {
+                        break 'add_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'add_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+                    surf_en_change = attach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change - self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_attach_energy((ex2, ey2, ez2), self.simlog.dg.val);
+                    }
 
-                self.state[idxg] = 1;
-                self.front.tpa_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) < 6 {
-                    self.front.tpb_add(idxg);
-                }
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), true);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), true, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 1;
+                        self.residence.attach(idxg, step_id);
+                        self.probes.record(idxg, 1, step_id);
+                        self.clusters.attach(idxg, &self.state, neibs, step_id);
+                        self.faces.attach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Attach, idxg, step_id);
+                        self.heat.deposit(idxg, 1.0);
+                        self.front.tpa_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) < 6 {
+                            self.front.tpb_add(idxg);
+                        }
 
-                let mut has_invalid_neib = false;
+                        let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
-                    }
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
 
-                    match self.state[neib_idx] {
-                        0 => self.front.tpa_add(neib_idx),
-                        1 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 0)
-                            {
-                                self.front.tpb_rem(neib_idx);
+                            match self.state[neib_idx] {
+                                0 => self.front.tpa_add(neib_idx),
+                                1 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 0
+                                    }) {
+                                        self.front.tpb_rem(neib_idx);
+                                    }
+                                }
+                                _ => {}
                             }
                         }
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Add");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Add");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
         if is_rem_step {
-            let tpb_len = self.front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = self.front.tpbs[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
-            }
-            d_e = surf_en_change + self.simlog.dg.val;
+            self.rng_audit.enter(RngEvent::Rem, rng);
+            for _ in 0..event_attempts(rng, self.poisson_means.1) {
+                'rem_event: {
+                    let Some(idxg) = self.sample_tpb(rng) else {
+                        break 'rem_event;
+                    };
+                    if self.is_anchored(idxg) {
+                        break 'rem_event;
+                    }
+                    if self.is_off_sub_lattice(grid, idxg, step_id) {
+                        break 'rem_event;
+                    }
+                    let idxg_nis = &neibs[idxg];
+                    let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
 
-            if d_e < 0.0 || (-d_e / self.simlog.k_t).exp() > rng.random::<f64>() {
-                self.simlog.update_n_sizes(-1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
+                    surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    d_e = surf_en_change + self.simlog.dg.val;
+                    if self.is_bond_counting {
+                        d_e = kossel_detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                    }
 
-                self.state[idxg] = 0;
-                self.front.tpb_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) > 0 {
-                    self.front.tpa_add(idxg);
-                }
+                    let k_t = self.heat.k_t(idxg, self.simlog.k_t);
+                    let is_masked = is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), false);
+                    let is_accepted =
+                        !is_masked && self.acceptance.accepts(d_e, k_t, || rng.random());
+                    self.facet_stats
+                        .record((smx_yz, smy_xz, smz_xy), false, is_accepted);
+                    if is_accepted {
+                        self.simlog.update_n_sizes(-1.0);
+                        self.simlog.update_conc();
+                        self.simlog.add_denergy(surf_en_change);
+
+                        self.state[idxg] = 0;
+                        self.residence.detach(idxg, step_id);
+                        self.probes.record(idxg, 0, step_id);
+                        self.clusters.detach(idxg);
+                        self.faces.detach((smx_yz, smy_xz, smz_xy));
+                        self.events.record(EventKind::Detach, idxg, step_id);
+                        self.heat.deposit(idxg, -1.0);
+                        self.front.tpb_rem(idxg);
+                        if (smx_yz + smy_xz + smz_xy) > 0 {
+                            self.front.tpa_add(idxg);
+                        }
 
-                let mut has_invalid_neib = false;
+                        let mut has_invalid_neib = false;
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
-                    }
+                        for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                            if neib_idx == NO_NEIB {
+                                has_invalid_neib = true;
+                                continue;
+                            }
 
-                    match self.state[neib_idx] {
-                        0 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 1)
-                            {
-                                self.front.tpa_rem(neib_idx);
+                            match self.state[neib_idx] {
+                                0 => {
+                                    if !neibs[neib_idx].iter().any(|&n| {
+                                        n as usize != NO_NEIB && self.state[n as usize] == 1
+                                    }) {
+                                        self.front.tpa_rem(neib_idx);
+                                    }
+                                }
+                                1 => self.front.tpb_add(neib_idx),
+                                _ => {}
                             }
                         }
-                        1 => self.front.tpb_add(neib_idx),
-                        _ => {}
-                    }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                        if has_invalid_neib {
+                            self.handle_stalled_boundary(step_id);
+                            return self.is_alive;
+                        }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Rem");
-                    return self.is_alive;
+                        if self.is_front_empty() {
+                            self.handle_stalled_front(step_id, "Rem");
+                            return self.is_alive;
+                        }
+                    }
                 }
             }
         }
 
-        'ballistic_rem: {
-            let tpb_len = self.front.tpbs_size;
-            let idxl = rng.random_range(0..tpb_len);
-            let idxg = self.front.tpbs[idxl];
-            let idxg_nis = &neibs[idxg];
-            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
-
-            surf_en_change = 0.0;
-            match smx_yz {
-                0 => surf_en_change -= ex2,
-                2 => surf_en_change += ex2,
-                _ => {}
-            }
-            match smy_xz {
-                0 => surf_en_change -= ey2,
-                2 => surf_en_change += ey2,
-                _ => {}
-            }
-            match smz_xy {
-                0 => surf_en_change -= ez2,
-                2 => surf_en_change += ez2,
-                _ => {}
+        if is_swap_step {
+            self.rng_audit.enter(RngEvent::Swap, rng);
+            self.swap_event(rng, grid, (ex2, ey2, ez2), face_mask, step_id);
+            if !self.is_alive {
+                return self.is_alive;
             }
-            // d_e = surf_en_change + self.simlog.dg.val;
-
-            let prob =
-                self.simlog.p_b * (1.0f64 - (surf_en_change / eisol)).powf(self.simlog.p_pow);
-            if prob > rng.random::<f64>() {
-                self.simlog.update_n_sizes(-1.0);
-                self.simlog.update_conc();
-                self.simlog.add_denergy(surf_en_change);
-
-                self.state[idxg] = 0;
-                self.front.tpb_rem(idxg);
-                if (smx_yz + smy_xz + smz_xy) > 0 {
-                    self.front.tpa_add(idxg);
+        }
+
+        self.rng_audit.enter(RngEvent::Ballistic, rng);
+        for _ in 0..event_attempts(rng, self.poisson_means.2) {
+            'ballistic_rem: {
+                let Some(idxg) = self.sample_tpb(rng) else {
+                    break 'ballistic_rem;
+                };
+                if self.is_anchored(idxg) {
+                    break 'ballistic_rem;
                 }
+                if self.is_off_sub_lattice(grid, idxg, step_id) {
+                    break 'ballistic_rem;
+                }
+                let idxg_nis = &neibs[idxg];
+                let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(&self.state, idxg_nis);
+
+                surf_en_change = detach_energy((smx_yz, smy_xz, smz_xy), (ex2, ey2, ez2));
+                // d_e = surf_en_change + self.simlog.dg.val;
 
-                let mut has_invalid_neib = false;
+                if is_face_masked(face_mask, (smx_yz, smy_xz, smz_xy), false) {
+                    break 'ballistic_rem;
+                }
 
-                for &neib_idx in idxg_nis.iter() {
-                    if neib_idx == usize::MAX {
-                        has_invalid_neib = true;
-                        continue;
+                let p_b = self.simlog.p_b_axes.site((smx_yz, smy_xz, smz_xy));
+                let prob = ballistic.probability(surf_en_change, p_b, self.simlog.p_pow);
+                if prob > rng.random::<f64>() {
+                    self.simlog.update_n_sizes(-1.0);
+                    self.simlog.update_conc();
+                    self.simlog.add_ballistic_denergy(surf_en_change);
+
+                    self.state[idxg] = 0;
+                    self.residence.detach(idxg, step_id);
+                    self.probes.record(idxg, 0, step_id);
+                    self.clusters.detach(idxg);
+                    self.faces.detach((smx_yz, smy_xz, smz_xy));
+                    self.events.record(EventKind::Ballistic, idxg, step_id);
+                    self.front.tpb_rem(idxg);
+                    if (smx_yz + smy_xz + smz_xy) > 0 {
+                        self.front.tpa_add(idxg);
                     }
 
-                    match self.state[neib_idx] {
-                        0 => {
-                            if !neibs[neib_idx]
-                                .iter()
-                                .any(|&n| n != usize::MAX && self.state[n] == 1)
-                            {
-                                self.front.tpa_rem(neib_idx);
+                    let mut has_invalid_neib = false;
+
+                    for neib_idx in idxg_nis.iter().map(|&n| n as usize) {
+                        if neib_idx == NO_NEIB {
+                            has_invalid_neib = true;
+                            continue;
+                        }
+
+                        match self.state[neib_idx] {
+                            0 => {
+                                if !neibs[neib_idx]
+                                    .iter()
+                                    .any(|&n| n as usize != NO_NEIB && self.state[n as usize] == 1)
+                                {
+                                    self.front.tpa_rem(neib_idx);
+                                }
                             }
+                            1 => self.front.tpb_add(neib_idx),
+                            _ => {}
                         }
-                        1 => self.front.tpb_add(neib_idx),
-                        _ => {}
                     }
-                }
 
-                if has_invalid_neib {
-                    self.handle_stalled_boundary(step_id);
-                    return self.is_alive;
-                }
+                    if has_invalid_neib {
+                        self.handle_stalled_boundary(step_id);
+                        return self.is_alive;
+                    }
 
-                if self.is_front_empty() {
-                    self.handle_stalled_front(step_id, "Rem");
-                    return self.is_alive;
+                    if self.is_front_empty() {
+                        self.handle_stalled_front(step_id, "Rem");
+                        return self.is_alive;
+                    }
                 }
             }
         }
 
         self.simlog.mk_step.val = step_id;
+        self.first_passage.update(self.simlog.n_cryst.val, step_id);
+        if self.detach_spectrum.is_on {
+            let written = self.detach_spectrum.sweep(
+                &self.path_dst,
+                step_id,
+                &self.state,
+                &self.front,
+                &grid.neibs,
+                (ex2, ey2, ez2),
+            );
+            faults::disable_on_error(
+                written,
+                DETACH_SPECTRUM_FILE_NAME,
+                &mut self.detach_spectrum.is_on,
+            );
+        }
 
-        if is_write_step {
-            self.write_action(grid);
+        if self.stall_watch(step_id) || is_write_step {
+            self.write_action(grid, false);
         }
 
         self.is_alive
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::rng::ScriptedRng;
+
+    const E2: (f64, f64, f64) = (1.0, 1.0, 1.0);
+    const DG: f64 = 0.3;
+
+    /// Item of one crystal cell in the middle of a periodic 5x5x5 grid: its six gas
+    /// neighbours are the TPA front, the cell itself the TPB front.
+    fn seed_item(name: &str) -> (Item, Grid) {
+        let grid = Grid::new(5, 5, 5, true, true, true).unwrap();
+        let mut state = vec![0u8; grid.size].into_boxed_slice();
+        state[grid.xyz_to_idx(2, 2, 2)] = 1;
+        let mut front = Frontier::new(grid.size);
+        fill_front(&state, &grid.neibs, &mut front);
+
+        let dir = std::env::temp_dir().join(format!(
+            "model_1_002_scripted_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let mut item = Item::new(0, state, front, dir).unwrap();
+        item.faces = CrystalFaces::of_state(&item.state, &grid.neibs);
+        item.simlog.k_t = 1.0;
+        item.simlog.dg.val = DG;
+        item.simlog.n_cryst.val = 1.0;
+        (item, grid)
+    }
+
+    /// One add step of mode 2.1.
+    fn add_step(item: &mut Item, grid: &mut Grid, rng: &mut ScriptedRng, step_id: u64) -> bool {
+        item.mode_2_1_step(rng, grid, E2, 0, step_id, (true, false, false, false))
+    }
+
+    #[test]
+    fn scripted_attach_follows_the_metropolis_draw() {
+        let (mut item, mut grid) = seed_item("attach");
+        assert_eq!((item.front.tpas_size, item.front.tpbs_size), (6, 1));
+
+        // A gas site next to the cell: one neighbour along its axis, none along the others.
+        let site = item.front.tpas[1];
+        let sums = compute_neighbor_sums(&item.state, &grid.neibs[site]);
+        let p_accept = (-(attach_energy(sums, E2) - DG)).exp();
+        assert!(0.01 < p_accept && p_accept < 0.99);
+
+        // Step 1: site 1 of 6, a draw just above the Metropolis probability - rejected.
+        let mut rng = ScriptedRng::new();
+        rng.push_index(1, 6).push_f64(p_accept + 0.01);
+        assert!(add_step(&mut item, &mut grid, &mut rng, 1));
+        assert_eq!(rng.remaining(), 0);
+        assert_eq!(item.state[site], 0);
+        assert_eq!(item.simlog.n_cryst.val, 1.0);
+        assert_eq!((item.front.tpas_size, item.front.tpbs_size), (6, 1));
+
+        // Step 2: the same site, a draw just below it - attached.
+        rng.push_index(1, 6).push_f64(p_accept - 0.01);
+        assert!(add_step(&mut item, &mut grid, &mut rng, 2));
+        assert_eq!(rng.remaining(), 0);
+        assert_eq!(item.state[site], 1);
+        assert_eq!(item.simlog.n_cryst.val, 2.0);
+        assert!(item.front.is_tpb(site) && !item.front.is_tpa(site));
+        // The dimer has 10 gas neighbours and both cells on its surface.
+        assert_eq!((item.front.tpas_size, item.front.tpbs_size), (10, 2));
+        assert_eq!(item.simlog.mk_step.val, 2);
+
+        let _ = fs::remove_dir_all(&item.path_dst);
+    }
+
+    #[test]
+    fn scripted_detach_of_the_last_cell_stops_the_item() {
+        let (mut item, mut grid) = seed_item("detach");
+        let cell = item.front.tpbs[0];
+        assert!(detach_energy((0, 0, 0), E2) + DG < 0.0);
+
+        // A downhill Metropolis move takes no acceptance draw: the site choice is all.
+        let mut rng = ScriptedRng::new();
+        rng.push_index(0, 1);
+        let is_alive =
+            item.mode_2_1_step(&mut rng, &mut grid, E2, 0, 1, (false, true, false, false));
+        assert_eq!(rng.remaining(), 0);
+
+        assert!(!is_alive);
+        assert_eq!(item.state[cell], 0);
+        assert_eq!(item.simlog.n_cryst.val, 0.0);
+        assert_eq!(item.front.tpbs_size, 0);
+        assert_eq!(item.simlog.mk_step.val, 1);
+
+        let _ = fs::remove_dir_all(&item.path_dst);
+    }
+}
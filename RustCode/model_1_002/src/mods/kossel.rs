@@ -0,0 +1,111 @@
+use crate::mods::{
+    acceptance::AcceptanceRule,
+    constants::KOSSEL_CHECK_FILE_NAME,
+    faults::{self, FaultPoint},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::PathBuf,
+};
+
+/// Running comparison of the simulated growth rate with the mean-field Kossel rate.
+///
+/// For isotropic energies a kink (half-crystal) site changes the surface energy by 0, so
+/// its Metropolis acceptances are `min(1, exp(dg/kT))` for attachment and
+/// `min(1, exp(-dg/kT))` for detachment. With one attempt every `AddI`/`RemI` steps the
+/// mean-field (Wilson-Frenkel) rate of an item is
+/// `v_mf = min(1, exp(dg/kT)) / AddI - min(1, exp(-dg/kT)) / RemI` particles per step.
+/// Every write step the ensemble rate since the previous write, per alive item, is
+/// compared with `v_mf` at the current `dg` and `kT`; the ratio is about the fraction of
+/// attempts landing on kink-like sites and should change slowly during a sane run.
+/// Under `Acceptance` glauber/heatbath the acceptances are `1 / (1 + exp(∓dg/kT))`.
+#[derive(Debug, Default)]
+pub struct KosselCheck {
+    pub is_on: bool,
+    add_rate: f64,
+    rem_rate: f64,
+    rule: AcceptanceRule,
+    last_step: u64,
+    last_n_cryst: f64,
+    pub last_ratio: f64,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl KosselCheck {
+    pub fn new(is_on: bool, add_i: u64, rem_i: u64, rule: AcceptanceRule) -> Self {
+        let rate = |interval: u64| match interval {
+            0 => 0.0,
+            i => 1.0 / i as f64,
+        };
+        Self {
+            is_on,
+            add_rate: rate(add_i),
+            rem_rate: rate(rem_i),
+            rule,
+            last_ratio: f64::NAN,
+            ..Self::default()
+        }
+    }
+
+    pub fn create_out_file(&mut self, path_dst: PathBuf, n_cryst0: f64) -> IoResult<()> {
+        let path_out_file = path_dst.join(KOSSEL_CHECK_FILE_NAME);
+
+        let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+        writeln!(out_file_buf, "# step:dg:kT:v_sim:v_mf:ratio")?;
+
+        self.out_file_buf = Some(out_file_buf);
+        self.last_n_cryst = n_cryst0;
+
+        Ok(())
+    }
+
+    /// Mean-field growth rate of one item (particles per step).
+    pub fn mean_field_rate(&self, dg: f64, k_t: f64) -> f64 {
+        let acceptance = |d_e: f64| self.rule.probability(d_e, k_t);
+        acceptance(-dg) * self.add_rate - acceptance(dg) * self.rem_rate
+    }
+
+    pub fn write_step(
+        &mut self,
+        step_id: u64,
+        n_cryst: f64,
+        items_alive: usize,
+        dg: f64,
+        k_t: f64,
+    ) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+
+        let steps = step_id.saturating_sub(self.last_step).max(1) as f64;
+        let v_sim = (n_cryst - self.last_n_cryst) / (steps * items_alive.max(1) as f64);
+        let v_mf = self.mean_field_rate(dg, k_t);
+        self.last_ratio = v_sim / v_mf;
+        self.last_step = step_id;
+        self.last_n_cryst = n_cryst;
+
+        if let Some(fbuf) = self.out_file_buf.as_mut() {
+            faults::inject(FaultPoint::Stats)?;
+            writeln!(
+                fbuf,
+                "{}:{:.5e}:{:.5e}:{:.5e}:{:.5e}:{:.5e}",
+                step_id, dg, k_t, v_sim, v_mf, self.last_ratio
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match self.out_file_buf.as_mut() {
+            Some(fbuf) => fbuf.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,567 @@
+use crate::mods::{
+    constants::{
+        COMMENT_LINE, CONFIG_FILE_NAME, INIT_TIME_STATES_FILE_NAME, MANIFEST_STATUS_FILE_NAME,
+        TIME_STATES_BIN_FILE_NAME, TIME_STATES_FILE_NAME, TIME_STATES_ZST_FILE_NAME,
+    },
+    ensemble::Ensemble,
+    io_handler,
+    state_header::StateHeader,
+    writer,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Result as IoResult, Write},
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Keys of the manifest itself; all other keys are settings of `InitSettings.ini`.
+const TOP_KEYS: [&str; 3] = ["base", "dir", "jobs"];
+const RUN_TABLE: &str = "[[run]]";
+
+#[derive(Debug, Default)]
+struct Run {
+    name: String,
+    /// Run whose final states this one starts from.
+    after: Option<usize>,
+    /// `(key, value)` settings over the base config, the shared ones first.
+    overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+struct Manifest {
+    /// Directory with the base `InitSettings.ini`, `InitStates.ini` and the files they name.
+    base: PathBuf,
+    /// Directory of the run directories and the status report.
+    dir: PathBuf,
+    jobs: usize,
+    runs: Vec<Run>,
+}
+
+#[derive(Debug, Clone)]
+enum Status {
+    Pending,
+    Running,
+    Done { dst_path: PathBuf, secs: f64 },
+    Failed { msg: String, secs: f64 },
+    Skipped(String),
+}
+
+/// Entry point of the `manifest` subcommand.
+///
+/// Usage: `manifest <manifest.toml> [--jobs N]`. The manifest (a TOML subset: `key =
+/// value` lines, `#` comments and `[[run]]` tables) lists the runs of a study:
+///
+/// ```toml
+/// base = "base"        # InitSettings.ini, InitStates.ini; relative to the manifest
+/// dir = "study"        # run directories (default: the manifest directory)
+/// jobs = 2             # runs at a time (default 1; `--jobs` wins)
+/// StepLim = 20000      # any other key: a setting of every run
+///
+/// [[run]]
+/// name = "anneal"
+/// T = 350
+///
+/// [[run]]
+/// name = "grow"
+/// after = "anneal"     # starts from the final states of "anneal"
+/// dg = 1e-21
+/// ```
+///
+/// Every run gets the directory `<dir>/<name>` with a copy of the files of `base`, its
+/// `InitSettings.ini` with the settings of the run put over the base ones. A run with
+/// `after` waits for that run and loads its final states (the last frame of every item,
+/// `LoadOption:-1` unless the run sets it); when that run fails, it is skipped. At the
+/// end the status of all runs is printed and written to `ManifestStatus.txt` in `dir`.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: manifest <manifest.toml> [--jobs N]";
+
+    let mut path_manifest: Option<PathBuf> = None;
+    let mut jobs: Option<usize> = None;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--jobs" | "-j" => {
+                jobs = Some(
+                    args_iter
+                        .next()
+                        .ok_or("Missing value after '--jobs'")?
+                        .parse()?,
+                );
+            }
+            _ => path_manifest = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let path_manifest = path_manifest.ok_or(usage)?;
+    let mut manifest = parse_manifest(&path_manifest)?;
+    if let Some(jobs) = jobs {
+        manifest.jobs = jobs;
+    }
+    let jobs = manifest.jobs.clamp(1, manifest.runs.len().max(1));
+    fs::create_dir_all(&manifest.dir)?;
+
+    println!(
+        "📒 Manifest '{}': {} run(s), {} at a time",
+        path_manifest.display(),
+        manifest.runs.len(),
+        jobs
+    );
+
+    let queue = (
+        Mutex::new(vec![Status::Pending; manifest.runs.len()]),
+        Condvar::new(),
+    );
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| worker_loop(&manifest, &queue));
+        }
+    });
+
+    let status = queue.0.into_inner().unwrap_or_else(|e| e.into_inner());
+    let path_report = manifest.dir.join(MANIFEST_STATUS_FILE_NAME);
+    write_report(&manifest, &status, &path_report)?;
+
+    println!("Run\tStatus");
+    for (run, status) in manifest.runs.iter().zip(&status) {
+        println!("{}\t{}", run.name, status_text(status));
+    }
+
+    let n_ok = status
+        .iter()
+        .filter(|s| matches!(s, Status::Done { .. }))
+        .count();
+    match n_ok == status.len() {
+        true => {
+            println!(
+                "✅ {} run(s) done; status in '{}'",
+                n_ok,
+                path_report.display()
+            );
+            Ok(())
+        }
+        false => Err(format!(
+            "{} of {} run(s) did not finish; status in '{}'",
+            status.len() - n_ok,
+            status.len(),
+            path_report.display()
+        )
+        .into()),
+    }
+}
+
+fn worker_loop(manifest: &Manifest, queue: &(Mutex<Vec<Status>>, Condvar)) {
+    let (lock, cvar) = queue;
+
+    loop {
+        let job = {
+            let mut status = lock.lock().unwrap();
+            loop {
+                skip_orphans(manifest, &mut status);
+                let ready = manifest.runs.iter().enumerate().find_map(|(i, run)| {
+                    match (&status[i], run.after.map(|j| &status[j])) {
+                        (Status::Pending, None) => Some((i, None)),
+                        (Status::Pending, Some(Status::Done { dst_path, .. })) => {
+                            Some((i, Some(dst_path.clone())))
+                        }
+                        _ => None,
+                    }
+                });
+                if let Some((i, states_from)) = ready {
+                    status[i] = Status::Running;
+                    break Some((i, states_from));
+                }
+                if !status.iter().any(|s| matches!(s, Status::Pending)) {
+                    break None;
+                }
+                status = cvar.wait(status).unwrap();
+            }
+        };
+
+        let Some((run_id, states_from)) = job else {
+            cvar.notify_all();
+            return;
+        };
+
+        let run = &manifest.runs[run_id];
+        println!("📒 ▶ {}", run.name);
+        let sta = Instant::now();
+        let result = execute(manifest, run, states_from.as_deref());
+        let secs = sta.elapsed().as_secs_f64();
+        let run_status = match result {
+            Ok(dst_path) => {
+                println!("📒 ✅ {} (Time: {:.1} s)", run.name, secs);
+                Status::Done { dst_path, secs }
+            }
+            Err(e) => {
+                eprintln!("📒 ❌ {}: {}", run.name, e);
+                Status::Failed {
+                    msg: e.to_string(),
+                    secs,
+                }
+            }
+        };
+
+        lock.lock().unwrap()[run_id] = run_status;
+        cvar.notify_all();
+    }
+}
+
+/// Skips the pending runs whose source run failed or was skipped.
+fn skip_orphans(manifest: &Manifest, status: &mut [Status]) {
+    loop {
+        let orphan = manifest.runs.iter().enumerate().find_map(|(i, run)| {
+            let j = run.after?;
+            match (&status[i], &status[j]) {
+                (Status::Pending, Status::Failed { .. } | Status::Skipped(_)) => Some((i, j)),
+                _ => None,
+            }
+        });
+        let Some((i, j)) = orphan else {
+            return;
+        };
+        status[i] = Status::Skipped(format!("'{}' did not finish", manifest.runs[j].name));
+    }
+}
+
+/// Prepares the run directory and runs the ensemble in it; the results directory.
+fn execute(manifest: &Manifest, run: &Run, states_from: Option<&Path>) -> Result<PathBuf> {
+    let run_dir = manifest.dir.join(&run.name);
+    fs::create_dir_all(&run_dir)?;
+
+    for entry in fs::read_dir(&manifest.base)? {
+        let path = entry?.path();
+        let is_replaced = path.file_name().is_some_and(|name| {
+            name == CONFIG_FILE_NAME
+                || (states_from.is_some() && name == INIT_TIME_STATES_FILE_NAME)
+        });
+        if path.is_file() && !is_replaced {
+            fs::copy(&path, run_dir.join(path.file_name().unwrap_or_default()))?;
+        }
+    }
+
+    let mut overrides = Vec::new();
+    if let Some(dst_path) = states_from {
+        let n_states = write_final_states(dst_path, &run_dir.join(INIT_TIME_STATES_FILE_NAME))?;
+        println!(
+            "📒 {}: {} final state(s) of '{}'",
+            run.name,
+            n_states,
+            dst_path.display()
+        );
+        overrides.push(("LoadOption".to_string(), "-1".to_string()));
+    }
+    overrides.extend(run.overrides.iter().cloned());
+
+    let base_config = fs::read_to_string(manifest.base.join(CONFIG_FILE_NAME))?;
+    fs::write(
+        run_dir.join(CONFIG_FILE_NAME),
+        apply_overrides(&base_config, &overrides),
+    )?;
+
+    let mut ensemble = Ensemble::from_dir(run_dir)?;
+    ensemble
+        .run_simulation()
+        .inspect_err(|_| ensemble.salvage())?;
+
+    Ok(ensemble.dst_path.clone())
+}
+
+/// Puts `key:value` over the line of the key in the settings part of `config` (before
+/// the comment line), or adds it at the end of that part.
+fn apply_overrides(config: &str, overrides: &[(String, String)]) -> String {
+    let mut lines: Vec<String> = config.lines().map(String::from).collect();
+    let mut end = lines
+        .iter()
+        .position(|line| line.trim() == COMMENT_LINE)
+        .unwrap_or(lines.len());
+
+    for (key, value) in overrides {
+        let line_id = lines[..end].iter().position(|line| {
+            line.split_once(':')
+                .is_some_and(|(line_key, _)| line_key.trim() == key)
+        });
+        match line_id {
+            Some(i) => {
+                let indent_len = lines[i].len() - lines[i].trim_start().len();
+                lines[i] = format!("{}{}:{}", &lines[i][..indent_len], key, value);
+            }
+            None => {
+                lines.insert(end, format!("{}:{}", key, value));
+                end += 1;
+            }
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Writes the last frame of every item of the results directory `dst_path`, in item
+/// order, as an `InitStates.ini`; the number of states.
+fn write_final_states(dst_path: &Path, path_states: &Path) -> Result<usize> {
+    let mut item_dirs: Vec<PathBuf> = fs::read_dir(dst_path)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.parse::<usize>().is_ok())
+        })
+        .collect();
+    item_dirs.sort();
+    if item_dirs.is_empty() {
+        return Err(format!("'{}' holds no item directories", dst_path.display()).into());
+    }
+
+    let mut states_buf = BufWriter::new(File::create(path_states)?);
+    let mut header: Option<StateHeader> = None;
+    for item_dir in &item_dirs {
+        let (item_header, state) = last_state(item_dir)?
+            .ok_or_else(|| format!("'{}' holds no states (WriteI: 0?)", item_dir.display()))?;
+        if let Some(item_header) = item_header
+            && header != Some(item_header)
+        {
+            writeln!(states_buf, "{}", item_header)?;
+            header = Some(item_header);
+        }
+        io_handler::write_frame(&mut states_buf, &state)?;
+    }
+    states_buf.flush()?;
+
+    Ok(item_dirs.len())
+}
+
+/// Last complete frame of the `TimeStates` file of an item directory, with its `#states`
+/// header (text files).
+fn last_state(item_dir: &Path) -> IoResult<Option<(Option<StateHeader>, Vec<u8>)>> {
+    let path_packed = item_dir.join(TIME_STATES_ZST_FILE_NAME);
+    if path_packed.exists() {
+        return Ok(writer::read_packed_frames(&path_packed)?
+            .pop()
+            .map(|(_, frame)| (None, frame.into_vec())));
+    }
+
+    let Some(path_states) = [TIME_STATES_BIN_FILE_NAME, TIME_STATES_FILE_NAME]
+        .iter()
+        .map(|name| item_dir.join(name))
+        .find(|p| p.exists())
+    else {
+        return Ok(None);
+    };
+    let mut frames = io_handler::StateFrames::open(&path_states)?;
+    let (mut frame, mut last) = (Vec::new(), None);
+    while frames.next_frame(&mut frame)? {
+        last = Some((frames.header(), std::mem::take(&mut frame)));
+    }
+
+    Ok(last)
+}
+
+fn parse_manifest(path: &Path) -> Result<Manifest> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest '{}': {}", path.display(), e))?;
+    let root = path.parent().unwrap_or(Path::new("."));
+    let line_err = |i: usize, msg: String| format!("{} (line {}): {}", path.display(), i + 1, msg);
+
+    let mut base: Option<PathBuf> = None;
+    let mut dir = root.to_path_buf();
+    let mut jobs = 1;
+    let mut shared: Vec<(String, String)> = Vec::new();
+    // Runs with the name of their `after` run and the line of their table
+    let mut runs: Vec<(Run, Option<String>, usize)> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == RUN_TABLE {
+            runs.push((Run::default(), None, i));
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(line_err(i, format!("only '{}' tables are supported", RUN_TABLE)).into());
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| line_err(i, format!("expected 'key = value': '{}'", line)))?;
+        let key = key.trim();
+        let value = parse_value(value.trim()).map_err(|e| line_err(i, e))?;
+
+        match (runs.last_mut(), key) {
+            (None, "base") => base = Some(root.join(value)),
+            (None, "dir") => dir = root.join(value),
+            (None, "jobs") => {
+                jobs = value
+                    .parse()
+                    .map_err(|_| line_err(i, format!("'jobs' must be a count: '{}'", value)))?
+            }
+            (Some((run, _, _)), "name") => run.name = value,
+            (Some((_, after, _)), "after") => *after = Some(value),
+            (run, key) => {
+                if !io_handler::is_config_key(key) {
+                    let msg = match run.is_none() && !TOP_KEYS.contains(&key) {
+                        true => format!("'{}' is neither a manifest key nor a setting", key),
+                        false => format!("'{}' is not a setting", key),
+                    };
+                    return Err(line_err(i, msg).into());
+                }
+                let overrides = match run {
+                    Some((run, _, _)) => &mut run.overrides,
+                    None => &mut shared,
+                };
+                if overrides.iter().any(|(k, _)| k == key) {
+                    return Err(line_err(i, format!("duplicate key '{}'", key)).into());
+                }
+                overrides.push((key.to_string(), value));
+            }
+        }
+    }
+
+    let base = base.ok_or_else(|| format!("{}: 'base' is not set", path.display()))?;
+    if !base.join(CONFIG_FILE_NAME).exists() {
+        return Err(format!("No {} in base '{}'", CONFIG_FILE_NAME, base.display()).into());
+    }
+    if runs.is_empty() {
+        return Err(format!("{}: no '{}' tables", path.display(), RUN_TABLE).into());
+    }
+
+    let names: Vec<String> = runs.iter().map(|(run, _, _)| run.name.clone()).collect();
+    for (k, (run, _, i)) in runs.iter().enumerate() {
+        let is_dir_name = !run.name.is_empty()
+            && run.name != "."
+            && run.name != ".."
+            && run
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+        if !is_dir_name {
+            return Err(line_err(
+                *i,
+                format!(
+                    "run name '{}' must be of letters, digits, '-', '_' and '.'",
+                    run.name
+                ),
+            )
+            .into());
+        }
+        if names[..k].contains(&run.name) {
+            return Err(line_err(*i, format!("duplicate run name '{}'", run.name)).into());
+        }
+    }
+
+    let mut resolved = Vec::with_capacity(runs.len());
+    for (mut run, after, i) in runs {
+        if let Some(after) = after {
+            let j = names.iter().position(|name| *name == after);
+            run.after =
+                Some(j.ok_or_else(|| {
+                    line_err(i, format!("'after' names an unknown run '{}'", after))
+                })?);
+        }
+        run.overrides = shared.iter().cloned().chain(run.overrides).collect();
+        resolved.push(run);
+    }
+
+    // Following `after` from any run must end within the run count
+    for (k, run) in resolved.iter().enumerate() {
+        let mut next = run.after;
+        for _ in 0..resolved.len() {
+            next = next.and_then(|j| resolved[j].after);
+        }
+        if next.is_some() {
+            return Err(format!(
+                "{}: the 'after' chain of run '{}' is a cycle",
+                path.display(),
+                names[k]
+            )
+            .into());
+        }
+    }
+
+    Ok(Manifest {
+        base,
+        dir,
+        jobs,
+        runs: resolved,
+    })
+}
+
+/// A line without its `#` comment (a `#` inside a quoted string is kept).
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Value of a manifest line: a quoted string without its quotes, anything else (numbers,
+/// booleans, expressions) as written.
+fn parse_value(value: &str) -> std::result::Result<String, String> {
+    if value.starts_with('[') || value.starts_with('{') {
+        return Err(format!(
+            "arrays and inline tables are not supported: '{}'",
+            value
+        ));
+    }
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote) {
+            return inner
+                .strip_suffix(quote)
+                .filter(|inner| !inner.contains(quote))
+                .map(String::from)
+                .ok_or_else(|| format!("malformed string '{}'", value));
+        }
+    }
+    match value.is_empty() {
+        true => Err("empty value".to_string()),
+        false => Ok(value.to_string()),
+    }
+}
+
+fn status_text(status: &Status) -> String {
+    match status {
+        Status::Pending | Status::Running => "not run".to_string(),
+        Status::Done { dst_path, secs } => format!("done in {:.1} s: {}", secs, dst_path.display()),
+        Status::Failed { msg, secs } => format!("failed after {:.1} s: {}", secs, msg),
+        Status::Skipped(reason) => format!("skipped: {}", reason),
+    }
+}
+
+/// `ManifestStatus.txt`: a line per run, tab-separated (the paths may hold ':').
+fn write_report(manifest: &Manifest, status: &[Status], path_report: &Path) -> IoResult<()> {
+    let mut report_buf = BufWriter::new(File::create(path_report)?);
+    writeln!(report_buf, "# run\tafter\tstatus\ttime_s\tresults\tnote")?;
+    for (run, status) in manifest.runs.iter().zip(status) {
+        let after = run.after.map_or("-", |j| manifest.runs[j].name.as_str());
+        let (status_name, secs, results, note) = match status {
+            Status::Pending | Status::Running => ("not_run", None, None, String::new()),
+            Status::Done { dst_path, secs } => ("done", Some(secs), Some(dst_path), String::new()),
+            Status::Failed { msg, secs } => ("failed", Some(secs), None, msg.replace('\n', " ")),
+            Status::Skipped(reason) => ("skipped", None, None, reason.clone()),
+        };
+        writeln!(
+            report_buf,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            run.name,
+            after,
+            status_name,
+            secs.map_or("-".to_string(), |secs| format!("{:.1}", secs)),
+            results.map_or("-".to_string(), |p| p.display().to_string()),
+            note
+        )?;
+    }
+    report_buf.flush()
+}
@@ -1,11 +1,64 @@
+pub mod anchor;
+pub mod audit;
+pub mod blocking;
+pub mod campaign;
+pub mod clusters;
+pub mod compare;
 pub mod constants;
+pub mod convert;
+pub mod detach_spectrum;
+pub mod diff;
+pub mod distributed;
 pub mod ensemble;
-pub mod frontier;
+pub mod events;
+pub mod exact;
+pub mod facets;
+pub mod faults;
+pub mod fixture;
+pub mod front_export;
+pub mod groups;
+pub mod heat;
 pub mod io_handler;
 pub mod item;
-pub mod lattice;
+pub mod kossel;
+pub mod manifest;
+pub mod minkowski;
+pub mod nuclei;
+pub mod orientation;
+pub mod pack;
+pub mod passage;
+pub mod passivation;
 pub mod paths;
+pub mod plots;
+pub mod probes;
+pub mod profile;
+pub mod residence;
+pub mod resume;
+pub mod reweight;
+pub mod rng;
+pub mod run;
+pub mod server;
 pub mod settings;
 pub mod simulation;
 pub mod state;
+pub mod status;
+pub mod steady;
+pub mod steering;
+pub mod step_extend;
+pub mod sublattice;
+pub mod thermostat;
 pub mod utils;
+pub mod view;
+pub mod write_tuner;
+pub mod writer;
+
+pub use mc_core::acceptance;
+pub use mc_core::ballistics;
+pub use mc_core::config_version;
+pub use mc_core::energy;
+pub use mc_core::frontier;
+pub use mc_core::lattice;
+pub use mc_core::mode;
+pub use mc_core::state_header;
+pub use mc_core::summation;
+pub use mc_core::unknown_keys;
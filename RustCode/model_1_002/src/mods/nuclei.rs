@@ -0,0 +1,123 @@
+use crate::mods::{constants::K_BOLTZMANN, settings::Settings};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// What `NucleiFilter` does with the initial states below the critical nucleus size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NucleiFilter {
+    Off,
+    /// Leave them out of the ensemble.
+    Drop,
+    /// Keep them, only listing them in `Nuclei.txt`.
+    Mark,
+}
+
+impl NucleiFilter {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Some(Self::Off),
+            "drop" => Some(Self::Drop),
+            "mark" => Some(Self::Mark),
+            _ => None,
+        }
+    }
+}
+
+/// Driving force of the configured run: `dg`, or `kT ln(C0 / C_eq)` in modes 2.X.
+pub fn initial_dg(cfg: &Settings) -> f64 {
+    match cfg.mode_major == 2 {
+        true => K_BOLTZMANN * cfg.temperature * (cfg.c0 / cfg.c_eq).ln(),
+        false => cfg.dg,
+    }
+}
+
+/// Critical nucleus size (cells) of the face-energy model at the driving force `dg`:
+/// the Wulff box with edges proportional to the face energies `ex`, `ey`, `ez` has
+/// `dG(l) = 6 ex ey ez l^2 - dg ex ey ez l^3`, maximal at `l = 4 / dg`, i.e.
+/// `n* = 64 ex ey ez / dg^3`. Infinite when `dg <= 0` (nothing grows).
+pub fn critical_size(cfg: &Settings, dg: f64) -> f64 {
+    let (ex, ey, ez) = (
+        cfg.g100 * cfg.ay * cfg.az,
+        cfg.g010 * cfg.ax * cfg.az,
+        cfg.g001 * cfg.ax * cfg.ay,
+    );
+    match dg > 0.0 {
+        true => 64.0 * ex * ey * ez / dg.powi(3),
+        false => f64::INFINITY,
+    }
+}
+
+/// Initial state checked against the critical size.
+#[derive(Debug, Clone, Copy)]
+pub struct Nucleus {
+    /// Index of the state in `InitStates.ini`.
+    pub line: usize,
+    pub n_cryst: usize,
+    pub is_critical: bool,
+    /// `None` when the state was dropped.
+    pub item_gid: Option<usize>,
+}
+
+/// Checks the loaded states against `n_crit`; with `NucleiFilter::Drop` the sub-critical
+/// ones are removed from `states` and `item_dims` (`default_dims` fills the table for the
+/// remaining items).
+pub fn filter(
+    states: &mut Vec<Vec<u8>>,
+    item_dims: &mut Vec<(usize, usize, usize)>,
+    default_dims: (usize, usize, usize),
+    n_crit: f64,
+    action: NucleiFilter,
+) -> Vec<Nucleus> {
+    let mut nuclei = Vec::with_capacity(states.len());
+    let mut kept_dims = Vec::with_capacity(states.len());
+    let mut line = 0;
+    states.retain(|state| {
+        let n_cryst = state.iter().filter(|&&cell| cell == 1).count();
+        let is_critical = n_cryst as f64 >= n_crit;
+        let is_kept = is_critical || action != NucleiFilter::Drop;
+        if is_kept {
+            kept_dims.push(item_dims.get(line).copied().unwrap_or(default_dims));
+        }
+        nuclei.push(Nucleus {
+            line,
+            n_cryst,
+            is_critical,
+            item_gid: is_kept.then(|| kept_dims.len() - 1),
+        });
+        line += 1;
+        is_kept
+    });
+    if action == NucleiFilter::Drop && !item_dims.is_empty() {
+        *item_dims = kept_dims;
+    }
+
+    nuclei
+}
+
+/// Writes `Nuclei.txt`: the critical size and one `line:n_cryst:critical:item_gid` row
+/// per initial state (`-` for a dropped one).
+pub fn write_report(path: &Path, dg: f64, n_crit: f64, nuclei: &[Nucleus]) -> IoResult<()> {
+    let mut buf = BufWriter::new(File::create(path).map_err(|e| {
+        IoError::new(
+            e.kind(),
+            format!("Failed to create file '{}': {}", path.display(), e),
+        )
+    })?);
+    writeln!(buf, "# dg={:.5e} n_crit={:.5e}", dg, n_crit)?;
+    writeln!(buf, "# line:n_cryst:critical:item_gid")?;
+    for nucleus in nuclei {
+        let item_gid = match nucleus.item_gid {
+            Some(gid) => gid.to_string(),
+            None => "-".to_string(),
+        };
+        writeln!(
+            buf,
+            "{}:{}:{}:{}",
+            nucleus.line, nucleus.n_cryst, nucleus.is_critical as u8, item_gid
+        )?;
+    }
+    buf.flush()
+}
@@ -0,0 +1,112 @@
+use crate::mods::lattice::Grid;
+
+/// Jacobi sweeps of `eigen_sym3`; a 3x3 matrix converges to f64 precision in a few.
+const JACOBI_SWEEPS: usize = 16;
+
+/// Principal axes of the crystal: the eigenvectors of the covariance (second moment)
+/// tensor of the crystal sites about their centre of mass, in physical units. The axes
+/// of the covariance and of the moment of inertia tensor coincide; `axes[0]` is the
+/// longest direction of the crystal (smallest moment of inertia).
+///
+/// Coordinates are not unwrapped across periodic boundaries, so a crystal crossing one
+/// gives meaningless axes, as it does for the bounding box sizes.
+#[derive(Debug, Clone, Copy)]
+pub struct PrincipalAxes {
+    /// Variances along the axes, in decreasing order.
+    pub variances: [f64; 3],
+    /// Unit axes (grid axes), `axes[k]` belongs to `variances[k]`.
+    pub axes: [[f64; 3]; 3],
+}
+
+impl PrincipalAxes {
+    /// Principal axes of the sites with `state == 1`; `None` for fewer than 2 sites.
+    pub fn of_crystal(state: &[u8], grid: &Grid, (ax, ay, az): (f64, f64, f64)) -> Option<Self> {
+        let (mut n, mut sum, mut sum2) = (0.0, [0.0; 3], [[0.0; 3]; 3]);
+        for (idx, _) in state.iter().enumerate().filter(|&(_, &s)| s == 1) {
+            let (x, y, z) = grid.idx_to_xyz(idx);
+            let r = [x as f64 * ax, y as f64 * ay, z as f64 * az];
+            n += 1.0;
+            for i in 0..3 {
+                sum[i] += r[i];
+                for j in i..3 {
+                    sum2[i][j] += r[i] * r[j];
+                }
+            }
+        }
+        if n < 2.0 {
+            return None;
+        }
+
+        let mut cov = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in i..3 {
+                cov[i][j] = sum2[i][j] / n - (sum[i] / n) * (sum[j] / n);
+                cov[j][i] = cov[i][j];
+            }
+        }
+
+        let (values, vectors) = eigen_sym3(cov);
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+
+        Some(Self {
+            variances: order.map(|k| values[k]),
+            axes: order.map(|k| [vectors[0][k], vectors[1][k], vectors[2][k]]),
+        })
+    }
+
+    /// Angle (degrees, 0..90) between the longest axis and `reference`; axes have no
+    /// sign, so a half turn reads as 0.
+    pub fn angle_to(&self, reference: &[f64; 3]) -> f64 {
+        let dot: f64 = (0..3).map(|i| self.axes[0][i] * reference[i]).sum();
+        dot.abs().min(1.0).acos().to_degrees()
+    }
+
+    /// `1 - var_min / var_max`: 0 for an isotropic crystal, whose axes (and so the
+    /// angle) are arbitrary, towards 1 for a needle or a plate.
+    pub fn anisotropy(&self) -> f64 {
+        match self.variances[0] > 0.0 {
+            true => 1.0 - self.variances[2] / self.variances[0],
+            false => 0.0,
+        }
+    }
+}
+
+/// Eigenvalues and eigenvectors (columns) of a symmetric 3x3 matrix, cyclic Jacobi.
+fn eigen_sym3(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..JACOBI_SWEEPS {
+        let off = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        let diag = a[0][0].powi(2) + a[1][1].powi(2) + a[2][2].powi(2);
+        if off <= f64::EPSILON * f64::EPSILON * diag {
+            break;
+        }
+
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            for row in a.iter_mut() {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in v.iter_mut() {
+                let (vkp, vkq) = (row[p], row[q]);
+                row[p] = c * vkp - s * vkq;
+                row[q] = s * vkp + c * vkq;
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
@@ -0,0 +1,407 @@
+use crate::mods::constants::{PACK_EXTENSION, PACK_MANIFEST_FILE_NAME};
+use chrono::Utc;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// zstd level of the archive when `--level` is not given.
+pub const PACK_ZSTD_LEVEL: i32 = 3;
+const BLOCK: usize = 512;
+/// Largest size of the octal `size` field of a tar header; larger files use the base-256
+/// form of GNU tar.
+const OCTAL_SIZE_MAX: u64 = 0o77_777_777_777;
+
+/// One packed file: path in the archive, size and FNV-1a hash of the content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackEntry {
+    pub path: String,
+    pub size: u64,
+    pub hash: u64,
+}
+
+/// Entry point of the `pack` subcommand.
+///
+/// Usage: `pack <run_dir> [--out <file>] [--level N]` bundles a run directory (config
+/// copy, states, logs, summary, run metadata) into one zstd-compressed tar, by default
+/// `<run_dir>.tar.zst` next to it, with `PackManifest.txt` (path, size and FNV-1a hash of
+/// every file) as its last entry. `pack --verify <archive>` reads an archive back and
+/// checks every file against the manifest, so a run moved between systems is known to
+/// be complete. The archive unpacks with `tar --zstd -xf`.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: pack <run_dir> [--out <file>] [--level N] | pack --verify <archive>";
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let mut out_path: Option<PathBuf> = None;
+    let mut verify_path: Option<PathBuf> = None;
+    let mut level = PACK_ZSTD_LEVEL;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                out_path = Some(PathBuf::from(
+                    args_iter.next().ok_or("Missing value after '--out'")?,
+                ));
+            }
+            "--level" => {
+                let value = args_iter.next().ok_or("Missing value after '--level'")?;
+                level = value
+                    .parse()
+                    .map_err(|_| format!("Invalid '--level' value '{value}'"))?;
+            }
+            "--verify" => {
+                verify_path = Some(PathBuf::from(
+                    args_iter.next().ok_or("Missing value after '--verify'")?,
+                ));
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if let Some(archive) = verify_path {
+        let n_files = verify(&archive)?;
+        println!(
+            "✅ '{}': {} file(s) match {}",
+            archive.display(),
+            n_files,
+            PACK_MANIFEST_FILE_NAME
+        );
+        return Ok(());
+    }
+
+    let [dir] = &dirs[..] else {
+        return Err(usage.into());
+    };
+    let (archive, entries) = pack_dir(dir, out_path.as_deref(), level)?;
+    println!(
+        "📦 '{}' packed into '{}' ({} file(s), {} bytes)",
+        dir.display(),
+        archive.display(),
+        entries.len(),
+        entries.iter().map(|e| e.size).sum::<u64>()
+    );
+
+    Ok(())
+}
+
+/// Packs the run directory `dir` into `out` (`None` - `<dir>.tar.zst`); returns the
+/// archive path and the packed files. The files are stored under the directory name, in
+/// path order; the manifest comes last.
+pub fn pack_dir(dir: &Path, out: Option<&Path>, level: i32) -> Result<(PathBuf, Vec<PackEntry>)> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Run directory '{}': {}", dir.display(), e))?;
+    let run_name = dir
+        .file_name()
+        .ok_or_else(|| format!("'{}' has no directory name", dir.display()))?
+        .to_string_lossy()
+        .into_owned();
+    let archive = match out {
+        Some(out) => out.to_path_buf(),
+        None => dir.with_file_name(format!("{run_name}.{PACK_EXTENSION}")),
+    };
+
+    let mut files = Vec::new();
+    collect_files(&dir, &mut files)?;
+    let archive_abs = archive.canonicalize().ok();
+    files.retain(|path| Some(path) != archive_abs.as_ref());
+    files.sort();
+
+    let file = File::create(&archive)
+        .map_err(|e| format!("Failed to create '{}': {}", archive.display(), e))?;
+    let mut encoder = zstd::stream::write::Encoder::new(BufWriter::new(file), level)?;
+
+    let mut entries = Vec::with_capacity(files.len());
+    for path in &files {
+        let rel = path
+            .strip_prefix(&dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let name = format!("{run_name}/{rel}");
+        let size = fs::metadata(path)?.len();
+        let mut reader = BufReader::new(
+            File::open(path).map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?,
+        );
+        let hash = write_entry(&mut encoder, &name, size, &mut reader)
+            .map_err(|e| format!("Failed to pack '{}': {}", path.display(), e))?;
+        entries.push(PackEntry {
+            path: rel,
+            size,
+            hash,
+        });
+    }
+
+    let manifest = manifest_text(&run_name, &entries);
+    let name = format!("{run_name}/{PACK_MANIFEST_FILE_NAME}");
+    write_entry(
+        &mut encoder,
+        &name,
+        manifest.len() as u64,
+        &mut manifest.as_bytes(),
+    )?;
+    encoder.write_all(&[0; 2 * BLOCK])?;
+    encoder.finish()?.flush()?;
+
+    Ok((archive, entries))
+}
+
+/// Reads `archive` through and checks its files against the manifest: missing, extra,
+/// resized and changed files are errors. Returns the number of checked files.
+pub fn verify(archive: &Path) -> Result<usize> {
+    let file = File::open(archive)
+        .map_err(|e| format!("Failed to open '{}': {}", archive.display(), e))?;
+    let mut decoder = zstd::stream::read::Decoder::new(file)?;
+
+    let mut found: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    let mut manifest: Option<String> = None;
+    let mut header = [0u8; BLOCK];
+    loop {
+        decoder.read_exact(&mut header)?;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let (name, size) = parse_header(&header)?;
+        // Directories, links and the extended headers of other tar writers.
+        if !matches!(header[156], b'0' | 0) {
+            std::io::copy(
+                &mut (&mut decoder).take(size + padding(size)),
+                &mut std::io::sink(),
+            )?;
+            continue;
+        }
+        let rel = match name.split_once('/') {
+            Some((_, rel)) => rel.to_string(),
+            None => name.clone(),
+        };
+
+        let mut content = (&mut decoder).take(size);
+        if rel == PACK_MANIFEST_FILE_NAME {
+            let mut text = String::new();
+            content.read_to_string(&mut text)?;
+            manifest = Some(text);
+        } else {
+            let hash = hash_copy(&mut content, &mut std::io::sink(), size)?;
+            found.insert(rel, (size, hash));
+        }
+        let pad = padding(size);
+        std::io::copy(&mut (&mut decoder).take(pad), &mut std::io::sink())?;
+    }
+
+    let manifest = manifest.ok_or(format!("no {PACK_MANIFEST_FILE_NAME} in the archive"))?;
+    let mut problems = Vec::new();
+    let mut n_listed = 0;
+    for entry in parse_manifest(&manifest)? {
+        n_listed += 1;
+        match found.remove(&entry.path) {
+            None => problems.push(format!("missing: {}", entry.path)),
+            Some((size, _)) if size != entry.size => problems.push(format!(
+                "size differs: {} ({} in the manifest, {} packed)",
+                entry.path, entry.size, size
+            )),
+            Some((_, hash)) if hash != entry.hash => {
+                problems.push(format!("content differs: {}", entry.path))
+            }
+            Some(_) => {}
+        }
+    }
+    problems.extend(
+        found
+            .keys()
+            .map(|path| format!("not in the manifest: {path}")),
+    );
+
+    match problems.is_empty() {
+        true => Ok(n_listed),
+        false => Err(format!(
+            "'{}' does not match its manifest:\n  {}",
+            archive.display(),
+            problems.join("\n  ")
+        )
+        .into()),
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in
+        fs::read_dir(dir).map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?
+    {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        } else {
+            eprintln!(
+                "⚠️ Warning: '{}' is not a regular file; not packed.",
+                entry.path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn manifest_text(run_name: &str, entries: &[PackEntry]) -> String {
+    let mut text = format!(
+        "# run: {}\n# packed: {}\n# files: {}\n# bytes: {}\n# path:size:fnv1a64\n",
+        run_name,
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+        entries.len(),
+        entries.iter().map(|e| e.size).sum::<u64>()
+    );
+    for entry in entries {
+        text.push_str(&format!(
+            "{}:{}:{:016x}\n",
+            entry.path, entry.size, entry.hash
+        ));
+    }
+    text
+}
+
+fn parse_manifest(text: &str) -> Result<Vec<PackEntry>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.rsplitn(3, ':');
+            let (Some(hash), Some(size), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(format!("malformed manifest line '{line}'").into());
+            };
+            Ok(PackEntry {
+                path: path.to_string(),
+                size: size
+                    .parse()
+                    .map_err(|_| format!("malformed manifest line '{line}'"))?,
+                hash: u64::from_str_radix(hash, 16)
+                    .map_err(|_| format!("malformed manifest line '{line}'"))?,
+            })
+        })
+        .collect()
+}
+
+/// Writes a ustar entry of `size` bytes read from `reader`; returns their FNV-1a hash.
+fn write_entry<W: Write, R: Read>(
+    writer: &mut W,
+    name: &str,
+    size: u64,
+    reader: &mut R,
+) -> Result<u64> {
+    writer.write_all(&tar_header(name, size)?)?;
+    let hash = hash_copy(&mut reader.take(size), writer, size)?;
+    writer.write_all(&vec![0; padding(size) as usize])?;
+    Ok(hash)
+}
+
+/// Copies exactly `size` bytes, hashing them on the way (`io_handler::frame_checksum`).
+fn hash_copy<R: Read, W: Write>(reader: &mut R, writer: &mut W, size: u64) -> Result<u64> {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut buf = vec![0u8; 1 << 16];
+    let mut copied = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n] {
+            hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    if copied != size {
+        return Err(
+            format!("{copied} bytes read, {size} expected (file changed while packing?)").into(),
+        );
+    }
+    Ok(hash)
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+/// ustar header of a regular file; a name over 100 bytes is split into `prefix/name` at a
+/// `/`.
+fn tar_header(name: &str, size: u64) -> Result<[u8; BLOCK]> {
+    let mut header = [0u8; BLOCK];
+    let (prefix, name) = match name.len() {
+        0..=100 => ("", name),
+        _ => name
+            .char_indices()
+            .filter(|&(i, c)| c == '/' && i <= 155 && name.len() - i - 1 <= 100)
+            .map(|(i, _)| (&name[..i], &name[i + 1..]))
+            .next()
+            .ok_or_else(|| format!("path '{name}' is too long for a tar header"))?,
+    };
+
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    match size <= OCTAL_SIZE_MAX {
+        true => header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes()),
+        false => {
+            header[124] = 0x80;
+            header[128..136].copy_from_slice(&size.to_be_bytes());
+        }
+    }
+    let mtime = Utc::now().timestamp().max(0);
+    header[136..148].copy_from_slice(format!("{mtime:011o}\0").as_bytes());
+    header[156] = b'0';
+    header[257..265].copy_from_slice(b"ustar\x0000");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    Ok(header)
+}
+
+/// Path and size of a tar header; checks its checksum.
+fn parse_header(header: &[u8; BLOCK]) -> Result<(String, u64)> {
+    let field = |range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    };
+
+    let stored = u32::from_str_radix(field(148..156).trim(), 8)
+        .map_err(|_| "malformed tar header checksum")?;
+    let checksum: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                b' ' as u32
+            } else {
+                b as u32
+            }
+        })
+        .sum();
+    if stored != checksum {
+        return Err("corrupt tar header (checksum mismatch)".into());
+    }
+
+    let size = match header[124] & 0x80 {
+        0 => u64::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|_| "malformed tar header size")?,
+        _ => u64::from_be_bytes(header[128..136].try_into()?),
+    };
+    let (name, prefix) = (field(0..100), field(345..500));
+    let path = match prefix.is_empty() {
+        true => name,
+        false => format!("{prefix}/{name}"),
+    };
+
+    Ok((path, size))
+}
@@ -0,0 +1,180 @@
+use crate::mods::constants::FIRST_PASSAGE_FILE_NAME;
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Sizes of a `FirstPassage` value: positive `n_cryst` thresholds separated by commas,
+/// sorted; `None` for an invalid one, empty for `""`/`none`.
+pub fn parse_thresholds(s: &str) -> Option<Vec<f64>> {
+    let s = s.trim();
+    if s.is_empty() || s.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+
+    let mut thresholds = s
+        .split(',')
+        .map(|t| {
+            t.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|t| t.is_finite() && *t > 0.0)
+        })
+        .collect::<Option<Vec<f64>>>()?;
+    thresholds.sort_by(f64::total_cmp);
+    thresholds.dedup();
+
+    Some(thresholds)
+}
+
+/// First step at which the crystal of an item reaches each size threshold
+/// (`n_cryst >= threshold`), and whether it later fell below it again.
+///
+/// A crystal already above a threshold at the start crosses it at step 0; a resumed run
+/// counts from the checkpoint. `FirstPassage.txt` holds `item:threshold:step:recrossed`
+/// rows (`step` -1: not reached), the `first_passage` rows of `Summary.txt` the share
+/// of the items that reached each threshold, the mean, min and max step and the returns.
+#[derive(Debug, Clone, Default)]
+pub struct FirstPassage {
+    pub is_on: bool,
+    thresholds: Vec<f64>,
+    crossed: Vec<Option<u64>>,
+    recrossed: Vec<bool>,
+    /// Thresholds crossed so far (they are crossed in order).
+    next: usize,
+}
+
+impl FirstPassage {
+    pub fn new(thresholds: &[f64]) -> Self {
+        Self {
+            is_on: !thresholds.is_empty(),
+            thresholds: thresholds.to_vec(),
+            crossed: vec![None; thresholds.len()],
+            recrossed: vec![false; thresholds.len()],
+            next: 0,
+        }
+    }
+
+    /// Checks the crystal size `n_cryst` after `step_id`.
+    #[inline(always)]
+    pub fn update(&mut self, n_cryst: f64, step_id: u64) {
+        if !self.is_on {
+            return;
+        }
+
+        while self.next < self.thresholds.len() && n_cryst >= self.thresholds[self.next] {
+            self.crossed[self.next] = Some(step_id);
+            self.next += 1;
+        }
+        for i in (0..self.next).rev() {
+            if n_cryst >= self.thresholds[i] {
+                break;
+            }
+            self.recrossed[i] = true;
+        }
+    }
+}
+
+/// First-passage times of all the items of the run, gathered as the items finish:
+/// `FirstPassage.txt` (`item:threshold:step:recrossed`, step -1 - never reached) and
+/// per threshold a line of `Summary.txt`.
+#[derive(Debug, Default)]
+pub struct PassageTimes {
+    pub is_on: bool,
+    thresholds: Vec<f64>,
+    items: Vec<(usize, FirstPassage)>,
+}
+
+impl PassageTimes {
+    pub fn new(thresholds: &[f64]) -> Self {
+        Self {
+            is_on: !thresholds.is_empty(),
+            thresholds: thresholds.to_vec(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Takes the record of a finished item; `n_cryst` and `step_id` - its last state.
+    pub fn collect(
+        &mut self,
+        item_gid: usize,
+        passage: &mut FirstPassage,
+        n_cryst: f64,
+        step_id: u64,
+    ) {
+        if !self.is_on {
+            return;
+        }
+
+        passage.update(n_cryst, step_id);
+        self.items.push((item_gid, std::mem::take(passage)));
+    }
+
+    /// Per threshold: the items that reached it, the mean, min and max first-passage
+    /// step and the items that fell below it again.
+    pub fn summary(&self) -> Vec<String> {
+        self.thresholds
+            .iter()
+            .enumerate()
+            .map(|(i, threshold)| {
+                let steps: Vec<u64> = self
+                    .items
+                    .iter()
+                    .filter_map(|(_, passage)| passage.crossed[i])
+                    .collect();
+                let n_recrossed = self
+                    .items
+                    .iter()
+                    .filter(|(_, passage)| passage.recrossed[i])
+                    .count();
+                match steps.is_empty() {
+                    true => format!(
+                        "first_passage n_cryst >= {}: 0/{} items",
+                        threshold,
+                        self.items.len()
+                    ),
+                    false => format!(
+                        "first_passage n_cryst >= {}: {}/{} items, step mean {:.1}, min {}, max {}; recrossed down: {}",
+                        threshold,
+                        steps.len(),
+                        self.items.len(),
+                        steps.iter().sum::<u64>() as f64 / steps.len() as f64,
+                        steps.iter().min().unwrap_or(&0),
+                        steps.iter().max().unwrap_or(&0),
+                        n_recrossed
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    pub fn write(&mut self, path_dst: &Path) -> IoResult<()> {
+        let path_out_file = path_dst.join(FIRST_PASSAGE_FILE_NAME);
+        let mut buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        self.items.sort_by_key(|&(item_gid, _)| item_gid);
+        writeln!(
+            buf,
+            "# item:threshold:step:recrossed (step -1 - never reached)"
+        )?;
+        for (item_gid, passage) in &self.items {
+            for (i, threshold) in passage.thresholds.iter().enumerate() {
+                writeln!(
+                    buf,
+                    "{}:{}:{}:{}",
+                    item_gid,
+                    threshold,
+                    passage.crossed[i].map_or(-1, |step| step as i64),
+                    passage.recrossed[i] as u8
+                )?;
+            }
+        }
+        buf.flush()
+    }
+}
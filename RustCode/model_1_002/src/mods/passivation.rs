@@ -0,0 +1,97 @@
+use crate::mods::{frontier::Frontier, rng::KernelRng, utils::event_attempts};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// Passivating species on the crystal surface (`PassRate`, `PassLife`): every step a
+/// Poisson number of them, with the mean `rate`, adsorbs onto random sites of the TPA and
+/// TPB fronts; an occupied site blocks every event on it (attachment, detachment, the
+/// ballistic channel, swaps) until the species desorbs after an exponential lifetime with
+/// the mean `life` steps. Models additives and inhibitors that pin steps and kinks.
+///
+/// Modes 2.x, `rate <= 100`, not with `ResumeFrom`. The blocked sites are the
+/// `pass_sites` row of `SimLog`; the adsorptions, desorptions and blocked events are
+/// printed at the end.
+#[derive(Debug, Default)]
+pub struct Passivation {
+    pub rate: f64,
+    pub life: f64,
+    /// Blocked sites by grid index, allocated on the first adsorption.
+    blocked: Vec<bool>,
+    /// Desorption step of every blocked site, earliest first.
+    expiry: BinaryHeap<Reverse<(u64, usize)>>,
+    pub adsorbed: u64,
+    pub desorbed: u64,
+    /// Events rejected on blocked sites.
+    pub blocked_attempts: u64,
+}
+
+impl Passivation {
+    pub fn new(rate: f64, life: f64) -> Self {
+        Self {
+            rate,
+            life,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Sites blocked now.
+    pub fn count(&self) -> usize {
+        self.expiry.len()
+    }
+
+    /// Desorbs the species whose lifetime ended by `step_id`, then adsorbs the new ones of
+    /// this step; an adsorption onto an already blocked site has no effect. No draws when
+    /// off.
+    pub fn step<R: KernelRng>(&mut self, rng: &mut R, front: &Frontier, size: usize, step_id: u64) {
+        if !self.is_on() {
+            return;
+        }
+
+        while let Some(&Reverse((expiry_step, idxg))) = self.expiry.peek() {
+            if expiry_step > step_id {
+                break;
+            }
+            self.expiry.pop();
+            self.blocked[idxg] = false;
+            self.desorbed += 1;
+        }
+
+        for _ in 0..event_attempts(rng, self.rate) {
+            let n_sites = front.tpas_size + front.tpbs_size;
+            if n_sites == 0 {
+                return;
+            }
+            let k = rng.random_range(0..n_sites);
+            let idxg = match k < front.tpas_size {
+                true => front.tpas[k],
+                false => front.tpbs[k - front.tpas_size],
+            };
+
+            if self.blocked.is_empty() {
+                self.blocked = vec![false; size];
+            }
+            if self.blocked[idxg] {
+                continue;
+            }
+            let life = (-self.life * (1.0 - rng.random::<f64>()).ln())
+                .ceil()
+                .max(1.0);
+            self.blocked[idxg] = true;
+            self.expiry.push(Reverse((step_id + life as u64, idxg)));
+            self.adsorbed += 1;
+        }
+    }
+
+    /// Whether the site `idxg` is blocked; counts the rejected event.
+    #[inline(always)]
+    pub fn is_blocked(&mut self, idxg: usize) -> bool {
+        let is_blocked = !self.blocked.is_empty() && self.blocked[idxg];
+        if is_blocked {
+            self.blocked_attempts += 1;
+        }
+        is_blocked
+    }
+}
@@ -0,0 +1,220 @@
+//! Quick-look SVG figures of a finished run in its `plots/` folder (feature `plots`):
+//! `n_cryst`, `conc` and `dg` of the ensemble, the crystal sizes of the first item and,
+//! with `FacetStats:true`, the acceptance rates per site class. SVG keeps the labels
+//! without system fonts, which the PNG backend would need.
+
+use std::{error::Error, path::PathBuf};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Entry point of the `plots` subcommand.
+///
+/// Usage: `plots <results dir>`. Writes the figures of an existing run, e.g. one made
+/// without the feature.
+pub fn run(args: &[String]) -> Result<()> {
+    let dir = PathBuf::from(args.first().ok_or("Usage: plots <results dir>")?);
+    let count = write_plots(&dir)?;
+    println!("✅ {} plot(s) written to '{}'", count, dir.display());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "plots"))]
+pub fn write_plots(_dir: &std::path::Path) -> Result<usize> {
+    Err("built without the 'plots' feature (cargo build --release --features plots)".into())
+}
+
+#[cfg(feature = "plots")]
+pub use figures::write_plots;
+
+#[cfg(feature = "plots")]
+mod figures {
+    use super::Result;
+    use crate::mods::{
+        constants::{FACET_STATS_FILE_NAME, PLOTS_DIR_NAME},
+        state::{self, SIM_LOG_ROWS},
+    };
+    use plotters::prelude::*;
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    const PLOT_SIZE: (u32, u32) = (800, 500);
+
+    type Series = (String, Vec<(f64, f64)>);
+
+    /// Writes the figures of the run in `dir` to `dir/plots`; returns their count.
+    pub fn write_plots(dir: &Path) -> Result<usize> {
+        let path_plots = dir.join(PLOTS_DIR_NAME);
+        fs::create_dir_all(&path_plots)
+            .map_err(|e| format!("Failed to create '{}': {e}", path_plots.display()))?;
+
+        let mut count = 0;
+        let log = read_log(dir)?;
+        for (name, title) in [
+            ("n_cryst", "Crystal particles"),
+            ("conc", "Concentration"),
+            ("dg", "dg (J)"),
+            ("phi", "Crystal volume fraction"),
+            ("depletion", "Reservoir depletion"),
+        ] {
+            let series = vec![(name.to_string(), history(&log, name))];
+            count += line_chart(&path_plots.join(format!("{name}.svg")), title, &series)?;
+        }
+
+        if let Some(item_dir) = first_item_dir(dir) {
+            let item_log = read_log(&item_dir)?;
+            let series: Vec<Series> = ["cryst_sx", "cryst_sy", "cryst_sz"]
+                .iter()
+                .map(|&name| (name.to_string(), history(&item_log, name)))
+                .collect();
+            let title = format!(
+                "Crystal sizes, item {}",
+                item_dir.file_name().unwrap_or_default().to_string_lossy()
+            );
+            count += line_chart(&path_plots.join("sizes.svg"), &title, &series)?;
+        }
+
+        let path_facets = dir.join(FACET_STATS_FILE_NAME);
+        if let Ok(text) = fs::read_to_string(&path_facets) {
+            let series = acceptance_series(&text);
+            count += line_chart(
+                &path_plots.join("acceptance.svg"),
+                "Acceptance rates",
+                &series,
+            )?;
+        }
+
+        Ok(count)
+    }
+
+    fn read_log(dir: &Path) -> Result<Vec<Vec<f64>>> {
+        let rows = state::read_sim_log_rows(dir)
+            .map_err(|e| format!("Failed to read the log of '{}': {e}", dir.display()))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row.iter().filter_map(|s| s.parse::<f64>().ok()).collect())
+            .collect())
+    }
+
+    /// `(mk_step, value)` points of the row `name`; empty if the row was not logged.
+    fn history(log: &[Vec<f64>], name: &str) -> Vec<(f64, f64)> {
+        let row = |name: &str| {
+            let row_id = SIM_LOG_ROWS.iter().position(|&n| n == name)?;
+            log.get(row_id)
+        };
+        match (row("mk_step"), row(name)) {
+            (Some(steps), Some(values)) => {
+                steps.iter().copied().zip(values.iter().copied()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The item sub-directory with the lowest id.
+    fn first_item_dir(dir: &Path) -> Option<PathBuf> {
+        let mut item_dirs: Vec<_> = fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_dir()
+                    && path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            })
+            .collect();
+        item_dirs.sort();
+        item_dirs.into_iter().next()
+    }
+
+    /// `<class>_add_acc` and `<class>_rem_acc` columns of `FacetStats.txt` against `step`.
+    fn acceptance_series(text: &str) -> Vec<Series> {
+        let Some(header) = text.lines().next().and_then(|l| l.strip_prefix("# ")) else {
+            return Vec::new();
+        };
+        let columns: Vec<&str> = header.split(':').collect();
+        let rows: Vec<Vec<f64>> = text
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+            .map(|l| l.split(':').filter_map(|v| v.trim().parse().ok()).collect())
+            .collect();
+
+        columns
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| name.ends_with("_add_acc") || name.ends_with("_rem_acc"))
+            .map(|(col, name)| {
+                let points = rows
+                    .iter()
+                    .filter_map(|row| Some((*row.first()?, *row.get(col)?)))
+                    .collect();
+                (name.to_string(), points)
+            })
+            .collect()
+    }
+
+    /// Axis range of `values`, widened when they are all equal.
+    fn range(values: impl Iterator<Item = f64>) -> std::ops::Range<f64> {
+        let (lo, hi) = values
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        match (lo.is_finite(), hi > lo) {
+            (false, _) => 0.0..1.0,
+            (true, true) => lo..hi,
+            (true, false) => {
+                let pad = match lo.abs() > 0.0 {
+                    true => lo.abs() * 0.1,
+                    false => 1.0,
+                };
+                lo - pad..hi + pad
+            }
+        }
+    }
+
+    /// Draws `series` against the step into `path`; 0 (no file) if there are no points.
+    fn line_chart(path: &Path, title: &str, series: &[Series]) -> Result<usize> {
+        let points = || series.iter().flat_map(|(_, pts)| pts.iter());
+        if points().next().is_none() {
+            return Ok(0);
+        }
+        let x_range = range(points().map(|&(x, _)| x));
+        let y_range = range(points().map(|&(_, y)| y));
+
+        let root = SVGBackend::new(path, PLOT_SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 22))
+            .margin(12)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(x_range, y_range)?;
+        chart
+            .configure_mesh()
+            .x_desc("step")
+            .y_label_formatter(&|v| format!("{:.3e}", v))
+            .draw()?;
+
+        for (id, (name, pts)) in series.iter().enumerate() {
+            let color = Palette99::pick(id).to_rgba();
+            chart
+                .draw_series(LineSeries::new(pts.iter().copied(), color.stroke_width(2)))?
+                .label(name)
+                .legend(move |(x, y)| PathElement::new([(x, y), (x + 16, y)], color));
+        }
+        if series.len() > 1 {
+            chart
+                .configure_series_labels()
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()?;
+        }
+        root.present()?;
+
+        Ok(1)
+    }
+}
@@ -0,0 +1,115 @@
+use crate::mods::{constants::PROBES_FILE_NAME, lattice::Grid};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Cells of a `Probes` value: grid coordinates `X:Y:Z` separated by commas, in the
+/// order given; `None` for an invalid one, empty for `""`/`none`.
+pub fn parse_probes(s: &str) -> Option<Vec<[usize; 3]>> {
+    let s = s.trim();
+    if s.is_empty() || s.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+
+    s.split(',')
+        .map(|probe| {
+            let coords: Vec<usize> = probe
+                .split(':')
+                .map(|c| c.trim().parse().ok())
+                .collect::<Option<_>>()?;
+            match coords[..] {
+                [x, y, z] => Some([x, y, z]),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Point detectors of an item (`Probes`): the step and the new state of every change of
+/// the probe cells, so the event timeline of a site is known without full snapshots.
+/// `Probes.txt` lists the probes (`probe:x:y:z:state` at the start) in the header, then
+/// one `step:probe:state` row per change. Changes are buffered between write steps.
+#[derive(Debug, Default)]
+pub struct SiteProbes {
+    pub is_on: bool,
+    /// Grid coordinates and initial state of the probes.
+    probes: Vec<([usize; 3], u8)>,
+    /// Cell of each probe.
+    cells: Vec<usize>,
+    events: Vec<(u64, u32, u8)>,
+    out_file_buf: Option<BufWriter<File>>,
+}
+
+impl SiteProbes {
+    /// Probes of an item with the initial `state` on `grid`; coordinates outside the grid
+    /// are left out and returned as the second value.
+    pub fn new(coords: &[[usize; 3]], state: &[u8], grid: &Grid) -> (Self, Vec<[usize; 3]>) {
+        let (inside, outside): (Vec<[usize; 3]>, Vec<[usize; 3]>) = coords
+            .iter()
+            .partition(|[x, y, z]| *x < grid.nx && *y < grid.ny && *z < grid.nz);
+        let cells: Vec<usize> = inside
+            .iter()
+            .map(|&[x, y, z]| grid.xyz_to_idx(x, y, z))
+            .collect();
+
+        let probes = Self {
+            is_on: !cells.is_empty(),
+            probes: inside
+                .into_iter()
+                .zip(cells.iter().map(|&idx| state[idx]))
+                .collect(),
+            cells,
+            events: Vec::new(),
+            out_file_buf: None,
+        };
+        (probes, outside)
+    }
+
+    /// Records the change of the cell `idxg` to `state` at `step_id` if it is a probe.
+    #[inline(always)]
+    pub fn record(&mut self, idxg: usize, state: u8, step_id: u64) {
+        if !self.is_on {
+            return;
+        }
+
+        for (probe, _) in self.cells.iter().enumerate().filter(|&(_, &c)| c == idxg) {
+            self.events.push((step_id, probe as u32, state));
+        }
+    }
+
+    /// Appends the buffered changes; the file is created on the first call.
+    pub fn write_events(&mut self, path_dst: &Path) -> IoResult<()> {
+        if self.out_file_buf.is_none() {
+            let path_out_file = path_dst.join(PROBES_FILE_NAME);
+            let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("Failed to create file '{}': {}", path_out_file.display(), e),
+                )
+            })?);
+            writeln!(out_file_buf, "# probe:x:y:z:state")?;
+            for (probe, ([x, y, z], state)) in self.probes.iter().enumerate() {
+                writeln!(out_file_buf, "# {}:{}:{}:{}:{}", probe, x, y, z, state)?;
+            }
+            writeln!(out_file_buf, "# step:probe:state")?;
+            self.out_file_buf = Some(out_file_buf);
+        }
+        let Some(out_file_buf) = &mut self.out_file_buf else {
+            return Ok(());
+        };
+
+        for (step_id, probe, state) in self.events.drain(..) {
+            writeln!(out_file_buf, "{}:{}:{}", step_id, probe, state)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        match &mut self.out_file_buf {
+            Some(out_file_buf) => out_file_buf.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::mods::{constants::DENSITY_PROFILE_FILE_STEM, lattice::Grid};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+const AXIS_NAMES: [char; 3] = ['x', 'y', 'z'];
+
+/// Layer-resolved occupancy of an item: at every write step, the fraction of crystal
+/// cells in each layer perpendicular to the chosen grid axes, one row per step in
+/// `DensityProfile_<axis>.txt` (`step:f_0:f_1:...`). A cheap way to follow the interface
+/// position and the density gradient of deposition geometries without full volumes.
+#[derive(Debug, Default)]
+pub struct DensityProfile {
+    pub is_on: bool,
+    /// Grid axes (0 - x, 1 - y, 2 - z) to profile.
+    axes: Vec<usize>,
+    out_file_bufs: Vec<BufWriter<File>>,
+}
+
+impl DensityProfile {
+    /// Grid axes of a `DensityProfile` value: letters of `xyz` (e.g. `z`, `xz`); `None`
+    /// for an invalid one, empty for `""`/`none`.
+    pub fn parse_axes(s: &str) -> Option<Vec<usize>> {
+        let s = s.trim().to_ascii_lowercase();
+        if s.is_empty() || s == "none" {
+            return Some(Vec::new());
+        }
+
+        let mut axes = Vec::new();
+        for c in s.chars() {
+            let axis = AXIS_NAMES.iter().position(|&a| a == c)?;
+            if axes.contains(&axis) {
+                return None;
+            }
+            axes.push(axis);
+        }
+        axes.sort_unstable();
+
+        Some(axes)
+    }
+
+    pub fn new(axes: &str) -> Self {
+        let axes = Self::parse_axes(axes).unwrap_or_default();
+        Self {
+            is_on: !axes.is_empty(),
+            axes,
+            out_file_bufs: Vec::new(),
+        }
+    }
+
+    /// Appends the profiles of `state` at `step_id`; the files are created on the first
+    /// call, with a header naming the grid size.
+    pub fn write_step(
+        &mut self,
+        path_dst: &Path,
+        step_id: u64,
+        state: &[u8],
+        grid: &Grid,
+    ) -> IoResult<()> {
+        if self.out_file_bufs.is_empty() {
+            self.create_out_files(path_dst, grid)?;
+        }
+
+        let sizes = [grid.nx, grid.ny, grid.nz];
+        let mut counts = sizes.map(|size| vec![0u64; size]);
+        let mut cells = state.iter();
+        for x in 0..grid.nx {
+            for y in 0..grid.ny {
+                for z in 0..grid.nz {
+                    if cells.next() == Some(&1) {
+                        counts[0][x] += 1;
+                        counts[1][y] += 1;
+                        counts[2][z] += 1;
+                    }
+                }
+            }
+        }
+
+        for (&axis, out_file_buf) in self.axes.iter().zip(&mut self.out_file_bufs) {
+            let layer_size = (grid.size / sizes[axis]) as f64;
+            write!(out_file_buf, "{}", step_id)?;
+            for &count in &counts[axis] {
+                write!(out_file_buf, ":{:.6}", count as f64 / layer_size)?;
+            }
+            writeln!(out_file_buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn create_out_files(&mut self, path_dst: &Path, grid: &Grid) -> IoResult<()> {
+        let sizes = [grid.nx, grid.ny, grid.nz];
+        for &axis in &self.axes {
+            let path_out_file = path_dst.join(format!(
+                "{}_{}.txt",
+                DENSITY_PROFILE_FILE_STEM, AXIS_NAMES[axis]
+            ));
+            let mut out_file_buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("Failed to create file '{}': {}", path_out_file.display(), e),
+                )
+            })?);
+            writeln!(
+                out_file_buf,
+                "# grid={}:{}:{} axis={} layers={} step:f_0:...:f_{}",
+                grid.nx,
+                grid.ny,
+                grid.nz,
+                AXIS_NAMES[axis],
+                sizes[axis],
+                sizes[axis] - 1
+            )?;
+            self.out_file_bufs.push(out_file_buf);
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> IoResult<()> {
+        for out_file_buf in &mut self.out_file_bufs {
+            out_file_buf.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::mods::{
+    constants::{AGE_MAP_FILE_NAME, RESIDENCE_HIST_FILE_NAME},
+    io_handler,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::Path,
+};
+
+/// Residence-time bins: bin 0 holds `t = 0`, bin `k` holds `2^(k-1) <= t < 2^k` steps.
+const RESIDENCE_BINS: usize = 65;
+
+/// Site-resolved attachment history of an item.
+///
+/// Every cell keeps the step of its last attachment (0 for the initial crystal, so
+/// residence times of initial cells are counted from the start). A detachment adds the
+/// time spent in the crystal to a log2 histogram. At the end the item writes the
+/// histogram and an age map: the attachment step of every crystal cell (-1 elsewhere),
+/// in the state file layout, showing the growth-history layering.
+#[derive(Debug, Default)]
+pub struct ResidenceStats {
+    pub is_on: bool,
+    attach_steps: Vec<u64>,
+    hist: Vec<u64>,
+}
+
+impl ResidenceStats {
+    pub fn new(is_on: bool, size: usize) -> Self {
+        match is_on {
+            true => Self {
+                is_on,
+                attach_steps: vec![0; size],
+                hist: vec![0; RESIDENCE_BINS],
+            },
+            false => Self::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn attach(&mut self, idxg: usize, step_id: u64) {
+        if self.is_on {
+            self.attach_steps[idxg] = step_id;
+        }
+    }
+
+    #[inline(always)]
+    pub fn detach(&mut self, idxg: usize, step_id: u64) {
+        if self.is_on {
+            let residence = step_id.saturating_sub(self.attach_steps[idxg]);
+            self.hist[(u64::BITS - residence.leading_zeros()) as usize] += 1;
+        }
+    }
+
+    /// Adds the histogram of `other` to this one (ensemble totals).
+    pub fn merge_hist(&mut self, other: &ResidenceStats) {
+        if self.hist.len() < other.hist.len() {
+            self.hist.resize(other.hist.len(), 0);
+        }
+        for (total, &count) in self.hist.iter_mut().zip(&other.hist) {
+            *total += count;
+        }
+    }
+
+    /// Writes `t_min:t_max:count` per non-empty bin (inclusive bounds in steps).
+    pub fn write_hist(&self, path_dst: &Path) -> IoResult<()> {
+        let path_out_file = path_dst.join(RESIDENCE_HIST_FILE_NAME);
+        let mut fbuf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        writeln!(fbuf, "# t_min:t_max:count")?;
+        for (bin, &count) in self.hist.iter().enumerate().filter(|(_, c)| **c > 0) {
+            let (t_min, t_max) = match bin {
+                0 => (0, 0),
+                _ => (1u64 << (bin - 1), (1u64 << (bin - 1)).saturating_mul(2) - 1),
+            };
+            writeln!(fbuf, "{}:{}:{}", t_min, t_max, count)?;
+        }
+        fbuf.flush()
+    }
+
+    pub fn write_age_map(&self, path_dst: &Path, state: &[u8]) -> IoResult<()> {
+        let path_out_file = path_dst.join(AGE_MAP_FILE_NAME);
+        let mut fbuf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        let ages: Vec<i64> = state
+            .iter()
+            .zip(&self.attach_steps)
+            .map(|(&cell, &step)| if cell == 1 { step as i64 } else { -1 })
+            .collect();
+        io_handler::write_state_uni_fast(&mut fbuf, &ages, &|v: i64| v.to_string())?;
+        fbuf.flush()
+    }
+}
@@ -0,0 +1,422 @@
+//! Exact restart of a run from its `Checkpoint.txt` (`ResumeFrom`).
+//!
+//! Next to the item states the checkpoint keeps what the states alone cannot give back:
+//! a `#resume` line with the step, the exact ChaCha8 position (seed, stream, word
+//! position), the thermostat temperature and the reservoir, and an `#item` line before
+//! every state with the item counters, the order of its front arrays, on which the
+//! site draws depend, the compensated energy sums, the orientation of the last log point
+//! and the position of its own random stream (`Deterministic`).
+//! Floats are written in their shortest round-trip form, so a resumed run continues bit
+//! for bit where the checkpoint was taken. The lines hold no ':', so the file still
+//! loads as `InitStates.ini`.
+//!
+//! `ResumeFrom` names the results folder of the run or the file itself. The states are
+//! taken from it instead of `InitStates.ini` (`LoadOption` is ignored) and the steps go
+//! on from the one after the checkpoint up to `StepLim`; the history values of the items
+//! (`tot_denergy`, `rot_angle`) count from the checkpoint. It excludes `Ranks > 1`,
+//! `ItemDimsFile` and `ItemGroupsFile`. The checkpoint of a crashed run has no `#resume`
+//! line and cannot be resumed.
+
+use crate::mods::{
+    constants::CHECKPOINT_FILE_NAME, item::Item, state::SimLog, summation::NeumaierSum,
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+const RESUME_TAG: &str = "#resume";
+const ITEM_TAG: &str = "#item";
+
+/// Reservoir and driving force of a `SimLog`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LogScalars {
+    pub n_cryst: f64,
+    pub n_gas: f64,
+    pub conc: f64,
+    pub dg: f64,
+    pub n_exch: f64,
+    pub k_t: f64,
+    /// `None` in the checkpoints that predate them; the values of the resumed run are kept.
+    pub n_gas0: Option<f64>,
+    pub n_cryst0: Option<f64>,
+}
+
+impl LogScalars {
+    pub fn of(simlog: &SimLog) -> Self {
+        Self {
+            n_cryst: simlog.n_cryst.val,
+            n_gas: simlog.n_gas.val,
+            conc: simlog.conc.val,
+            dg: simlog.dg.val,
+            n_exch: simlog.n_exch.val,
+            k_t: simlog.k_t,
+            n_gas0: Some(simlog.n_gas0),
+            n_cryst0: Some(simlog.n_cryst0),
+        }
+    }
+
+    pub fn apply(&self, simlog: &mut SimLog) {
+        simlog.n_cryst.val = self.n_cryst;
+        simlog.n_gas.val = self.n_gas;
+        simlog.conc.val = self.conc;
+        simlog.dg.val = self.dg;
+        simlog.n_exch.val = self.n_exch;
+        simlog.k_t = self.k_t;
+        if let Some(n_gas0) = self.n_gas0 {
+            simlog.n_gas0 = n_gas0;
+        }
+        if let Some(n_cryst0) = self.n_cryst0 {
+            simlog.n_cryst0 = n_cryst0;
+        }
+    }
+
+    fn fields(&self) -> String {
+        let mut fields = format!(
+            "n_cryst={:?} n_gas={:?} conc={:?} dg={:?} n_exch={:?} k_t={:?}",
+            self.n_cryst, self.n_gas, self.conc, self.dg, self.n_exch, self.k_t
+        );
+        if let Some(n_gas0) = self.n_gas0 {
+            fields += &format!(" n_gas0={:?}", n_gas0);
+        }
+        if let Some(n_cryst0) = self.n_cryst0 {
+            fields += &format!(" n_cryst0={:?}", n_cryst0);
+        }
+        fields
+    }
+
+    fn parse(fields: &HashMap<&str, &str>) -> Result<Self, String> {
+        Ok(Self {
+            n_cryst: field(fields, "n_cryst")?,
+            n_gas: field(fields, "n_gas")?,
+            conc: field(fields, "conc")?,
+            dg: field(fields, "dg")?,
+            n_exch: field(fields, "n_exch")?,
+            k_t: field(fields, "k_t")?,
+            n_gas0: fields
+                .contains_key("n_gas0")
+                .then(|| field(fields, "n_gas0"))
+                .transpose()?,
+            n_cryst0: fields
+                .contains_key("n_cryst0")
+                .then(|| field(fields, "n_cryst0"))
+                .transpose()?,
+        })
+    }
+}
+
+/// Energy sums and orientation of an item `SimLog`, which its state alone cannot give
+/// back. `None` in the checkpoints that predate them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSums {
+    /// `tot_denergy`, `tot_denergy_surf` and `tot_denergy_full`.
+    pub denergy: Option<[NeumaierSum; 3]>,
+    pub axis_ref: Option<[f64; 3]>,
+    pub axis_prev: Option<[f64; 3]>,
+    /// `rot_angle`, `rot_step` and `inertia_aniso` of the last log point.
+    pub rot: Option<[f64; 3]>,
+}
+
+impl LogSums {
+    pub fn of(simlog: &SimLog) -> Self {
+        Self {
+            denergy: Some([
+                simlog.tot_denergy_sum,
+                simlog.tot_denergy_surf_sum,
+                simlog.tot_denergy_full_sum,
+            ]),
+            axis_ref: simlog.axis_ref,
+            axis_prev: simlog.axis_prev,
+            rot: Some([
+                simlog.rot_angle.val,
+                simlog.rot_step.val,
+                simlog.inertia_aniso.val,
+            ]),
+        }
+    }
+
+    pub fn apply(&self, simlog: &mut SimLog) {
+        if let Some([sum, surf, full]) = self.denergy {
+            simlog.tot_denergy_sum = sum;
+            simlog.tot_denergy.val = sum.value();
+            simlog.tot_denergy_surf_sum = surf;
+            simlog.tot_denergy_surf.val = surf.value();
+            simlog.tot_denergy_full_sum = full;
+            simlog.tot_denergy_full.val = full.value();
+        }
+        if let Some([rot_angle, rot_step, inertia_aniso]) = self.rot {
+            simlog.axis_ref = self.axis_ref;
+            simlog.axis_prev = self.axis_prev;
+            simlog.rot_angle.val = rot_angle;
+            simlog.rot_step.val = rot_step;
+            simlog.inertia_aniso.val = inertia_aniso;
+            simlog.is_axes_restored = true;
+        }
+    }
+
+    fn fields(&self) -> String {
+        let mut fields = String::new();
+        if let Some(sums) = &self.denergy {
+            for (key, sum) in ["denergy", "denergy_surf", "denergy_full"].iter().zip(sums) {
+                let (sum, comp, abs_sum, count) = sum.parts();
+                fields += &format!(" {}={:?},{:?},{:?},{}", key, sum, comp, abs_sum, count);
+            }
+        }
+        if let Some(rot) = &self.rot {
+            fields += &format!(
+                " axis_ref={} axis_prev={} rot={}",
+                join_axis(self.axis_ref),
+                join_axis(self.axis_prev),
+                join_floats(rot)
+            );
+        }
+        fields
+    }
+
+    fn parse(fields: &HashMap<&str, &str>) -> Result<Self, String> {
+        let mut sums = Self::default();
+        if fields.contains_key("denergy") {
+            let sum = |key: &str| -> Result<NeumaierSum, String> {
+                let value: String = field(fields, key)?;
+                let bad = || format!("bad value '{}' of '{}'", value, key);
+                match value.split(',').collect::<Vec<_>>()[..] {
+                    [sum, comp, abs_sum, count] => Ok(NeumaierSum::from_parts((
+                        sum.parse().map_err(|_| bad())?,
+                        comp.parse().map_err(|_| bad())?,
+                        abs_sum.parse().map_err(|_| bad())?,
+                        count.parse().map_err(|_| bad())?,
+                    ))),
+                    _ => Err(bad()),
+                }
+            };
+            sums.denergy = Some([sum("denergy")?, sum("denergy_surf")?, sum("denergy_full")?]);
+        }
+        if fields.contains_key("rot") {
+            sums.axis_ref = parse_axis(fields, "axis_ref")?;
+            sums.axis_prev = parse_axis(fields, "axis_prev")?;
+            sums.rot = parse_axis(fields, "rot")?;
+        }
+        Ok(sums)
+    }
+}
+
+fn join_floats(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{:?}", v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn join_axis(axis: Option<[f64; 3]>) -> String {
+    match axis {
+        Some(axis) => join_floats(&axis),
+        None => "-".to_string(),
+    }
+}
+
+/// Three comma-separated floats, `-` for `None`.
+fn parse_axis(fields: &HashMap<&str, &str>, key: &str) -> Result<Option<[f64; 3]>, String> {
+    let value: String = field(fields, key)?;
+    if value == "-" {
+        return Ok(None);
+    }
+
+    let bad = || format!("bad value '{}' of '{}'", value, key);
+    let values: Vec<f64> = value
+        .split(',')
+        .map(|v| v.parse().map_err(|_| bad()))
+        .collect::<Result<_, _>>()?;
+    match values[..] {
+        [a, b, c] => Ok(Some([a, b, c])),
+        _ => Err(bad()),
+    }
+}
+
+/// Saved state of one item besides its grid state.
+#[derive(Debug, Clone, Default)]
+pub struct ItemResume {
+    pub scalars: LogScalars,
+    pub sums: LogSums,
+    pub tpas: Vec<usize>,
+    pub tpbs: Vec<usize>,
+    /// Stream and word position of the item's own random stream (`Deterministic`).
+    pub rng_pos: Option<(u64, u128)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumePoint {
+    pub step: u64,
+    pub rng: ChaCha8Rng,
+    pub temperature: f64,
+    pub ensemble: LogScalars,
+    /// In the order of the state lines.
+    pub items: Vec<ItemResume>,
+}
+
+/// `ResumeFrom` is a results directory or the checkpoint file itself.
+pub fn checkpoint_path(resume_from: &str) -> PathBuf {
+    let path = PathBuf::from(resume_from.trim());
+    match path.is_dir() {
+        true => path.join(CHECKPOINT_FILE_NAME),
+        false => path,
+    }
+}
+
+/// The `#resume` line of a checkpoint taken after step `step_id`.
+pub fn resume_line(step_id: u64, rng: &ChaCha8Rng, temperature: f64, simlog: &SimLog) -> String {
+    let seed: String = rng
+        .get_seed()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!(
+        "{} step={} seed={} stream={} word_pos={} temperature={:?} {}",
+        RESUME_TAG,
+        step_id,
+        seed,
+        rng.get_stream(),
+        rng.get_word_pos(),
+        temperature,
+        LogScalars::of(simlog).fields()
+    )
+}
+
+/// The `#item` line written before the state of `item`.
+pub fn item_line(item: &Item) -> String {
+    let join = |sites: &[usize]| match sites.is_empty() {
+        true => "-".to_string(),
+        false => sites
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+    let rng_pos = match &item.rng {
+        Some(rng) => format!(
+            " stream={} word_pos={}",
+            rng.get_stream(),
+            rng.get_word_pos()
+        ),
+        None => String::new(),
+    };
+    format!(
+        "{} gid={} {}{} tpa={} tpb={}{}",
+        ITEM_TAG,
+        item.item_gid,
+        LogScalars::of(&item.simlog).fields(),
+        LogSums::of(&item.simlog).fields(),
+        join(&item.front.tpas[..item.front.tpas_size]),
+        join(&item.front.tpbs[..item.front.tpbs_size]),
+        rng_pos
+    )
+}
+
+/// Reads the resume data of `path` (a `Checkpoint.txt`).
+pub fn read(path: &Path) -> IoResult<ResumePoint> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |line_num: usize, msg: String| {
+        IoError::new(
+            ErrorKind::InvalidData,
+            format!("{} (line {}): {}", path.display(), line_num + 1, msg),
+        )
+    };
+
+    let mut point: Option<ResumePoint> = None;
+    let mut items = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let (tag, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let fields: HashMap<&str, &str> = rest
+            .split_whitespace()
+            .filter_map(|f| f.split_once('='))
+            .collect();
+
+        match tag {
+            RESUME_TAG => {
+                point = Some(parse_resume(&fields).map_err(|e| invalid(line_num, e))?);
+            }
+            ITEM_TAG => items.push(parse_item(&fields).map_err(|e| invalid(line_num, e))?),
+            _ => {}
+        }
+    }
+
+    let mut point = point.ok_or_else(|| {
+        IoError::new(
+            ErrorKind::InvalidData,
+            format!(
+                "'{}' has no '{}' line (written by an older version or by a failed run)",
+                path.display(),
+                RESUME_TAG
+            ),
+        )
+    })?;
+    point.items = items;
+
+    Ok(point)
+}
+
+fn field<T: std::str::FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    let value = fields
+        .get(key)
+        .ok_or_else(|| format!("missing '{}'", key))?;
+    value
+        .parse()
+        .map_err(|_| format!("bad value '{}' of '{}'", value, key))
+}
+
+fn parse_resume(fields: &HashMap<&str, &str>) -> Result<ResumePoint, String> {
+    let seed_hex: String = field(fields, "seed")?;
+    let mut seed = [0u8; 32];
+    if seed_hex.len() != 2 * seed.len() {
+        return Err(format!("bad seed '{}'", seed_hex));
+    }
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&seed_hex[2 * i..2 * i + 2], 16)
+            .map_err(|_| format!("bad seed '{}'", seed_hex))?;
+    }
+
+    let mut rng = ChaCha8Rng::from_seed(seed);
+    rng.set_stream(field(fields, "stream")?);
+    rng.set_word_pos(field(fields, "word_pos")?);
+
+    Ok(ResumePoint {
+        step: field(fields, "step")?,
+        rng,
+        temperature: field(fields, "temperature")?,
+        ensemble: LogScalars::parse(fields)?,
+        items: Vec::new(),
+    })
+}
+
+fn parse_item(fields: &HashMap<&str, &str>) -> Result<ItemResume, String> {
+    let sites = |key: &str| -> Result<Vec<usize>, String> {
+        match field::<String>(fields, key)?.as_str() {
+            "-" => Ok(Vec::new()),
+            list => list
+                .split(',')
+                .map(|s| {
+                    s.parse()
+                        .map_err(|_| format!("bad site '{}' in '{}'", s, key))
+                })
+                .collect(),
+        }
+    };
+
+    let rng_pos = match fields.contains_key("word_pos") {
+        true => Some((field(fields, "stream")?, field(fields, "word_pos")?)),
+        false => None,
+    };
+
+    Ok(ItemResume {
+        scalars: LogScalars::parse(fields)?,
+        sums: LogSums::parse(fields)?,
+        tpas: sites("tpa")?,
+        tpbs: sites("tpb")?,
+        rng_pos,
+    })
+}
@@ -0,0 +1,354 @@
+use crate::mods::{
+    constants::{ENERGY_HIST_FILE_NAME, K_BOLTZMANN},
+    state::SimLog,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Error as IoError, Result as IoResult, Write},
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Spread of `kT` or `dg` over the samples, relative to `kT`, above which the histogram
+/// is not one ensemble (thermostat noise, reservoir depletion).
+const SPREAD_MAX: f64 = 0.01;
+/// Effective samples below which a reweighted estimate is flagged.
+const ESS_MIN: f64 = 100.0;
+
+/// Joint histogram of the surface energy and the crystal size of the configurations an
+/// item visits (`EnergyHistBin`), for single-histogram reweighting.
+///
+/// Every kernel step from `StatsFrom` on counts the configuration it starts from: the
+/// surface energy `E` (J) relative to the initial state, accumulated from the accepted
+/// events (`tot_denergy`), in bins of `bin`, and `n_cryst`. With the Metropolis rates of
+/// `DetachModel` axis the visits are distributed as `exp(-(E - n * dg) / kT)` once the
+/// item is in equilibrium, so `reweight` estimates the averages at a nearby `kT'` (and
+/// `dg'`) without a new run. `kT` and `dg` are averaged over the samples; they must stay
+/// (nearly) constant, i.e. no thermostat noise and a reservoir that the crystal does not
+/// deplete (large `N_tot` or exchange).
+#[derive(Debug, Default)]
+pub struct EnergyHistogram {
+    /// Bin width (J); 0 - off.
+    pub bin: f64,
+    pub from_step: u64,
+    counts: HashMap<(i64, u64), u64>,
+    samples: u64,
+    /// Sums and sums of squares of `kT` and `dg` over the samples.
+    k_t_sums: (f64, f64),
+    dg_sums: (f64, f64),
+}
+
+impl EnergyHistogram {
+    pub fn new(bin: f64, from_step: u64) -> Self {
+        Self {
+            bin,
+            from_step,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.bin > 0.0
+    }
+
+    /// Counts the configuration of `simlog` at the start of `step_id`.
+    #[inline(always)]
+    pub fn sample(&mut self, step_id: u64, simlog: &SimLog) {
+        if !self.is_on() || step_id < self.from_step {
+            return;
+        }
+
+        let bin_id = (simlog.tot_denergy.val / self.bin).round() as i64;
+        *self
+            .counts
+            .entry((bin_id, simlog.n_cryst.val as u64))
+            .or_insert(0) += 1;
+        self.samples += 1;
+        self.k_t_sums.0 += simlog.k_t;
+        self.k_t_sums.1 += simlog.k_t * simlog.k_t;
+        self.dg_sums.0 += simlog.dg.val;
+        self.dg_sums.1 += simlog.dg.val * simlog.dg.val;
+    }
+
+    /// Writes `EnergyHist.txt`: a `# key=value` header with the sampled ensemble, then
+    /// `E:n_cryst:count` lines (`E` - bin centre, J), sorted.
+    pub fn write(&self, path_dst: &Path) -> IoResult<()> {
+        let path_out_file = path_dst.join(ENERGY_HIST_FILE_NAME);
+        let mut buf = BufWriter::new(File::create(&path_out_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_out_file.display(), e),
+            )
+        })?);
+
+        let (k_t, k_t_std) = mean_std(self.k_t_sums, self.samples);
+        let (dg, dg_std) = mean_std(self.dg_sums, self.samples);
+        writeln!(
+            buf,
+            "# bin={:e} k_t={:e} k_t_std={:e} dg={:e} dg_std={:e} samples={} from_step={}",
+            self.bin, k_t, k_t_std, dg, dg_std, self.samples, self.from_step
+        )?;
+        writeln!(buf, "# E:n_cryst:count")?;
+        let sorted: BTreeMap<_, _> = self.counts.iter().collect();
+        for (&(bin_id, n_cryst), count) in sorted {
+            writeln!(buf, "{:e}:{}:{}", bin_id as f64 * self.bin, n_cryst, count)?;
+        }
+        buf.flush()
+    }
+}
+
+fn mean_std((sum, sum_sq): (f64, f64), n: u64) -> (f64, f64) {
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+    let mean = sum / n as f64;
+    (mean, (sum_sq / n as f64 - mean * mean).max(0.0).sqrt())
+}
+
+/// `EnergyHist.txt` read back.
+struct Histogram {
+    k_t: f64,
+    k_t_std: f64,
+    dg: f64,
+    dg_std: f64,
+    /// `(E, n_cryst, count)`.
+    bins: Vec<(f64, f64, f64)>,
+}
+
+impl Histogram {
+    fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let header = text
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix('#'))
+            .ok_or_else(|| format!("'{}': missing the header line", path.display()))?;
+        let value = |key: &str| -> Result<f64> {
+            header
+                .split_whitespace()
+                .find_map(|kv| kv.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| format!("'{}': missing '{}' in the header", path.display(), key))?
+                .parse::<f64>()
+                .map_err(|e| format!("'{}': invalid '{}': {}", path.display(), key, e).into())
+        };
+
+        let mut bins = Vec::new();
+        for line in text
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+        {
+            let fields = line
+                .split(':')
+                .map(|s| s.trim().parse::<f64>())
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .map_err(|e| format!("'{}': invalid line '{}': {}", path.display(), line, e))?;
+            match fields[..] {
+                [energy, n_cryst, count] => bins.push((energy, n_cryst, count)),
+                _ => return Err(format!("'{}': invalid line '{}'", path.display(), line).into()),
+            }
+        }
+
+        Ok(Self {
+            k_t: value("k_t")?,
+            k_t_std: value("k_t_std")?,
+            dg: value("dg")?,
+            dg_std: value("dg_std")?,
+            bins,
+        })
+    }
+
+    /// Averages at `k_t_new` and `dg_new`: every bin reweighted by
+    /// `exp(-(E - n * dg') / kT' + (E - n * dg) / kT)`. Returns `<n_cryst>`, its standard
+    /// deviation, `<E>` and the effective number of samples `(sum w)^2 / sum w^2`.
+    fn reweight(&self, k_t_new: f64, dg_new: f64) -> (f64, f64, f64, f64) {
+        let log_w: Vec<f64> = self
+            .bins
+            .iter()
+            .map(|&(energy, n_cryst, count)| {
+                count.ln() - (energy - n_cryst * dg_new) / k_t_new
+                    + (energy - n_cryst * self.dg) / self.k_t
+            })
+            .collect();
+        let log_w_max = log_w.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        let (mut w_sum, mut n_sum, mut n2_sum, mut e_sum, mut w2_per_count) =
+            (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (&(energy, n_cryst, count), log_w) in self.bins.iter().zip(&log_w) {
+            let w = (log_w - log_w_max).exp();
+            w_sum += w;
+            n_sum += w * n_cryst;
+            n2_sum += w * n_cryst * n_cryst;
+            e_sum += w * energy;
+            // `count` samples of weight `w / count` each.
+            w2_per_count += w * w / count;
+        }
+        let n_mean = n_sum / w_sum;
+        let n_std = (n2_sum / w_sum - n_mean * n_mean).max(0.0).sqrt();
+
+        (n_mean, n_std, e_sum / w_sum, w_sum * w_sum / w2_per_count)
+    }
+}
+
+/// Entry point of the `reweight` subcommand.
+///
+/// Usage: `reweight <run or item dir> --temp T1[,T2,...] [--dg DG]`. Single-histogram
+/// reweighting of the `EnergyHist.txt` of every item (see `EnergyHistogram` for the
+/// assumptions): prints `<n_cryst>`, its standard deviation, `<E>` and the effective
+/// sample size at every temperature `T` (K), with `dg` kept at the sampled mean or set by
+/// `--dg` (J). The estimates are trustworthy only near the sampled `T`, while the
+/// effective sample size stays large; fewer than `ESS_MIN` are flagged.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: reweight <run or item dir> --temp T1[,T2,...] [--dg DG]";
+
+    let mut dir: Option<PathBuf> = None;
+    let mut temps: Vec<f64> = Vec::new();
+    let mut dg_new: Option<f64> = None;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| format!("Missing value after '{}'", name))
+        };
+        match arg.as_str() {
+            "--temp" | "-t" => {
+                temps = value("--temp")?
+                    .split(',')
+                    .map(|s| s.trim().parse::<f64>())
+                    .collect::<std::result::Result<_, _>>()?;
+            }
+            "--dg" => dg_new = Some(value("--dg")?.parse()?),
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, usage).into()),
+        }
+    }
+
+    let Some(dir) = dir else {
+        return Err(usage.into());
+    };
+    if temps.is_empty() || temps.iter().any(|&t| t.is_nan() || t <= 0.0) {
+        return Err(format!("'--temp' needs temperatures > 0\n{}", usage).into());
+    }
+
+    let paths = histogram_paths(&dir)?;
+    if paths.is_empty() {
+        return Err(format!(
+            "No {} in '{}' (run with EnergyHistBin > 0)",
+            ENERGY_HIST_FILE_NAME,
+            dir.display()
+        )
+        .into());
+    }
+
+    for path in paths {
+        let hist = Histogram::read(&path)?;
+        if hist.bins.is_empty() {
+            println!("# {}: ⚠️ no samples, skipped.", path.display());
+            continue;
+        }
+        let dg = dg_new.unwrap_or(hist.dg);
+        println!(
+            "# {}: T={:.3} K, dg={:e} J, {} bins",
+            path.display(),
+            hist.k_t / K_BOLTZMANN,
+            hist.dg,
+            hist.bins.len()
+        );
+        if hist.k_t_std.max(hist.dg_std) > SPREAD_MAX * hist.k_t {
+            println!(
+                "⚠️ kT or dg varied over the samples (std {:e}, {:e} J); the histogram is not one ensemble.",
+                hist.k_t_std, hist.dg_std
+            );
+        }
+        println!("# T:n_cryst:n_cryst_std:E:ess");
+        for &temp in &temps {
+            let (n_mean, n_std, e_mean, ess) = hist.reweight(K_BOLTZMANN * temp, dg);
+            println!(
+                "{}:{:.6e}:{:.6e}:{:.6e}:{:.1}{}",
+                temp,
+                n_mean,
+                n_std,
+                e_mean,
+                ess,
+                match ess < ESS_MIN {
+                    true => " [too few effective samples]",
+                    false => "",
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// `EnergyHist.txt` of `dir` itself, or of its item subdirectories, sorted.
+fn histogram_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let own = dir.join(ENERGY_HIST_FILE_NAME);
+    if own.is_file() {
+        return Ok(vec![own]);
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?
+        .filter_map(|entry| Some(entry.ok()?.path().join(ENERGY_HIST_FILE_NAME)))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn two_bin_histogram_reweights_to_boltzmann_averages() {
+        // Sampled at kT = 1, dg = 0: twice (E = 0, n = 1), once (E = 1, n = 2); the
+        // sample before `from_step` is left out.
+        let mut simlog = SimLog::new();
+        simlog.k_t = 1.0;
+        let mut energy_hist = EnergyHistogram::new(1.0, 5);
+        for (step_id, energy, n_cryst) in
+            [(4, 7.0, 9.0), (5, 0.0, 1.0), (6, 1.0, 2.0), (7, 0.0, 1.0)]
+        {
+            simlog.tot_denergy.val = energy;
+            simlog.n_cryst.val = n_cryst;
+            energy_hist.sample(step_id, &simlog);
+        }
+
+        let dir = std::env::temp_dir().join(format!("model_1_002_reweight_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        energy_hist.write(&dir).unwrap();
+        let hist = Histogram::read(&dir.join(ENERGY_HIST_FILE_NAME)).unwrap();
+        assert_eq!(hist.bins, [(0.0, 1.0, 2.0), (1.0, 2.0, 1.0)]);
+        assert_eq!((hist.k_t, hist.k_t_std, hist.dg), (1.0, 0.0, 0.0));
+
+        // The sampled ensemble: plain averages, every sample effective.
+        let (n_mean, n_std, e_mean, ess) = hist.reweight(1.0, 0.0);
+        assert_close(n_mean, 4.0 / 3.0);
+        assert_close(n_std, 2.0_f64.sqrt() / 3.0);
+        assert_close(e_mean, 1.0 / 3.0);
+        assert_close(ess, 3.0);
+
+        // kT' = 0.5: the bin E = 1 gets the weight exp(-1 / 0.5 + 1) = 1/e.
+        let e = std::f64::consts::E;
+        let (n_mean, _, e_mean, _) = hist.reweight(0.5, 0.0);
+        assert_close(n_mean, (2.0 + 2.0 / e) / (2.0 + 1.0 / e));
+        assert_close(e_mean, (1.0 / e) / (2.0 + 1.0 / e));
+
+        // dg' = 1: every particle in the crystal gets the weight e.
+        let (n_mean, ..) = hist.reweight(1.0, 1.0);
+        assert_close(n_mean, (2.0 * e + 2.0 * e * e) / (2.0 * e + e * e));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
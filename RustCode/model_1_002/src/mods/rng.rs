@@ -0,0 +1,110 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Random source of the item kernels (`Item::mode_2_x_step`, the swap event).
+///
+/// The kernels are generic over it, so a test or a debugging harness can replace the
+/// ChaCha stream by a scripted one and force a given site choice or Metropolis outcome.
+/// `word_pos` is the stream position used by `RngAudit`.
+pub trait KernelRng: Rng {
+    fn word_pos(&self) -> u128;
+}
+
+impl KernelRng for ChaCha8Rng {
+    #[inline(always)]
+    fn word_pos(&self) -> u128 {
+        self.get_word_pos()
+    }
+}
+
+/// Own stream of item `item_gid` (`Deterministic`): the ensemble seed on stream
+/// `item_gid + 1`, stream 0 being the ensemble's. The draws of an item then do not
+/// depend on the other items of its process, so the split over `Ranks` does not change
+/// the results.
+pub fn item_stream(seed: u64, item_gid: usize) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(item_gid as u64 + 1);
+    rng
+}
+
+#[cfg(any(test, feature = "mock-rng"))]
+pub use scripted::ScriptedRng;
+
+#[cfg(any(test, feature = "mock-rng"))]
+mod scripted {
+    use super::KernelRng;
+    use rand::RngCore;
+    use std::collections::VecDeque;
+
+    /// Scripted random source (feature `mock-rng`, always in the unit tests): returns queued 64-bit words in order
+    /// and panics when the script runs out, so an unexpected extra draw is caught.
+    ///
+    /// `next_u64` returns a whole word, `next_u32` its upper half. The helpers encode the
+    /// values the kernels ask for with rand 0.9: `push_index(i, n)` makes the next
+    /// `random_range(0..n)` (n < 2^31) return `i`, `push_f64(x)` makes the next
+    /// `random::<f64>()` return `x` (rounded down to a multiple of 2^-53).
+    #[derive(Debug, Default, Clone)]
+    pub struct ScriptedRng {
+        words: VecDeque<u64>,
+        drawn: u128,
+    }
+
+    impl ScriptedRng {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn push_word(&mut self, word: u64) -> &mut Self {
+            self.words.push_back(word);
+            self
+        }
+
+        /// Index draws go through the 32-bit widening multiply `hi(word * n)`; the smallest
+        /// word giving `i` also keeps the low half below the resampling threshold.
+        pub fn push_index(&mut self, i: usize, n: usize) -> &mut Self {
+            assert!(i < n && n < 1 << 31, "ScriptedRng: index {i} out of 0..{n}");
+            let word32 = ((i as u64) << 32).div_ceil(n as u64);
+            self.push_word(word32 << 32)
+        }
+
+        pub fn push_f64(&mut self, x: f64) -> &mut Self {
+            assert!((0.0..1.0).contains(&x), "ScriptedRng: {x} out of [0, 1)");
+            self.push_word(((x * (1u64 << 53) as f64) as u64) << 11)
+        }
+
+        /// Number of words left in the script.
+        pub fn remaining(&self) -> usize {
+            self.words.len()
+        }
+
+        fn pop(&mut self) -> u64 {
+            self.drawn += 1;
+            self.words
+                .pop_front()
+                .unwrap_or_else(|| panic!("ScriptedRng: script exhausted at draw {}", self.drawn))
+        }
+    }
+
+    impl RngCore for ScriptedRng {
+        fn next_u32(&mut self) -> u32 {
+            (self.pop() >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.pop()
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            for chunk in dst.chunks_mut(8) {
+                let bytes = self.pop().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+
+    impl KernelRng for ScriptedRng {
+        fn word_pos(&self) -> u128 {
+            self.drawn
+        }
+    }
+}
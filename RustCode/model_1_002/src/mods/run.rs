@@ -0,0 +1,94 @@
+//! Entry point for embedding the model in another Rust program: settings built in code
+//! (`Settings::builder()`) in, the outcome of the run out, without `InitSettings.ini`
+//! or the binary. The run writes its directory as usual.
+//!
+//! ```no_run
+//! use model_1_002::{Settings, Simulation};
+//!
+//! let cfg = Settings::builder().sx(40).sy(40).sz(40).step_lim(10_000).build()?;
+//! let res = Simulation::run(cfg)?;
+//! println!("{}: n_cryst {:.5e}", res.termination.as_str(), res.n_cryst);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::mods::{
+    ensemble::{Ensemble, Termination},
+    settings::Settings,
+};
+use std::{
+    error::Error,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Outcome of `Simulation::run`: the final values of the ensemble log (the line of the
+/// run in the campaign index) and the crystal size of every local item.
+#[derive(Debug, Clone)]
+pub struct SimResult {
+    /// Directory of the run, with `Summary.txt`, `SimLogRec.txt` and the items.
+    pub dst_path: PathBuf,
+    pub termination: Termination,
+    pub mk_step: u64,
+    pub n_cryst: f64,
+    pub conc: f64,
+    pub dg: f64,
+    pub items_alive: usize,
+    pub items: usize,
+    /// `n_cryst` of the local items by `item_gid`; 0 for the dropped ones.
+    pub item_n_cryst: Vec<f64>,
+    pub elapsed: Duration,
+}
+
+impl SimResult {
+    fn new(ensemble: &Ensemble, elapsed: Duration) -> Self {
+        let simlog = &ensemble.simlog;
+        let mut item_n_cryst = vec![0.0; ensemble.items_len0];
+        for item in &ensemble.items {
+            if let Some(n_cryst) = item_n_cryst.get_mut(item.item_gid) {
+                *n_cryst = item.simlog.n_cryst.val;
+            }
+        }
+
+        Self {
+            dst_path: ensemble.dst_path.clone(),
+            termination: ensemble.termination,
+            mk_step: simlog.mk_step.val,
+            n_cryst: simlog.n_cryst.val,
+            conc: simlog.conc.val,
+            dg: simlog.dg.val,
+            items_alive: ensemble.items.len(),
+            items: ensemble.items_len,
+            item_n_cryst,
+            elapsed,
+        }
+    }
+}
+
+/// One run of the ensemble from `Settings`.
+pub struct Simulation;
+
+impl Simulation {
+    /// `run_in` the current directory: `InitStates.ini` is read from it (`LoadOption`)
+    /// and the run directory is created in it.
+    pub fn run(cfg: Settings) -> Result<SimResult> {
+        let src_path = std::env::current_dir().map_err(|e| format!("current_dir() failed: {e}"))?;
+
+        Self::run_in(cfg, src_path)
+    }
+
+    /// Validates `cfg` and runs it with `src_path` as the program folder. A failed run
+    /// saves what it can (`Ensemble::salvage`) before the error is returned.
+    pub fn run_in(cfg: Settings, src_path: PathBuf) -> Result<SimResult> {
+        cfg.validate()?;
+        let started = Instant::now();
+
+        let mut ensemble = Ensemble::with_settings(cfg, src_path)?;
+        ensemble
+            .run_simulation()
+            .inspect_err(|_| ensemble.salvage())?;
+
+        Ok(SimResult::new(&ensemble, started.elapsed()))
+    }
+}
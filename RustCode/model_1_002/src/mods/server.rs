@@ -0,0 +1,510 @@
+use crate::mods::{
+    constants::{CONFIG_FILE_NAME, INIT_TIME_STATES_FILE_NAME, SUMMARY_FILE_NAME},
+    ensemble::Ensemble,
+};
+use chrono::Utc;
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fs,
+    io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Job status markers written into every job directory.
+const JOB_RUNNING_MARK: &str = ".running";
+const JOB_DONE_MARK: &str = ".done";
+const JOB_FAILED_MARK: &str = ".failed";
+/// Creating this file in the work directory stops the server once the queued jobs finish.
+const QUEUE_STOP_FILE: &str = "STOP";
+/// Bound address of the server, written into the work directory for the clients.
+const SERVER_ADDR_FILE: &str = "ServerAddr.txt";
+
+const DEFAULT_ADDR: &str = "127.0.0.1:0";
+/// Pause of the accept loop between polls for a connection or the stop flag.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// Interval of the work directory scan (`--scan`).
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Idle time after which a client connection is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest request line read; a longer one is rejected and the connection closed.
+const REQUEST_MAX_LEN: usize = 8192;
+/// Largest job payload (config and states) accepted by `submit`.
+const PAYLOAD_MAX_LEN: usize = 256 << 20;
+/// Line of a `submit` payload after which the lines go to `InitStates.ini`.
+const STATES_SEPARATOR: &str = "%% InitStates.ini";
+/// Last line of every request payload and reply; a data line starting with `.` is sent
+/// with one more `.` in front.
+const END_LINE: &str = ".";
+
+#[derive(Debug, Clone)]
+enum JobState {
+    Queued,
+    Running,
+    /// Directory of the finished run.
+    Done(PathBuf),
+    Failed(String),
+}
+
+impl JobState {
+    fn describe(&self) -> String {
+        match self {
+            Self::Queued => "queued".to_string(),
+            Self::Running => "running".to_string(),
+            Self::Done(dst_path) => format!("done {}", dst_path.display()),
+            Self::Failed(e) => format!("failed {}", e.replace('\n', "; ")),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    dir: PathBuf,
+    state: JobState,
+}
+
+/// Jobs of the server by id, in the order they came, and the ids waiting for a worker.
+#[derive(Debug, Default)]
+struct JobQueue {
+    jobs: Vec<Job>,
+    pending: VecDeque<usize>,
+    known: HashSet<PathBuf>,
+    is_closed: bool,
+}
+
+impl JobQueue {
+    /// Registers the job directory `dir` (once); it waits for a worker after `enqueue`.
+    fn add(&mut self, dir: PathBuf) -> Option<usize> {
+        if !self.known.insert(dir.clone()) {
+            return None;
+        }
+        self.jobs.push(Job {
+            dir,
+            state: JobState::Queued,
+        });
+
+        Some(self.jobs.len() - 1)
+    }
+
+    fn line(&self, job_id: usize) -> String {
+        let job = &self.jobs[job_id];
+        format!("{} {} {}", job_id, job.dir.display(), job.state.describe())
+    }
+}
+
+type SharedQueue = (Mutex<JobQueue>, Condvar);
+
+/// Entry point of the `serve` subcommand.
+///
+/// Usage: `serve <work dir> [--addr HOST:PORT] [--workers N] [--scan] [--once]`. The
+/// server listens on a loopback address (by default a free port, written into
+/// `ServerAddr.txt` of the work directory) for line-based requests, every one answered
+/// by lines ending with a `.` line:
+/// - `submit [name]`, then the `InitSettings.ini` lines (optionally `%% InitStates.ini`
+///   and the states) and a `.` line: the job gets a directory in the work directory and
+///   waits for one of the `N` worker threads; the reply is `ok <id>`;
+/// - `status [id]`: `<id> <dir> queued|running|done <run dir>|failed <error>` of the job,
+///   or of every job;
+/// - `result <id>`: `Summary.txt` of a finished job;
+/// - `stop`: no new jobs; the server exits once the queued ones finish (as on a `STOP`
+///   file in the work directory).
+///
+/// With `--scan` every sub-directory of the work directory with an `InitSettings.ini` is
+/// a job as well, looked for every 5 s; `--once` runs those and exits without listening.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: serve <work dir> [--addr HOST:PORT] [--workers N] [--scan] [--once]";
+    let mut work_dir: Option<PathBuf> = None;
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut scan = false;
+    let mut once = false;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--addr" => {
+                addr = args_iter
+                    .next()
+                    .ok_or("Missing value after '--addr'")?
+                    .to_string();
+            }
+            "--workers" | "-w" => {
+                workers = args_iter
+                    .next()
+                    .ok_or("Missing value after '--workers'")?
+                    .parse()?;
+            }
+            "--scan" => scan = true,
+            "--once" => once = true,
+            _ => work_dir = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let work_dir = work_dir.ok_or(usage)?;
+    if !work_dir.is_dir() {
+        return Err(format!("Work directory '{}' not found", work_dir.display()).into());
+    }
+
+    let workers = workers.max(1);
+    let queue: Arc<SharedQueue> = Arc::new((Mutex::new(JobQueue::default()), Condvar::new()));
+    let handles: Vec<_> = (0..workers)
+        .map(|worker_id| {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || worker_loop(worker_id, &queue))
+        })
+        .collect();
+
+    if once {
+        println!(
+            "🖥️ Running the jobs of '{}' with {} worker(s)",
+            work_dir.display(),
+            workers
+        );
+        enqueue_scanned(&queue, &work_dir)?;
+    } else {
+        let addr: SocketAddr = addr
+            .parse()
+            .map_err(|e| format!("Invalid '--addr' value '{}': {e}", addr))?;
+        if !addr.ip().is_loopback() {
+            return Err(format!("'--addr' must be a loopback address, got {}", addr).into());
+        }
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        fs::write(work_dir.join(SERVER_ADDR_FILE), format!("{}\n", addr))?;
+        println!(
+            "🖥️ Serving '{}' on {} with {} worker(s)",
+            work_dir.display(),
+            addr,
+            workers
+        );
+
+        listen(&listener, &queue, &work_dir, scan)?;
+    }
+
+    {
+        let (lock, cvar) = &*queue;
+        lock.lock().unwrap().is_closed = true;
+        cvar.notify_all();
+    }
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    println!("✅ Queue '{}' processed", work_dir.display());
+
+    Ok(())
+}
+
+/// Accept loop: a thread per client, the work directory scanned every `POLL_INTERVAL`
+/// with `scan`, until `stop` or the `STOP` file.
+fn listen(
+    listener: &TcpListener,
+    queue: &Arc<SharedQueue>,
+    work_dir: &Path,
+    scan: bool,
+) -> Result<()> {
+    let is_stopped = Arc::new(AtomicBool::new(false));
+    let mut next_scan = Instant::now();
+
+    while !is_stopped.load(Ordering::Relaxed) && !work_dir.join(QUEUE_STOP_FILE).exists() {
+        if scan && Instant::now() >= next_scan {
+            enqueue_scanned(queue, work_dir)?;
+            next_scan = Instant::now() + POLL_INTERVAL;
+        }
+
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let queue = Arc::clone(queue);
+                let work_dir = work_dir.to_path_buf();
+                let is_stopped = Arc::clone(&is_stopped);
+                thread::spawn(move || {
+                    if let Err(e) = serve_client(stream, &queue, &work_dir, &is_stopped) {
+                        eprintln!("⚠️ Warning: Client {}: {e}", peer);
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn enqueue_scanned(queue: &SharedQueue, work_dir: &Path) -> Result<()> {
+    let new_jobs = scan_queue(work_dir)?;
+    let (lock, cvar) = queue;
+    let mut jobs = lock.lock().unwrap();
+    for job in new_jobs {
+        if let Some(job_id) = jobs.add(job) {
+            jobs.pending.push_back(job_id);
+        }
+    }
+    cvar.notify_all();
+
+    Ok(())
+}
+
+fn scan_queue(queue_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut jobs: Vec<PathBuf> = fs::read_dir(queue_dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_dir() && p.join(CONFIG_FILE_NAME).exists())
+        .filter(|p| {
+            [JOB_RUNNING_MARK, JOB_DONE_MARK, JOB_FAILED_MARK]
+                .iter()
+                .all(|m| !p.join(m).exists())
+        })
+        .collect();
+    jobs.sort();
+
+    Ok(jobs)
+}
+
+fn serve_client(
+    stream: TcpStream,
+    queue: &SharedQueue,
+    work_dir: &Path,
+    is_stopped: &AtomicBool,
+) -> IoResult<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = reader
+            .by_ref()
+            .take(REQUEST_MAX_LEN as u64 + 1)
+            .read_line(&mut line)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if n > REQUEST_MAX_LEN {
+            writeln!(writer, "err request over {} bytes", REQUEST_MAX_LEN)?;
+            writeln!(writer, "{}", END_LINE)?;
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("request over {} bytes", REQUEST_MAX_LEN),
+            ));
+        }
+        let request = line.trim();
+        let (command, arg) = request.split_once(' ').unwrap_or((request, ""));
+        let arg = arg.trim();
+
+        let reply = match command {
+            "" => continue,
+            "submit" => submit(&mut reader, arg, queue, work_dir)?,
+            "status" => status(queue, arg),
+            "result" => result(queue, arg),
+            "stop" => {
+                is_stopped.store(true, Ordering::Relaxed);
+                vec!["ok stopping after the queued jobs".to_string()]
+            }
+            _ => vec![format!(
+                "err unknown command '{}' (submit, status, result, stop)",
+                command
+            )],
+        };
+
+        for reply_line in &reply {
+            if reply_line.starts_with('.') {
+                writer.write_all(b".")?;
+            }
+            writeln!(writer, "{}", reply_line)?;
+        }
+        writeln!(writer, "{}", END_LINE)?;
+        writer.flush()?;
+    }
+}
+
+/// Reads the payload of `submit` and queues the job in a new directory of `work_dir`.
+fn submit(
+    reader: &mut impl BufRead,
+    name: &str,
+    queue: &SharedQueue,
+    work_dir: &Path,
+) -> IoResult<Vec<String>> {
+    let mut config = String::new();
+    let mut states = String::new();
+    let mut is_states = false;
+    let mut len = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let budget = (PAYLOAD_MAX_LEN - len) as u64 + 1;
+        if reader.by_ref().take(budget).read_line(&mut line)? == 0 {
+            return Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "connection closed inside a 'submit' payload",
+            ));
+        }
+        len += line.len();
+        if len > PAYLOAD_MAX_LEN {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("'submit' payload over {} bytes", PAYLOAD_MAX_LEN),
+            ));
+        }
+
+        let content = line.trim_end_matches(['\r', '\n']);
+        match content {
+            END_LINE => break,
+            STATES_SEPARATOR if !is_states => is_states = true,
+            _ => {
+                let content = match content.starts_with("..") {
+                    true => &content[1..],
+                    false => content,
+                };
+                let target = if is_states { &mut states } else { &mut config };
+                target.push_str(content);
+                target.push('\n');
+            }
+        }
+    }
+
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .take(64)
+        .collect();
+    let job_dir = work_dir.join(match name.is_empty() {
+        true => format!("{}_job", Utc::now().timestamp_micros()),
+        false => format!("{}_{}", Utc::now().timestamp_micros(), name),
+    });
+
+    let (lock, cvar) = queue;
+    let Some(job_id) = lock.lock().unwrap().add(job_dir.clone()) else {
+        return Ok(vec![format!(
+            "err job directory '{}' taken",
+            job_dir.display()
+        )]);
+    };
+
+    let written = fs::create_dir(&job_dir)
+        .and_then(|_| fs::write(job_dir.join(CONFIG_FILE_NAME), &config))
+        .and_then(|_| match is_states {
+            true => fs::write(job_dir.join(INIT_TIME_STATES_FILE_NAME), &states),
+            false => Ok(()),
+        });
+
+    let mut jobs = lock.lock().unwrap();
+    if let Err(e) = written {
+        let e = format!("Failed to write job '{}': {e}", job_dir.display());
+        jobs.jobs[job_id].state = JobState::Failed(e.clone());
+        return Ok(vec![format!("err {}", e)]);
+    }
+    jobs.pending.push_back(job_id);
+    cvar.notify_one();
+    println!("📥 Job {} queued: {}", job_id, job_dir.display());
+
+    Ok(vec![format!("ok {}", job_id)])
+}
+
+fn parse_job_id(jobs: &JobQueue, arg: &str) -> std::result::Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(job_id) if job_id < jobs.jobs.len() => Ok(job_id),
+        _ => Err(format!("err no job '{}'", arg)),
+    }
+}
+
+fn status(queue: &SharedQueue, arg: &str) -> Vec<String> {
+    let jobs = queue.0.lock().unwrap();
+    if arg.is_empty() {
+        return (0..jobs.jobs.len())
+            .map(|job_id| jobs.line(job_id))
+            .collect();
+    }
+
+    match parse_job_id(&jobs, arg) {
+        Ok(job_id) => vec![jobs.line(job_id)],
+        Err(e) => vec![e],
+    }
+}
+
+fn result(queue: &SharedQueue, arg: &str) -> Vec<String> {
+    let state = {
+        let jobs = queue.0.lock().unwrap();
+        match parse_job_id(&jobs, arg) {
+            Ok(job_id) => jobs.jobs[job_id].state.clone(),
+            Err(e) => return vec![e],
+        }
+    };
+
+    let JobState::Done(dst_path) = state else {
+        return vec![format!("err job {} is {}", arg, state.describe())];
+    };
+    let path_summary = dst_path.join(SUMMARY_FILE_NAME);
+    match fs::read_to_string(&path_summary) {
+        Ok(summary) => summary.lines().map(str::to_string).collect(),
+        Err(e) => vec![format!(
+            "err failed to read '{}': {e}",
+            path_summary.display()
+        )],
+    }
+}
+
+fn worker_loop(worker_id: usize, queue: &SharedQueue) {
+    let (lock, cvar) = queue;
+
+    loop {
+        let job = {
+            let mut jobs = lock.lock().unwrap();
+            loop {
+                if let Some(job_id) = jobs.pending.pop_front() {
+                    jobs.jobs[job_id].state = JobState::Running;
+                    break Some((job_id, jobs.jobs[job_id].dir.clone()));
+                }
+                if jobs.is_closed {
+                    break None;
+                }
+                jobs = cvar.wait(jobs).unwrap();
+            }
+        };
+
+        let Some((job_id, job_dir)) = job else {
+            return;
+        };
+
+        println!("[Worker {}] ▶ {}", worker_id, job_dir.display());
+        let _ = fs::write(job_dir.join(JOB_RUNNING_MARK), b"");
+
+        let sta = Instant::now();
+        let result = Ensemble::from_dir(job_dir.clone()).and_then(|mut e| {
+            e.run_simulation().inspect_err(|_| e.salvage())?;
+            Ok(e.dst_path.clone())
+        });
+        let _ = fs::remove_file(job_dir.join(JOB_RUNNING_MARK));
+
+        let state = match result {
+            Ok(dst_path) => {
+                let _ = fs::write(job_dir.join(JOB_DONE_MARK), b"");
+                println!(
+                    "[Worker {}] ✅ {} (Time: {:?})",
+                    worker_id,
+                    job_dir.display(),
+                    sta.elapsed()
+                );
+                JobState::Done(dst_path)
+            }
+            Err(e) => {
+                let _ = fs::write(job_dir.join(JOB_FAILED_MARK), e.to_string());
+                eprintln!("[Worker {}] ❌ {}: {}", worker_id, job_dir.display(), e);
+                JobState::Failed(e.to_string())
+            }
+        };
+        lock.lock().unwrap().jobs[job_id].state = state;
+    }
+}
@@ -1,4 +1,23 @@
-use std::{borrow::Cow, error::Error, fmt};
+use crate::mods::{
+    acceptance::AcceptanceRule,
+    anchor::Anchor,
+    ballistics::BallisticLaw,
+    constants::POISSON_MEAN_MAX,
+    frontier::{BuildIndex, HashedIndex, SiteIndex},
+    lattice::Idx,
+    mode,
+    nuclei::NucleiFilter,
+    passage, probes,
+    profile::DensityProfile,
+    state::{ConcNegAction, EnergyAccounting},
+    status,
+    sublattice::SubLattice,
+    unknown_keys::UnknownKey,
+    utils::{self, FACE_001, FACE_010, FACE_100},
+    writer::QueueFull,
+};
+
+pub use mc_core::settings::SettingsError;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Settings {
@@ -13,6 +32,9 @@ pub struct Settings {
     pub pz: bool,
 
     pub temperature: f64,
+    pub t_sigma: f64,
+    pub t_tau: f64,
+    pub t_every: u64,
     pub ax: f64,
     pub ay: f64,
     pub az: f64,
@@ -20,26 +42,141 @@ pub struct Settings {
     pub g100: f64,
     pub g010: f64,
     pub g001: f64,
+    pub axis_map: String,
 
-    pub mode: f64,
+    /// `mode` as `MAJOR.MINOR`, see `mode()`.
+    pub mode_major: u8,
+    pub mode_minor: u8,
     pub dg: f64,
     pub c_eq: f64,
     pub c0: f64,
     pub n_tot: f64,
     pub n0_cr: f64,
+    pub gas_dist_pow: f64,
+    pub gas_dist_noise: f64,
+    /// `GasDist`: relative `C0` of every item, comma-separated, `none` - off.
+    pub gas_dist: String,
+    pub c_ext: f64,
+    pub k_exch: f64,
+    pub chemostat_c: f64,
+    pub chemostat_rate: f64,
+    pub conc_neg_max: u64,
+    pub conc_neg_action: String,
+    pub dg_exact: bool,
     pub p_b: f64,
+    pub p_b_x: f64,
+    pub p_b_y: f64,
+    pub p_b_z: f64,
     pub p_pow: f64,
+    pub e_des: f64,
+    pub pb_expr: String,
+    pub detach_model: String,
+    pub acceptance: String,
+    pub energy_accounting: String,
+
+    pub freeze_100: bool,
+    pub freeze_010: bool,
+    pub freeze_001: bool,
+
+    pub template_file: String,
+    pub item_dims_file: String,
+    pub item_groups_file: String,
 
     pub add_i: u64,
     pub add_from: u64,
     pub rem_i: u64,
     pub rem_from: u64,
+    pub swap_i: u64,
+    pub swap_from: u64,
 
     pub load_option: i64,
+    pub nuclei_filter: String,
 
     pub step_lim: u64,
     pub print_i: u64,
     pub write_i: u64,
+    pub write_io_pct: f64,
+    pub write_i_max: u64,
+    pub step_lim_max: u64,
+    pub extend_rate: f64,
+    pub extend_pct: f64,
+    pub extend_window: u64,
+    pub control_i: u64,
+    pub checkpoint_i: u64,
+    pub resume_from: String,
+    pub max_hours: f64,
+
+    pub item_write_i: u64,
+    pub item_keep_first: usize,
+    pub item_keep_last: usize,
+    pub item_roi: String,
+    pub item_stride: usize,
+    pub item_max_mb: f64,
+    pub stall_thr: usize,
+    pub stale_retry: u32,
+    pub throttle_thr: usize,
+    pub throttle_i: u64,
+    pub poisson_add: f64,
+    pub poisson_rem: f64,
+    pub poisson_bal: f64,
+    pub anchor: String,
+    pub sub_lattice: String,
+    pub front_rebuild_i: u64,
+    pub front_rebuild_adopt: bool,
+    pub pass_rate: f64,
+    pub pass_life: f64,
+    pub cluster_track: bool,
+    pub heat_release: f64,
+    pub heat_cell: usize,
+    pub heat_diff: f64,
+    pub heat_decay: f64,
+
+    pub steady_w: usize,
+    pub steady_tol: f64,
+    pub steady_m: usize,
+    pub steady_stop: bool,
+
+    pub stats_from: u64,
+    pub energy_hist_bin: f64,
+    pub log_decim: u64,
+
+    pub rng_audit: bool,
+    pub facet_stats: bool,
+    pub kossel_check: bool,
+    pub residence_stats: bool,
+    pub density_profile: String,
+    pub minkowski: bool,
+    pub event_export: bool,
+    pub front_export: bool,
+    pub first_passage: String,
+    pub detach_spectrum_i: u64,
+    pub probes: String,
+
+    pub mem_budget_gb: f64,
+    /// `SparseFront`: requires the hashed frontier, which is chosen at build time.
+    pub sparse_front: bool,
+    pub write_queue: usize,
+    pub queue_full: String,
+    pub snap_compress: bool,
+    pub snap_dual: bool,
+
+    pub campaign: String,
+    pub campaign_dir: String,
+    pub pack_run: bool,
+
+    pub ranks: usize,
+    /// Rank of this process (set by the `rank` subcommand, not read from the config).
+    pub rank: usize,
+    pub coord_addr: String,
+    pub sync_i: u64,
+    pub deterministic: bool,
+    pub status_addr: String,
+    /// `Preset` applied before the config keys (see `PRESETS`), `none` without one;
+    /// `--preset <name>` replaces the key. Written as the `preset` row of `Summary.txt`.
+    pub preset: String,
+    /// Refuse to run with keys no setting reads (`Strict`); those met by `load_config`.
+    pub strict: bool,
+    pub unknown_keys: Vec<UnknownKey>,
 }
 
 impl Default for Settings {
@@ -56,6 +193,9 @@ impl Default for Settings {
             pz: false,
 
             temperature: 300.0,
+            t_sigma: 0.0,
+            t_tau: 1000.0,
+            t_every: 1,
             ax: 5.85E-10,
             ay: 1.78E-10,
             az: 4.41E-10,
@@ -63,26 +203,134 @@ impl Default for Settings {
             g100: 0.41,
             g010: 0.54,
             g001: 0.22,
+            axis_map: "xyz".to_string(),
 
-            mode: 1.1,
+            mode_major: 1,
+            mode_minor: 1,
             dg: 0.0,
             c_eq: 9.58767e-08,
             c0: 9.58767e-08,
             n_tot: 5e12,
             n0_cr: -1.0,
+            gas_dist_pow: 0.0,
+            gas_dist_noise: 0.0,
+            gas_dist: "none".to_string(),
+            c_ext: 0.0,
+            k_exch: 0.0,
+            chemostat_c: -1.0,
+            chemostat_rate: 0.0,
+            conc_neg_max: 1000,
+            conc_neg_action: "warn".to_string(),
+            dg_exact: false,
             p_b: 0.3,
+            p_b_x: -1.0,
+            p_b_y: -1.0,
+            p_b_z: -1.0,
             p_pow: 1.0,
+            e_des: -1.0,
+            pb_expr: String::new(),
+            detach_model: "axis".to_string(),
+            acceptance: "metropolis".to_string(),
+            energy_accounting: "surface".to_string(),
+
+            freeze_100: false,
+            freeze_010: false,
+            freeze_001: false,
+
+            template_file: String::new(),
+            item_dims_file: String::new(),
+            item_groups_file: String::new(),
 
             add_i: 1,
             add_from: 1,
             rem_i: 1,
             rem_from: 1,
+            swap_i: 0,
+            swap_from: 1,
 
             load_option: 0, // 0 means generate new, >0 means load specific line, -1 means load last line
+            nuclei_filter: "none".to_string(),
 
             step_lim: 100,
             print_i: 10,
             write_i: 1,
+            write_io_pct: 0.0,
+            write_i_max: 0,
+            step_lim_max: 0,
+            extend_rate: 0.0,
+            extend_pct: 50.0,
+            extend_window: 0,
+            control_i: 0,
+            checkpoint_i: 0,
+            resume_from: String::new(),
+            max_hours: 0.0,
+
+            item_write_i: 0,
+            item_keep_first: 0,
+            item_keep_last: 0,
+            item_roi: String::new(),
+            item_stride: 1,
+            item_max_mb: 0.0,
+            stall_thr: 0,
+            stale_retry: 3,
+            throttle_thr: 0,
+            throttle_i: 10,
+            poisson_add: 0.0,
+            poisson_rem: 0.0,
+            poisson_bal: 0.0,
+            anchor: "none".to_string(),
+            sub_lattice: "none".to_string(),
+            front_rebuild_i: 0,
+            front_rebuild_adopt: false,
+            pass_rate: 0.0,
+            pass_life: 100.0,
+            cluster_track: false,
+            heat_release: 0.0,
+            heat_cell: 4,
+            heat_diff: 0.1,
+            heat_decay: 0.01,
+
+            steady_w: 0,
+            steady_tol: 1e-3,
+            steady_m: 3,
+            steady_stop: false,
+
+            stats_from: 1,
+            energy_hist_bin: 0.0,
+            log_decim: 0,
+
+            rng_audit: false,
+            facet_stats: false,
+            kossel_check: false,
+            residence_stats: false,
+            density_profile: "none".to_string(),
+            minkowski: false,
+            event_export: false,
+            front_export: false,
+            first_passage: "none".to_string(),
+            detach_spectrum_i: 0,
+            probes: "none".to_string(),
+
+            mem_budget_gb: 0.0,
+            sparse_front: false,
+            write_queue: 0,
+            queue_full: "block".to_string(),
+            snap_compress: false,
+            snap_dual: false,
+
+            campaign: "none".to_string(),
+            campaign_dir: "none".to_string(),
+            pack_run: false,
+
+            ranks: 1,
+            rank: 0,
+            coord_addr: "127.0.0.1:7070".to_string(),
+            sync_i: 1,
+            deterministic: false,
+            status_addr: "none".to_string(),
+            preset: "none".to_string(),
+            strict: false,
+            unknown_keys: Vec::new(),
         }
     }
 }
@@ -92,7 +340,159 @@ impl Settings {
         Self::default()
     }
 
+    /// `(major, minor)` of the mode, e.g. `(2, 1)` for 2.1.
+    pub fn mode(&self) -> (u8, u8) {
+        (self.mode_major, self.mode_minor)
+    }
+
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::new()
+    }
+
+    pub fn face_mask(&self) -> u8 {
+        ((self.freeze_100 as u8) * FACE_100)
+            | ((self.freeze_010 as u8) * FACE_010)
+            | ((self.freeze_001 as u8) * FACE_001)
+    }
+
+    /// Grid axis (0 - x, 1 - y, 2 - z) of the crystal axes [100], [010], [001], parsed
+    /// from `AxisMap`; `None` if it is not a permutation of `xyz`.
+    pub fn axis_perm(&self) -> Option<[usize; 3]> {
+        let axes: Vec<usize> = self
+            .axis_map
+            .trim()
+            .chars()
+            .map(|c| match c.to_ascii_lowercase() {
+                'x' => Some(0),
+                'y' => Some(1),
+                'z' => Some(2),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+
+        match axes[..] {
+            [a, b, c] if a != b && b != c && a != c => Some([a, b, c]),
+            _ => None,
+        }
+    }
+
+    /// `true` for Kossel bond-counting kinetics (`DetachModel: bonds`), `false` for the
+    /// default surface-energy Metropolis rule (`axis`).
+    pub fn is_bond_counting(&self) -> bool {
+        self.detach_model.trim().eq_ignore_ascii_case("bonds")
+    }
+
+    /// Acceptance function of the kernel events (`Acceptance`).
+    pub fn acceptance_rule(&self) -> AcceptanceRule {
+        AcceptanceRule::parse(&self.acceptance).unwrap_or_default()
+    }
+
+    /// Desorption energy of the ballistic removals: `EDes`, or the energy of an isolated
+    /// particle `eisol = 2 (Ex + Ey + Ez)` for `EDes <= 0`.
+    pub fn desorption_energy(&self) -> f64 {
+        match self.e_des > 0.0 {
+            true => self.e_des,
+            false => {
+                2.0 * (self.g100 * self.ay * self.az
+                    + self.g010 * self.ax * self.az
+                    + self.g001 * self.ax * self.ay)
+            }
+        }
+    }
+
+    /// What the ballistic removals add to `tot_denergy` (`EnergyAccounting`).
+    pub fn energy_accounting_policy(&self) -> EnergyAccounting {
+        EnergyAccounting::parse(&self.energy_accounting).unwrap_or_default()
+    }
+
+    /// Template mask file, `None` when template growth is off (empty or `none`).
+    pub fn template_path(&self) -> Option<&str> {
+        match self.template_file.trim() {
+            "" | "none" => None,
+            path => Some(path),
+        }
+    }
+
+    /// Per-item grid size table, `None` when all items use `Sx`/`Sy`/`Sz` (empty or `none`).
+    pub fn item_dims_path(&self) -> Option<&str> {
+        match self.item_dims_file.trim() {
+            "" | "none" => None,
+            path => Some(path),
+        }
+    }
+
+    /// Per-item group table, `None` when the items are not grouped (empty or `none`).
+    pub fn item_groups_path(&self) -> Option<&str> {
+        match self.item_groups_file.trim() {
+            "" | "none" => None,
+            path => Some(path),
+        }
+    }
+
+    /// Results directory or checkpoint to resume from, `None` for a fresh run (empty or
+    /// `none`).
+    pub fn resume_path(&self) -> Option<&str> {
+        match self.resume_from.trim() {
+            "" | "none" => None,
+            path => Some(path),
+        }
+    }
+
+    /// Campaign of the run, `None` when it is not indexed (empty or `none`).
+    pub fn campaign_name(&self) -> Option<&str> {
+        match self.campaign.trim() {
+            "" | "none" => None,
+            name => Some(name),
+        }
+    }
+
+    /// Folder of the campaign index, `None` for the program folder (empty or `none`).
+    pub fn campaign_dir_path(&self) -> Option<&str> {
+        match self.campaign_dir.trim() {
+            "" | "none" => None,
+            dir => Some(dir),
+        }
+    }
+
+    fn is_item_roi_off(&self) -> bool {
+        matches!(self.item_roi.trim(), "" | "none")
+    }
+
+    /// Snapshot box `X0:X1:Y0:Y1:Z0:Z1` (inclusive cell bounds), `None` when off (empty or
+    /// `none`) or malformed.
+    pub fn item_roi_bounds(&self) -> Option<[(usize, usize); 3]> {
+        if self.is_item_roi_off() {
+            return None;
+        }
+
+        let bounds: Vec<usize> = self
+            .item_roi
+            .split(':')
+            .map(|v| v.trim().parse().ok())
+            .collect::<Option<_>>()?;
+
+        match bounds[..] {
+            [x0, x1, y0, y1, z0, z1] if x0 <= x1 && y0 <= y1 && z0 <= z1 => {
+                Some([(x0, x1), (y0, y1), (z0, z1)])
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether item snapshots are a region/subsample of the grid (`ItemRoi`, `ItemStride`).
+    pub fn is_snapshot_subsampled(&self) -> bool {
+        !self.is_item_roi_off() || self.item_stride > 1
+    }
+
     pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.strict && !self.unknown_keys.is_empty() {
+            let keys: Vec<&str> = self.unknown_keys.iter().map(|k| k.key.as_str()).collect();
+            return Err(SettingsError::simple(
+                "Strict",
+                format!("unknown keys in the config: {}", keys.join(", ")),
+            ));
+        }
+        mode::check_mode(self.mode()).map_err(|e| SettingsError::simple("mode", e))?;
         if self.sx == 0 {
             return Err(SettingsError::simple("Sx", "must be > 0"));
         }
@@ -102,15 +502,82 @@ impl Settings {
         if self.sz == 0 {
             return Err(SettingsError::simple("Sz", "must be > 0"));
         }
+        let grid_size = self
+            .sx
+            .checked_mul(self.sy)
+            .and_then(|v| v.checked_mul(self.sz));
+        if grid_size.is_none_or(|size| size as u128 >= Idx::MAX as u128) {
+            return Err(SettingsError::simple(
+                "Sx/Sy/Sz",
+                format!(
+                    "grid {}x{}x{} does not fit the {}-bit index type (rebuild without/with the 'idx-u32' feature)",
+                    self.sx,
+                    self.sy,
+                    self.sz,
+                    Idx::BITS
+                ),
+            ));
+        }
+        if self.axis_perm().is_none() {
+            return Err(SettingsError::simple(
+                "AxisMap",
+                "must be a permutation of 'xyz'",
+            ));
+        }
+        if !matches!(
+            self.detach_model.trim().to_ascii_lowercase().as_str(),
+            "axis" | "bonds"
+        ) {
+            return Err(SettingsError::simple(
+                "DetachModel",
+                "must be 'axis' or 'bonds'",
+            ));
+        }
+        if AcceptanceRule::parse(&self.acceptance).is_none() {
+            return Err(SettingsError::simple(
+                "Acceptance",
+                "must be 'metropolis', 'glauber' or 'heatbath'",
+            ));
+        }
+        if EnergyAccounting::parse(&self.energy_accounting).is_none() {
+            return Err(SettingsError::simple(
+                "EnergyAccounting",
+                "must be 'surface' or 'total'",
+            ));
+        }
         if self.temperature <= 0.0 {
             return Err(SettingsError::simple("T", "must be > 0"));
         }
+        if self.energy_hist_bin.is_nan() || self.energy_hist_bin < 0.0 {
+            return Err(SettingsError::simple(
+                "EnergyHistBin",
+                "must be >= 0 (0: off)",
+            ));
+        }
+        if self.energy_hist_bin > 0.0 && self.is_bond_counting() {
+            return Err(SettingsError::simple(
+                "EnergyHistBin",
+                "needs DetachModel axis (the bonds rates do not sample exp(-(E - n * dg) / kT))",
+            ));
+        }
+        if self.t_sigma < 0.0 {
+            return Err(SettingsError::simple("T_sigma", "must be >= 0"));
+        }
+        if self.t_sigma > 0.0 && self.t_tau <= 0.0 {
+            return Err(SettingsError::simple("T_tau", "must be > 0"));
+        }
+        if self.t_every == 0 {
+            return Err(SettingsError::simple("T_every", "must be > 0"));
+        }
         if self.add_from < 1 {
             return Err(SettingsError::simple("AddFrom", "must be > 0"));
         }
         if self.rem_from < 1 {
             return Err(SettingsError::simple("RemFrom", "must be > 0"));
         }
+        if self.swap_from < 1 {
+            return Err(SettingsError::simple("SwapFrom", "must be > 0"));
+        }
         // if self.ax <= 0.0 || self.ay <= 0.0 || self.az <= 0.0 {
         //     return Err(SettingsError::simple("Ax/Ay/Az", "must be > 0"));
         // }
@@ -123,6 +590,331 @@ impl Settings {
         // if self.print_on_step == 0 {
         //     return Err(SettingsError::simple("PrintOnStep", "must be > 0"));
         // }
+        if self.item_write_i > 0
+            && (self.write_i == 0 || !self.item_write_i.is_multiple_of(self.write_i))
+        {
+            return Err(SettingsError::simple(
+                "ItemWriteI",
+                "must be a multiple of WriteI",
+            ));
+        }
+        if self.write_io_pct.is_nan() || !(0.0..100.0).contains(&self.write_io_pct) {
+            return Err(SettingsError::simple(
+                "WriteIoPct",
+                "must be in [0, 100) (0: off)",
+            ));
+        }
+        if self.write_io_pct > 0.0 {
+            if self.write_i == 0 {
+                return Err(SettingsError::simple("WriteIoPct", "needs WriteI > 0"));
+            }
+            if self.write_i_max > 0 && self.write_i_max < self.write_i {
+                return Err(SettingsError::simple(
+                    "WriteIMax",
+                    "must be >= WriteI (0: up to StepLim)",
+                ));
+            }
+            if self.item_write_i > 0 {
+                return Err(SettingsError::simple(
+                    "WriteIoPct",
+                    "cannot be combined with ItemWriteI (a multiple of the WriteI it changes)",
+                ));
+            }
+        }
+        if self.step_lim_max > 0 {
+            if self.step_lim_max < self.step_lim {
+                return Err(SettingsError::simple(
+                    "StepLimMax",
+                    "must be >= StepLim (0: off)",
+                ));
+            }
+            if self.ranks > 1 {
+                return Err(SettingsError::simple(
+                    "StepLimMax",
+                    "is not supported for a distributed ensemble (Ranks > 1)",
+                ));
+            }
+        }
+        if !self.extend_rate.is_finite() || self.extend_rate < 0.0 {
+            return Err(SettingsError::simple("ExtendRate", "must be >= 0"));
+        }
+        if self.extend_pct.is_nan() || !(0.0..=100.0).contains(&self.extend_pct) {
+            return Err(SettingsError::simple("ExtendPct", "must be in [0, 100]"));
+        }
+        if !self.is_item_roi_off() && self.item_roi_bounds().is_none() {
+            return Err(SettingsError::simple(
+                "ItemRoi",
+                "must be 'X0:X1:Y0:Y1:Z0:Z1' with X0 <= X1, Y0 <= Y1, Z0 <= Z1",
+            ));
+        }
+        if self.item_stride == 0 {
+            return Err(SettingsError::simple("ItemStride", "must be > 0"));
+        }
+        if self.item_max_mb < 0.0 {
+            return Err(SettingsError::simple("ItemMaxMb", "must be >= 0"));
+        }
+        if self.write_queue > 0 && self.item_max_mb > 0.0 {
+            return Err(SettingsError::simple(
+                "WriteQueue",
+                "cannot be combined with ItemMaxMb (the thinning rewrites TimeStates.txt)",
+            ));
+        }
+        if self.sparse_front && BuildIndex::NAME != HashedIndex::NAME {
+            return Err(SettingsError::simple(
+                "SparseFront",
+                format!(
+                    "the frontier of this build is '{}'; build with the 'front-hashed' feature",
+                    BuildIndex::NAME
+                ),
+            ));
+        }
+        if QueueFull::parse(&self.queue_full).is_none() {
+            return Err(SettingsError::simple(
+                "QueueFull",
+                "must be 'block', 'drop' or 'abort'",
+            ));
+        }
+        if self.snap_compress && self.write_queue == 0 {
+            return Err(SettingsError::simple(
+                "SnapCompress",
+                "needs WriteQueue > 0",
+            ));
+        }
+        if self.snap_dual && !self.snap_compress {
+            return Err(SettingsError::simple("SnapDual", "needs SnapCompress:true"));
+        }
+        if self.throttle_thr > 0 && self.throttle_i == 0 {
+            return Err(SettingsError::simple(
+                "ThrottleI",
+                "must be >= 1 with ThrottleThr > 0",
+            ));
+        }
+        for (key, mean) in [
+            ("PoissonAdd", self.poisson_add),
+            ("PoissonRem", self.poisson_rem),
+            ("PoissonBal", self.poisson_bal),
+        ] {
+            if !(0.0..=POISSON_MEAN_MAX).contains(&mean) {
+                return Err(SettingsError::simple(
+                    key,
+                    format!("must be in [0, {}] (0: one attempt)", POISSON_MEAN_MAX),
+                ));
+            }
+        }
+        if Anchor::parse(&self.anchor).is_none() {
+            return Err(SettingsError::simple(
+                "Anchor",
+                "must be 'none', 'seed' or X0:X1:Y0:Y1:Z0:Z1",
+            ));
+        }
+        if SubLattice::parse(&self.sub_lattice).is_none() {
+            return Err(SettingsError::simple(
+                "SubLattice",
+                "must be 'none', 'parity' or 'stride:N' with N >= 2",
+            ));
+        }
+        if !(0.0..=POISSON_MEAN_MAX).contains(&self.pass_rate) {
+            return Err(SettingsError::simple(
+                "PassRate",
+                format!("must be in [0, {}] (0: off)", POISSON_MEAN_MAX),
+            ));
+        }
+        if self.pass_rate > 0.0 && (self.pass_life.is_nan() || self.pass_life <= 0.0) {
+            return Err(SettingsError::simple("PassLife", "must be > 0"));
+        }
+        if self.heat_release.is_nan() || self.heat_release < 0.0 {
+            return Err(SettingsError::simple(
+                "HeatRelease",
+                "must be >= 0 (0: off)",
+            ));
+        }
+        if self.heat_release > 0.0 {
+            if self.heat_cell == 0 {
+                return Err(SettingsError::simple("HeatCell", "must be > 0"));
+            }
+            if !(0.0..=1.0).contains(&self.heat_diff) {
+                return Err(SettingsError::simple("HeatDiff", "must be in [0, 1]"));
+            }
+            if !(0.0..=1.0).contains(&self.heat_decay) {
+                return Err(SettingsError::simple("HeatDecay", "must be in [0, 1]"));
+            }
+            // Explicit diffusion over 6 neighbours: the new excess of a cell is a
+            // non-negative mix of the old ones only up to this bound.
+            if 6.0 * self.heat_diff + self.heat_decay > 1.0 {
+                return Err(SettingsError::simple(
+                    "HeatDiff",
+                    format!(
+                        "6 * HeatDiff + HeatDecay = {} exceeds 1; the explicit heat diffusion would be unstable (lower HeatDiff or HeatDecay)",
+                        6.0 * self.heat_diff + self.heat_decay
+                    ),
+                ));
+            }
+        }
+        if !(0.0..1.0).contains(&self.gas_dist_noise) {
+            return Err(SettingsError::simple("GasDistNoise", "must be in [0, 1)"));
+        }
+        match utils::parse_gas_dist(&self.gas_dist) {
+            None => {
+                return Err(SettingsError::simple(
+                    "GasDist",
+                    "must be 'none' or values > 0 separated by commas, one per item (e.g. '0.5,1,1.5')",
+                ));
+            }
+            Some(list)
+                if !list.is_empty() && (self.gas_dist_pow != 0.0 || self.gas_dist_noise > 0.0) =>
+            {
+                return Err(SettingsError::simple(
+                    "GasDist",
+                    "a list excludes GasDistPow and GasDistNoise (set them to 0)",
+                ));
+            }
+            Some(_) => {}
+        }
+        if !(0.0..=1.0).contains(&self.k_exch) {
+            return Err(SettingsError::simple("k_exch", "must be in [0, 1]"));
+        }
+        if self.c_ext < 0.0 {
+            return Err(SettingsError::simple("C_ext", "must be >= 0"));
+        }
+        if self.chemostat_c >= 0.0 {
+            if self.chemostat_rate <= 0.0 {
+                return Err(SettingsError::simple(
+                    "ChemostatRate",
+                    "must be > 0 with ChemostatC >= 0",
+                ));
+            }
+            if self.k_exch > 0.0 {
+                return Err(SettingsError::simple(
+                    "ChemostatC",
+                    "cannot be combined with k_exch (both drive the reservoir)",
+                ));
+            }
+        }
+        if DensityProfile::parse_axes(&self.density_profile).is_none() {
+            return Err(SettingsError::simple(
+                "DensityProfile",
+                "must be 'none' or grid axes 'x', 'y', 'z' (e.g. 'z', 'xz'), each at most once",
+            ));
+        }
+        if passage::parse_thresholds(&self.first_passage).is_none() {
+            return Err(SettingsError::simple(
+                "FirstPassage",
+                "must be 'none' or crystal sizes > 0 separated by commas (e.g. '100,1000')",
+            ));
+        }
+        if !matches!(
+            self.status_addr.trim().to_ascii_lowercase().as_str(),
+            "" | "none"
+        ) && status::parse_addr(&self.status_addr).is_none()
+        {
+            return Err(SettingsError::simple(
+                "StatusAddr",
+                "must be 'none' or an address ip:port (e.g. '127.0.0.1:0' for a port chosen by the system)",
+            ));
+        }
+        if probes::parse_probes(&self.probes).is_none() {
+            return Err(SettingsError::simple(
+                "Probes",
+                "must be 'none' or grid cells X:Y:Z separated by commas (e.g. '10:10:12,10:10:13')",
+            ));
+        }
+        if NucleiFilter::parse(&self.nuclei_filter).is_none() {
+            return Err(SettingsError::simple(
+                "NucleiFilter",
+                "must be 'none', 'drop' or 'mark'",
+            ));
+        }
+        if ConcNegAction::parse(&self.conc_neg_action).is_none() {
+            return Err(SettingsError::simple(
+                "ConcNegAction",
+                "must be 'warn', 'pause' or 'abort'",
+            ));
+        }
+        if self.steady_w > 0 && self.steady_tol < 0.0 {
+            return Err(SettingsError::simple("SteadyTol", "must be >= 0"));
+        }
+        // Compiles and test-evaluates the expression; the energies are set at run time.
+        BallisticLaw::new(self.e_des, 1.0, &self.pb_expr, 1.0, self.temperature)
+            .map_err(|e| SettingsError::simple("PbExpr", e))?;
+        if self.kossel_check {
+            let (ex, ey, ez) = (
+                self.g100 * self.ay * self.az,
+                self.g010 * self.ax * self.az,
+                self.g001 * self.ax * self.ay,
+            );
+            let e_max = ex.max(ey).max(ez);
+            if e_max - ex.min(ey).min(ez) > 1e-9 * e_max.abs() {
+                return Err(SettingsError::simple(
+                    "KosselCheck",
+                    "needs isotropic energies (g100*Ay*Az = g010*Ax*Az = g001*Ax*Ay)",
+                ));
+            }
+        }
+        if self.ranks == 0 {
+            return Err(SettingsError::simple("Ranks", "must be > 0"));
+        }
+        if self.max_hours.is_nan() || self.max_hours < 0.0 {
+            return Err(SettingsError::simple(
+                "MaxHours",
+                "must be >= 0 (0: no limit)",
+            ));
+        }
+        if self.sync_i == 0 {
+            return Err(SettingsError::simple("SyncI", "must be > 0"));
+        }
+        if self.deterministic && self.ranks > 1 && self.sync_i != 1 {
+            return Err(SettingsError::simple(
+                "Deterministic",
+                "needs SyncI = 1 with Ranks > 1 (the ranks see each other's crystals every step)",
+            ));
+        }
+        if self.item_groups_path().is_some() && self.ranks > 1 {
+            return Err(SettingsError::simple(
+                "ItemGroupsFile",
+                "is not supported for a distributed ensemble (Ranks > 1)",
+            ));
+        }
+        if self.resume_path().is_some() {
+            if self.ranks > 1 {
+                return Err(SettingsError::simple(
+                    "ResumeFrom",
+                    "is not supported for a distributed ensemble (Ranks > 1)",
+                ));
+            }
+            if self.item_dims_path().is_some() {
+                return Err(SettingsError::simple(
+                    "ResumeFrom",
+                    "cannot be combined with ItemDimsFile (the checkpoint keeps only the alive items)",
+                ));
+            }
+            if self.item_groups_path().is_some() {
+                return Err(SettingsError::simple(
+                    "ResumeFrom",
+                    "cannot be combined with ItemGroupsFile (the checkpoint keeps only the alive items)",
+                ));
+            }
+            if self.pass_rate > 0.0 {
+                return Err(SettingsError::simple(
+                    "ResumeFrom",
+                    "cannot be combined with PassRate > 0 (the checkpoint does not keep the blocked sites)",
+                ));
+            }
+            if self.heat_release > 0.0 {
+                return Err(SettingsError::simple(
+                    "ResumeFrom",
+                    "cannot be combined with HeatRelease > 0 (the checkpoint does not keep the heat field)",
+                ));
+            }
+        }
+        if self
+            .campaign
+            .contains(|c: char| matches!(c, '/' | '\\' | ':') || c.is_control())
+        {
+            return Err(SettingsError::simple(
+                "Campaign",
+                "must be a plain name (no '/', '\\' or ':')",
+            ));
+        }
         if self.dir_prefix.trim().is_empty() {
             return Err(SettingsError::simple("DirPrefix", "cannot be empty"));
         }
@@ -130,80 +922,270 @@ impl Settings {
     }
 }
 
-#[derive(Debug)]
-pub struct SettingsError {
-    pub key: Option<Cow<'static, str>>,
-    pub value: Option<String>,
-    pub source: Box<dyn Error + Send + Sync>,
-}
+pub const PRESET_KEY: &str = "Preset";
 
-impl SettingsError {
-    pub fn new<K: Into<Cow<'static, str>>, V: Into<String>, E: Error + Send + Sync + 'static>(
-        key: K,
-        value: V,
-        source: E,
-    ) -> Self {
-        Self {
-            key: Some(key.into()),
-            value: Some(value.into()),
-            source: Box::new(source),
-        }
-    }
+/// Material and reservoir shared by the presets.
+const PRESET_BASE: &[(&str, &str)] = &[
+    ("Seed", "1012"),
+    ("T", "300"),
+    ("Ax", "2.42724e-10"),
+    ("Ay", "7.38546e-11"),
+    ("Az", "1.82977e-10"),
+    ("g100", "0.41"),
+    ("g010", "0.54"),
+    ("g001", "0.22"),
+    ("mode", "2.1"),
+    ("C_eq", "9.58767e-08"),
+    ("C0", "2e-6"),
+    ("N_tot", "3e10"),
+];
 
-    pub fn simple<K: Into<Cow<'static, str>>, M: Into<String>>(key: K, message: M) -> Self {
-        Self {
-            key: Some(key.into()),
-            value: None,
-            source: Box::new(SimpleMsg(message.into())),
-        }
-    }
+/// Named baselines of `Preset` (or `--preset <name>`): config keys set before the keys of
+/// `InitSettings.ini`, which override them, so a config may hold only `Preset` and the
+/// changed keys. The states still come from `InitStates.ini` (e.g. made by `fixture`)
+/// and must fit the preset grid.
+pub const PRESETS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "small-test",
+        &[
+            ("DirPrefix", "SmallTest"),
+            ("Sx", "20"),
+            ("Sy", "20"),
+            ("Sz", "20"),
+            ("Px", "true"),
+            ("Py", "true"),
+            ("Pz", "true"),
+            ("LoadOption", "-1"),
+            ("StepLim", "6000"),
+            ("PrintI", "1000"),
+            ("WriteI", "300"),
+        ],
+    ),
+    (
+        "growth-demo",
+        &[
+            ("DirPrefix", "GrowthDemo"),
+            ("Sx", "50"),
+            ("Sy", "50"),
+            ("Sz", "50"),
+            ("Px", "false"),
+            ("Py", "false"),
+            ("Pz", "false"),
+            ("LoadOption", "1"),
+            ("StepLim", "1000000"),
+            ("PrintI", "100000"),
+            ("WriteI", "10000"),
+        ],
+    ),
+    (
+        "ensemble-baseline",
+        &[
+            ("DirPrefix", "EnsembleBaseline"),
+            ("Sx", "30"),
+            ("Sy", "30"),
+            ("Sz", "30"),
+            ("Px", "true"),
+            ("Py", "true"),
+            ("Pz", "true"),
+            ("LoadOption", "-1"),
+            ("StepLim", "200000"),
+            ("PrintI", "20000"),
+            ("WriteI", "2000"),
+            ("Deterministic", "true"),
+        ],
+    ),
+];
 
-    pub fn from_io(err: std::io::Error) -> Self {
-        Self {
-            key: None,
-            value: None,
-            source: Box::new(err),
-        }
-    }
+/// Config keys of the preset `name`, the shared ones first; `None` for an unknown name.
+pub fn preset_keys(name: &str) -> Option<Vec<(&'static str, &'static str)>> {
+    let (_, keys) = PRESETS.iter().find(|(preset, _)| *preset == name.trim())?;
+    Some(PRESET_BASE.iter().chain(keys.iter()).copied().collect())
 }
 
-impl fmt::Display for SettingsError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match (&self.key, &self.value) {
-            (Some(key), Some(value)) => {
-                write!(
-                    f,
-                    "Failed to parse '{}' with value '{}': {}",
-                    key, value, self.source
-                )
+macro_rules! builder_setters {
+    ($($field:ident: $type:ty),* $(,)?) => {
+        $(
+            pub fn $field(&mut self, val: $type) -> &mut Self {
+                self.cfg.$field = val;
+                self
             }
-            (Some(key), None) => {
-                write!(f, "Invalid value for '{}': {}", key, self.source)
-            }
-            _ => write!(f, "Settings error: {}", self.source),
-        }
-    }
+        )*
+    };
 }
 
-impl Error for SettingsError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&*self.source)
-    }
+/// Programmatic construction of `Settings`: starts from the defaults, every
+/// field has a setter, `build()` runs `Settings::validate`.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsBuilder {
+    cfg: Settings,
 }
 
-impl From<std::io::Error> for SettingsError {
-    fn from(err: std::io::Error) -> Self {
-        Self::from_io(err)
+impl SettingsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_settings(cfg: Settings) -> Self {
+        Self { cfg }
     }
-}
 
-#[derive(Debug)]
-struct SimpleMsg(String);
+    builder_setters! {
+        dir_prefix: String,
+        seed: u64,
+
+        sx: usize,
+        sy: usize,
+        sz: usize,
+        px: bool,
+        py: bool,
+        pz: bool,
+
+        temperature: f64,
+        t_sigma: f64,
+        t_tau: f64,
+        t_every: u64,
+        ax: f64,
+        ay: f64,
+        az: f64,
+
+        g100: f64,
+        g010: f64,
+        g001: f64,
+        axis_map: String,
+
+        mode_major: u8,
+        mode_minor: u8,
+        dg: f64,
+        c_eq: f64,
+        c0: f64,
+        n_tot: f64,
+        n0_cr: f64,
+        gas_dist_pow: f64,
+        gas_dist_noise: f64,
+        gas_dist: String,
+        c_ext: f64,
+        k_exch: f64,
+        chemostat_c: f64,
+        chemostat_rate: f64,
+        conc_neg_max: u64,
+        conc_neg_action: String,
+        dg_exact: bool,
+        p_b: f64,
+        p_b_x: f64,
+        p_b_y: f64,
+        p_b_z: f64,
+        p_pow: f64,
+        e_des: f64,
+        pb_expr: String,
+        detach_model: String,
+        acceptance: String,
+        energy_accounting: String,
+
+        freeze_100: bool,
+        freeze_010: bool,
+        freeze_001: bool,
+
+        template_file: String,
+        item_dims_file: String,
+        item_groups_file: String,
+
+        add_i: u64,
+        add_from: u64,
+        rem_i: u64,
+        rem_from: u64,
+        swap_i: u64,
+        swap_from: u64,
 
-impl fmt::Display for SimpleMsg {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        load_option: i64,
+        nuclei_filter: String,
+
+        step_lim: u64,
+        print_i: u64,
+        write_i: u64,
+        write_io_pct: f64,
+        write_i_max: u64,
+        step_lim_max: u64,
+        extend_rate: f64,
+        extend_pct: f64,
+        extend_window: u64,
+        control_i: u64,
+        checkpoint_i: u64,
+        resume_from: String,
+        max_hours: f64,
+
+        item_write_i: u64,
+        item_keep_first: usize,
+        item_keep_last: usize,
+        item_roi: String,
+        item_stride: usize,
+        item_max_mb: f64,
+        stall_thr: usize,
+        stale_retry: u32,
+        throttle_thr: usize,
+        throttle_i: u64,
+        poisson_add: f64,
+        poisson_rem: f64,
+        poisson_bal: f64,
+        anchor: String,
+        sub_lattice: String,
+        front_rebuild_i: u64,
+        front_rebuild_adopt: bool,
+        pass_rate: f64,
+        pass_life: f64,
+        cluster_track: bool,
+        heat_release: f64,
+        heat_cell: usize,
+        heat_diff: f64,
+        heat_decay: f64,
+
+        steady_w: usize,
+        steady_tol: f64,
+        steady_m: usize,
+        steady_stop: bool,
+
+        stats_from: u64,
+        energy_hist_bin: f64,
+        log_decim: u64,
+
+        rng_audit: bool,
+        facet_stats: bool,
+        kossel_check: bool,
+        residence_stats: bool,
+        density_profile: String,
+        minkowski: bool,
+        event_export: bool,
+        front_export: bool,
+        first_passage: String,
+        detach_spectrum_i: u64,
+        probes: String,
+
+        mem_budget_gb: f64,
+        sparse_front: bool,
+        write_queue: usize,
+        queue_full: String,
+        snap_compress: bool,
+        snap_dual: bool,
+        campaign: String,
+        campaign_dir: String,
+        pack_run: bool,
+        ranks: usize,
+        rank: usize,
+        coord_addr: String,
+        sync_i: u64,
+        deterministic: bool,
+        status_addr: String,
+        preset: String,
+        strict: bool,
+        unknown_keys: Vec<UnknownKey>,
+    }
+
+    /// `(major, minor)` of the mode set so far.
+    pub fn mode(&self) -> (u8, u8) {
+        self.cfg.mode()
     }
-}
 
-impl Error for SimpleMsg {}
+    pub fn build(&self) -> Result<Settings, SettingsError> {
+        self.cfg.validate()?;
+        Ok(self.cfg.clone())
+    }
+}
@@ -1,15 +1,49 @@
-use crate::mods::{constants::SIM_LOG_FILE_NAME, frontier::Frontier, io_handler, lattice::Grid};
+use crate::mods::{
+    ballistics::BallisticAxes,
+    constants::{SIM_LOG_FILE_NAME, SIM_LOG_REC_FILE_NAME, SIM_LOG_STATS_FILE_NAME},
+    faults::{self, FaultPoint},
+    frontier::Frontier,
+    lattice::{Grid, Idx},
+    minkowski::Minkowski,
+    orientation::PrincipalAxes,
+    summation::NeumaierSum,
+    utils::{RunningStats, compute_neighbor_sums},
+};
 
 use std::{
     fmt::Debug,
-    fs::File,
-    io::{BufWriter, Error as IoError, Result as IoResult, Write},
-    path::PathBuf,
+    fs::{self, File},
+    io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
+/// Logged value types; `as_f64` feeds the running aggregates of `LogEntry`.
+pub trait LogValue: Debug + Copy + 'static {
+    fn as_f64(self) -> f64;
+}
+
+impl LogValue for f64 {
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+impl LogValue for usize {
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl LogValue for u64 {
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
 pub struct LogEntry<T: Debug + 'static> {
     pub val: T,
     pub log: Vec<T>,
+    pub stats: RunningStats,
     pub is_on: bool,
     pub format_f: Box<dyn Fn(T) -> String + 'static>,
 }
@@ -19,13 +53,14 @@ impl<T: Debug + 'static> Debug for LogEntry<T> {
         f.debug_struct("LogEntry")
             .field("val", &self.val)
             .field("log", &self.log)
+            .field("stats", &self.stats)
             .field("is_on", &self.is_on)
             .field("format_f", &"<closure>")
             .finish()
     }
 }
 
-impl<T: Debug + Copy + 'static> LogEntry<T> {
+impl<T: LogValue> LogEntry<T> {
     pub fn new<F>(val: T, is_on: bool, format_f: F) -> Self
     where
         F: Fn(T) -> String + 'static,
@@ -33,41 +68,272 @@ impl<T: Debug + Copy + 'static> LogEntry<T> {
         Self {
             val,
             log: Vec::new(),
+            stats: RunningStats::default(),
             is_on,
             format_f: Box::new(format_f),
         }
     }
 
     pub fn push_if_enabled(&mut self) {
+        self.record(true);
+    }
+
+    /// Feeds the running aggregates; the value itself is stored only if `keep`.
+    pub fn record(&mut self, keep: bool) {
         if self.is_on {
-            self.log.push(self.val);
+            self.stats.push(self.val.as_f64());
+            if keep {
+                self.log.push(self.val);
+            }
+        }
+    }
+
+    /// `name=value` field of a `SimLogRec.txt` record, `None` while the entry is off.
+    fn rec_field(&self, name: &str) -> Option<String> {
+        self.is_on
+            .then(|| format!("{}={}", name, (self.format_f)(self.val)))
+    }
+
+    /// `name:v1:v2:...` row of `SimLog.txt`, nothing while the entry is off.
+    fn write_row(&self, buf: &mut impl Write, name: &str) -> IoResult<()> {
+        if !self.is_on {
+            return Ok(());
+        }
+        write!(buf, "{}", name)?;
+        for &v in &self.log {
+            write!(buf, ":{}", (self.format_f)(v))?;
+        }
+        writeln!(buf)
+    }
+}
+
+/// Row names of `SimLog.txt`, in the order written by `SimLog::write_log_to_file`; each
+/// row starts with its name (`name:v1:v2:...`), the rows that are off are left out.
+pub const SIM_LOG_ROWS: [&str; 35] = [
+    "n_gas",
+    "n_cryst",
+    "conc",
+    "dg",
+    "tot_denergy",
+    "cryst_sx",
+    "cryst_sy",
+    "cryst_sz",
+    "mk_step",
+    "tpas_size",
+    "tpbs_size",
+    "tot_denergy_err",
+    "steady_step",
+    "surf_area",
+    "sv_ratio",
+    "sphericity",
+    "n_exch",
+    "temp",
+    "rot_angle",
+    "rot_step",
+    "inertia_aniso",
+    "exch_flux",
+    "mink_v",
+    "mink_s",
+    "mink_m",
+    "mink_chi",
+    "front_div",
+    "pass_sites",
+    "phi",
+    "depletion",
+    "clusters",
+    "tot_denergy_surf",
+    "tot_denergy_full",
+    "heat_t_mean",
+    "heat_t_max",
+];
+
+/// What to do once the reservoir concentration went negative more than `ConcNegMax` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcNegAction {
+    /// Warn once and go on.
+    Warn,
+    /// Warn once; `dg` keeps its last value while the concentration is negative.
+    Pause,
+    /// Stop the run.
+    Abort,
+}
+
+impl ConcNegAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "warn" => Some(Self::Warn),
+            "pause" => Some(Self::Pause),
+            "abort" => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// What the ballistic removals add to `tot_denergy` (`EnergyAccounting`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnergyAccounting {
+    /// The surface energy change only, as every other event.
+    #[default]
+    Surface,
+    /// The surface energy change and the desorption work `Edes` of the removed particle.
+    Total,
+}
+
+impl EnergyAccounting {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "surface" => Some(Self::Surface),
+            "total" => Some(Self::Total),
+            _ => None,
+        }
+    }
+}
+
+/// Crystal cells of an item and their faces not shared with another crystal cell, per
+/// grid axis, kept up to date by the kernels on every accepted event, so the morphology
+/// rows need no scan of the grid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CrystalFaces {
+    pub volume: u64,
+    pub exposed_faces: [u64; 3],
+}
+
+impl CrystalFaces {
+    /// Counts of `state` from scratch, for a loaded or rebuilt state.
+    pub fn of_state(state: &[u8], neibs: &[[Idx; 6]]) -> Self {
+        let mut faces = Self::default();
+        for (idxg, _) in state.iter().enumerate().filter(|&(_, &s)| s == 1) {
+            let (smx_yz, smy_xz, smz_xy) = compute_neighbor_sums(state, &neibs[idxg]);
+            faces.volume += 1;
+            faces.exposed_faces[0] += (2 - smx_yz) as u64;
+            faces.exposed_faces[1] += (2 - smy_xz) as u64;
+            faces.exposed_faces[2] += (2 - smz_xy) as u64;
+        }
+        faces
+    }
+
+    /// A cell with the crystal neighbour sums `sums` joins the crystal: it brings
+    /// `2 - s` faces along each axis and covers `s` faces of its neighbours.
+    #[inline(always)]
+    pub fn attach(&mut self, (smx_yz, smy_xz, smz_xy): (u8, u8, u8)) {
+        self.volume += 1;
+        for (faces, s) in self.exposed_faces.iter_mut().zip([smx_yz, smy_xz, smz_xy]) {
+            *faces = *faces + 2 - 2 * s as u64;
+        }
+    }
+
+    /// Reverse of `attach`, with the sums taken before the cell leaves.
+    #[inline(always)]
+    pub fn detach(&mut self, (smx_yz, smy_xz, smz_xy): (u8, u8, u8)) {
+        self.volume -= 1;
+        for (faces, s) in self.exposed_faces.iter_mut().zip([smx_yz, smy_xz, smz_xy]) {
+            *faces = *faces + 2 * s as u64 - 2;
         }
     }
 }
+
 #[derive(Debug)]
 pub struct SimLog {
     pub k_t: f64,
     pub p_b: f64,
+    /// Per-axis `p_b` in grid axes (`p_b_x`, `p_b_y`, `p_b_z`).
+    pub p_b_axes: BallisticAxes,
     pub p_pow: f64,
 
     pub conc_eq: f64,
     pub conc: LogEntry<f64>,
     pub conc_neg_count: u64,
+    /// `conc_neg_count` went past `ConcNegMax`.
+    pub conc_neg_exceeded: bool,
 
     pub n_tot: f64,
 
     pub n_cryst: LogEntry<f64>,
     pub n_gas: LogEntry<f64>,
+    pub n_exch: LogEntry<f64>,
+    pub temp: LogEntry<f64>,
     pub dg: LogEntry<f64>,
     pub tot_denergy: LogEntry<f64>,
+    pub tot_denergy_err: LogEntry<f64>,
+    pub tot_denergy_sum: NeumaierSum,
+    /// Energy change sums of the ballistic modes (x.2, x.3): surface-only and total with
+    /// the desorption work `e_des` of every ballistic removal; `tot_denergy` is one of
+    /// them (`EnergyAccounting`).
+    pub tot_denergy_surf: LogEntry<f64>,
+    pub tot_denergy_full: LogEntry<f64>,
+    pub tot_denergy_surf_sum: NeumaierSum,
+    pub tot_denergy_full_sum: NeumaierSum,
+    pub e_des: f64,
+    pub energy_accounting: EnergyAccounting,
 
     pub cryst_sx: LogEntry<usize>,
     pub cryst_sy: LogEntry<usize>,
     pub cryst_sz: LogEntry<usize>,
+    pub tpas_size: LogEntry<usize>,
+    pub tpbs_size: LogEntry<usize>,
     pub mk_step: LogEntry<u64>,
+    pub steady_step: LogEntry<u64>,
+
+    pub cell: (f64, f64, f64),
+    pub surf_area: LogEntry<f64>,
+    pub sv_ratio: LogEntry<f64>,
+    pub sphericity: LogEntry<f64>,
+    /// Angle (degrees) of the longest principal axis to the first measured one and to
+    /// the previous log point, and the anisotropy of the crystal (`PrincipalAxes`).
+    pub rot_angle: LogEntry<f64>,
+    pub rot_step: LogEntry<f64>,
+    pub inertia_aniso: LogEntry<f64>,
+    /// Gas exchanged with the outside on the last step (`k_exch`, `ChemostatC`), > 0 - fed.
+    pub exch_flux: LogEntry<f64>,
+    /// Minkowski functionals of the crystal (`Minkowski`): volume, surface, integral mean
+    /// curvature and Euler characteristic.
+    pub mink_v: LogEntry<f64>,
+    pub mink_s: LogEntry<f64>,
+    pub mink_m: LogEntry<f64>,
+    pub mink_chi: LogEntry<f64>,
+    /// Sites in which the incremental front differed from a full rebuild at the last
+    /// check (`FrontRebuildI`).
+    pub front_div: LogEntry<usize>,
+    /// Sites blocked by passivating species (`PassRate`).
+    pub pass_sites: LogEntry<usize>,
+    /// Crystal volume fraction `n_cryst / n_tot` and reservoir depletion
+    /// `1 - n_gas / n_gas0` (modes 2.x; < 0 when the exchange fed more than was taken).
+    pub phi: LogEntry<f64>,
+    pub depletion: LogEntry<f64>,
+    /// Separate crystals on the grid (`ClusterTrack`).
+    pub clusters: LogEntry<usize>,
+    /// Mean and largest temperature of the coarse heat field (`HeatRelease`).
+    pub heat_t_mean: LogEntry<f64>,
+    pub heat_t_max: LogEntry<f64>,
+    /// Gas of the reservoir at the start of the run.
+    pub n_gas0: f64,
+    /// `dg` from the gas excess over saturation rebuilt from the particle counts moved
+    /// since the start (`DgExact`): its concentration `conc0` and crystal `n_cryst0`.
+    pub dg_exact: bool,
+    pub conc0: f64,
+    pub n_cryst0: f64,
+    pub axis_ref: Option<[f64; 3]>,
+    pub axis_prev: Option<[f64; 3]>,
+    /// The orientation rows were restored from a checkpoint (`ResumeFrom`): the next
+    /// `measure_orientation` logs them as they are instead of measuring.
+    pub is_axes_restored: bool,
+
+    /// Keep every `log_decim`-th log point in memory (0 - all of them); the aggregates
+    /// in `SimLogStats.txt` still cover every point.
+    pub log_decim: u64,
+    pub n_log_points: u64,
 
     pub path_out_file: Option<PathBuf>,
     pub out_file_buf: Option<BufWriter<File>>,
+    /// Append-only `SimLogRec.txt`: one flushed record per kept log point, so the
+    /// histories survive a crash (`read_sim_log_rows` rebuilds the row layout).
+    pub rec_file_buf: Option<BufWriter<File>>,
+}
+
+impl Default for SimLog {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SimLog {
@@ -79,25 +345,71 @@ impl SimLog {
         Self {
             k_t: 0.0,
             p_b: 0.0,
+            p_b_axes: BallisticAxes::default(),
             p_pow: 0.0,
 
             conc_eq: 0.0,
             conc: LogEntry::new(0.0, false, fmt1),
             conc_neg_count: 0,
+            conc_neg_exceeded: false,
 
             n_tot: 0.0,
 
             n_cryst: LogEntry::new(0.0, true, fmt1),
             n_gas: LogEntry::new(0.0, false, fmt1),
+            n_exch: LogEntry::new(0.0, false, fmt1),
+            temp: LogEntry::new(0.0, false, fmt1),
             dg: LogEntry::new(0.0, false, fmt1),
             tot_denergy: LogEntry::new(0.0, true, fmt1),
+            tot_denergy_err: LogEntry::new(0.0, true, fmt1),
+            tot_denergy_sum: NeumaierSum::new(),
+            tot_denergy_surf: LogEntry::new(0.0, false, fmt1),
+            tot_denergy_full: LogEntry::new(0.0, false, fmt1),
+            tot_denergy_surf_sum: NeumaierSum::new(),
+            tot_denergy_full_sum: NeumaierSum::new(),
+            e_des: 0.0,
+            energy_accounting: EnergyAccounting::Surface,
             cryst_sx: LogEntry::new(0, true, fmt2),
             cryst_sy: LogEntry::new(0, true, fmt2),
             cryst_sz: LogEntry::new(0, true, fmt2),
+            tpas_size: LogEntry::new(0, true, fmt2),
+            tpbs_size: LogEntry::new(0, true, fmt2),
             mk_step: LogEntry::new(0, true, fmt3),
+            steady_step: LogEntry::new(0, false, fmt3),
+
+            cell: (1.0, 1.0, 1.0),
+            surf_area: LogEntry::new(0.0, true, fmt1),
+            sv_ratio: LogEntry::new(0.0, true, fmt1),
+            sphericity: LogEntry::new(0.0, true, fmt1),
+            rot_angle: LogEntry::new(0.0, true, fmt1),
+            rot_step: LogEntry::new(0.0, true, fmt1),
+            inertia_aniso: LogEntry::new(0.0, true, fmt1),
+            exch_flux: LogEntry::new(0.0, false, fmt1),
+            mink_v: LogEntry::new(0.0, false, fmt1),
+            mink_s: LogEntry::new(0.0, false, fmt1),
+            mink_m: LogEntry::new(0.0, false, fmt1),
+            mink_chi: LogEntry::new(0.0, false, fmt1),
+            front_div: LogEntry::new(0, false, fmt2),
+            pass_sites: LogEntry::new(0, false, fmt2),
+            phi: LogEntry::new(0.0, false, fmt1),
+            depletion: LogEntry::new(0.0, false, fmt1),
+            clusters: LogEntry::new(0, false, fmt2),
+            heat_t_mean: LogEntry::new(0.0, false, fmt1),
+            heat_t_max: LogEntry::new(0.0, false, fmt1),
+            n_gas0: 0.0,
+            dg_exact: false,
+            conc0: 0.0,
+            n_cryst0: 0.0,
+            axis_ref: None,
+            axis_prev: None,
+            is_axes_restored: false,
+
+            log_decim: 0,
+            n_log_points: 0,
 
             path_out_file: None,
             out_file_buf: None,
+            rec_file_buf: None,
         }
     }
 
@@ -111,16 +423,35 @@ impl SimLog {
             )
         })?);
 
+        let path_rec_file = path_dst.join(SIM_LOG_REC_FILE_NAME);
+        let rec_file_buf = BufWriter::new(File::create(&path_rec_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!("Failed to create file '{}': {}", path_rec_file.display(), e),
+            )
+        })?);
+
         self.path_out_file = Some(path_out_file);
         self.out_file_buf = Some(out_file_buf);
+        self.rec_file_buf = Some(rec_file_buf);
 
         Ok(())
     }
 
+    /// Appends one `name=value:...` record and flushes it right away.
+    fn append_record(&mut self, fields: impl Iterator<Item = String>) {
+        if let Some(buf) = &mut self.rec_file_buf {
+            let line = fields.collect::<Vec<_>>().join(":");
+            let _ = faults::inject(FaultPoint::Record)
+                .and_then(|_| writeln!(buf, "{}", line))
+                .and_then(|_| buf.flush());
+        }
+    }
+
     pub fn initialize(
         &mut self,
         k_t: f64,
-        sim_mode: f64,
+        sim_mode: (u8, u8),
         dg0: f64,
         conc_eq: f64,
         conc0: f64,
@@ -135,23 +466,26 @@ impl SimLog {
 
         self.conc_eq = conc_eq;
         self.conc.val = conc0;
+        self.conc0 = conc0;
 
         self.n_tot = n_tot;
 
         self.n_cryst.val = n_cryst0;
+        self.n_cryst0 = n_cryst0;
         self.dg.val = dg0;
 
-        if sim_mode >= 2.1 {
+        if sim_mode.0 == 2 {
             let n_gas0 = conc0 * (n_tot - n_cryst0);
-            let conc_ratio = conc0 / conc_eq;
-            let dg0 = k_t * conc_ratio.ln();
 
             self.n_gas.val = n_gas0;
-            self.dg.val = dg0;
+            self.n_gas0 = n_gas0;
+            self.update_dg();
 
             self.conc.is_on = true;
             self.n_gas.is_on = true;
             self.dg.is_on = true;
+            self.phi.is_on = true;
+            self.depletion.is_on = true;
         }
     }
 
@@ -183,6 +517,28 @@ impl SimLog {
         self.n_gas.val -= dn_cryst;
     }
 
+    /// Solvent exchange: relaxes the reservoir concentration towards `conc_ext` by the
+    /// fraction `rate` per call. The exchanged gas is accumulated in `n_exch`.
+    pub fn exchange(&mut self, conc_ext: f64, rate: f64) {
+        let n_liquid = self.n_tot - self.n_cryst.val;
+        let dn_gas = rate * (conc_ext * n_liquid - self.n_gas.val);
+
+        self.n_gas.val += dn_gas;
+        self.n_exch.val += dn_gas;
+        self.exch_flux.val = dn_gas;
+    }
+
+    /// Chemostat: feeds or drains up to `max_rate` gas particles per call to hold the
+    /// reservoir concentration at `conc_set`. The exchanged gas is accumulated in `n_exch`.
+    pub fn chemostat(&mut self, conc_set: f64, max_rate: f64) {
+        let n_liquid = self.n_tot - self.n_cryst.val;
+        let dn_gas = (conc_set * n_liquid - self.n_gas.val).clamp(-max_rate, max_rate);
+
+        self.n_gas.val += dn_gas;
+        self.n_exch.val += dn_gas;
+        self.exch_flux.val = dn_gas;
+    }
+
     pub fn update_conc(&mut self) {
         self.conc.val = self.n_gas.val / (self.n_tot - self.n_cryst.val);
 
@@ -192,17 +548,110 @@ impl SimLog {
     }
 
     pub fn update_dg(&mut self) {
+        if self.dg_exact {
+            self.dg.val = self.k_t * self.supersaturation().ln_1p();
+            return;
+        }
+
         let conc_ratio = self.conc.val / self.conc_eq;
         self.dg.val = self.k_t * conc_ratio.ln();
     }
 
-    pub fn update_conc_and_dg(&mut self) {
+    /// `C / C_eq - 1` without forming the ratio: the gas excess over saturation
+    /// `n_gas - C_eq * n_liquid` is that of the start, `(C0 - C_eq) * n_liquid0`, plus the
+    /// exchanged gas, less `1 - C_eq` per particle moved into the crystal. The moved
+    /// particles are whole counts, exact in `f64`, so nothing cancels near `C = C_eq`.
+    fn supersaturation(&self) -> f64 {
+        let n_excess0 = (self.conc0 - self.conc_eq) * (self.n_tot - self.n_cryst0);
+        let n_moved = self.n_cryst.val - self.n_cryst0;
+        let n_excess = n_excess0 + self.n_exch.val - n_moved * (1.0 - self.conc_eq);
+        n_excess / (self.conc_eq * (self.n_tot - self.n_cryst.val))
+    }
+
+    /// Updates the concentration and `dg` with the `ConcNegMax` check (`max_count == 0` -
+    /// no limit): reports the first time the negative concentrations exceed it and
+    /// applies `action`. Returns `true` when the run has to stop.
+    pub fn update_conc_and_dg(
+        &mut self,
+        step_id: u64,
+        max_count: u64,
+        action: ConcNegAction,
+    ) -> bool {
         self.update_conc();
-        self.update_dg();
+
+        let is_over = max_count > 0 && self.conc_neg_count > max_count;
+        if is_over && !self.conc_neg_exceeded {
+            self.conc_neg_exceeded = true;
+            let status = format!(
+                "Step: {} -> The reservoir concentration went negative {} times (ConcNegMax: {}); n_gas: {:.5e}; n_cryst: {:.5e}; C: {:.5e}; N_tot: {:.5e}",
+                step_id,
+                self.conc_neg_count,
+                max_count,
+                self.n_gas.val,
+                self.n_cryst.val,
+                self.conc.val,
+                self.n_tot
+            );
+            match action {
+                ConcNegAction::Warn => eprintln!("⚠️ Warning: {}.", status),
+                ConcNegAction::Pause => eprintln!(
+                    "⚠️ Warning: {}; dg is kept while the concentration is negative.",
+                    status
+                ),
+                ConcNegAction::Abort => eprintln!("❌ {}; stopping the run.", status),
+            }
+        }
+
+        // Under `pause` a negative concentration never reaches `dg` (its log is NaN), from
+        // the first such step on, so the kept `dg` is the last finite one.
+        if !(action == ConcNegAction::Pause && self.conc.val < 0.0) {
+            self.update_dg();
+        }
+
+        is_over && action == ConcNegAction::Abort
     }
 
     pub fn add_denergy(&mut self, tot_denergy: f64) {
-        self.tot_denergy.val += tot_denergy;
+        self.tot_denergy_sum.add(tot_denergy);
+        self.tot_denergy.val = self.tot_denergy_sum.value();
+        if self.tot_denergy_surf.is_on {
+            self.add_split_denergy(tot_denergy, tot_denergy);
+        }
+    }
+
+    /// `add_denergy` of a ballistic removal with the surface energy change `surf_denergy`:
+    /// the full sum also takes the desorption work `e_des`, `tot_denergy` takes it with
+    /// `EnergyAccounting:total`.
+    pub fn add_ballistic_denergy(&mut self, surf_denergy: f64) {
+        let full_denergy = surf_denergy + self.e_des;
+        self.tot_denergy_sum.add(match self.energy_accounting {
+            EnergyAccounting::Surface => surf_denergy,
+            EnergyAccounting::Total => full_denergy,
+        });
+        self.tot_denergy.val = self.tot_denergy_sum.value();
+        if self.tot_denergy_surf.is_on {
+            self.add_split_denergy(surf_denergy, full_denergy);
+        }
+    }
+
+    fn add_split_denergy(&mut self, surf_denergy: f64, full_denergy: f64) {
+        self.tot_denergy_surf_sum.add(surf_denergy);
+        self.tot_denergy_surf.val = self.tot_denergy_surf_sum.value();
+        self.tot_denergy_full_sum.add(full_denergy);
+        self.tot_denergy_full.val = self.tot_denergy_full_sum.value();
+    }
+
+    /// Turns on the split energy sums of the ballistic modes; `e_des` - desorption work
+    /// of a ballistic removal.
+    pub fn track_desorption(&mut self, e_des: f64, accounting: EnergyAccounting) {
+        self.e_des = e_des;
+        self.energy_accounting = accounting;
+        self.tot_denergy_surf.is_on = true;
+        self.tot_denergy_full.is_on = true;
+    }
+
+    pub fn denergy_error_bound(&self) -> f64 {
+        self.tot_denergy_sum.error_bound()
     }
 
     pub fn measure_cryst_sizes(&mut self, grid: &mut Grid, front: &Frontier) {
@@ -230,31 +679,294 @@ impl SimLog {
         self.cryst_sz.val = grid.nz_ib.iter().sum();
     }
 
+    pub fn measure_front_sizes(&mut self, front: &Frontier) {
+        self.tpas_size.val = front.tpas_size;
+        self.tpbs_size.val = front.tpbs_size;
+    }
+
+    /// Surface area (sum of exposed cell faces), surface-to-volume ratio and sphericity
+    /// `π^(1/3) (6V)^(2/3) / A` of the crystal, using the physical cell sizes `cell`.
+    pub fn measure_morphology(&mut self, faces: &CrystalFaces) {
+        let (ax, ay, az) = self.cell;
+        let [faces_x, faces_y, faces_z] = faces.exposed_faces.map(|n| n as f64);
+
+        let area = faces_x * ay * az + faces_y * ax * az + faces_z * ax * ay;
+        let volume = faces.volume as f64 * ax * ay * az;
+
+        self.surf_area.val = area;
+        (self.sv_ratio.val, self.sphericity.val) = match area > 0.0 {
+            true => (
+                area / volume,
+                std::f64::consts::PI.cbrt() * (6.0 * volume).powf(2.0 / 3.0) / area,
+            ),
+            false => (0.0, 0.0),
+        };
+    }
+
+    /// Orientation of the crystal from its principal axes: the rotation of the longest
+    /// axis since the first measurement and since the previous one (reorientation events
+    /// show up as jumps), and how well the axes are defined (`inertia_aniso`).
+    pub fn measure_orientation(&mut self, state: &[u8], grid: &Grid) {
+        if std::mem::take(&mut self.is_axes_restored) {
+            return;
+        }
+
+        let Some(axes) = PrincipalAxes::of_crystal(state, grid, self.cell) else {
+            self.rot_step.val = 0.0;
+            self.inertia_aniso.val = 0.0;
+            return;
+        };
+
+        let axis_ref = *self.axis_ref.get_or_insert(axes.axes[0]);
+        let axis_prev = self.axis_prev.replace(axes.axes[0]).unwrap_or(axes.axes[0]);
+        self.rot_angle.val = axes.angle_to(&axis_ref);
+        self.rot_step.val = axes.angle_to(&axis_prev);
+        self.inertia_aniso.val = axes.anisotropy();
+    }
+
+    /// Minkowski functionals of the crystal (`Minkowski`), when the rows are on; a falling
+    /// `mink_chi` under ballistic erosion marks tunnels and pores opening in the crystal.
+    pub fn measure_minkowski(&mut self, state: &[u8], grid: &Grid) {
+        if !self.mink_chi.is_on {
+            return;
+        }
+
+        let mink = Minkowski::of_crystal(state, grid, self.cell);
+        self.mink_v.val = mink.volume;
+        self.mink_s.val = mink.surface;
+        self.mink_m.val = mink.mean_curv;
+        self.mink_chi.val = mink.euler;
+    }
+
+    pub fn mark_steady_state(&mut self, step_id: u64) {
+        self.steady_step.val = step_id;
+        self.steady_step.is_on = true;
+        self.steady_step.push_if_enabled();
+
+        let field = self.steady_step.rec_field("steady_step");
+        self.append_record(field.into_iter());
+    }
+
     pub fn add_log_point(&mut self) {
-        self.n_gas.push_if_enabled();
-        self.n_cryst.push_if_enabled();
-        self.conc.push_if_enabled();
-        self.dg.push_if_enabled();
-        self.tot_denergy.push_if_enabled();
-        self.cryst_sx.push_if_enabled();
-        self.cryst_sy.push_if_enabled();
-        self.cryst_sz.push_if_enabled();
-        self.mk_step.push_if_enabled();
+        let keep = self.log_decim == 0 || self.n_log_points.is_multiple_of(self.log_decim);
+        self.n_log_points += 1;
+
+        self.phi.val = self.n_cryst.val / self.n_tot;
+        if self.n_gas0 > 0.0 {
+            self.depletion.val = 1.0 - self.n_gas.val / self.n_gas0;
+        }
+
+        self.n_gas.record(keep);
+        self.n_cryst.record(keep);
+        self.conc.record(keep);
+        self.dg.record(keep);
+        self.tot_denergy.record(keep);
+        self.tot_denergy_err.val = self.denergy_error_bound();
+        self.tot_denergy_err.record(keep);
+        self.cryst_sx.record(keep);
+        self.cryst_sy.record(keep);
+        self.cryst_sz.record(keep);
+        self.tpas_size.record(keep);
+        self.tpbs_size.record(keep);
+        self.surf_area.record(keep);
+        self.sv_ratio.record(keep);
+        self.sphericity.record(keep);
+        self.n_exch.record(keep);
+        self.temp.record(keep);
+        self.rot_angle.record(keep);
+        self.rot_step.record(keep);
+        self.inertia_aniso.record(keep);
+        self.exch_flux.record(keep);
+        self.mink_v.record(keep);
+        self.mink_s.record(keep);
+        self.mink_m.record(keep);
+        self.mink_chi.record(keep);
+        self.front_div.record(keep);
+        self.pass_sites.record(keep);
+        self.phi.record(keep);
+        self.depletion.record(keep);
+        self.clusters.record(keep);
+        self.tot_denergy_surf.record(keep);
+        self.tot_denergy_full.record(keep);
+        self.heat_t_mean.record(keep);
+        self.heat_t_max.record(keep);
+        self.mk_step.record(keep);
+
+        if keep {
+            let fields = [
+                self.n_gas.rec_field("n_gas"),
+                self.n_cryst.rec_field("n_cryst"),
+                self.conc.rec_field("conc"),
+                self.dg.rec_field("dg"),
+                self.tot_denergy.rec_field("tot_denergy"),
+                self.cryst_sx.rec_field("cryst_sx"),
+                self.cryst_sy.rec_field("cryst_sy"),
+                self.cryst_sz.rec_field("cryst_sz"),
+                self.mk_step.rec_field("mk_step"),
+                self.tpas_size.rec_field("tpas_size"),
+                self.tpbs_size.rec_field("tpbs_size"),
+                self.tot_denergy_err.rec_field("tot_denergy_err"),
+                self.surf_area.rec_field("surf_area"),
+                self.sv_ratio.rec_field("sv_ratio"),
+                self.sphericity.rec_field("sphericity"),
+                self.n_exch.rec_field("n_exch"),
+                self.temp.rec_field("temp"),
+                self.rot_angle.rec_field("rot_angle"),
+                self.rot_step.rec_field("rot_step"),
+                self.inertia_aniso.rec_field("inertia_aniso"),
+                self.exch_flux.rec_field("exch_flux"),
+                self.mink_v.rec_field("mink_v"),
+                self.mink_s.rec_field("mink_s"),
+                self.mink_m.rec_field("mink_m"),
+                self.mink_chi.rec_field("mink_chi"),
+                self.front_div.rec_field("front_div"),
+                self.pass_sites.rec_field("pass_sites"),
+                self.phi.rec_field("phi"),
+                self.depletion.rec_field("depletion"),
+                self.clusters.rec_field("clusters"),
+                self.tot_denergy_surf.rec_field("tot_denergy_surf"),
+                self.tot_denergy_full.rec_field("tot_denergy_full"),
+                self.heat_t_mean.rec_field("heat_t_mean"),
+                self.heat_t_max.rec_field("heat_t_max"),
+            ];
+            self.append_record(fields.into_iter().flatten());
+        }
+    }
+
+    /// Writes `SimLogStats.txt` next to `SimLog.txt`: one `name:count:mean:std:min:max:last`
+    /// line per logged row, over every log point rather than only the decimated ones.
+    fn write_stats_file(&self) -> IoResult<()> {
+        let Some(path_out_file) = &self.path_out_file else {
+            return Ok(());
+        };
+
+        let rows = [
+            &self.n_gas.stats,
+            &self.n_cryst.stats,
+            &self.conc.stats,
+            &self.dg.stats,
+            &self.tot_denergy.stats,
+            &self.cryst_sx.stats,
+            &self.cryst_sy.stats,
+            &self.cryst_sz.stats,
+            &self.mk_step.stats,
+            &self.tpas_size.stats,
+            &self.tpbs_size.stats,
+            &self.tot_denergy_err.stats,
+            &self.steady_step.stats,
+            &self.surf_area.stats,
+            &self.sv_ratio.stats,
+            &self.sphericity.stats,
+            &self.n_exch.stats,
+            &self.temp.stats,
+            &self.rot_angle.stats,
+            &self.rot_step.stats,
+            &self.inertia_aniso.stats,
+            &self.exch_flux.stats,
+            &self.mink_v.stats,
+            &self.mink_s.stats,
+            &self.mink_m.stats,
+            &self.mink_chi.stats,
+            &self.front_div.stats,
+            &self.pass_sites.stats,
+            &self.phi.stats,
+            &self.depletion.stats,
+            &self.clusters.stats,
+            &self.tot_denergy_surf.stats,
+            &self.tot_denergy_full.stats,
+            &self.heat_t_mean.stats,
+            &self.heat_t_max.stats,
+        ];
+
+        let path_stats_file = path_out_file.with_file_name(SIM_LOG_STATS_FILE_NAME);
+        let mut buf = BufWriter::new(File::create(&path_stats_file).map_err(|e| {
+            IoError::new(
+                e.kind(),
+                format!(
+                    "Failed to create file '{}': {}",
+                    path_stats_file.display(),
+                    e
+                ),
+            )
+        })?);
+
+        writeln!(buf, "# name:count:mean:std:min:max:last")?;
+        for (name, stats) in SIM_LOG_ROWS.iter().zip(rows) {
+            if stats.count == 0 {
+                continue;
+            }
+            writeln!(
+                buf,
+                "{}:{}:{:.15e}:{:.15e}:{:.15e}:{:.15e}:{:.15e}",
+                name,
+                stats.count,
+                stats.mean,
+                stats.std(),
+                stats.min,
+                stats.max,
+                stats.last
+            )?;
+        }
+
+        buf.flush()
+    }
+
+    /// Empties `SimLog.txt` and drops the unwritten buffer, so a retried
+    /// `write_log_to_file` does not append to the rows of a failed attempt.
+    fn rewind_out_file(&mut self) -> IoResult<()> {
+        if let Some(buf) = self.out_file_buf.take() {
+            let (mut file, _) = buf.into_parts();
+            let rewound = file.set_len(0).and_then(|_| file.seek(SeekFrom::Start(0)));
+            self.out_file_buf = Some(BufWriter::new(file));
+            rewound?;
+        }
+        Ok(())
     }
 
     pub fn write_log_to_file(&mut self) -> IoResult<()> {
+        self.rewind_out_file()?;
+        faults::inject(FaultPoint::Log)?;
         if let Some(buf) = &mut self.out_file_buf {
-            io_handler::write_state_uni(buf, &self.n_gas.log, &self.n_gas.format_f)?;
-            io_handler::write_state_uni(buf, &self.n_cryst.log, &self.n_cryst.format_f)?;
-            io_handler::write_state_uni(buf, &self.conc.log, &self.conc.format_f)?;
-            io_handler::write_state_uni(buf, &self.dg.log, &self.dg.format_f)?;
-            io_handler::write_state_uni(buf, &self.tot_denergy.log, &self.tot_denergy.format_f)?;
-            io_handler::write_state_uni(buf, &self.cryst_sx.log, &self.cryst_sx.format_f)?;
-            io_handler::write_state_uni(buf, &self.cryst_sy.log, &self.cryst_sy.format_f)?;
-            io_handler::write_state_uni(buf, &self.cryst_sz.log, &self.cryst_sz.format_f)?;
-            io_handler::write_state_uni(buf, &self.mk_step.log, &self.mk_step.format_f)?;
+            self.n_gas.write_row(buf, "n_gas")?;
+            self.n_cryst.write_row(buf, "n_cryst")?;
+            self.conc.write_row(buf, "conc")?;
+            self.dg.write_row(buf, "dg")?;
+            self.tot_denergy.write_row(buf, "tot_denergy")?;
+            self.cryst_sx.write_row(buf, "cryst_sx")?;
+            self.cryst_sy.write_row(buf, "cryst_sy")?;
+            self.cryst_sz.write_row(buf, "cryst_sz")?;
+            self.mk_step.write_row(buf, "mk_step")?;
+            self.tpas_size.write_row(buf, "tpas_size")?;
+            self.tpbs_size.write_row(buf, "tpbs_size")?;
+            self.tot_denergy_err.write_row(buf, "tot_denergy_err")?;
+            self.steady_step.write_row(buf, "steady_step")?;
+            self.surf_area.write_row(buf, "surf_area")?;
+            self.sv_ratio.write_row(buf, "sv_ratio")?;
+            self.sphericity.write_row(buf, "sphericity")?;
+            self.n_exch.write_row(buf, "n_exch")?;
+            self.temp.write_row(buf, "temp")?;
+            self.rot_angle.write_row(buf, "rot_angle")?;
+            self.rot_step.write_row(buf, "rot_step")?;
+            self.inertia_aniso.write_row(buf, "inertia_aniso")?;
+            self.exch_flux.write_row(buf, "exch_flux")?;
+            self.mink_v.write_row(buf, "mink_v")?;
+            self.mink_s.write_row(buf, "mink_s")?;
+            self.mink_m.write_row(buf, "mink_m")?;
+            self.mink_chi.write_row(buf, "mink_chi")?;
+            self.front_div.write_row(buf, "front_div")?;
+            self.pass_sites.write_row(buf, "pass_sites")?;
+            self.phi.write_row(buf, "phi")?;
+            self.depletion.write_row(buf, "depletion")?;
+            self.clusters.write_row(buf, "clusters")?;
+            self.tot_denergy_surf.write_row(buf, "tot_denergy_surf")?;
+            self.tot_denergy_full.write_row(buf, "tot_denergy_full")?;
+            self.heat_t_mean.write_row(buf, "heat_t_mean")?;
+            self.heat_t_max.write_row(buf, "heat_t_max")?;
 
             buf.flush()?;
+            if self.log_decim > 0 {
+                self.write_stats_file()?;
+            }
             Ok(())
         } else {
             eprintln!("Error: Log file not initialized!");
@@ -265,3 +977,146 @@ impl SimLog {
         }
     }
 }
+
+/// Rows of `SimLog.txt` (in `SIM_LOG_ROWS` order, empty for the rows that are off) for
+/// the run directory `dir`.
+///
+/// A complete run has them in `SimLog.txt`; a log of an older build, without the row
+/// names, is taken row by row as it is. After a crash that file is empty, and the rows
+/// are rebuilt from the records of `SimLogRec.txt`: every `name=value` field is appended
+/// to its row, which gives the same layout as the final write.
+pub fn read_sim_log_rows(dir: &Path) -> IoResult<Vec<Vec<String>>> {
+    let path_log = dir.join(SIM_LOG_FILE_NAME);
+    let text = fs::read_to_string(&path_log).unwrap_or_default();
+
+    let split = |line: &str| -> Vec<String> {
+        match line.trim() {
+            "" => Vec::new(),
+            trimmed => trimmed.split(':').map(|s| s.trim().to_string()).collect(),
+        }
+    };
+    let row_id = |name: &str| SIM_LOG_ROWS.iter().position(|&n| n == name.trim());
+
+    let is_keyed = text
+        .lines()
+        .any(|line| line.split(':').next().and_then(row_id).is_some());
+    if !is_keyed && !text.trim().is_empty() {
+        return Ok(text.lines().map(split).collect());
+    }
+
+    if is_keyed {
+        let mut rows: Vec<Vec<String>> = vec![Vec::new(); SIM_LOG_ROWS.len()];
+        for (line_num, line) in text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+        {
+            let mut fields = split(line);
+            let name = fields.remove(0);
+            let row_id = row_id(&name).ok_or_else(|| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} (line {}): unknown row '{}'",
+                        path_log.display(),
+                        line_num + 1,
+                        name
+                    ),
+                )
+            })?;
+            rows[row_id] = fields.into_iter().filter(|f| !f.is_empty()).collect();
+        }
+        return Ok(rows);
+    }
+
+    let path_rec = dir.join(SIM_LOG_REC_FILE_NAME);
+    let text = fs::read_to_string(&path_rec).map_err(|e| {
+        IoError::new(
+            e.kind(),
+            format!(
+                "'{}' is empty and '{}' is unreadable: {}",
+                path_log.display(),
+                path_rec.display(),
+                e
+            ),
+        )
+    })?;
+
+    let mut rows: Vec<Vec<String>> = vec![Vec::new(); SIM_LOG_ROWS.len()];
+    for (line_num, line) in text.lines().enumerate() {
+        for field in line.split(':').filter(|f| !f.trim().is_empty()) {
+            let (row_id, value) = field
+                .split_once('=')
+                .and_then(|(name, value)| {
+                    let row_id = SIM_LOG_ROWS.iter().position(|&n| n == name.trim())?;
+                    Some((row_id, value.trim().to_string()))
+                })
+                .ok_or_else(|| {
+                    IoError::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{} (line {}): invalid field '{}'",
+                            path_rec.display(),
+                            line_num + 1,
+                            field
+                        ),
+                    )
+                })?;
+            rows[row_id].push(value);
+        }
+    }
+
+    Ok(rows)
+}
+
+/// `SimLog.txt` text of `rows` (in `SIM_LOG_ROWS` order), without the empty rows.
+pub fn sim_log_text(rows: &[Vec<String>]) -> String {
+    SIM_LOG_ROWS
+        .iter()
+        .zip(rows)
+        .filter(|(_, row)| !row.is_empty())
+        .map(|(name, row)| format!("{}:{}\n", name, row.join(":")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sim_log_rows_are_keyed_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("model_1_002_simlog_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut simlog = SimLog::new();
+        simlog.create_out_file(dir.clone()).unwrap();
+        for step in [1, 2] {
+            simlog.n_cryst.val = step as f64;
+            simlog.mk_step.val = step;
+            simlog.add_log_point();
+        }
+        simlog.write_log_to_file().unwrap();
+
+        // Only the rows that are on, each under its name; `n_gas` is off by default.
+        let text = fs::read_to_string(dir.join(SIM_LOG_FILE_NAME)).unwrap();
+        assert!(text.lines().any(|l| l == "mk_step:1:2"));
+        assert!(!text.lines().any(|l| l.starts_with("n_gas")));
+        assert!(text.lines().all(|l| l.contains(':')));
+
+        let row = |rows: &[Vec<String>], name| {
+            rows[SIM_LOG_ROWS.iter().position(|&n| n == name).unwrap()].clone()
+        };
+        let rows = read_sim_log_rows(&dir).unwrap();
+        assert_eq!(rows.len(), SIM_LOG_ROWS.len());
+        assert_eq!(row(&rows, "mk_step"), ["1", "2"]);
+        assert!(row(&rows, "n_gas").is_empty());
+        assert_eq!(sim_log_text(&rows), text);
+
+        // A log of an older build: positional rows, taken as they are.
+        fs::write(dir.join(SIM_LOG_FILE_NAME), "\n1:2\n").unwrap();
+        let rows = read_sim_log_rows(&dir).unwrap();
+        assert_eq!(rows, [vec![], vec!["1".to_string(), "2".to_string()]]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
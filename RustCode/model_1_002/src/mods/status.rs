@@ -0,0 +1,266 @@
+use crate::mods::{constants::RUN_META_FILE_NAME, state::SimLog};
+use std::{
+    fs,
+    io::{ErrorKind, Read, Result as IoResult, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Pause of the accept loop between polls for a connection or the stop flag.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest request head read.
+const REQUEST_MAX_LEN: usize = 8192;
+
+/// `StatusAddr` as a socket address; `None` when off (empty or `none`) or malformed.
+pub fn parse_addr(s: &str) -> Option<SocketAddr> {
+    let s = s.trim();
+    if s.is_empty() || s.eq_ignore_ascii_case("none") {
+        return None;
+    }
+
+    s.parse().ok()
+}
+
+/// State of a run as served by the status endpoint.
+#[derive(Debug, Clone)]
+struct RunStatus {
+    run_id: String,
+    state: &'static str,
+    first_step: u64,
+    step: u64,
+    step_lim: u64,
+    items_alive: usize,
+    items_total: usize,
+    n_cryst: f64,
+    conc: f64,
+    dg: f64,
+    temperature: f64,
+    started: Instant,
+}
+
+impl RunStatus {
+    fn to_json(&self) -> String {
+        let done = self.step.saturating_sub(self.first_step.saturating_sub(1));
+        let total = self
+            .step_lim
+            .saturating_sub(self.first_step.saturating_sub(1));
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let eta = match (self.state, done) {
+            ("running", 0) => None,
+            ("running", done) => Some(elapsed / done as f64 * total.saturating_sub(done) as f64),
+            _ => Some(0.0),
+        };
+        let percent = match total {
+            0 => 100.0,
+            total => 100.0 * done.min(total) as f64 / total as f64,
+        };
+
+        format!(
+            "{{\"run_id\":\"{}\",\"state\":\"{}\",\"step\":{},\"step_lim\":{},\"percent\":{},\"elapsed_s\":{},\"eta_s\":{},\"items_alive\":{},\"items_total\":{},\"n_cryst\":{},\"conc\":{},\"dg\":{},\"temperature\":{}}}",
+            json_escape(&self.run_id),
+            self.state,
+            self.step,
+            self.step_lim,
+            json_number(percent),
+            json_number(elapsed),
+            eta.map_or("null".to_string(), json_number),
+            self.items_alive,
+            self.items_total,
+            json_number(self.n_cryst),
+            json_number(self.conc),
+            json_number(self.dg),
+            json_number(self.temperature)
+        )
+    }
+}
+
+/// Read-only HTTP status endpoint of a run (`StatusAddr`): `GET /` or `GET /status`
+/// returns the run id, step, percent complete, ETA and the ensemble observables as JSON,
+/// for dashboards scraping many running simulations. The bound address (port 0 - chosen
+/// by the system) goes to `RunMeta.txt` of the run. Served from its own thread until the
+/// value is dropped.
+#[derive(Debug)]
+pub struct StatusServer {
+    pub addr: SocketAddr,
+    status: Arc<Mutex<RunStatus>>,
+    is_stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    /// Binds `addr` and writes `RunMeta.txt` into `dst_path`.
+    pub fn start(
+        addr: SocketAddr,
+        dst_path: &Path,
+        first_step: u64,
+        step_lim: u64,
+        items_total: usize,
+    ) -> IoResult<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let run_id = dst_path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        fs::write(
+            dst_path.join(RUN_META_FILE_NAME),
+            format!(
+                "run_id: {}\npid: {}\nstatus_addr: {}\nstatus_url: http://{}/status\n",
+                run_id,
+                std::process::id(),
+                addr,
+                addr
+            ),
+        )?;
+
+        let status = Arc::new(Mutex::new(RunStatus {
+            run_id,
+            state: "running",
+            first_step,
+            step: first_step.saturating_sub(1),
+            step_lim,
+            items_alive: items_total,
+            items_total,
+            n_cryst: 0.0,
+            conc: 0.0,
+            dg: 0.0,
+            temperature: 0.0,
+            started: Instant::now(),
+        }));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+
+        let thread_status = Arc::clone(&status);
+        let thread_stopped = Arc::clone(&is_stopped);
+        let thread = thread::Builder::new()
+            .name("status-server".to_string())
+            .spawn(move || serve(listener, &thread_status, &thread_stopped))?;
+
+        Ok(Self {
+            addr,
+            status,
+            is_stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Records the state of the run after `step_id`.
+    pub fn update(&self, step_id: u64, step_lim: u64, items_alive: usize, simlog: &SimLog) {
+        if let Ok(mut status) = self.status.lock() {
+            status.step = step_id;
+            status.step_lim = step_lim;
+            status.items_alive = items_alive;
+            status.n_cryst = simlog.n_cryst.val;
+            status.conc = simlog.conc.val;
+            status.dg = simlog.dg.val;
+            status.temperature = simlog.temp.val;
+        }
+    }
+
+    /// Marks the run as ended (`state` - its termination).
+    pub fn finish(&self, state: &'static str) {
+        if let Ok(mut status) = self.status.lock() {
+            status.state = state;
+        }
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve(listener: TcpListener, status: &Mutex<RunStatus>, is_stopped: &AtomicBool) {
+    while !is_stopped.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = respond(stream, status) {
+                    eprintln!("⚠️ Warning: Status endpoint: {e}");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(e) => {
+                eprintln!("⚠️ Warning: Status endpoint stopped: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Answers one request; the connection is closed after the response.
+fn respond(mut stream: TcpStream, status: &Mutex<RunStatus>) -> IoResult<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < REQUEST_MAX_LEN {
+        match stream.read(&mut buf)? {
+            0 => break,
+            n => head.extend_from_slice(&buf[..n]),
+        }
+    }
+    let request = String::from_utf8_lossy(&head);
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (code, body) = match (method, path.split('?').next().unwrap_or("")) {
+        ("GET" | "HEAD", "/" | "/status") => (
+            "200 OK",
+            status
+                .lock()
+                .map_or("{}".to_string(), |status| status.to_json()),
+        ),
+        ("GET" | "HEAD", _) => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "{\"error\":\"method not allowed\"}".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        code,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+/// A finite number as JSON (exponent form below 1e-3, e.g. `conc` and `dg`), `null`
+/// otherwise.
+fn json_number(value: f64) -> String {
+    match value.is_finite() {
+        true if value != 0.0 && value.abs() < 1e-3 => format!("{:e}", value),
+        true => format!("{}", value),
+        false => "null".to_string(),
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+/// Sliding-window steady-state detector.
+///
+/// Collects `(step, value)` samples at write steps and, every `window` samples,
+/// fits a least-squares line through the last `window` points. A window is
+/// "flat" when the fitted change over the window, relative to the window mean,
+/// stays below `tol`. Steady state is reported after `windows_req` consecutive
+/// flat windows.
+#[derive(Debug)]
+pub struct SteadyStateDetector {
+    pub is_on: bool,
+    pub window: usize,
+    pub tol: f64,
+    pub windows_req: usize,
+    pub detected_step: Option<u64>,
+    samples: VecDeque<(f64, f64)>,
+    since_check: usize,
+    flat_count: usize,
+}
+
+impl SteadyStateDetector {
+    pub fn new(window: usize, tol: f64, windows_req: usize) -> Self {
+        Self {
+            is_on: window > 1,
+            window,
+            tol,
+            windows_req: windows_req.max(1),
+            detected_step: None,
+            samples: VecDeque::with_capacity(window),
+            since_check: 0,
+            flat_count: 0,
+        }
+    }
+
+    /// Adds a sample and returns `true` only on the step where steady state is first detected.
+    pub fn update(&mut self, step_id: u64, value: f64) -> bool {
+        if !self.is_on || self.detected_step.is_some() {
+            return false;
+        }
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((step_id as f64, value));
+        self.since_check += 1;
+
+        if self.samples.len() < self.window || self.since_check < self.window {
+            return false;
+        }
+        self.since_check = 0;
+
+        match self.relative_change() <= self.tol {
+            true => self.flat_count += 1,
+            false => self.flat_count = 0,
+        }
+
+        if self.flat_count >= self.windows_req {
+            self.detected_step = Some(step_id);
+            return true;
+        }
+
+        false
+    }
+
+    fn relative_change(&self) -> f64 {
+        let n = self.samples.len() as f64;
+        let (sum_x, sum_y) = self
+            .samples
+            .iter()
+            .fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+        let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+
+        let (mut sxy, mut sxx) = (0.0, 0.0);
+        for &(x, y) in &self.samples {
+            sxy += (x - mean_x) * (y - mean_y);
+            sxx += (x - mean_x) * (x - mean_x);
+        }
+
+        if sxx == 0.0 {
+            return 0.0;
+        }
+
+        let span = self.samples.back().unwrap().0 - self.samples.front().unwrap().0;
+        (sxy / sxx * span).abs() / mean_y.abs().max(1.0)
+    }
+}
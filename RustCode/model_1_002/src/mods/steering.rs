@@ -0,0 +1,77 @@
+use std::{fs, path::PathBuf};
+
+/// Commands read from the control file during one poll.
+///
+/// Within a step they are applied in a fixed priority order: the new `PrintI` at once,
+/// `write` on the same step (as a regular write step), `checkpoint` after all items
+/// have moved, and `stop` last, so a file with several commands saves data before the
+/// run ends.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SteerActions {
+    pub stop: bool,
+    pub checkpoint: bool,
+    pub write: bool,
+    pub print_i: Option<u64>,
+}
+
+/// Steering of a running simulation through a `control` file in its output directory.
+///
+/// Every `every` steps the file is read and removed, so each command is applied once.
+/// One command per line: `stop`, `checkpoint`, `write`, `PrintI <n>` (or `PrintI:<n>`).
+#[derive(Debug)]
+pub struct Steering {
+    pub is_on: bool,
+    path: PathBuf,
+    every: u64,
+}
+
+impl Steering {
+    pub fn new(path: PathBuf, every: u64) -> Self {
+        Self {
+            is_on: every > 0,
+            path,
+            every,
+        }
+    }
+
+    pub fn poll(&mut self, step_id: u64) -> SteerActions {
+        let mut actions = SteerActions::default();
+        if !self.is_on || !step_id.is_multiple_of(self.every) {
+            return actions;
+        }
+
+        let Ok(text) = fs::read_to_string(&self.path) else {
+            return actions;
+        };
+        if let Err(e) = fs::remove_file(&self.path) {
+            eprintln!(
+                "⚠️ Warning: Failed to remove '{}': {}",
+                self.path.display(),
+                e
+            );
+        }
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let mut parts = line.splitn(2, [':', ' ']);
+            let command = parts.next().unwrap_or("").to_ascii_lowercase();
+            let value = parts.next().unwrap_or("").trim();
+
+            match command.as_str() {
+                "stop" => actions.stop = true,
+                "checkpoint" | "checkpoint-now" => actions.checkpoint = true,
+                "write" | "write-now" => actions.write = true,
+                "printi" => match value.parse::<u64>() {
+                    Ok(print_i) => actions.print_i = Some(print_i),
+                    Err(_) => eprintln!(
+                        "⚠️ Warning: Invalid PrintI value in control file: '{}'",
+                        line
+                    ),
+                },
+                _ => eprintln!("⚠️ Warning: Unknown control command: '{}'", line),
+            }
+        }
+
+        println!("Step: {} -> Control file: {:?}", step_id, actions);
+        actions
+    }
+}
@@ -0,0 +1,110 @@
+/// Step limit that moves on while the crystals still grow (`StepLimMax`).
+///
+/// The `pct` percentile of the crystal sizes of the alive items is taken at the first
+/// step within `window` steps of the limit and at the limit itself. If it grew by at
+/// least `rate` cells per step in between, the limit moves `window` steps further, up to
+/// `cap`, and the next check compares against the size at the old limit. Otherwise (or
+/// at `cap`) the run ends at the limit, as with a plain `StepLim`.
+///
+/// `window`, `pct` and `rate` are `ExtendWindow`, `ExtendPct` and `ExtendRate`. Every
+/// move is announced on the console and the final limit is the `step_lim` row of
+/// `Summary.txt`. Not available with `Ranks > 1`.
+#[derive(Debug)]
+pub struct StepLimExtender {
+    pub is_on: bool,
+    /// Step limit in effect.
+    pub limit: u64,
+    pub limit0: u64,
+    cap: u64,
+    rate: f64,
+    pct: f64,
+    window: u64,
+    /// Step and percentile size at the start of the measured window.
+    mark: Option<(u64, f64)>,
+    /// Growth rate of the last check, cells per step.
+    pub last_rate: f64,
+    pub n_extensions: u32,
+}
+
+impl StepLimExtender {
+    pub fn new(step_lim: u64, cap: u64, rate: f64, pct: f64, window: u64) -> Self {
+        Self {
+            is_on: cap > step_lim,
+            limit: step_lim,
+            limit0: step_lim,
+            cap,
+            rate,
+            pct,
+            window: match window {
+                0 => (step_lim / 10).max(1),
+                window => window,
+            },
+            mark: None,
+            last_rate: 0.0,
+            n_extensions: 0,
+        }
+    }
+
+    /// Last step the run loop may reach.
+    pub fn max_step(&self) -> u64 {
+        match self.is_on {
+            true => self.cap,
+            false => self.limit,
+        }
+    }
+
+    /// Checks the run after `step_id`; `sizes` - crystal sizes of the alive items, called
+    /// only on the measured steps. Returns `true` when the run ends at this step.
+    pub fn update(&mut self, step_id: u64, sizes: impl FnOnce() -> Vec<f64>) -> bool {
+        if !self.is_on {
+            return false;
+        }
+
+        let is_limit = step_id >= self.limit;
+        if self.mark.is_none() && !is_limit && step_id + self.window >= self.limit {
+            self.mark = Some((step_id, percentile(sizes(), self.pct)));
+            return false;
+        }
+        if !is_limit {
+            return false;
+        }
+
+        let size = percentile(sizes(), self.pct);
+        self.last_rate = match self.mark {
+            Some((mark_step, mark_size)) if step_id > mark_step => {
+                (size - mark_size) / (step_id - mark_step) as f64
+            }
+            _ => 0.0,
+        };
+        if self.last_rate < self.rate {
+            return true;
+        }
+        if self.limit >= self.cap {
+            println!(
+                "⚠️ Step: {} -> StepLimMax ({}) reached while the crystals still grow ({:.3e} cells/step).",
+                step_id, self.cap, self.last_rate
+            );
+            return true;
+        }
+
+        self.limit = (self.limit + self.window).min(self.cap);
+        self.mark = Some((step_id, size));
+        self.n_extensions += 1;
+        println!(
+            "📒 Step: {} -> The crystals still grow ({:.3e} cells/step at the {}th percentile); StepLim extended to {}.",
+            step_id, self.last_rate, self.pct, self.limit
+        );
+        false
+    }
+}
+
+/// Nearest-rank `pct` percentile of `values`; 0 for none.
+fn percentile(mut values: Vec<f64>, pct: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.sort_by(f64::total_cmp);
+    let rank = (pct / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
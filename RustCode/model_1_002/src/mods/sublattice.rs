@@ -0,0 +1,58 @@
+use crate::mods::lattice::Grid;
+
+/// Sub-lattice masking of the kernel events (`SubLattice`): the cells are split into `n`
+/// classes by `(x + y + z) mod n` and at step `s` only the cells of class `s mod n` take
+/// events, the others are skipped. Neighbouring cells are never in the same class (for
+/// `n >= 2`, up to the periodic boundary of an axis whose size is not a multiple of `n`),
+/// so the events of a step do not interact - the update order of the checkerboard
+/// kernels.
+///
+/// The mask covers attachments, detachments, the ballistic channel and both cells of a
+/// swap; the skipped attempts are counted and printed at the end. A periodic axis whose
+/// size is not a multiple of `n` is warned about.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubLattice {
+    #[default]
+    Off,
+    /// Number of classes, `parity` - 2.
+    Stride(usize),
+}
+
+impl SubLattice {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Some(Self::Off),
+            "parity" => Some(Self::Stride(2)),
+            s => {
+                let n = s.strip_prefix("stride:")?.trim().parse().ok()?;
+                (n >= 2).then_some(Self::Stride(n))
+            }
+        }
+    }
+
+    /// Whether events on the cell `idxg` are allowed at `step_id`.
+    #[inline(always)]
+    pub fn allows(self, grid: &Grid, idxg: usize, step_id: u64) -> bool {
+        match self {
+            Self::Off => true,
+            Self::Stride(n) => {
+                let (x, y, z) = grid.idx_to_xyz(idxg);
+                (x + y + z) % n == (step_id % n as u64) as usize
+            }
+        }
+    }
+
+    /// Periodic axes (0 - x, 1 - y, 2 - z) of `grid` whose size is not a multiple of the
+    /// stride: across their boundary neighbours share a class.
+    pub fn broken_axes(self, grid: &Grid) -> Vec<usize> {
+        match self {
+            Self::Off => Vec::new(),
+            Self::Stride(n) => [(grid.nx, grid.px), (grid.ny, grid.py), (grid.nz, grid.pz)]
+                .iter()
+                .enumerate()
+                .filter(|(_, (size, periodic))| *periodic && size % n != 0)
+                .map(|(axis, _)| axis)
+                .collect(),
+        }
+    }
+}
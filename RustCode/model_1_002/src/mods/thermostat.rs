@@ -0,0 +1,56 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+/// Ornstein-Uhlenbeck temperature noise.
+///
+/// `T` relaxes towards `t_mean` with correlation time `tau` (steps) and fluctuates with
+/// the stationary standard deviation `sigma` (K). The process is sampled exactly every
+/// `every` steps: `T' = t_mean + (T - t_mean) * d + sigma * sqrt(1 - d^2) * N(0, 1)`,
+/// `d = exp(-every / tau)`. `C_eq` is kept fixed, only `kT` follows `T`.
+#[derive(Debug)]
+pub struct OuThermostat {
+    pub is_on: bool,
+    pub temperature: f64,
+    t_mean: f64,
+    decay: f64,
+    kick: f64,
+    every: u64,
+}
+
+impl OuThermostat {
+    pub fn new(t_mean: f64, sigma: f64, tau: f64, every: u64) -> Self {
+        let is_on = sigma > 0.0 && tau > 0.0;
+        let every = every.max(1);
+        let decay = match is_on {
+            true => (-(every as f64) / tau).exp(),
+            false => 1.0,
+        };
+
+        Self {
+            is_on,
+            temperature: t_mean,
+            t_mean,
+            decay,
+            kick: sigma * (1.0 - decay * decay).sqrt(),
+            every,
+        }
+    }
+
+    /// Advances `T` on every `every`-th step; returns `true` when it changed.
+    pub fn update(&mut self, step_id: u64, rng: &mut ChaCha8Rng) -> bool {
+        if !self.is_on || !step_id.is_multiple_of(self.every) {
+            return false;
+        }
+
+        // Box-Muller; `1 - u` keeps the logarithm finite.
+        let u1 = 1.0 - rng.random::<f64>();
+        let u2 = rng.random::<f64>();
+        let normal = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+
+        let temperature =
+            self.t_mean + (self.temperature - self.t_mean) * self.decay + self.kick * normal;
+        self.temperature = temperature.max(1e-3 * self.t_mean);
+
+        true
+    }
+}
@@ -2,36 +2,180 @@ use crate::mods::{
     constants::K_BOLTZMANN,
     frontier::Frontier,
     io_handler,
-    lattice::Grid,
+    lattice::{Grid, Idx},
     settings::{Settings, SettingsError},
     state::SimLog,
 };
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
-#[inline(always)]
-pub fn rebuild_front(states: &[u8], neibs: &[[usize; 6]], front: &mut Frontier) -> f64 {
-    let mut cluster_size = 0.0;
+pub use mc_core::utils::{compute_neighbor_sums, fill_front};
 
-    println!("Updating gas and cluster fronts...");
-    for (i, &state) in states.iter().enumerate() {
-        if state == 1 {
-            cluster_size += 1.0;
-
-            let mut has_vacancy_neighbor = false;
-
-            for neib_idx in neibs[i] {
-                if neib_idx != usize::MAX {
-                    if states[neib_idx] == 0 {
-                        has_vacancy_neighbor = true;
-                        front.tpa_add(neib_idx);
-                    }
-                }
-            }
-
-            if has_vacancy_neighbor {
-                front.tpb_add(i);
-            }
+/// Running count, mean and variance (Welford), extrema and last value of a series,
+/// kept in O(1) memory.
+#[derive(Debug, Clone, Copy)]
+pub struct RunningStats {
+    pub count: u64,
+    pub mean: f64,
+    m2: f64,
+    pub min: f64,
+    pub max: f64,
+    pub last: f64,
+}
+
+impl Default for RunningStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            last: 0.0,
         }
     }
+}
+
+impl RunningStats {
+    #[inline(always)]
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.last = x;
+    }
+
+    /// Sample standard deviation (0 for fewer than two values).
+    pub fn std(&self) -> f64 {
+        match self.count {
+            0 | 1 => 0.0,
+            n => (self.m2 / (n - 1) as f64).sqrt(),
+        }
+    }
+}
+
+/// Per-item multipliers of the initial concentration `C0`,
+/// `w_i ~ n_cryst0_i^pow * (1 + noise * U(-1, 1))`, normalised to a mean of 1.
+/// `pow = 0` and `noise = 0` give the same `C0` for every item.
+pub fn gas_weights(n_cryst0: &[f64], pow: f64, noise: f64, rng: &mut ChaCha8Rng) -> Vec<f64> {
+    let mut weights: Vec<f64> = n_cryst0
+        .iter()
+        .map(|&n| {
+            let jitter = match noise > 0.0 {
+                true => 1.0 + noise * rng.random_range(-1.0..1.0),
+                false => 1.0,
+            };
+            n.max(1.0).powf(pow) * jitter
+        })
+        .collect();
+
+    normalize_mean(&mut weights);
+    weights
+}
+
+/// Per-item multipliers of `C0` given by a `GasDist` list, normalised to a mean of 1.
+pub fn listed_gas_weights(list: &[f64]) -> Vec<f64> {
+    let mut weights = list.to_vec();
+    normalize_mean(&mut weights);
+    weights
+}
+
+fn normalize_mean(weights: &mut [f64]) {
+    let mean = weights.iter().sum::<f64>() / weights.len().max(1) as f64;
+    if mean > 0.0 {
+        weights.iter_mut().for_each(|w| *w /= mean);
+    }
+}
+
+/// Values of a `GasDist` list: positive numbers separated by commas, in item order;
+/// `None` for an invalid one, empty for `""`/`none`.
+pub fn parse_gas_dist(s: &str) -> Option<Vec<f64>> {
+    let s = s.trim();
+    if s.is_empty() || s.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+
+    s.split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+        })
+        .collect()
+}
+
+const BYTES_PER_GB: f64 = (1u64 << 30) as f64;
+
+/// Rough memory footprint of a run: the neighbour table plus, per item, the state,
+/// the frontier maps and the retained snapshot tail, plus a full snapshot queue
+/// (`WriteQueue`). The hashed frontier (`front-hashed`) grows with the front and is not
+/// counted.
+pub fn estimate_memory_bytes(
+    grid_size: usize,
+    items: usize,
+    keep_last: usize,
+    write_queue: usize,
+) -> u128 {
+    let frontier = Frontier::new_bytes(grid_size) as u128;
+    let grid_size = grid_size as u128;
+
+    let neibs = grid_size * 6 * size_of::<Idx>() as u128;
+    let item = grid_size * (1 + keep_last as u128) + frontier;
+
+    // The snapshot queue is shared by the items.
+    neibs + items as u128 * item + write_queue as u128 * grid_size
+}
+
+/// `MemAvailable` from `/proc/meminfo`; `None` where it cannot be read.
+pub fn available_memory_bytes() -> Option<u128> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: u128 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kb * 1024)
+}
+
+/// Fails if the estimated footprint of `items` items exceeds `MemBudgetGb`
+/// (0 - the available RAM, < 0 - no check).
+pub fn check_memory_budget(cfg: &Settings, items: usize) -> Result<(), SettingsError> {
+    let budget = match cfg.mem_budget_gb {
+        gb if gb < 0.0 => None,
+        gb if gb > 0.0 => Some((gb * BYTES_PER_GB) as u128),
+        _ => available_memory_bytes(),
+    };
+    let Some(budget) = budget else {
+        return Ok(());
+    };
+
+    let estimate = estimate_memory_bytes(
+        cfg.sx * cfg.sy * cfg.sz,
+        items,
+        cfg.item_keep_last,
+        cfg.write_queue,
+    );
+    if estimate > budget {
+        return Err(SettingsError::simple(
+            "MemBudgetGb",
+            format!(
+                "estimated memory {:.2} GB for {} item(s) exceeds the budget of {:.2} GB",
+                estimate as f64 / BYTES_PER_GB,
+                items,
+                budget as f64 / BYTES_PER_GB
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn rebuild_front(states: &[u8], neibs: &[[Idx; 6]], front: &mut Frontier) -> f64 {
+    println!("Updating gas and cluster fronts...");
+    let cluster_size = fill_front(states, neibs, front);
     println!(
         "Update completed! Gas front nodes: {}, Cluster front nodes: {}",
         front.tpas_size, front.tpbs_size,
@@ -40,28 +184,75 @@ pub fn rebuild_front(states: &[u8], neibs: &[[usize; 6]], front: &mut Frontier)
     cluster_size
 }
 
+/// Sites in exactly one of the two frontiers, TPA and TPB counted separately.
+pub fn front_divergence(a: &Frontier, b: &Frontier) -> usize {
+    let only_in = |x: &Frontier, y: &Frontier| {
+        x.tpas.iter().filter(|&&idxg| !y.is_tpa(idxg)).count()
+            + x.tpbs.iter().filter(|&&idxg| !y.is_tpb(idxg)).count()
+    };
+    only_in(a, b) + only_in(b, a)
+}
+
+/// Face-family bits of `Settings::face_mask`: {100}, {010}, {001}. `is_face_masked`
+/// expects them moved to grid axes with `Grid::mask_to_grid_axes`.
+pub const FACE_100: u8 = 0b001;
+pub const FACE_010: u8 = 0b010;
+pub const FACE_001: u8 = 0b100;
+
+/// Returns `true` if every interface normal of the site belongs to a frozen face family.
+/// For a gas site (`attach`) normals point to crystal neighbours, for a crystal site
+/// they point to the missing ones.
+#[inline(always)]
+pub fn is_face_masked(face_mask: u8, (smx_yz, smy_xz, smz_xy): (u8, u8, u8), attach: bool) -> bool {
+    if face_mask == 0 {
+        return false;
+    }
+
+    let is_open = |sm: u8| if attach { sm > 0 } else { sm < 2 };
+    let normals = ((is_open(smx_yz) as u8) * FACE_100)
+        | ((is_open(smy_xz) as u8) * FACE_010)
+        | ((is_open(smz_xy) as u8) * FACE_001);
+
+    normals != 0 && (normals & !face_mask) == 0
+}
+
+/// Acceptance energy of attachment under Kossel bond-counting kinetics (`DetachModel:
+/// bonds`): independent of the site, set so that a kink site (one bond per axis) is in
+/// detailed balance with `kossel_detach_energy`.
 #[inline(always)]
-pub fn compute_neighbor_sums(states: &[u8], idxg_nis: &[usize; 6]) -> (u8, u8, u8) {
-    let mut x_axis_neighbors = 0;
-    let mut y_axis_neighbors = 0;
-    let mut z_axis_neighbors = 0;
-
-    for i in 0..6 {
-        let idx = unsafe { *idxg_nis.get_unchecked(i) };
-        if idx != usize::MAX {
-            let state = unsafe { *states.get_unchecked(idx) };
-            if state == 1 {
-                match i {
-                    0 | 1 => x_axis_neighbors += 1,
-                    2 | 3 => y_axis_neighbors += 1,
-                    4 | 5 => z_axis_neighbors += 1,
-                    _ => unreachable!(), // Цикл всегда от 0 до 5
-                }
-            }
-        }
+pub fn kossel_attach_energy((ex2, ey2, ez2): (f64, f64, f64), dg: f64) -> f64 {
+    ex2 + ey2 + ez2 - ex2.min(ey2).min(ez2) - dg
+}
+
+/// Acceptance energy of detachment under Kossel bond-counting kinetics: the bond energy
+/// `Σ n_a·E_a` of the removed particle (`E_a` - bond along axis `a`, equal to the
+/// energy of the two faces it creates), shifted by the weakest bond so that a particle
+/// with a single bond always detaches.
+#[inline(always)]
+pub fn kossel_detach_energy(
+    (smx_yz, smy_xz, smz_xy): (u8, u8, u8),
+    (ex2, ey2, ez2): (f64, f64, f64),
+) -> f64 {
+    smx_yz as f64 * ex2 + smy_xz as f64 * ey2 + smz_xy as f64 * ez2 - ex2.min(ey2).min(ez2)
+}
+
+/// Attempts of an event type on one step: exactly one for `mean <= 0`, otherwise a
+/// Poisson(`mean`) count (Knuth's product of uniforms, `mean + 1` draws on average;
+/// `mean` up to `POISSON_MEAN_MAX` keeps `exp(-mean)` well above underflow).
+#[inline(always)]
+pub fn event_attempts<R: Rng>(rng: &mut R, mean: f64) -> u32 {
+    if mean <= 0.0 {
+        return 1;
     }
 
-    (x_axis_neighbors, y_axis_neighbors, z_axis_neighbors)
+    let limit = (-mean).exp();
+    let mut n = 0;
+    let mut p = rng.random::<f64>();
+    while p > limit {
+        n += 1;
+        p *= rng.random::<f64>();
+    }
+    n
 }
 
 // #[inline(always)]
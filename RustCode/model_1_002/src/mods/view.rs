@@ -0,0 +1,86 @@
+use crate::mods::{item::Item, lattice::Grid};
+use std::fmt;
+
+/// Borrowed view of the current state of an item, for library users computing their own
+/// observables without copying the grid: the occupancy in the state file layout
+/// (`idx = z + y·nz + x·ny·nz`, 1 - crystal) with the grid size, and the two fronts -
+/// gas cells next to the crystal (`tpas`) and crystal cells next to the gas (`tpbs`),
+/// in no particular order.
+#[derive(Debug, Clone, Copy)]
+pub struct StateView<'a> {
+    pub item_gid: usize,
+    /// `false` for an item that stopped at this step.
+    pub is_alive: bool,
+    /// `(nx, ny, nz)`.
+    pub dims: (usize, usize, usize),
+    pub state: &'a [u8],
+    pub tpas: &'a [usize],
+    pub tpbs: &'a [usize],
+}
+
+impl<'a> StateView<'a> {
+    pub fn new(item: &'a Item, grid: &Grid) -> Self {
+        Self {
+            item_gid: item.item_gid,
+            is_alive: item.is_alive,
+            dims: (grid.nx, grid.ny, grid.nz),
+            state: &item.state,
+            tpas: &item.front.tpas,
+            tpbs: &item.front.tpbs,
+        }
+    }
+
+    #[inline(always)]
+    pub fn xyz_to_idx(&self, x: usize, y: usize, z: usize) -> usize {
+        z + (y + x * self.dims.1) * self.dims.2
+    }
+
+    #[inline(always)]
+    pub fn idx_to_xyz(&self, idx: usize) -> (usize, usize, usize) {
+        let (_, ny, nz) = self.dims;
+        (idx / (ny * nz), (idx / nz) % ny, idx % nz)
+    }
+
+    /// State of the cell `(x, y, z)`.
+    #[inline(always)]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.state[self.xyz_to_idx(x, y, z)]
+    }
+}
+
+/// Views of `items`, each on its own grid (`grid` or `item_grids[grid_id - 1]`).
+pub fn views<'a>(grid: &Grid, item_grids: &[Grid], items: &'a [Item]) -> Vec<StateView<'a>> {
+    items
+        .iter()
+        .map(|item| {
+            let grid = match item.grid_id {
+                0 => grid,
+                id => &item_grids[id - 1],
+            };
+            StateView::new(item, grid)
+        })
+        .collect()
+}
+
+type WriteFn = Box<dyn FnMut(u64, &[StateView<'_>]) + Send>;
+
+/// Callback of a library user, called on every write step of `Ensemble::run_simulation`
+/// with the step and the views of the local items; the views are valid only inside the
+/// call.
+pub struct WriteObserver(WriteFn);
+
+impl WriteObserver {
+    pub fn new(f: impl FnMut(u64, &[StateView<'_>]) + Send + 'static) -> Self {
+        Self(Box::new(f))
+    }
+
+    pub fn call(&mut self, step_id: u64, views: &[StateView<'_>]) {
+        (self.0)(step_id, views)
+    }
+}
+
+impl fmt::Debug for WriteObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WriteObserver")
+    }
+}
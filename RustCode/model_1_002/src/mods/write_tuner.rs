@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+/// Write steps per measurement window.
+const WINDOW_WRITES: u32 = 8;
+
+/// Write interval that backs off when writing takes too much of the run (`WriteIoPct`).
+///
+/// The time the items spend in their write actions (snapshots, profiles, the logged
+/// measurements) is summed over a window of `WINDOW_WRITES` write steps and compared
+/// with the wall-clock time of the window. Above `share_max` the interval is doubled, up
+/// to `write_i_max`, so the new write steps are a subset of the old ones. The interval
+/// never shrinks back; the user `WriteI` is the lower bound.
+///
+/// Every doubling is announced on the console and the final interval is the `write_i`
+/// row of `Summary.txt`. Not available with `ItemWriteI > 0`.
+#[derive(Debug)]
+pub struct WriteTuner {
+    pub is_on: bool,
+    pub write_i: u64,
+    pub write_i0: u64,
+    write_i_max: u64,
+    /// Largest write time per wall-clock time, 0..1.
+    share_max: f64,
+    window_io: Duration,
+    window_writes: u32,
+    window_start: Instant,
+    /// Write time share of the last window.
+    pub last_share: f64,
+    pub n_adjustments: u32,
+}
+
+impl WriteTuner {
+    pub fn new(write_i: u64, io_pct: f64, write_i_max: u64) -> Self {
+        Self {
+            is_on: io_pct > 0.0 && write_i > 0,
+            write_i,
+            write_i0: write_i,
+            write_i_max,
+            share_max: io_pct / 100.0,
+            window_io: Duration::ZERO,
+            window_writes: 0,
+            window_start: Instant::now(),
+            last_share: 0.0,
+            n_adjustments: 0,
+        }
+    }
+
+    /// Starts the first window at the first step, after the initialization.
+    pub fn start(&mut self) {
+        self.window_start = Instant::now();
+    }
+
+    pub fn is_write_step(&self, step_id: u64) -> bool {
+        self.write_i > 0 && step_id.is_multiple_of(self.write_i)
+    }
+
+    /// Adds the write time `io` of the write step `step_id`; at the end of a window
+    /// doubles the interval if the share is exceeded.
+    pub fn update(&mut self, step_id: u64, io: Duration) {
+        if !self.is_on {
+            return;
+        }
+
+        self.window_io += io;
+        self.window_writes += 1;
+        if self.window_writes < WINDOW_WRITES {
+            return;
+        }
+
+        let wall = self.window_start.elapsed().as_secs_f64();
+        self.last_share = match wall > 0.0 {
+            true => (self.window_io.as_secs_f64() / wall).min(1.0),
+            false => 0.0,
+        };
+        self.window_io = Duration::ZERO;
+        self.window_writes = 0;
+        self.window_start = Instant::now();
+
+        if self.last_share <= self.share_max || self.write_i * 2 > self.write_i_max {
+            return;
+        }
+
+        let write_i_old = self.write_i;
+        self.write_i *= 2;
+        self.n_adjustments += 1;
+        println!(
+            "Step: {} -> Writing took {:.0}% of the wall time (WriteIoPct: {}); WriteI {} -> {}.",
+            step_id,
+            self.last_share * 100.0,
+            self.share_max * 100.0,
+            write_i_old,
+            self.write_i
+        );
+        if self.write_i * 2 > self.write_i_max {
+            println!(
+                "Step: {} -> WriteI can grow no further (WriteIMax: {}).",
+                step_id, self.write_i_max
+            );
+        }
+    }
+}
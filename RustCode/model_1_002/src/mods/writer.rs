@@ -0,0 +1,533 @@
+//! Background writing of the item snapshots (`WriteQueue > 0`).
+//!
+//! Two levels of buffering: the items hand their frames to a queue bounded to
+//! `WriteQueue` frames (memory `<= WriteQueue * frame size`), a writer thread empties it
+//! into one buffered file per item. What happens when the queue is full is set by
+//! `QueueFull`: `block` waits for the writer, `drop` skips the snapshot, `abort` skips it
+//! and stops the run. Final snapshots always wait.
+//!
+//! With `SnapCompress:true` the thread stores each frame XOR the previous frame of the
+//! item, zstd-compressed, in `TimeStates.zst` (`TimeStates.txt` then keeps the header
+//! lines only); `convert --format txt` restores the text frames. `SnapDual:true` keeps
+//! writing the text frames as well, and the pair is compared at the end of the run
+//! (`compare_dual`).
+
+use crate::mods::{
+    constants::{
+        BIN_FORMAT_VERSION, FRAME_END_TAG, TIME_STATES_FILE_NAME, TIME_STATES_ZST_FILE_NAME,
+        TIME_STATES_ZST_MAGIC,
+    },
+    faults::{self, FaultPoint},
+    io_handler,
+};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{
+        BufRead, BufReader, BufWriter, Error as IoError, ErrorKind, Read, Result as IoResult, Write,
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+};
+
+/// zstd level of `TimeStates.zst`; the deltas of consecutive frames are mostly zeros,
+/// higher levels gain little and cost the writer its lead.
+const SNAPSHOT_ZSTD_LEVEL: i32 = 3;
+/// `#end`, a space, 16 hex digits and the newline.
+const FRAME_END_LINE_LEN: usize = FRAME_END_TAG.len() + 18;
+
+/// Policy for a snapshot that finds the queue full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFull {
+    Block,
+    Drop,
+    Abort,
+}
+
+impl QueueFull {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "block" => Some(Self::Block),
+            "drop" => Some(Self::Drop),
+            "abort" => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// Files the thread writes the frames to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapFormat {
+    /// `TimeStates.txt`.
+    Text,
+    /// `TimeStates.zst` (`SnapCompress`).
+    Packed,
+    /// Both (`SnapDual`).
+    Dual,
+}
+
+impl SnapFormat {
+    pub fn new(is_compressed: bool, is_dual: bool) -> Self {
+        match (is_compressed, is_dual) {
+            (false, _) => Self::Text,
+            (true, false) => Self::Packed,
+            (true, true) => Self::Dual,
+        }
+    }
+}
+
+/// Queue depth and throughput of the writer, shared by the items and the thread. The
+/// depth is the frames sent but not yet taken by the thread.
+#[derive(Debug, Default)]
+pub struct WriterMetrics {
+    sent: AtomicUsize,
+    received: AtomicUsize,
+    sent_bytes: AtomicUsize,
+    received_bytes: AtomicUsize,
+    peak_depth: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    raw_bytes: AtomicU64,
+    out_bytes: AtomicU64,
+    is_aborted: AtomicBool,
+}
+
+impl WriterMetrics {
+    fn on_sent(&self, bytes: usize) {
+        let sent = self.sent.fetch_add(1, Ordering::Relaxed) + 1;
+        let sent_bytes = self.sent_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let depth = sent.saturating_sub(self.received.load(Ordering::Relaxed));
+        let queued = sent_bytes.saturating_sub(self.received_bytes.load(Ordering::Relaxed));
+        self.peak_depth.fetch_max(depth, Ordering::Relaxed);
+        self.peak_bytes.fetch_max(queued, Ordering::Relaxed);
+    }
+
+    fn on_received(&self, bytes: usize) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.received_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn depth(&self) -> usize {
+        let received = self.received.load(Ordering::Relaxed);
+        self.sent.load(Ordering::Relaxed).saturating_sub(received)
+    }
+
+    pub fn peak_depth(&self) -> usize {
+        self.peak_depth.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+struct Frame {
+    item_gid: usize,
+    path_time_states: PathBuf,
+    step_id: u64,
+    values: Box<[u8]>,
+}
+
+enum Job {
+    Frame(Frame),
+    /// The item wrote its final snapshot: flush and close its file.
+    Close(usize),
+    Finish,
+}
+
+/// Sending side of the queue, one clone per item.
+#[derive(Debug, Clone)]
+pub struct WriterHandle {
+    tx: SyncSender<Job>,
+    policy: QueueFull,
+    metrics: Arc<WriterMetrics>,
+}
+
+impl WriterHandle {
+    /// Queues a snapshot of step `step_id`; `false` if it was dropped (full queue under
+    /// `drop`/`abort`, or the writer is gone). `is_final` snapshots wait for room.
+    pub fn send(
+        &self,
+        item_gid: usize,
+        path_time_states: &Path,
+        step_id: u64,
+        values: Box<[u8]>,
+        is_final: bool,
+    ) -> bool {
+        let bytes = values.len();
+        let job = Job::Frame(Frame {
+            item_gid,
+            path_time_states: path_time_states.to_path_buf(),
+            step_id,
+            values,
+        });
+
+        let is_sent = match (self.policy, is_final) {
+            (QueueFull::Block, _) | (_, true) => self.tx.send(job).is_ok(),
+            (policy, false) => match self.tx.try_send(job) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    if policy == QueueFull::Abort {
+                        self.metrics.is_aborted.store(true, Ordering::Relaxed);
+                    }
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+        };
+        match is_sent {
+            true => self.metrics.on_sent(bytes),
+            false => {
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        is_sent
+    }
+
+    /// Closes the file of the item once its queued frames are written.
+    pub fn close(&self, item_gid: usize) {
+        let _ = self.tx.send(Job::Close(item_gid));
+    }
+}
+
+/// The writer thread and its queue; `off()` when `WriteQueue == 0`.
+#[derive(Debug, Default)]
+pub struct SnapshotWriter {
+    handle: Option<WriterHandle>,
+    thread: Option<JoinHandle<()>>,
+    capacity: usize,
+}
+
+impl SnapshotWriter {
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    pub fn start(capacity: usize, policy: QueueFull, format: SnapFormat) -> IoResult<Self> {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let metrics = Arc::new(WriterMetrics::default());
+
+        let thread_metrics = Arc::clone(&metrics);
+        let thread = thread::Builder::new()
+            .name("snapshot-writer".to_string())
+            .spawn(move || write_loop(rx, &thread_metrics, format))?;
+
+        Ok(Self {
+            handle: Some(WriterHandle {
+                tx,
+                policy,
+                metrics,
+            }),
+            thread: Some(thread),
+            capacity,
+        })
+    }
+
+    pub fn handle(&self) -> Option<WriterHandle> {
+        self.handle.clone()
+    }
+
+    /// A snapshot was dropped under `QueueFull:abort`.
+    pub fn is_aborted(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|h| h.metrics.is_aborted.load(Ordering::Relaxed))
+    }
+
+    /// `depth/capacity (peak P), D dropped` for the progress lines.
+    pub fn queue_status(&self) -> Option<String> {
+        let metrics = &self.handle.as_ref()?.metrics;
+        Some(format!(
+            "{}/{} (peak {}), {} dropped",
+            metrics.depth(),
+            self.capacity,
+            metrics.peak_depth(),
+            metrics.dropped()
+        ))
+    }
+
+    /// Writes what is still queued, stops the thread and reports the totals.
+    pub fn finish(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let _ = handle.tx.send(Job::Finish);
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            eprintln!("⚠️ Warning: The snapshot writer thread panicked.");
+        }
+
+        let m = &handle.metrics;
+        let (raw, out) = (
+            m.raw_bytes.load(Ordering::Relaxed),
+            m.out_bytes.load(Ordering::Relaxed),
+        );
+        println!(
+            "🗄️ Snapshot writer: {} frame(s) written ({} -> {} bytes), queue peak {}/{} frames ({} bytes), {} dropped, {} failed.",
+            m.written.load(Ordering::Relaxed),
+            raw,
+            out,
+            m.peak_depth(),
+            self.capacity,
+            m.peak_bytes.load(Ordering::Relaxed),
+            m.dropped(),
+            m.failed.load(Ordering::Relaxed)
+        );
+        if m.is_aborted.load(Ordering::Relaxed) {
+            eprintln!(
+                "❌ The run was stopped because the snapshot queue was full (QueueFull:abort)."
+            );
+        }
+    }
+}
+
+/// Output of one item on the writer thread.
+struct Sink {
+    text: Option<BufWriter<File>>,
+    packed: Option<BufWriter<File>>,
+    /// Previous frame for the deltas of `TimeStates.zst`.
+    prev: Option<Box<[u8]>>,
+}
+
+impl Sink {
+    fn open(path_time_states: &Path, format: SnapFormat) -> IoResult<Self> {
+        let open = |path: &Path, is_packed: bool| {
+            match is_packed {
+                true => File::create(path),
+                false => OpenOptions::new().append(true).open(path),
+            }
+            .map(BufWriter::new)
+            .map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("Failed to open file '{}': {}", path.display(), e),
+                )
+            })
+        };
+
+        let text = match format {
+            SnapFormat::Packed => None,
+            _ => Some(open(path_time_states, false)?),
+        };
+        let packed = match format {
+            SnapFormat::Text => None,
+            _ => {
+                let mut fbuf = open(
+                    &path_time_states.with_file_name(TIME_STATES_ZST_FILE_NAME),
+                    true,
+                )?;
+                fbuf.write_all(TIME_STATES_ZST_MAGIC)?;
+                fbuf.write_all(&BIN_FORMAT_VERSION.to_le_bytes())?;
+                Some(fbuf)
+            }
+        };
+
+        Ok(Self {
+            text,
+            packed,
+            prev: None,
+        })
+    }
+
+    /// Writes `frame`; returns the bytes written.
+    fn write(&mut self, frame: Frame) -> IoResult<usize> {
+        faults::inject(FaultPoint::Snapshot)?;
+        let mut out = 0;
+        if let Some(fbuf) = &mut self.text {
+            io_handler::write_frame(fbuf, &frame.values)?;
+            fbuf.flush()?;
+            out += 2 * frame.values.len() + FRAME_END_LINE_LEN;
+        }
+        let Some(fbuf) = &mut self.packed else {
+            return Ok(out);
+        };
+
+        let delta = delta(self.prev.as_deref(), &frame.values);
+        let packed = zstd::bulk::compress(&delta, SNAPSHOT_ZSTD_LEVEL)?;
+        fbuf.write_all(&frame.step_id.to_le_bytes())?;
+        fbuf.write_all(&(frame.values.len() as u32).to_le_bytes())?;
+        fbuf.write_all(&(packed.len() as u32).to_le_bytes())?;
+        fbuf.write_all(&packed)?;
+        fbuf.flush()?;
+        self.prev = Some(frame.values);
+
+        Ok(out + 16 + packed.len())
+    }
+
+    fn flush(&mut self) {
+        for fbuf in [&mut self.text, &mut self.packed].into_iter().flatten() {
+            let _ = fbuf.flush();
+        }
+    }
+}
+
+/// `frame` XOR `prev` (the frame itself if there is no previous frame of its size).
+fn delta(prev: Option<&[u8]>, frame: &[u8]) -> Vec<u8> {
+    match prev {
+        Some(prev) if prev.len() == frame.len() => {
+            prev.iter().zip(frame).map(|(a, b)| a ^ b).collect()
+        }
+        _ => frame.to_vec(),
+    }
+}
+
+fn write_loop(rx: Receiver<Job>, metrics: &WriterMetrics, format: SnapFormat) {
+    let mut sinks: HashMap<usize, Sink> = HashMap::new();
+
+    for job in rx {
+        let frame = match job {
+            Job::Frame(frame) => frame,
+            Job::Close(item_gid) => {
+                if let Some(mut sink) = sinks.remove(&item_gid) {
+                    sink.flush();
+                }
+                continue;
+            }
+            Job::Finish => break,
+        };
+
+        let (item_gid, step_id, bytes) = (frame.item_gid, frame.step_id, frame.values.len());
+        metrics.on_received(bytes);
+        let written = match sinks.get_mut(&item_gid) {
+            Some(sink) => sink.write(frame),
+            None => Sink::open(&frame.path_time_states, format)
+                .and_then(|sink| sinks.entry(item_gid).or_insert(sink).write(frame)),
+        };
+
+        match written {
+            Ok(out) => {
+                metrics.written.fetch_add(1, Ordering::Relaxed);
+                metrics
+                    .raw_bytes
+                    .fetch_add(2 * bytes as u64, Ordering::Relaxed);
+                metrics.out_bytes.fetch_add(out as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                if metrics.failed.fetch_add(1, Ordering::Relaxed) == 0 {
+                    eprintln!(
+                        "[Item ID: {:05}] ⚠️ Warning: Failed to write the snapshot of step {}: {}; the writer skips failed snapshots.",
+                        item_gid, step_id, e
+                    );
+                }
+            }
+        }
+    }
+
+    for sink in sinks.values_mut() {
+        sink.flush();
+    }
+}
+
+/// Frames of a `TimeStates.zst` as `(step, values)`.
+pub fn read_packed_frames(path: &Path) -> IoResult<Vec<(u64, Box<[u8]>)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let invalid = |msg: String| IoError::new(ErrorKind::InvalidData, msg);
+
+    let mut head = [0u8; 8];
+    reader.read_exact(&mut head)?;
+    if &head[..4] != TIME_STATES_ZST_MAGIC {
+        return Err(invalid(format!(
+            "'{}' is not a TimeStates.zst file",
+            path.display()
+        )));
+    }
+    let version = u32::from_le_bytes(head[4..].try_into().unwrap_or_default());
+    if version != BIN_FORMAT_VERSION {
+        return Err(invalid(format!("unsupported format version {version}")));
+    }
+
+    let mut frames: Vec<(u64, Box<[u8]>)> = Vec::new();
+    let mut record = [0u8; 16];
+    while !reader.fill_buf()?.is_empty() {
+        // A record cut while being written (a crash) ends the file
+        let mut packed = Vec::new();
+        let read = reader.read_exact(&mut record).and_then(|_| {
+            let packed_len = u32::from_le_bytes(record[12..].try_into().unwrap_or_default());
+            packed = vec![0u8; packed_len as usize];
+            reader.read_exact(&mut packed)
+        });
+        match read {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                eprintln!(
+                    "⚠️ Warning: '{}': the final frame is incomplete, skipped; last complete frame: {}.",
+                    path.display(),
+                    match frames.len() {
+                        0 => "none".to_string(),
+                        n => format!("{} (0-based)", n - 1),
+                    }
+                );
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+        let step_id = u64::from_le_bytes(record[..8].try_into().unwrap_or_default());
+        let raw_len = u32::from_le_bytes(record[8..12].try_into().unwrap_or_default()) as usize;
+
+        let delta_values = zstd::bulk::decompress(&packed, raw_len)?;
+        if delta_values.len() != raw_len {
+            return Err(invalid(format!(
+                "frame of step {step_id}: expected {raw_len} values"
+            )));
+        }
+
+        let prev = frames.last().map(|(_, values)| &values[..]);
+        frames.push((step_id, delta(prev, &delta_values).into_boxed_slice()));
+    }
+
+    Ok(frames)
+}
+
+/// Compares the frames of the `TimeStates.txt` and `TimeStates.zst` of an item
+/// (`SnapDual`): the frames compared and the first difference, `None` if they match.
+pub fn compare_dual(item_dir: &Path) -> IoResult<(usize, Option<String>)> {
+    let packed = read_packed_frames(&item_dir.join(TIME_STATES_ZST_FILE_NAME))?;
+    let mut text = io_handler::StateFrames::open(&item_dir.join(TIME_STATES_FILE_NAME))?;
+
+    let mut frame = Vec::new();
+    for (i, (step_id, values)) in packed.iter().enumerate() {
+        if !text.next_frame(&mut frame)? {
+            return Ok((
+                i,
+                Some(format!(
+                    "TimeStates.txt ends after {} frame(s), TimeStates.zst holds {}",
+                    i,
+                    packed.len()
+                )),
+            ));
+        }
+        if frame[..] != values[..] {
+            let difference = match frame.iter().zip(values.iter()).position(|(a, b)| a != b) {
+                Some(cell) => format!("frame {} (step {}) differs at cell {}", i, step_id, cell),
+                None => format!(
+                    "frame {} (step {}) holds {} values in TimeStates.txt, {} in TimeStates.zst",
+                    i,
+                    step_id,
+                    frame.len(),
+                    values.len()
+                ),
+            };
+            return Ok((i, Some(difference)));
+        }
+    }
+    if text.next_frame(&mut frame)? {
+        return Ok((
+            packed.len(),
+            Some(format!(
+                "TimeStates.txt holds more than the {} frame(s) of TimeStates.zst",
+                packed.len()
+            )),
+        ));
+    }
+
+    Ok((packed.len(), None))
+}
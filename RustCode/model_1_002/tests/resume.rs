@@ -0,0 +1,161 @@
+//! A run checkpointed halfway (`CheckpointI`) and resumed from it (`ResumeFrom`) must
+//! end bit for bit where the uninterrupted run of the same settings ends.
+
+use model_1_002::{Settings, SettingsBuilder, SimResult, Simulation};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const STEPS: u64 = 2_000;
+const EDGE: usize = 14;
+
+/// Fresh program folder with an `InitStates.ini` of two items: crystal cubes of edge 6
+/// and 8 centred in the grid.
+fn src_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "model_1_002_resume_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let cube = |edge: usize| {
+        let (lo, hi) = ((EDGE - edge) / 2, (EDGE + edge) / 2);
+        let inside = |c: usize| (lo..hi).contains(&c);
+        (0..EDGE * EDGE * EDGE)
+            .map(|idx| {
+                let (x, y, z) = (idx % EDGE, idx / EDGE % EDGE, idx / (EDGE * EDGE));
+                match inside(x) && inside(y) && inside(z) {
+                    true => "1",
+                    false => "0",
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(":")
+    };
+    fs::write(
+        dir.join("InitStates.ini"),
+        format!("{}\n{}\n", cube(6), cube(8)),
+    )
+    .unwrap();
+
+    dir
+}
+
+/// Growing mode 2.1 items with a thermostat and per-item streams, so the checkpoint
+/// has to restore the ensemble and item RNG positions, the temperature and the reservoir.
+fn settings(step_lim: u64) -> Settings {
+    Settings::builder()
+        .dir_prefix("Resume".to_string())
+        .seed(1012)
+        .sx(EDGE)
+        .sy(EDGE)
+        .sz(EDGE)
+        .mode_major(2)
+        .mode_minor(1)
+        .c0(4e-7)
+        .n_tot(4e8)
+        .t_sigma(2.0)
+        .t_tau(50.0)
+        .load_option(-1)
+        .deterministic(true)
+        .step_lim(step_lim)
+        .print_i(step_lim)
+        .write_i(100)
+        .build()
+        .unwrap()
+}
+
+fn run(cfg: Settings, src: &Path) -> SimResult {
+    Simulation::run_in(cfg, src.to_path_buf()).unwrap()
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Rows of a `SimLogRec.txt` after the (last) row of step `step`.
+fn log_rows_after(path: &Path, step: u64) -> Vec<String> {
+    let tag = format!(":mk_step={}:", step);
+    let rows: Vec<String> = read(path).lines().map(str::to_string).collect();
+    let last = rows
+        .iter()
+        .rposition(|row| row.contains(&tag))
+        .unwrap_or_else(|| panic!("{}: no row of step {}", path.display(), step));
+    rows[last + 1..].to_vec()
+}
+
+/// Last frame of a `TimeStates.txt`: the final state of the item.
+fn last_frame(path: &Path) -> String {
+    read(path)
+        .lines()
+        .rev()
+        .find(|line| !line.starts_with('#'))
+        .unwrap_or_else(|| panic!("{}: no frames", path.display()))
+        .to_string()
+}
+
+#[test]
+fn resumed_run_is_bit_identical() {
+    let src = src_dir("straight");
+    let straight = run(settings(STEPS), &src);
+
+    let src_half = src_dir("half");
+    let half = run(
+        SettingsBuilder::from_settings(settings(STEPS / 2))
+            .checkpoint_i(STEPS / 2)
+            .build()
+            .unwrap(),
+        &src_half,
+    );
+    let resumed = run(
+        SettingsBuilder::from_settings(settings(STEPS))
+            .resume_from(half.dst_path.display().to_string())
+            .build()
+            .unwrap(),
+        &src_half,
+    );
+
+    assert_eq!(straight.items_alive, 2);
+    assert_eq!(resumed.mk_step, straight.mk_step);
+    assert_eq!(resumed.n_cryst.to_bits(), straight.n_cryst.to_bits());
+    assert_eq!(resumed.item_n_cryst, straight.item_n_cryst);
+
+    // A resumed log starts with a row of the checkpoint step, in which the items
+    // already carry the `dg` of the next step; from there on it is the straight log.
+    let half_step = STEPS / 2;
+    let ensemble_log = Path::new("SimLogRec.txt");
+    assert_eq!(
+        read(&resumed.dst_path.join(ensemble_log)),
+        read(&straight.dst_path.join(ensemble_log))
+            .lines()
+            .skip_while(|row| !row.contains(&format!(":mk_step={}:", half_step)))
+            .map(|row| format!("{}\n", row))
+            .collect::<String>(),
+        "ensemble SimLogRec.txt differs"
+    );
+    for item in ["00000", "00001"] {
+        let frames = Path::new(item).join("TimeStates.txt");
+        assert_eq!(
+            last_frame(&resumed.dst_path.join(&frames)),
+            last_frame(&straight.dst_path.join(&frames)),
+            "final state of item {} differs",
+            item
+        );
+
+        let log = Path::new(item).join("SimLogRec.txt");
+        let resumed_rows = log_rows_after(&resumed.dst_path.join(&log), half_step);
+        assert!(resumed_rows.len() >= 10);
+        assert_eq!(
+            resumed_rows,
+            log_rows_after(&straight.dst_path.join(&log), half_step),
+            "SimLogRec.txt of item {} differs",
+            item
+        );
+    }
+
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&src_half);
+}